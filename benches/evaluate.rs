@@ -0,0 +1,69 @@
+//! Timing for evaluating typical expressions.
+//!
+//! Run with `cargo bench`. Reports the mean time to evaluate each expression,
+//! without memoization, so every evaluation repeats all of its work;
+//! then the mean time to roll each expression once with a compiled program,
+//! and to roll it in a batch.
+
+use std::time::{Duration, Instant};
+
+use dicer::{
+    Closed, Evaluator,
+    sample::{Program, SplitMix64},
+};
+
+const EXPRESSIONS: &[&str] = &[
+    "d20 + 5",
+    "2d20kh + 7",
+    "4d6kh3",
+    "8d6",
+    "[ATK: d20] (ATK = 20) * (2d6 + 3) + (ATK < 20) * (ATK + 5 >= 15) * (d6 + 3)",
+    "3d6! + d4!",
+    "18d10",
+    "d[1..20000] + d[1..20000]",
+];
+
+/// Run for a fixed time rather than a fixed number of iterations,
+/// so slow and fast expressions both get a reasonable sample.
+fn time(label: &str, mut f: impl FnMut()) {
+    let budget = Duration::from_millis(500);
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < budget {
+        f();
+        iterations += 1;
+    }
+    let per_iteration = start.elapsed() / iterations;
+    println!("{label:80} {per_iteration:>12.2?} ({iterations} iterations)");
+}
+
+fn main() {
+    for e in EXPRESSIONS {
+        let closed: Closed = e.parse().expect("valid expression");
+        let mut evaluator = Evaluator::new(false);
+        time(e, || {
+            std::hint::black_box(evaluator.eval(&closed).expect("evaluation succeeds"));
+        });
+    }
+
+    println!();
+    for e in EXPRESSIONS {
+        let closed: Closed = e.parse().expect("valid expression");
+        let mut program: Program = closed.compile().expect("compiles");
+        let mut rng = SplitMix64::new(1);
+        time(&format!("sample {e}"), || {
+            std::hint::black_box(program.sample(&mut rng).expect("sample succeeds"));
+        });
+    }
+
+    println!();
+    const BATCH: usize = 1000;
+    for e in EXPRESSIONS {
+        let closed: Closed = e.parse().expect("valid expression");
+        let mut program: Program = closed.compile().expect("compiles");
+        let mut rng = SplitMix64::new(1);
+        time(&format!("sample_n({BATCH}) {e}"), || {
+            std::hint::black_box(program.sample_n(&mut rng, BATCH).expect("samples succeed"));
+        });
+    }
+}
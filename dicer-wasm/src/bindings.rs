@@ -22,6 +22,16 @@ pub mod exports {
                     InvalidSymbol,
                     UnboundSymbols,
                     ZeroFacedDie,
+                    Overflow,
+                    RerollsForever,
+                    DropTooMany,
+                    OverBudget,
+                    InvalidCheckpoint,
+                    ImpossibleCondition,
+                    TooComplex,
+                    Timeout,
+                    RecursionLimit,
+                    Other,
                 }
                 impl ::core::fmt::Debug for ErrorCode {
                     fn fmt(
@@ -50,6 +60,36 @@ pub mod exports {
                             ErrorCode::ZeroFacedDie => {
                                 f.debug_tuple("ErrorCode::ZeroFacedDie").finish()
                             }
+                            ErrorCode::Overflow => {
+                                f.debug_tuple("ErrorCode::Overflow").finish()
+                            }
+                            ErrorCode::RerollsForever => {
+                                f.debug_tuple("ErrorCode::RerollsForever").finish()
+                            }
+                            ErrorCode::DropTooMany => {
+                                f.debug_tuple("ErrorCode::DropTooMany").finish()
+                            }
+                            ErrorCode::OverBudget => {
+                                f.debug_tuple("ErrorCode::OverBudget").finish()
+                            }
+                            ErrorCode::InvalidCheckpoint => {
+                                f.debug_tuple("ErrorCode::InvalidCheckpoint").finish()
+                            }
+                            ErrorCode::ImpossibleCondition => {
+                                f.debug_tuple("ErrorCode::ImpossibleCondition").finish()
+                            }
+                            ErrorCode::TooComplex => {
+                                f.debug_tuple("ErrorCode::TooComplex").finish()
+                            }
+                            ErrorCode::Timeout => {
+                                f.debug_tuple("ErrorCode::Timeout").finish()
+                            }
+                            ErrorCode::RecursionLimit => {
+                                f.debug_tuple("ErrorCode::RecursionLimit").finish()
+                            }
+                            ErrorCode::Other => {
+                                f.debug_tuple("ErrorCode::Other").finish()
+                            }
                         }
                     }
                 }
@@ -67,6 +107,16 @@ pub mod exports {
                             4 => ErrorCode::InvalidSymbol,
                             5 => ErrorCode::UnboundSymbols,
                             6 => ErrorCode::ZeroFacedDie,
+                            7 => ErrorCode::Overflow,
+                            8 => ErrorCode::RerollsForever,
+                            9 => ErrorCode::DropTooMany,
+                            10 => ErrorCode::OverBudget,
+                            11 => ErrorCode::InvalidCheckpoint,
+                            12 => ErrorCode::ImpossibleCondition,
+                            13 => ErrorCode::TooComplex,
+                            14 => ErrorCode::Timeout,
+                            15 => ErrorCode::RecursionLimit,
+                            16 => ErrorCode::Other,
                             _ => panic!("invalid enum discriminant"),
                         }
                     }
@@ -952,18 +1002,21 @@ pub(crate) use __export_dicer_wasm_impl as export;
 )]
 #[doc(hidden)]
 #[allow(clippy::octal_escapes)]
-pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 528] = *b"\
-\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07\x8f\x03\x01A\x02\x01\
-A\x02\x01B\x14\x01m\x07\x05parse\x0enegative-count\x0ckeep-too-few\x0edivide-by-\
-zero\x0einvalid-symbol\x0funbound-symbols\x0ezero-faced-die\x04\0\x0aerror-code\x03\
-\0\0\x01r\x02\x04code\x01\x07messages\x04\0\x05error\x03\0\x02\x04\0\x0cdistribu\
-tion\x03\x01\x04\0\x0aexpression\x03\x01\x04\0\x09evaluator\x03\x01\x01i\x06\x01\
-@\0\0\x07\x04\0\x16[constructor]evaluator\x01\x08\x01h\x06\x01h\x05\x01p\x0a\x01\
-j\x01s\x01\x03\x01@\x02\x04self\x09\x05exprs\x0b\0\x0c\x04\0+[method]evaluator.r\
-ender-distribution-table\x01\x0d\x01i\x05\x01j\x01\x0e\x01\x03\x01@\x01\x04texts\
-\0\x0f\x04\0\x05parse\x01\x10\x04\0\x14cceckman:dicer/dicer\x05\0\x04\0\x19cceck\
-man:dicer/dicer-wasm\x04\0\x0b\x10\x01\0\x0adicer-wasm\x03\0\0\0G\x09producers\x01\
-\x0cprocessed-by\x02\x0dwit-component\x070.227.1\x10wit-bindgen-rust\x060.41.0";
+pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 661] = *b"\
+\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07\x94\x04\x01A\x02\
+\x01A\x02\x01B\x14\x01m\x11\x05parse\x0enegative-count\x0ckeep-too-few\x0edivid\
+e-by-zero\x0einvalid-symbol\x0funbound-symbols\x0ezero-faced-die\x08overflow\
+\x0frerolls-forever\x0ddrop-too-many\x0bover-budget\x12invalid-checkpoint\x14im\
+possible-condition\x0btoo-complex\x07timeout\x0frecursion-limit\x05other\x04\0\
+\x0aerror-code\x03\0\0\x01r\x02\x04code\x01\x07messages\x04\0\x05error\x03\0\
+\x02\x04\0\x0cdistribution\x03\x01\x04\0\x0aexpression\x03\x01\x04\0\x09evaluat\
+or\x03\x01\x01i\x06\x01@\0\0\x07\x04\0\x16[constructor]evaluator\x01\x08\x01h\
+\x06\x01h\x05\x01p\x0a\x01j\x01s\x01\x03\x01@\x02\x04self\x09\x05exprs\x0b\0\
+\x0c\x04\0+[method]evaluator.render-distribution-table\x01\x0d\x01i\x05\x01j\
+\x01\x0e\x01\x03\x01@\x01\x04texts\0\x0f\x04\0\x05parse\x01\x10\x04\0\x14cceckm\
+an:dicer/dicer\x05\0\x04\0\x19cceckman:dicer/dicer-wasm\x04\0\x0b\x10\x01\0\x0a\
+dicer-wasm\x03\0\0\0G\x09producers\x01\x0cprocessed-by\x02\x0dwit-component\x07\
+0.227.1\x10wit-bindgen-rust\x060.41.0";
 #[inline(never)]
 #[doc(hidden)]
 pub fn __link_custom_section_describing_imports() {
@@ -47,10 +47,20 @@ impl From<dicer::Error> for dicer_bindings::Error {
             ParseError(_, _) => ErrorCode::Parse,
             NegativeCount(_) => ErrorCode::NegativeCount,
             KeepTooFew(_, _) => ErrorCode::KeepTooFew,
+            DropTooMany(_, _) => ErrorCode::DropTooMany,
             DivideByZero(_) => ErrorCode::DivideByZero,
             InvalidSymbolCharacter(_) => ErrorCode::InvalidSymbol,
             UnboundSymbols(_) => ErrorCode::UnboundSymbols,
             ZeroFacedDie() => ErrorCode::ZeroFacedDie,
+            Overflow(_) => ErrorCode::Overflow,
+            RerollsForever(_) => ErrorCode::RerollsForever,
+            OverBudget(_) => ErrorCode::OverBudget,
+            InvalidCheckpoint(_) => ErrorCode::InvalidCheckpoint,
+            ImpossibleCondition(_) => ErrorCode::ImpossibleCondition,
+            TooComplex(_) => ErrorCode::TooComplex,
+            Timeout(_) => ErrorCode::Timeout,
+            RecursionLimit(_) => ErrorCode::RecursionLimit,
+            _ => ErrorCode::Other,
         };
         dicer_bindings::Error { code, message }
     }
@@ -55,12 +55,29 @@ impl Display for Closed {
 }
 
 impl Closed {
+    /// Wrap a node whose children are closed. The node must not use any symbols itself.
+    pub(crate) fn new(node: ExpressionTree<Closed>) -> Closed {
+        Closed(node)
+    }
+
+    /// Return a copy of the expression with each node replaced by `f`, from the leaves up:
+    /// `f` sees each node with its children already replaced.
+    ///
+    /// `f` must not introduce unbound symbols.
+    pub(crate) fn rewrite(
+        &self,
+        f: &mut impl FnMut(ExpressionTree<Closed>) -> ExpressionTree<Closed>,
+    ) -> Closed {
+        let node = self.0.map_children(|child| child.rewrite(f));
+        Closed(f(node))
+    }
+
     /// Return a copy of the expression tree where the given expression is replaced with the
     /// provided constant.
     ///
     /// Note that any Closed expression can be given a substitution with a fixed value and remained
     /// Closed, as the substitution results in strictly fewer unbound variables.
-    pub(crate) fn substitute(&self, sym: &Symbol, value: isize) -> Closed {
+    pub(crate) fn substitute(&self, sym: &Symbol, value: i64) -> Closed {
         let expr = if value < 0 {
             ExpressionTree::Negated(Box::new(Closed(ExpressionTree::Modifier(Constant(
                 value.unsigned_abs(),
@@ -74,9 +91,10 @@ impl Closed {
     fn substitute_inner(&self, sym: &Symbol, expr: &Closed) -> Closed {
         match self.inner() {
             ExpressionTree::Symbol(symbol) if symbol == sym => expr.clone(),
-            ExpressionTree::Modifier(_) | ExpressionTree::Die(_) | ExpressionTree::Symbol(_) => {
-                self.clone()
-            }
+            ExpressionTree::Modifier(_)
+            | ExpressionTree::Die(_)
+            | ExpressionTree::CustomDie(_)
+            | ExpressionTree::Symbol(_) => self.clone(),
             ExpressionTree::Negated(e) => Closed(ExpressionTree::Negated(Box::new(
                 e.substitute_inner(sym, expr),
             ))),
@@ -90,7 +108,7 @@ impl Closed {
                 Closed(ExpressionTree::Repeated {
                     count,
                     value,
-                    ranker: *ranker,
+                    ranker: ranker.map(|n| Box::new(n.substitute_inner(sym, expr))),
                 })
             }
             ExpressionTree::Product(a, b) => {
@@ -103,6 +121,11 @@ impl Closed {
                 let b = Box::new(b.substitute_inner(sym, expr));
                 Closed(ExpressionTree::Floor(a, b))
             }
+            ExpressionTree::Modulo(a, b) => {
+                let a = Box::new(a.substitute_inner(sym, expr));
+                let b = Box::new(b.substitute_inner(sym, expr));
+                Closed(ExpressionTree::Modulo(a, b))
+            }
             ExpressionTree::Comparison { a, b, op } => {
                 let a = Box::new(a.substitute_inner(sym, expr));
                 let b = Box::new(b.substitute_inner(sym, expr));
@@ -127,6 +150,31 @@ impl Closed {
                     tail,
                 })
             }
+            ExpressionTree::Call { function, args } => Closed(ExpressionTree::Call {
+                function: *function,
+                args: args.iter().map(|v| v.substitute_inner(sym, expr)).collect(),
+            }),
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => Closed(ExpressionTree::Explode {
+                value: Box::new(value.substitute_inner(sym, expr)),
+                explosion: *explosion,
+                trigger: *trigger,
+            }),
+            ExpressionTree::Reroll { value, reroll } => Closed(ExpressionTree::Reroll {
+                value: Box::new(value.substitute_inner(sym, expr)),
+                reroll: *reroll,
+            }),
+            ExpressionTree::Successes { value, successes } => Closed(ExpressionTree::Successes {
+                value: Box::new(value.substitute_inner(sym, expr)),
+                successes: *successes,
+            }),
+            ExpressionTree::Lookup { value, bands } => Closed(ExpressionTree::Lookup {
+                value: Box::new(value.substitute_inner(sym, expr)),
+                bands: bands.clone(),
+            }),
         }
     }
 }
@@ -156,6 +204,7 @@ fn closed_under(
     match tree {
         ExpressionTree::Modifier(a) => Ok(Closed(ExpressionTree::Modifier(*a))),
         ExpressionTree::Die(a) => Ok(Closed(ExpressionTree::Die(*a))),
+        ExpressionTree::CustomDie(a) => Ok(Closed(ExpressionTree::CustomDie(a.clone()))),
         ExpressionTree::Symbol(symbol) => {
             if bindings.search(symbol).is_some() {
                 Ok(Closed(ExpressionTree::Symbol(symbol.to_owned())))
@@ -172,16 +221,20 @@ fn closed_under(
             value,
             ranker,
         } => {
-            let (count, value) = combine_close_results(
+            let counts = combine_close_results(
                 closed_under(bindings, count.inner()),
                 closed_under(bindings, value.inner()),
-            )?;
-            let count = Box::new(count);
-            let value = Box::new(value);
+            );
+            let ranker = ranker.try_map(|n| closed_under(bindings, n.inner()).map(Box::new));
+            let ((count, value), ranker) = match (counts, ranker) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(a), Err(b)) => return Err(a.into_iter().chain(b).collect()),
+                (Err(a), _) | (_, Err(a)) => return Err(a),
+            };
             Ok(Closed(ExpressionTree::Repeated {
-                count,
-                value,
-                ranker: *ranker,
+                count: Box::new(count),
+                value: Box::new(value),
+                ranker,
             }))
         }
         ExpressionTree::Product(a, b) => {
@@ -191,26 +244,9 @@ fn closed_under(
             )?;
             Ok(Closed(ExpressionTree::Product(Box::new(a), Box::new(b))))
         }
-        ExpressionTree::Sum(items) => {
-            let mut unbound: HashSet<Symbol> = Default::default();
-            let items: Vec<Closed> = items
-                .iter()
-                .filter_map(|item| match closed_under(bindings, item.inner()) {
-                    Ok(v) => Some(v),
-                    Err(e) => {
-                        for e in e {
-                            unbound.insert(e);
-                        }
-                        None
-                    }
-                })
-                .collect();
-            if unbound.is_empty() {
-                Ok(Closed(ExpressionTree::Sum(items)))
-            } else {
-                Err(unbound)
-            }
-        }
+        ExpressionTree::Sum(items) => Ok(Closed(ExpressionTree::Sum(closed_under_all(
+            bindings, items,
+        )?))),
         ExpressionTree::Floor(a, b) => {
             let (a, b) = combine_close_results(
                 closed_under(bindings, a.inner()),
@@ -218,6 +254,13 @@ fn closed_under(
             )?;
             Ok(Closed(ExpressionTree::Floor(Box::new(a), Box::new(b))))
         }
+        ExpressionTree::Modulo(a, b) => {
+            let (a, b) = combine_close_results(
+                closed_under(bindings, a.inner()),
+                closed_under(bindings, b.inner()),
+            )?;
+            Ok(Closed(ExpressionTree::Modulo(Box::new(a), Box::new(b))))
+        }
         ExpressionTree::Comparison { a, b, op } => {
             let (a, b) = combine_close_results(
                 closed_under(bindings, a.inner()),
@@ -251,6 +294,56 @@ fn closed_under(
                 tail: Box::new(tail),
             }))
         }
+        ExpressionTree::Call { function, args } => Ok(Closed(ExpressionTree::Call {
+            function: *function,
+            args: closed_under_all(bindings, args)?,
+        })),
+        ExpressionTree::Explode {
+            value,
+            explosion,
+            trigger,
+        } => Ok(Closed(ExpressionTree::Explode {
+            value: Box::new(closed_under(bindings, value.inner())?),
+            explosion: *explosion,
+            trigger: *trigger,
+        })),
+        ExpressionTree::Reroll { value, reroll } => Ok(Closed(ExpressionTree::Reroll {
+            value: Box::new(closed_under(bindings, value.inner())?),
+            reroll: *reroll,
+        })),
+        ExpressionTree::Successes { value, successes } => Ok(Closed(ExpressionTree::Successes {
+            value: Box::new(closed_under(bindings, value.inner())?),
+            successes: *successes,
+        })),
+        ExpressionTree::Lookup { value, bands } => Ok(Closed(ExpressionTree::Lookup {
+            value: Box::new(closed_under(bindings, value.inner())?),
+            bands: bands.clone(),
+        })),
+    }
+}
+
+/// Close each of the expressions, collecting all the unbound symbols if any are unbound.
+fn closed_under_all(
+    bindings: &AvailableBinding<Closed>,
+    items: &[RawExpression],
+) -> Result<Vec<Closed>, HashSet<Symbol>> {
+    let mut unbound: HashSet<Symbol> = Default::default();
+    let items: Vec<Closed> = items
+        .iter()
+        .filter_map(|item| match closed_under(bindings, item.inner()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                for e in e {
+                    unbound.insert(e);
+                }
+                None
+            }
+        })
+        .collect();
+    if unbound.is_empty() {
+        Ok(items)
+    } else {
+        Err(unbound)
     }
 }
 
@@ -336,22 +429,30 @@ mod tests {
             return Some(tree);
         }
         match tree {
-            ExpressionTree::Negated(e) => search_for(e.inner(), predicate),
+            ExpressionTree::Negated(e)
+            | ExpressionTree::Explode { value: e, .. }
+            | ExpressionTree::Reroll { value: e, .. }
+            | ExpressionTree::Successes { value: e, .. }
+            | ExpressionTree::Lookup { value: e, .. } => search_for(e.inner(), predicate),
             ExpressionTree::Repeated {
                 count,
                 value,
-                ranker: _,
-            } => search_for(count.inner(), predicate).or(search_for(value.inner(), predicate)),
+                ranker,
+            } => search_for(count.inner(), predicate)
+                .or(search_for(value.inner(), predicate))
+                .or(ranker
+                    .count()
+                    .and_then(|n| search_for(n.inner(), predicate))),
             ExpressionTree::Product(a, b) => {
                 search_for(a.inner(), predicate).or(search_for(b.inner(), predicate))
             }
-            ExpressionTree::Floor(a, b) => {
+            ExpressionTree::Floor(a, b) | ExpressionTree::Modulo(a, b) => {
                 search_for(a.inner(), predicate).or(search_for(b.inner(), predicate))
             }
             ExpressionTree::Comparison { a, b, op: _ } => {
                 search_for(a.inner(), predicate).or(search_for(b.inner(), predicate))
             }
-            ExpressionTree::Sum(items) => {
+            ExpressionTree::Sum(items) | ExpressionTree::Call { args: items, .. } => {
                 for item in items {
                     if let Some(v) = search_for(item.inner(), predicate) {
                         return Some(v);
@@ -360,7 +461,7 @@ mod tests {
                 None
             }
             ExpressionTree::Binding {
-                symbol,
+                symbol: _,
                 value,
                 tail,
             } => search_for(value.inner(), predicate).or(search_for(tail.inner(), predicate)),
@@ -415,6 +516,7 @@ mod tests {
                 properties::floor(&strat),
                 properties::sum(&strat),
                 properties::comparison(&strat),
+                properties::call(&strat),
             ]
             .prop_map(RawExpression::from)
         });
@@ -496,26 +598,31 @@ mod tests {
             }
             ExpressionTree::Modifier(_) => None,
             ExpressionTree::Die(_) => None,
+            ExpressionTree::CustomDie(_) => None,
             ExpressionTree::Symbol(sym) if sym == symbol => Some(tree),
             ExpressionTree::Symbol(_) => None,
-            ExpressionTree::Negated(e) => unbound_tree(symbol, e.inner()),
+            ExpressionTree::Negated(e)
+            | ExpressionTree::Explode { value: e, .. }
+            | ExpressionTree::Reroll { value: e, .. }
+            | ExpressionTree::Successes { value: e, .. }
+            | ExpressionTree::Lookup { value: e, .. } => unbound_tree(symbol, e.inner()),
             ExpressionTree::Repeated {
                 count,
                 value,
-                ranker: _,
-            } => {
-                unbound_tree(symbol, count.inner()).or_else(|| unbound_tree(symbol, value.inner()))
-            }
+                ranker,
+            } => unbound_tree(symbol, count.inner())
+                .or_else(|| unbound_tree(symbol, value.inner()))
+                .or_else(|| ranker.count().and_then(|n| unbound_tree(symbol, n.inner()))),
             ExpressionTree::Product(a, b) => {
                 unbound_tree(symbol, a.inner()).or_else(|| unbound_tree(symbol, b.inner()))
             }
-            ExpressionTree::Floor(a, b) => {
+            ExpressionTree::Floor(a, b) | ExpressionTree::Modulo(a, b) => {
                 unbound_tree(symbol, a.inner()).or_else(|| unbound_tree(symbol, b.inner()))
             }
             ExpressionTree::Comparison { a, b, op: _ } => {
                 unbound_tree(symbol, a.inner()).or_else(|| unbound_tree(symbol, b.inner()))
             }
-            ExpressionTree::Sum(items) => items
+            ExpressionTree::Sum(items) | ExpressionTree::Call { args: items, .. } => items
                 .iter()
                 .filter_map(|v| unbound_tree(symbol, v.inner()))
                 .next(),
@@ -0,0 +1,118 @@
+//! Shared storage for many expressions.
+//!
+//! An application with a library of macros may hold thousands of expressions,
+//! many of which have parts in common: `d20 + 5` and `(d20 + 5) * 2` both roll `d20 + 5`.
+//! An [`ExprArena`] stores each distinct sub-expression once, and hands out small
+//! [`ExpressionRef`] handles that are cheap to copy, compare, and hash.
+
+use std::collections::HashMap;
+
+use crate::{
+    Closed, Error,
+    parse::RawExpression,
+    symbolic::{ExpressionTree, ExpressionWrapper},
+};
+
+/// A handle to an expression stored in an [`ExprArena`].
+///
+/// Two handles from the same arena are equal exactly when their expressions are equal,
+/// so handles can be used to deduplicate expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExpressionRef(u32);
+
+/// Storage for expressions, sharing each distinct sub-expression between all the
+/// expressions that contain it.
+#[derive(Debug, Default, Clone)]
+pub struct ExprArena {
+    /// Each distinct node; children refer to earlier nodes.
+    nodes: Vec<ExpressionTree<ExpressionRef>>,
+    /// The handle for each node already stored.
+    index: HashMap<ExpressionTree<ExpressionRef>, ExpressionRef>,
+}
+
+impl ExprArena {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Store the expression, returning its handle.
+    /// Storing an expression that is already present returns the same handle.
+    pub fn insert(&mut self, expression: &Closed) -> ExpressionRef {
+        let node = expression.inner().map_children(|child| self.insert(child));
+        if let Some(r) = self.index.get(&node) {
+            return *r;
+        }
+        let r = ExpressionRef(u32::try_from(self.nodes.len()).expect("too many expressions"));
+        self.nodes.push(node.clone());
+        self.index.insert(node, r);
+        r
+    }
+
+    /// Parse the expression and store it, returning its handle.
+    pub fn parse(&mut self, s: &str) -> Result<ExpressionRef, Error> {
+        let expression: Closed = s.parse()?;
+        Ok(self.insert(&expression))
+    }
+
+    /// Retrieve a stored expression.
+    ///
+    /// Panics if the handle is from a different arena.
+    pub fn expression(&self, r: ExpressionRef) -> Closed {
+        self.raw(r)
+            .try_into()
+            .expect("stored expressions are closed")
+    }
+
+    /// The number of distinct sub-expressions stored.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the arena has no expressions.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn raw(&self, r: ExpressionRef) -> RawExpression {
+        self.nodes[r.0 as usize]
+            .map_children(|child| self.raw(*child))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deduplicate() {
+        let mut arena = ExprArena::new();
+        let a = arena.parse("2d6 + 3").unwrap();
+        let b = arena.parse("2d6+3").unwrap();
+        assert_eq!(a, b);
+        // 2, d6, 2d6, 3, and the sum.
+        assert_eq!(arena.len(), 5);
+
+        // Only the new parts of a larger expression take up space.
+        let c = arena.parse("(2d6 + 3) * 2").unwrap();
+        assert_ne!(a, c);
+        assert_eq!(arena.len(), 6);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut arena = ExprArena::new();
+        for s in [
+            "[ATK: d20] (ATK = 20) * (2d6 + 3) + (ATK < 20) * (ATK + 5 >= 15) * (d6 + 3)",
+            "soak(4d6ro1kh3, d4!)",
+            "[X: d4] [Y: (X)d6] Y / X",
+        ] {
+            let want: Closed = s.parse().unwrap();
+            let r = arena.insert(&want);
+            let got = arena.expression(r);
+            assert_eq!(got, want);
+            assert_eq!(got.distribution().unwrap(), want.distribution().unwrap());
+        }
+    }
+}
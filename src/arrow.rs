@@ -0,0 +1,123 @@
+//! Export of distributions as [Apache Arrow](https://arrow.apache.org/) record batches,
+//! for data-analysis pipelines. Requires the `arrow` feature.
+//!
+//! Polars and pandas (via pyarrow) can both load a `RecordBatch` without copying it.
+
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+
+use crate::Distribution;
+
+/// A record batch with one row for each value of the distribution that occurs:
+/// columns `value` (int64), `occurrences` (uint64), and `probability` (float64).
+pub fn record_batch(distribution: &Distribution) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        Field::new("value", DataType::Int64, false),
+        Field::new("occurrences", DataType::UInt64, false),
+        Field::new("probability", DataType::Float64, false),
+    ]);
+    let (values, occurrences, probabilities) = columns(distribution);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int64Array::from(values)) as ArrayRef,
+            Arc::new(UInt64Array::from(occurrences)),
+            Arc::new(Float64Array::from(probabilities)),
+        ],
+    )
+}
+
+/// A record batch of several labelled distributions (e.g. one per expression),
+/// in "long" form: the columns of [`record_batch`], after a `label` (utf8) column.
+pub fn record_batch_multi(
+    inputs: &[(impl AsRef<str>, Distribution)],
+) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        Field::new("label", DataType::Utf8, false),
+        Field::new("value", DataType::Int64, false),
+        Field::new("occurrences", DataType::UInt64, false),
+        Field::new("probability", DataType::Float64, false),
+    ]);
+    let mut labels = Vec::new();
+    let (mut values, mut occurrences, mut probabilities) = (Vec::new(), Vec::new(), Vec::new());
+    for (label, distribution) in inputs {
+        let (v, o, p) = columns(distribution);
+        labels.extend(std::iter::repeat_n(label.as_ref(), v.len()));
+        values.extend(v);
+        occurrences.extend(o);
+        probabilities.extend(p);
+    }
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(StringArray::from(labels)) as ArrayRef,
+            Arc::new(Int64Array::from(values)),
+            Arc::new(UInt64Array::from(occurrences)),
+            Arc::new(Float64Array::from(probabilities)),
+        ],
+    )
+}
+
+/// The values that occur, their occurrences, and their probabilities.
+fn columns(distribution: &Distribution) -> (Vec<i64>, Vec<u64>, Vec<f64>) {
+    let mut values = Vec::with_capacity(distribution.support_len());
+    let mut occurrences = Vec::with_capacity(distribution.support_len());
+    let mut probabilities = Vec::with_capacity(distribution.support_len());
+    for (value, o) in distribution.occurrences() {
+        values.push(value);
+        occurrences.push(o as u64);
+        probabilities.push(distribution.probability_f64(value));
+    }
+    (values, occurrences, probabilities)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+
+    use super::*;
+    use crate::Closed;
+
+    fn distribution(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn single() {
+        let batch = record_batch(&distribution("2d6")).unwrap();
+        assert_eq!(batch.num_rows(), 11);
+        assert_eq!(batch.num_columns(), 3);
+        let values = batch
+            .column_by_name("value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!((values.value(0), values.value(10)), (2, 12));
+        let occurrences = batch
+            .column_by_name("occurrences")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(occurrences.value(5), 6);
+    }
+
+    #[test]
+    fn multi() {
+        let batch = record_batch_multi(&[("d4", distribution("d4")), ("d66", distribution("d66"))])
+            .unwrap();
+        // Only values that occur have rows.
+        assert_eq!(batch.num_rows(), 4 + 36);
+        let labels = batch
+            .column_by_name("label")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!((labels.value(0), labels.value(4)), ("d4", "d66"));
+        assert_eq!(labels.null_count(), 0);
+    }
+}
@@ -0,0 +1,174 @@
+//! Evaluation of many expressions at once, for shell pipelines.
+//!
+//! [`run`] reads one expression per line and writes one JSON object per line
+//! (newline-delimited JSON), as `dicer --batch` does with stdin and stdout.
+//! Blank lines and comments, starting with `#`, are skipped. A line that doesn't parse
+//! or evaluate gets an object with its error, and the lines after it carry on.
+//!
+//! Each object has the line's number, counting from 1, and its `input`. An evaluated line
+//! also has the `mean` and `stddev`, and whether they're `exact`; an exact one also has its
+//! `min`, `max`, and `distribution`, a list of `[value, probability]` pairs. A line that
+//! failed has an `error` message instead, and its stable [`code`](crate::ErrorKind::code).
+
+use std::io::{BufRead, Write};
+
+use crate::{Closed, lint::skipped};
+
+/// Evaluate each line of the input within `budget` combinations, as
+/// [`Closed::evaluate_auto`] counts them, and write its result to the output as it goes.
+pub fn run(input: impl BufRead, mut output: impl Write, budget: usize) -> std::io::Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if skipped(&line) {
+            continue;
+        }
+        writeln!(output, "{}", evaluate_line(i + 1, line.trim(), budget))?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// The JSON object for the expression on the given line.
+pub fn evaluate_line(line: usize, input: &str, budget: usize) -> String {
+    let mut json = format!("{{\"line\":{line},\"input\":{}", string(input));
+    match input
+        .parse::<Closed>()
+        .and_then(|e| e.evaluate_auto(budget))
+    {
+        Ok(evaluation) => {
+            let moments = evaluation.moments();
+            json += &format!(
+                ",\"mean\":{},\"stddev\":{},\"exact\":{}",
+                number(moments.mean.value),
+                number(moments.stddev()),
+                evaluation.range().is_some()
+            );
+            if let (Some((min, max)), Some(probabilities)) =
+                (evaluation.range(), evaluation.probabilities())
+            {
+                let pairs: Vec<String> = probabilities
+                    .iter()
+                    .map(|(v, p)| format!("[{v},{}]", number(*p)))
+                    .collect();
+                json += &format!(
+                    ",\"min\":{min},\"max\":{max},\"distribution\":[{}]",
+                    pairs.join(",")
+                );
+            }
+        }
+        Err(e) => {
+            json += &format!(
+                ",\"code\":{},\"error\":{}",
+                string(e.code()),
+                string(&e.to_string())
+            );
+        }
+    }
+    json.push('}');
+    json
+}
+
+/// The number as JSON, which has no infinities or NaN: those are null.
+fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+/// The string as JSON, quoted and escaped.
+fn string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::DEFAULT_BUDGET;
+
+    #[test]
+    fn lines() {
+        let input = "2d2\n\n# a comment\nd6 +\n3 * 2\n";
+        let mut output = Vec::new();
+        run(input.as_bytes(), &mut output, DEFAULT_BUDGET).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "{\"line\":1,\"input\":\"2d2\",\"mean\":3,\"stddev\":0.7071067811865476,\
+             \"exact\":true,\"min\":2,\"max\":4,\"distribution\":[[2,0.25],[3,0.5],[4,0.25]]}"
+        );
+        // An error doesn't stop the lines after it.
+        assert!(
+            lines[1].starts_with(
+                "{\"line\":4,\"input\":\"d6 +\",\"code\":\"parse-error\",\"error\":\"parse error"
+            ),
+            "{}",
+            lines[1]
+        );
+        assert_eq!(
+            lines[2],
+            "{\"line\":5,\"input\":\"3 * 2\",\"mean\":6,\"stddev\":0,\
+             \"exact\":true,\"min\":6,\"max\":6,\"distribution\":[[6,1]]}"
+        );
+    }
+
+    #[test]
+    fn oversized() {
+        // Lines too wide to store exactly don't take the lines after them down.
+        for wide in [
+            "d[1..1000000000000]",
+            "d1000000000000",
+            "d{1,1000000000000}",
+            "d2 * 1000000000000",
+        ] {
+            let input = format!("d2\n{wide}\n3\n");
+            let mut output = Vec::new();
+            run(input.as_bytes(), &mut output, DEFAULT_BUDGET).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let lines: Vec<&str> = output.lines().collect();
+            assert_eq!(lines.len(), 3, "{output}");
+            assert!(
+                lines[0].contains("\"distribution\":[[1,0.5],[2,0.5]]"),
+                "{output}"
+            );
+            assert!(lines[1].contains("\"exact\":false"), "{output}");
+            assert!(lines[2].contains("\"distribution\":[[3,1]]"), "{output}");
+        }
+    }
+
+    #[test]
+    fn approximate() {
+        let got = evaluate_line(1, "100d6 * 100d6", 1000);
+        assert!(got.contains("\"exact\":false"), "{got}");
+        assert!(!got.contains("distribution"), "{got}");
+
+        // Too many rolls to count in a usize is still exact.
+        let got = evaluate_line(1, "30d100", DEFAULT_BUDGET);
+        assert!(
+            got.contains("\"exact\":true,\"min\":30,\"max\":3000,\"distribution\":[[30,"),
+            "{got}"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string("a\"b\\c\td\u{1}"), "\"a\\\"b\\\\c\\td\\u0001\"");
+        assert_eq!(number(f64::NAN), "null");
+    }
+}
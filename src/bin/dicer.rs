@@ -2,10 +2,66 @@
 //!
 //! Accepts a dice expression on stdin.
 //! Prints an HTML table to stdout, using classes from charts.css.
+//!
+//! `dicer --batch` instead reads one expression per line, and prints one JSON object per line
+//! with its statistics and distribution, or its error.
+//!
+//! `dicer lint FILE` instead checks a file of named expressions, one per line
+//! (`longsword: d20 + 5 >= 15`), and prints what it finds.
+//! It exits with status 1 if it finds anything.
+//!
+//! `dicer names FILE` lists the names of the expressions in the file, and
+//! `dicer show FILE NAME` prints the HTML table for one of them. Without a file, they use
+//! the macros saved from `dicer repl`.
+//!
+//! `dicer repl` starts an interactive session: each line defines a macro, evaluates
+//! an expression or macro, or saves or loads the macros with `:save` and `:load`.
+//!
+//! `dicer completions SHELL` prints a completion script for bash, zsh, or fish, which
+//! completes subcommands, files, and, for `show`, the names of the saved macros or of the
+//! expressions in a file: for bash, `source <(dicer completions bash)`.
+//!
+//! `dicer watch FILE` evaluates each named expression in the file, and again each time
+//! the file changes, printing their statistics. With `--serve PORT`, it instead serves them
+//! as a web page at `http://localhost:PORT/`, which updates itself as the file changes.
 
-use std::io::{Read, Write};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use dicer::{dashboard, lint::DEFAULT_BUDGET};
+use maud::PreEscaped;
+
+/// How often to check whether a watched file has changed.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["--batch"] => return batch(),
+        ["repl"] => return repl(),
+        ["lint", path] => return lint(path),
+        ["names"] => return names(&saved()),
+        ["names", path] => return names(path),
+        ["show", name] => return show(&saved(), name),
+        ["show", path, name] => return show(path, name),
+        ["completions", shell] => return completions(shell),
+        ["watch", path] => return watch(path, None),
+        ["watch", path, "--serve", port] => {
+            let port = port.parse().expect("port must be a number");
+            return watch(path, Some(port));
+        }
+        _ => (),
+    }
+
     let mut input = String::new();
     std::io::stdin()
         .lock()
@@ -17,3 +73,186 @@ fn main() {
     write!(stdout, "{}", figure.into_string()).unwrap();
     stdout.flush().unwrap();
 }
+
+fn batch() {
+    dicer::batch::run(
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+        DEFAULT_BUDGET,
+    )
+    .expect("failed to read from stdin or write to stdout");
+}
+
+fn repl() {
+    let mut session = dicer::session::Session::new();
+    let mut stdout = std::io::stdout().lock();
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        write!(stdout, "> ").unwrap();
+        stdout.flush().unwrap();
+        let Some(line) = lines.next() else {
+            writeln!(stdout).unwrap();
+            return;
+        };
+        let line = line.expect("failed to read input from stdin");
+        if !line.trim().is_empty() {
+            writeln!(stdout, "{}", session.respond(&line)).unwrap();
+        }
+    }
+}
+
+fn read(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| panic!("failed to read {path}: {e}"))
+}
+
+/// The file `dicer repl` saves macros to by default.
+fn saved() -> String {
+    let path = dicer::session::default_path().expect("no home directory for saved macros");
+    path.to_string_lossy().into_owned()
+}
+
+fn names(path: &str) {
+    let source = read(path);
+    let mut stdout = std::io::stdout().lock();
+    for (name, _) in dicer::lint::named(&source) {
+        writeln!(stdout, "{name}").unwrap();
+    }
+    stdout.flush().unwrap();
+}
+
+fn show(path: &str, name: &str) {
+    let source = read(path);
+    let (_, expression) = dicer::lint::named(&source)
+        .find(|(n, _)| *n == name)
+        .unwrap_or_else(|| panic!("no expression named {name} in {path}"));
+    let e: dicer::Closed = expression.parse().unwrap();
+    let d = e.distribution().unwrap();
+    let mut stdout = std::io::stdout().lock();
+    write!(
+        stdout,
+        "{}",
+        dicer::html::table_multi_dist(&[(name, d)]).into_string()
+    )
+    .unwrap();
+    stdout.flush().unwrap();
+}
+
+fn completions(shell: &str) {
+    let script = dicer::completions::script(shell).unwrap_or_else(|| {
+        panic!(
+            "no completions for {shell}; try {}",
+            dicer::completions::SHELLS.join(", ")
+        )
+    });
+    print!("{script}");
+}
+
+fn lint(path: &str) {
+    let source = read(path);
+    let findings = dicer::lint::lint(&source, DEFAULT_BUDGET);
+
+    let mut stdout = std::io::stdout().lock();
+    for finding in &findings {
+        writeln!(stdout, "{path}:{finding}").unwrap();
+    }
+    stdout.flush().unwrap();
+    if !findings.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// The latest rendering of the watched file, and how many times it's been rendered.
+type Latest = Arc<(Mutex<(u64, String)>, Condvar)>;
+
+fn watch(path: &str, port: Option<u16>) {
+    let latest: Latest = Default::default();
+    if let Some(port) = port {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .unwrap_or_else(|e| panic!("failed to listen on port {port}: {e}"));
+        eprintln!("serving {path} at http://localhost:{port}/");
+        let latest = latest.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let latest = latest.clone();
+                std::thread::spawn(move || serve(stream, &latest));
+            }
+        });
+    }
+
+    let mut modified = None;
+    loop {
+        let now = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if modified != Some(now) {
+            modified = Some(now);
+            let source = std::fs::read_to_string(path).unwrap_or_default();
+            let rows = dashboard::rows(&source, DEFAULT_BUDGET);
+            if port.is_none() {
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "--- {path}").unwrap();
+                for row in &rows {
+                    writeln!(stdout, "{row}").unwrap();
+                }
+                stdout.flush().unwrap();
+            }
+            let (lock, changed) = &*latest;
+            let mut latest = lock.lock().unwrap();
+            *latest = (latest.0 + 1, dashboard::table(&rows).into_string());
+            changed.notify_all();
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Respond to one HTTP request: the page, or a stream of events as the page changes.
+fn serve(mut stream: TcpStream, latest: &Latest) {
+    let mut request = String::new();
+    let mut reader = BufReader::new(&stream);
+    if reader.read_line(&mut request).is_err() {
+        return;
+    }
+    // Skip the headers.
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+        header.clear();
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (lock, changed) = &**latest;
+    if path == dashboard::EVENTS_PATH {
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\n\r\n";
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        let mut sent = 0;
+        loop {
+            let table = {
+                let guard = changed
+                    .wait_while(lock.lock().unwrap(), |(version, _)| *version == sent)
+                    .unwrap();
+                sent = guard.0;
+                guard.1.clone()
+            };
+            let mut event: String = table.lines().map(|l| format!("data: {l}\n")).collect();
+            event.push('\n');
+            if stream.write_all(event.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+
+    let (status, body) = if path == "/" {
+        let table = lock.lock().unwrap().1.clone();
+        ("200 OK", dashboard::page(PreEscaped(table)).into_string())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
@@ -0,0 +1,141 @@
+//! Guaranteed bounds on probabilities, from a [`Summary`] of an expression.
+//!
+//! When an expression is too large to evaluate exactly, its [`Summary`] may still have
+//! an exact mean and variance. Those are enough to bound the probability of the tails,
+//! with the one-sided Chebyshev (Cantelli) inequality and Hoeffding's inequality:
+//! the true probability is never beyond the bound.
+//!
+//! If the summary's moments are only approximate, the bounds use only its range,
+//! which is always guaranteed.
+
+use crate::Summary;
+
+/// An upper bound on the probability that the expression is at least `t`.
+pub fn at_least_upper_bound(summary: &Summary, t: i64) -> f64 {
+    if t > summary.max {
+        return 0.0;
+    }
+    if t <= summary.min || !summary.exact {
+        return 1.0;
+    }
+    let distance = t as f64 - summary.mean;
+    if distance <= 0.0 {
+        return 1.0;
+    }
+    let variance = summary.variance;
+    let cantelli = variance / (variance + distance * distance);
+    let hoeffding = if summary.range_squares > 0.0 {
+        (-2.0 * distance * distance / summary.range_squares).exp()
+    } else {
+        1.0
+    };
+    cantelli.min(hoeffding)
+}
+
+/// An upper bound on the probability that the expression is at most `t`.
+pub fn at_most_upper_bound(summary: &Summary, t: i64) -> f64 {
+    // The same as the upper tail of the negated expression.
+    let negated = Summary {
+        mean: -summary.mean,
+        min: summary.max.saturating_neg(),
+        max: summary.min.saturating_neg(),
+        ..*summary
+    };
+    at_least_upper_bound(&negated, t.saturating_neg())
+}
+
+/// A lower bound on the probability that the expression is at least `t`.
+pub fn at_least_lower_bound(summary: &Summary, t: i64) -> f64 {
+    match t.checked_sub(1) {
+        Some(below) => 1.0 - at_most_upper_bound(summary, below),
+        None => 1.0,
+    }
+}
+
+/// A lower bound on the probability that the expression is at most `t`.
+pub fn at_most_lower_bound(summary: &Summary, t: i64) -> f64 {
+    match t.checked_add(1) {
+        Some(above) => 1.0 - at_least_upper_bound(summary, above),
+        None => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    /// Check that the bounds contain the true probabilities of each tail.
+    fn check(expression: &str) {
+        let e: Closed = expression.parse().unwrap();
+        let summary = e.summarize().unwrap();
+        let d = e.distribution().unwrap();
+        for t in (d.min() - 2)..=(d.max() + 2) {
+            let at_least: f64 = d
+                .occurrences()
+                .filter(|(v, _)| *v >= t)
+                .map(|(v, _)| d.probability_f64(v))
+                .sum();
+            let at_most: f64 = d
+                .occurrences()
+                .filter(|(v, _)| *v <= t)
+                .map(|(v, _)| d.probability_f64(v))
+                .sum();
+            let slack = 1e-9;
+            assert!(
+                at_least <= at_least_upper_bound(&summary, t) + slack,
+                "{expression} >= {t}"
+            );
+            assert!(
+                at_least >= at_least_lower_bound(&summary, t) - slack,
+                "{expression} >= {t}"
+            );
+            assert!(
+                at_most <= at_most_upper_bound(&summary, t) + slack,
+                "{expression} <= {t}"
+            );
+            assert!(
+                at_most >= at_most_lower_bound(&summary, t) - slack,
+                "{expression} <= {t}"
+            );
+        }
+    }
+
+    #[test]
+    fn contain_probabilities() {
+        for e in [
+            "20d6",
+            "d20 + 5",
+            "10d10 - 5d4",
+            "4d6kh3",
+            "[X: d6] X * X",
+            "3",
+        ] {
+            check(e);
+        }
+    }
+
+    #[test]
+    fn tails() {
+        let summary = "100d6".parse::<Closed>().unwrap().summarize().unwrap();
+        assert!(summary.exact);
+        // Far out in the tail, Hoeffding's inequality is tight.
+        assert!(at_least_upper_bound(&summary, 500) < 1e-7);
+        assert!(at_most_upper_bound(&summary, 200) < 1e-7);
+        assert!(at_least_lower_bound(&summary, 200) > 1.0 - 1e-7);
+        // Closer in, Cantelli's inequality is.
+        let variance = 100.0 * 35.0 / 12.0;
+        let cantelli = variance / (variance + 25.0 * 25.0);
+        assert!((at_least_upper_bound(&summary, 375) - cantelli).abs() < 1e-9);
+        // Near the mean, nothing useful can be said.
+        assert_eq!(at_least_upper_bound(&summary, 350), 1.0);
+        // Beyond the range, the probability is zero.
+        assert_eq!(at_least_upper_bound(&summary, 601), 0.0);
+
+        // Without exact moments, only the range is used.
+        let summary = "100d6kh50".parse::<Closed>().unwrap().summarize().unwrap();
+        assert!(!summary.exact);
+        assert_eq!(at_least_upper_bound(&summary, 299), 1.0);
+        assert_eq!(at_least_upper_bound(&summary, 301), 0.0);
+    }
+}
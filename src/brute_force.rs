@@ -0,0 +1,653 @@
+//! Brute-force evaluation, for checking the [`Evaluator`] against.
+//!
+//! [`distribution`] evaluates an expression by enumerating every combination of rolls of
+//! every die in it, with none of the evaluator's shortcuts: no convolution, no memoization,
+//! and exact probabilities rather than counts of rolls. It's slow, and only practical for
+//! small expressions, but simple enough to check by eye.
+//! [`check`] compares the evaluator's distributions against it.
+//!
+//! This module is built with the `brute-force` feature, so that downstream crates can
+//! verify the evaluator on their own expressions.
+
+use std::collections::{BTreeMap, HashSet};
+
+use itertools::Itertools;
+use num::{CheckedAdd, CheckedDiv, CheckedMul, rational::Ratio};
+
+use crate::{
+    Closed, Distribution, Error, Evaluator,
+    discrete::{digit, digit_places, largest_set},
+    symbolic::{
+        Constant, CustomDie, Die, Explosion, ExpressionTree, ExpressionWrapper, Function, Ranker,
+        Symbol,
+    },
+};
+
+/// The combinations of rolls [`check`] enumerates before giving up on an expression.
+pub const DEFAULT_BUDGET: usize = 1_000_000;
+
+type Probability = Ratio<u128>;
+
+/// Each value an expression may take, with its probability.
+type Outcomes = BTreeMap<i64, Probability>;
+
+/// Evaluate the expression by enumerating every combination of rolls, with exploding dice
+/// exploding at most `explosion_depth` times.
+///
+/// Fails with [`Error::OverBudget`] if that takes more than `budget` combinations of rolls,
+/// and otherwise with the errors the [`Evaluator`] fails with.
+pub fn distribution(
+    e: &Closed,
+    explosion_depth: usize,
+    budget: usize,
+) -> Result<Distribution, Error> {
+    let mut enumerator = Enumerator {
+        explosion_depth,
+        budget,
+        spent: 0,
+        scopes: Vec::new(),
+    };
+    let outcomes = enumerator.outcomes(e)?;
+    let overflow = || Error::Overflow(e.to_string());
+    // Put the probabilities over a common denominator, as counts of rolls.
+    let common = outcomes.values().try_fold(1u128, |lcm, p| {
+        (lcm / num::integer::gcd(lcm, *p.denom())).checked_mul(*p.denom())
+    });
+    let common = common.ok_or_else(overflow)?;
+    let occurrences: Result<Vec<(i64, usize)>, Error> = outcomes
+        .iter()
+        .map(|(v, p)| {
+            let o = p.numer() * (common / p.denom());
+            Ok((*v, usize::try_from(o).map_err(|_| overflow())?))
+        })
+        .collect();
+    Ok(Distribution::from_occurrences(occurrences?).expect("expressions have outcomes"))
+}
+
+/// An evaluator's result that disagrees with brute force.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub expression: String,
+    /// The evaluator that disagreed.
+    pub evaluator: &'static str,
+    /// The result by brute force.
+    pub expected: Result<Distribution, Error>,
+    /// The evaluator's result.
+    pub got: Result<Distribution, Error>,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |result: &Result<Distribution, Error>| match result {
+            Ok(d) => d
+                .occurrences()
+                .map(|(v, o)| format!("{v}: {}", Ratio::new(o, d.total())))
+                .join(", "),
+            Err(e) => format!("error: {e}"),
+        };
+        write!(
+            f,
+            "the {} disagrees with brute force on {}: expected {{{}}}, got {{{}}}",
+            self.evaluator,
+            self.expression,
+            describe(&self.expected),
+            describe(&self.got)
+        )
+    }
+}
+
+/// Check the evaluator's distribution of the expression, with and without memoization,
+/// against the brute-force [`distribution`].
+///
+/// The two agree if they give each value the same probability, or if both fail.
+/// The evaluator may also fail with [`Error::Overflow`] where brute force succeeds,
+/// since it counts rolls in machine integers.
+/// Expressions that take more than [`DEFAULT_BUDGET`] combinations of rolls to enumerate
+/// aren't checked.
+pub fn check(e: &Closed) -> Result<(), Box<Mismatch>> {
+    let depth = Evaluator::DEFAULT_EXPLOSION_DEPTH;
+    let expected = match distribution(e, depth, DEFAULT_BUDGET) {
+        Err(Error::OverBudget(_)) => return Ok(()),
+        expected => expected,
+    };
+    for (evaluator, memoize) in [("evaluator", false), ("memoized evaluator", true)] {
+        let got = Evaluator::new(memoize).with_explosion_depth(depth).eval(e);
+        let agree = match (&expected, &got) {
+            (Ok(a), Ok(b)) => {
+                a.occurrences()
+                    .map(|(v, _)| v)
+                    .eq(b.occurrences().map(|(v, _)| v))
+                    && a.occurrences()
+                        .all(|(v, _)| a.probability(v) == b.probability(v))
+            }
+            (Err(_), Err(_)) | (Ok(_), Err(Error::Overflow(_))) => true,
+            _ => false,
+        };
+        if !agree {
+            return Err(Box::new(Mismatch {
+                expression: e.to_string(),
+                evaluator,
+                expected,
+                got,
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// State for enumerating the rolls of an expression.
+struct Enumerator {
+    explosion_depth: usize,
+    budget: usize,
+    /// Combinations of rolls enumerated so far.
+    spent: usize,
+    /// The values of bound symbols, innermost last.
+    scopes: Vec<(Symbol, i64)>,
+}
+
+impl Enumerator {
+    /// Record that `n` combinations are about to be enumerated for the expression.
+    fn spend(&mut self, e: &Closed, n: usize) -> Result<(), Error> {
+        self.spent = self.spent.saturating_add(n);
+        if self.spent > self.budget {
+            Err(Error::OverBudget(e.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Every combination of one outcome from each list, with its probability.
+    fn combinations<T: Clone>(
+        &mut self,
+        e: &Closed,
+        lists: &[Vec<(T, Probability)>],
+    ) -> Result<Vec<(Vec<T>, Probability)>, Error> {
+        let n = lists
+            .iter()
+            .fold(1usize, |acc, l| acc.saturating_mul(l.len()));
+        self.spend(e, n)?;
+        if lists.is_empty() {
+            return Ok(vec![(Vec::new(), Probability::from_integer(1))]);
+        }
+        lists
+            .iter()
+            .map(|l| l.iter())
+            .multi_cartesian_product()
+            .map(|combination| {
+                let p = combination
+                    .iter()
+                    .try_fold(Probability::from_integer(1), |acc, (_, p)| {
+                        acc.checked_mul(p)
+                    })
+                    .ok_or_else(|| Error::Overflow(e.to_string()))?;
+                Ok((combination.into_iter().map(|(v, _)| v.clone()).collect(), p))
+            })
+            .collect()
+    }
+
+    /// The outcomes of the expression, as a list.
+    fn list(&mut self, e: &Closed) -> Result<Vec<(i64, Probability)>, Error> {
+        Ok(self.outcomes(e)?.into_iter().collect())
+    }
+
+    /// The outcomes of each of the expressions, and then the result of `f` on each
+    /// combination of them, which fails if `f` returns None.
+    fn combine(
+        &mut self,
+        e: &Closed,
+        items: &[&Closed],
+        f: impl Fn(&[i64]) -> Result<Option<i64>, Error>,
+    ) -> Result<Outcomes, Error> {
+        let lists: Vec<_> = items
+            .iter()
+            .map(|item| self.list(item))
+            .collect::<Result<_, _>>()?;
+        let mut outcomes = Outcomes::new();
+        for (values, p) in self.combinations(e, &lists)? {
+            let value = f(&values)?.ok_or_else(|| Error::Overflow(e.to_string()))?;
+            add(e, &mut outcomes, value, p)?;
+        }
+        Ok(outcomes)
+    }
+
+    fn outcomes(&mut self, e: &Closed) -> Result<Outcomes, Error> {
+        let overflow = || Error::Overflow(e.to_string());
+        let uniform = |faces: &mut dyn Iterator<Item = i64>, n: u128| -> Result<Outcomes, Error> {
+            let mut outcomes = Outcomes::new();
+            for face in faces {
+                add(e, &mut outcomes, face, Probability::new(1, n))?;
+            }
+            Ok(outcomes)
+        };
+        Ok(match e.inner() {
+            ExpressionTree::Modifier(Constant(c)) => [(
+                i64::try_from(*c).map_err(|_| overflow())?,
+                Probability::from_integer(1),
+            )]
+            .into(),
+            ExpressionTree::Die(Die(n)) => {
+                let max = i64::try_from(*n).map_err(|_| overflow())?;
+                self.spend(e, *n)?;
+                uniform(&mut (1..=max), *n as u128)?
+            }
+            ExpressionTree::CustomDie(CustomDie::Range(lo, hi)) => {
+                let n = hi.abs_diff(*lo) as u128 + 1;
+                self.spend(e, usize::try_from(n).unwrap_or(usize::MAX))?;
+                uniform(&mut (*lo..=*hi), n)?
+            }
+            ExpressionTree::CustomDie(CustomDie::Named { faces, .. } | CustomDie::Faces(faces)) => {
+                self.spend(e, faces.len())?;
+                uniform(&mut faces.iter().copied(), faces.len() as u128)?
+            }
+            ExpressionTree::CustomDie(CustomDie::Fudge) => uniform(&mut (-1..=1), 3)?,
+            ExpressionTree::CustomDie(CustomDie::Weighted(faces)) => {
+                self.spend(e, faces.len())?;
+                let total: u128 = faces.iter().map(|(_, w)| *w as u128).sum();
+                let mut outcomes = Outcomes::new();
+                for (face, weight) in faces {
+                    add(
+                        e,
+                        &mut outcomes,
+                        *face,
+                        Probability::new(*weight as u128, total),
+                    )?;
+                }
+                outcomes
+            }
+            ExpressionTree::CustomDie(CustomDie::Digits(sizes)) => {
+                let places = digit_places(sizes).ok_or_else(overflow)?;
+                let dice: Vec<Vec<(i64, Probability)>> = sizes
+                    .iter()
+                    .zip(places)
+                    .map(|(size, place)| {
+                        (1..=*size as i64)
+                            .map(|face| (face * place, Probability::new(1, *size as u128)))
+                            .collect()
+                    })
+                    .collect();
+                let mut outcomes = Outcomes::new();
+                for (digits, p) in self.combinations(e, &dice)? {
+                    add(e, &mut outcomes, digits.iter().sum(), p)?;
+                }
+                outcomes
+            }
+            ExpressionTree::Symbol(symbol) => {
+                let (_, value) = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find(|(s, _)| s == symbol)
+                    .ok_or_else(|| Error::UnboundSymbols(HashSet::from([symbol.clone()])))?;
+                [(*value, Probability::from_integer(1))].into()
+            }
+            ExpressionTree::Negated(a) => {
+                self.combine(e, &[a.as_ref()], |v| Ok(v[0].checked_neg()))?
+            }
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker,
+            } => self.repeat(e, count, value, ranker)?,
+            ExpressionTree::Product(a, b) => {
+                self.combine(e, &[a.as_ref(), b.as_ref()], |v| Ok(v[0].checked_mul(v[1])))?
+            }
+            ExpressionTree::Floor(a, b) | ExpressionTree::Modulo(a, b) => {
+                let op = if matches!(e.inner(), ExpressionTree::Floor(..)) {
+                    i64::checked_div
+                } else {
+                    i64::checked_rem_euclid
+                };
+                self.combine(e, &[a.as_ref(), b.as_ref()], |v| {
+                    if v[1] == 0 {
+                        return Err(Error::DivideByZero(e.to_string()));
+                    }
+                    Ok(op(v[0], v[1]))
+                })?
+            }
+            ExpressionTree::Sum(items) => {
+                let items: Vec<&Closed> = items.iter().collect();
+                self.combine(e, &items, |v| {
+                    Ok(v.iter().try_fold(0i64, |acc, v| acc.checked_add(*v)))
+                })?
+            }
+            ExpressionTree::Comparison { a, b, op } => {
+                self.combine(e, &[a.as_ref(), b.as_ref()], |v| {
+                    Ok(Some(op.compare(v[0], v[1]) as i64))
+                })?
+            }
+            ExpressionTree::Binding {
+                symbol,
+                value,
+                tail,
+            } => {
+                let mut outcomes = Outcomes::new();
+                for (v, p) in self.list(value)? {
+                    self.scopes.push((symbol.clone(), v));
+                    let tail = self.outcomes(tail);
+                    self.scopes.pop();
+                    for (t, q) in tail? {
+                        add(e, &mut outcomes, t, p.checked_mul(&q).ok_or_else(overflow)?)?;
+                    }
+                }
+                outcomes
+            }
+            ExpressionTree::Call { function, args } => {
+                let args: Vec<&Closed> = args.iter().collect();
+                self.combine(e, &args, |v| {
+                    Ok(match (function, v) {
+                        (Function::Soak, [damage, armor]) => {
+                            if damage <= armor {
+                                Some(0)
+                            } else {
+                                damage.checked_sub(*armor)
+                            }
+                        }
+                        (Function::Tens, [x]) => Some(digit(*x, 1)),
+                        (Function::Units, [x]) => Some(digit(*x, 0)),
+                        (Function::Abs, [x]) => x.checked_abs(),
+                        (Function::Max, [a, b]) => Some(*a.max(b)),
+                        (Function::Min, [a, b]) => Some(*a.min(b)),
+                        (Function::Clamp, [x, lo, hi]) => Some(*x.max(lo).min(hi)),
+                        (Function::Save, [damage, saved]) => {
+                            Some(if *saved != 0 { damage / 2 } else { *damage })
+                        }
+                        (Function::If, [condition, then, otherwise]) => {
+                            Some(if *condition != 0 { *then } else { *otherwise })
+                        }
+                        _ => panic!("wrong number of arguments to {function} in {e}"),
+                    })
+                })?
+            }
+            ExpressionTree::Explode { .. } => {
+                let mut outcomes = Outcomes::new();
+                for (rolls, p) in self.rolls(e)? {
+                    let total = rolls.iter().try_fold(0i64, |acc, v| acc.checked_add(*v));
+                    add(e, &mut outcomes, total.ok_or_else(overflow)?, p)?;
+                }
+                outcomes
+            }
+            ExpressionTree::Reroll { value, reroll } => {
+                let first = self.list(value)?;
+                let mut outcomes = Outcomes::new();
+                if reroll.recursive {
+                    // Rerolling until a face is kept is the same as never rolling the others.
+                    let kept: Vec<_> = first.iter().filter(|(v, _)| !reroll.applies(*v)).collect();
+                    let total = kept
+                        .iter()
+                        .try_fold(Probability::from_integer(0), |acc, (_, p)| {
+                            acc.checked_add(p)
+                        })
+                        .ok_or_else(overflow)?;
+                    if kept.is_empty() {
+                        return Err(Error::RerollsForever(e.to_string()));
+                    }
+                    for (v, p) in kept {
+                        add(
+                            e,
+                            &mut outcomes,
+                            *v,
+                            p.checked_div(&total).ok_or_else(overflow)?,
+                        )?;
+                    }
+                } else {
+                    let pairs = [first.clone(), first.clone()];
+                    for (rolls, p) in self.combinations(e, &pairs)? {
+                        let kept = if reroll.applies(rolls[0]) {
+                            rolls[1]
+                        } else {
+                            rolls[0]
+                        };
+                        add(e, &mut outcomes, kept, p)?;
+                    }
+                }
+                outcomes
+            }
+            ExpressionTree::Successes { value, successes } => {
+                let mut outcomes = Outcomes::new();
+                for (rolls, p) in self.rolls(value)? {
+                    add(
+                        e,
+                        &mut outcomes,
+                        rolls.iter().map(|v| successes.score(*v)).sum(),
+                        p,
+                    )?;
+                }
+                outcomes
+            }
+            ExpressionTree::Lookup { value, bands } => {
+                self.combine(e, &[value.as_ref()], |v| Ok(Some(bands.apply(v[0]))))?
+            }
+        })
+    }
+
+    /// The rolls of a single (modified) die, each with its probability.
+    ///
+    /// Each roll of an exploding die is listed separately. A compounding or penetrating die
+    /// has a single roll, its total, with each reroll after the first counting one less
+    /// if it penetrates. So does an imploding die, with each reroll subtracted.
+    /// Other dice have a single roll.
+    fn rolls(&mut self, e: &Closed) -> Result<Vec<(Vec<i64>, Probability)>, Error> {
+        let ExpressionTree::Explode {
+            value,
+            explosion,
+            trigger,
+        } = e.inner()
+        else {
+            return Ok(self
+                .list(e)?
+                .into_iter()
+                .map(|(v, p)| (vec![v], p))
+                .collect());
+        };
+        let die = self.list(value)?;
+        let face = match explosion {
+            Explosion::Imploding => die.first(),
+            _ => die.last(),
+        };
+        let face = face.expect("dice have faces").0;
+        // How a roll after the first counts.
+        let later = |v: i64| match explosion {
+            Explosion::Penetrating => v.checked_sub(1),
+            Explosion::Imploding => v.checked_neg(),
+            _ => Some(v),
+        };
+        // Roll the die once for each explosion allowed, and stop at the first that doesn't.
+        let rolls = vec![die; self.explosion_depth + 1];
+        let mut result = BTreeMap::new();
+        for (faces, p) in self.combinations(e, &rolls)? {
+            let stop = faces[..self.explosion_depth]
+                .iter()
+                .position(|v| !trigger.explodes(face, *v))
+                .unwrap_or(self.explosion_depth);
+            let counted: Option<Vec<i64>> = faces[..=stop]
+                .iter()
+                .enumerate()
+                .map(|(i, v)| if i == 0 { Some(*v) } else { later(*v) })
+                .collect();
+            let mut counted = counted.ok_or_else(|| Error::Overflow(e.to_string()))?;
+            if *explosion != Explosion::Standard {
+                let total = counted.iter().try_fold(0i64, |acc, v| acc.checked_add(*v));
+                counted = vec![total.ok_or_else(|| Error::Overflow(e.to_string()))?];
+            }
+            let entry: &mut Probability = result.entry(counted).or_default();
+            *entry = entry
+                .checked_add(&p)
+                .ok_or_else(|| Error::Overflow(e.to_string()))?;
+        }
+        Ok(result.into_iter().collect())
+    }
+
+    fn repeat(
+        &mut self,
+        e: &Closed,
+        count: &Closed,
+        value: &Closed,
+        ranker: &Ranker<Box<Closed>>,
+    ) -> Result<Outcomes, Error> {
+        let counts = self.list(count)?;
+        let keeps = match ranker.count() {
+            Some(keep) => self.list(keep)?,
+            None => vec![(0, Probability::from_integer(1))],
+        };
+        let die = self.rolls(value)?;
+        let overflow = || Error::Overflow(e.to_string());
+        let mut outcomes = Outcomes::new();
+        for (n, count_p) in counts {
+            for (k, keep_p) in &keeps {
+                if n < 0 || *k < 0 {
+                    return Err(Error::NegativeCount(e.to_string()));
+                }
+                let ranker = ranker.map(|_| *k as usize);
+                if (n as usize) < ranker.min_count() {
+                    return Err(ranker.too_few(e.to_string()));
+                }
+                let p = count_p.checked_mul(keep_p).ok_or_else(overflow)?;
+                let dice = vec![die.clone(); n as usize];
+                for (rolls, q) in self.combinations(e, &dice)? {
+                    let mut pool: Vec<i64> = rolls.concat();
+                    let value = if ranker == Ranker::Matching {
+                        Some(largest_set(&mut pool))
+                    } else {
+                        if ranker.keeps_highest() {
+                            pool.sort_by(|a, b| b.cmp(a));
+                        } else if ranker != Ranker::All {
+                            pool.sort();
+                        }
+                        let keep = if ranker == Ranker::All {
+                            pool.len()
+                        } else {
+                            ranker.keep(n) as usize
+                        };
+                        pool[..keep]
+                            .iter()
+                            .try_fold(0i64, |acc, v| acc.checked_add(*v))
+                    };
+                    let q = q.checked_mul(&p).ok_or_else(overflow)?;
+                    add(e, &mut outcomes, value.ok_or_else(overflow)?, q)?;
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Add the probability to the value's outcome.
+fn add(e: &Closed, outcomes: &mut Outcomes, value: i64, p: Probability) -> Result<(), Error> {
+    let entry = outcomes.entry(value).or_default();
+    *entry = entry
+        .checked_add(&p)
+        .ok_or_else(|| Error::Overflow(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate::Constraints,
+        quiz::{question, simple},
+        sample::SplitMix64,
+    };
+
+    fn brute(s: &str) -> Result<Distribution, Error> {
+        distribution(&s.parse().unwrap(), 2, DEFAULT_BUDGET)
+    }
+
+    #[test]
+    fn small_expressions() {
+        let d = brute("2d6").unwrap();
+        assert_eq!(d.probability(7), Ratio::new(1, 6));
+        let d = brute("[X: d6] X - X").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(1, 1));
+        // Explodes at most twice.
+        let d = brute("d4!").unwrap();
+        assert_eq!(d.probability(12), Ratio::new(1, 64));
+        assert_eq!(d.max(), 12);
+        assert!(matches!(
+            brute("d6 / (d2 - 1)"),
+            Err(Error::DivideByZero(_))
+        ));
+        assert!(matches!(brute("d2rr<=2"), Err(Error::RerollsForever(_))));
+        assert!(matches!(
+            distribution(&"10d10".parse().unwrap(), 2, 1000),
+            Err(Error::OverBudget(_))
+        ));
+    }
+
+    #[test]
+    fn agrees_with_evaluator() {
+        for e in [
+            "3d6",
+            "4d6kh3",
+            "4d6dl1 + d4",
+            "3d6kl2",
+            "(d4)d6dh1",
+            "(d3)d6kh(d2)",
+            "4d6m",
+            "3d6!kh2",
+            "d6!! + d6!p",
+            "3d6!!kh2",
+            "2d4!pkl",
+            "3d4!ikh2",
+            "d6!i<=2",
+            "d10!>=9",
+            "3d10s>=6f1",
+            "2d6!s>=5",
+            "2d20ro=1kh",
+            "d6rr<3",
+            "d20gwf",
+            "[X: d6] (X)d4",
+            "[ATK: d20] (ATK = 20) * 2d4 + (ATK < 20) * (ATK + 4 >= 12) * d4",
+            "d6 / d3 + d6 % d4",
+            "-d6 * d{-1,0,1,5}",
+            "dF + d{1:3,2:1} + d[-2..2] + d66",
+            "tens(d100) + units(d100)",
+            "abs(d6 - d6) + max(d4, d4) - min(d4, d4)",
+            "clamp(2d6, 4, 9)",
+            "save(3d6, d20 >= 12)",
+            "soak(2d6, d4)",
+            "if(d2 - 1, d6, d8)",
+            "d20 -> {..5: -1, 16..: 1}",
+            "0d6",
+            "(d2 - 2)d6",
+            "3d6kh4",
+        ] {
+            let e: Closed = e.parse().unwrap();
+            if let Err(mismatch) = check(&e) {
+                panic!("{mismatch}");
+            }
+        }
+    }
+
+    #[test]
+    fn agrees_on_quiz_questions() {
+        let mut rng = SplitMix64::new(532);
+        let constraints = Constraints {
+            dice: 3,
+            faces: vec![4, 6, 8, 10],
+            ..simple()
+        };
+        for _ in 0..50 {
+            let q = question(&mut rng, &constraints).unwrap();
+            if let Err(mismatch) = check(&q.expression) {
+                panic!("{mismatch}");
+            }
+        }
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let e: Closed = "d4".parse().unwrap();
+        let mismatch = Mismatch {
+            expression: e.to_string(),
+            evaluator: "evaluator",
+            expected: brute("d4"),
+            got: brute("d2"),
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "the evaluator disagrees with brute force on d4: \
+             expected {1: 1/4, 2: 1/4, 3: 1/4, 4: 1/4}, got {1: 1/2, 2: 1/2}"
+        );
+    }
+}
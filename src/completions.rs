@@ -0,0 +1,247 @@
+//! Shell completion scripts for the CLI, as `dicer completions SHELL` prints them.
+//!
+//! Each script is generated from [`SUBCOMMANDS`], so a subcommand and what its arguments
+//! complete to are written down once. Names are completed when the shell asks for them, by
+//! running `dicer names`: the macros saved from `dicer repl`, or the expressions in a file.
+
+/// What an argument completes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    /// Any file.
+    File,
+    /// The names of the macros saved to [`default_path`](crate::session::default_path),
+    /// as `dicer names` lists them.
+    Saved,
+    /// The names in the file given as the previous argument, as `dicer names FILE` lists them.
+    NamesInFile,
+    /// One of these words.
+    Words(&'static [&'static str]),
+}
+
+/// A subcommand of the CLI, and what its arguments complete to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subcommand {
+    pub name: &'static str,
+    /// What the subcommand does, for shells that show it.
+    pub about: &'static str,
+    /// What each argument, in order, completes to.
+    pub args: &'static [&'static [Completion]],
+}
+
+/// The shells [`script`] writes completions for.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// The subcommands of the CLI.
+pub const SUBCOMMANDS: &[Subcommand] = {
+    use Completion::*;
+    &[
+        Subcommand {
+            name: "--batch",
+            about: "Evaluate expressions from stdin as JSON",
+            args: &[],
+        },
+        Subcommand {
+            name: "lint",
+            about: "Check a file of named expressions",
+            args: &[&[File]],
+        },
+        Subcommand {
+            name: "names",
+            about: "List the saved macros, or the names in a file",
+            args: &[&[File]],
+        },
+        Subcommand {
+            name: "repl",
+            about: "Start an interactive session",
+            args: &[],
+        },
+        Subcommand {
+            name: "show",
+            about: "Show a saved macro, or a named expression in a file",
+            args: &[&[Saved, File], &[NamesInFile]],
+        },
+        Subcommand {
+            name: "watch",
+            about: "Evaluate a file as it changes",
+            args: &[&[File], &[Words(&["--serve"])]],
+        },
+        Subcommand {
+            name: "completions",
+            about: "Print a completion script",
+            args: &[&[Words(SHELLS)]],
+        },
+    ]
+};
+
+/// The completion script for the shell, or None if it's not one of [`SHELLS`].
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+/// Each argument of each subcommand: the subcommand's name, the argument's index, and what it
+/// completes to.
+fn arguments() -> impl Iterator<Item = (&'static str, usize, &'static [Completion])> {
+    SUBCOMMANDS.iter().flat_map(|subcommand| {
+        (0..)
+            .zip(subcommand.args)
+            .map(|(i, completions)| (subcommand.name, i, *completions))
+    })
+}
+
+/// Completions for bash: `source <(dicer completions bash)`.
+fn bash() -> String {
+    let words = |words: &[&str]| format!("$(compgen -W $'{}' -- \"$cur\")", words.join("\\n"));
+    let names: Vec<&str> = SUBCOMMANDS.iter().map(|s| s.name).collect();
+    let mut script = format!(
+        r#"_dicer() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local IFS=$'\n'
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=({})
+        return
+    fi
+    case "${{COMP_WORDS[1]}} $COMP_CWORD" in
+"#,
+        words(&names)
+    );
+    for (name, i, completions) in arguments() {
+        let replies: Vec<String> = completions
+            .iter()
+            .map(|completion| match completion {
+                Completion::File => "$(compgen -f -- \"$cur\")".to_string(),
+                Completion::Saved => {
+                    "$(compgen -W \"$(dicer names 2>/dev/null)\" -- \"$cur\")".to_string()
+                }
+                Completion::NamesInFile => "$(compgen -W \"$(dicer names \
+                     \"${COMP_WORDS[COMP_CWORD-1]}\" 2>/dev/null)\" -- \"$cur\")"
+                    .to_string(),
+                Completion::Words(w) => words(w),
+            })
+            .collect();
+        script += &format!(
+            "    \"{name} {}\") COMPREPLY=({}) ;;\n",
+            i + 2,
+            replies.join(" ")
+        );
+    }
+    script += "    esac\n}\ncomplete -F _dicer dicer\n";
+    script
+}
+
+/// Completions for zsh: `source <(dicer completions zsh)`.
+fn zsh() -> String {
+    let names: Vec<&str> = SUBCOMMANDS.iter().map(|s| s.name).collect();
+    let mut script = format!(
+        r#"#compdef dicer
+_dicer() {{
+    if (( CURRENT == 2 )); then
+        compadd -- {}
+        return
+    fi
+    case "$words[2] $CURRENT" in
+"#,
+        names.join(" ")
+    );
+    for (name, i, completions) in arguments() {
+        let commands: Vec<String> = completions
+            .iter()
+            .map(|completion| match completion {
+                Completion::File => "_files".to_string(),
+                Completion::Saved => "compadd -- ${(f)\"$(dicer names 2>/dev/null)\"}".to_string(),
+                Completion::NamesInFile => {
+                    "compadd -- ${(f)\"$(dicer names $words[CURRENT-1] 2>/dev/null)\"}".to_string()
+                }
+                Completion::Words(w) => format!("compadd -- {}", w.join(" ")),
+            })
+            .collect();
+        script += &format!("    \"{name} {}\") {} ;;\n", i + 3, commands.join("; "));
+    }
+    script += "    esac\n}\ncompdef _dicer dicer\n";
+    script
+}
+
+/// Completions for fish: `dicer completions fish | source`.
+fn fish() -> String {
+    // fish reads a leading `--` as an option to `complete`, so long options are given as such.
+    let word = |word: &str| match word.strip_prefix("--") {
+        Some(long) => format!("-l {long}"),
+        None => format!("-a {word}"),
+    };
+    let mut script = "complete -c dicer -f\n".to_string();
+    for subcommand in SUBCOMMANDS {
+        script += &format!(
+            "complete -c dicer -n __fish_use_subcommand {} -d '{}'\n",
+            word(subcommand.name),
+            subcommand.about
+        );
+    }
+    for (name, i, completions) in arguments() {
+        let condition = format!(
+            "'__fish_seen_subcommand_from {name}; and test (count (commandline -opc)) -eq {}'",
+            i + 2
+        );
+        for completion in completions {
+            let candidates = match completion {
+                Completion::File => vec!["-F".to_string()],
+                Completion::Saved => vec!["-a '(dicer names 2>/dev/null)'".to_string()],
+                Completion::NamesInFile => {
+                    vec!["-a '(dicer names (commandline -opc)[-1] 2>/dev/null)'".to_string()]
+                }
+                Completion::Words(w) => w.iter().map(|w| word(w)).collect(),
+            };
+            for candidates in candidates {
+                script += &format!("complete -c dicer -n {condition} {candidates}\n");
+            }
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subcommand() {
+        for shell in SHELLS {
+            let script = script(shell).unwrap();
+            for subcommand in SUBCOMMANDS {
+                assert!(
+                    script.contains(subcommand.name.trim_start_matches('-')),
+                    "{shell}: {}",
+                    subcommand.name
+                );
+            }
+            // Names are listed when completing: once for saved macros, once for a file's.
+            assert_eq!(script.matches("dicer names").count(), 2, "{shell}");
+        }
+        assert_eq!(script("tcsh"), None);
+    }
+
+    #[test]
+    fn arguments_by_position() {
+        let bash = script("bash").unwrap();
+        assert!(bash.contains(r#""show 2") COMPREPLY=($(compgen -W "$(dicer names 2>/dev/null)""#));
+        assert!(bash.contains(
+            r#""show 3") COMPREPLY=($(compgen -W "$(dicer names "${COMP_WORDS[COMP_CWORD-1]}""#
+        ));
+        assert!(bash.contains(r#""completions 2") COMPREPLY=($(compgen -W $'bash\nzsh\nfish'"#));
+
+        let zsh = script("zsh").unwrap();
+        assert!(
+            zsh.contains(r#""show 3") compadd -- ${(f)"$(dicer names 2>/dev/null)"}; _files ;;"#)
+        );
+        assert!(zsh.contains(r#""watch 4") compadd -- --serve ;;"#));
+
+        let fish = script("fish").unwrap();
+        assert!(fish.contains("complete -c dicer -n __fish_use_subcommand -l batch"));
+        assert!(fish.contains(
+            "complete -c dicer -n '__fish_seen_subcommand_from watch; and test (count (commandline -opc)) -eq 3' -l serve"
+        ));
+    }
+}
@@ -0,0 +1,254 @@
+//! Known results for popular mechanics, worked out independently of this crate.
+//!
+//! Each [`Entry`] pairs an expression with an exact fact about it: its whole distribution,
+//! its mean, or the chance of rolling a target or more. They serve as regression anchors,
+//! checked against the evaluator by [`Entry::diff`], and as worked examples of the syntax.
+//!
+//! This module is built with the `corpus` feature.
+
+use num::rational::Ratio;
+
+use crate::{Closed, RandomVariable, testing::distribution_diff};
+
+/// The sums of 2d6, out of 36 rolls.
+const TWO_D6: [(i64, usize); 11] = [
+    (2, 1),
+    (3, 2),
+    (4, 3),
+    (5, 4),
+    (6, 5),
+    (7, 6),
+    (8, 5),
+    (9, 4),
+    (10, 3),
+    (11, 2),
+    (12, 1),
+];
+
+/// The sums of 3d6, out of 216 rolls.
+const THREE_D6: [(i64, usize); 16] = [
+    (3, 1),
+    (4, 3),
+    (5, 6),
+    (6, 10),
+    (7, 15),
+    (8, 21),
+    (9, 25),
+    (10, 27),
+    (11, 27),
+    (12, 25),
+    (13, 21),
+    (14, 15),
+    (15, 10),
+    (16, 6),
+    (17, 3),
+    (18, 1),
+];
+
+/// Ability scores rolled as 4d6, dropping the lowest, out of 1296 rolls.
+const FOUR_D6_KEEP_3: [(i64, usize); 16] = [
+    (3, 1),
+    (4, 4),
+    (5, 10),
+    (6, 21),
+    (7, 38),
+    (8, 62),
+    (9, 91),
+    (10, 122),
+    (11, 148),
+    (12, 167),
+    (13, 172),
+    (14, 160),
+    (15, 131),
+    (16, 94),
+    (17, 54),
+    (18, 21),
+];
+
+/// The sums of 4dF, out of 81 rolls.
+const FOUR_DF: [(i64, usize); 9] = [
+    (-4, 1),
+    (-3, 4),
+    (-2, 10),
+    (-1, 16),
+    (0, 19),
+    (1, 16),
+    (2, 10),
+    (3, 4),
+    (4, 1),
+];
+
+/// For each target, the chance in 400 of a d20 with advantage, and with disadvantage,
+/// rolling it or more.
+const ADVANTAGE: [(i64, usize, usize); 20] = [
+    (1, 400, 400),
+    (2, 399, 361),
+    (3, 396, 324),
+    (4, 391, 289),
+    (5, 384, 256),
+    (6, 375, 225),
+    (7, 364, 196),
+    (8, 351, 169),
+    (9, 336, 144),
+    (10, 319, 121),
+    (11, 300, 100),
+    (12, 279, 81),
+    (13, 256, 64),
+    (14, 231, 49),
+    (15, 204, 36),
+    (16, 175, 25),
+    (17, 144, 16),
+    (18, 111, 9),
+    (19, 76, 4),
+    (20, 39, 1),
+];
+
+/// A fact about an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Known {
+    /// The probability of each value; values not listed never occur.
+    Distribution(Vec<(i64, Ratio<usize>)>),
+    /// The mean.
+    Mean(Ratio<i64>),
+    /// The probability of rolling the value or more.
+    AtLeast(i64, Ratio<usize>),
+}
+
+impl std::fmt::Display for Known {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Known::Distribution(table) => {
+                for (i, (value, p)) in table.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}: {p}")?;
+                }
+                Ok(())
+            }
+            Known::Mean(mean) => write!(f, "mean {mean}"),
+            Known::AtLeast(value, p) => write!(f, "{value} or more: {p}"),
+        }
+    }
+}
+
+/// An expression, and a fact about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// What the expression models.
+    pub name: String,
+    pub expression: String,
+    pub known: Known,
+}
+
+impl Entry {
+    /// How the evaluator's result for the expression differs from the known fact,
+    /// or None if it doesn't.
+    pub fn diff(&self) -> Option<String> {
+        let d = match self
+            .expression
+            .parse::<Closed>()
+            .and_then(|e| e.distribution())
+        {
+            Ok(d) => d,
+            Err(e) => return Some(e.to_string()),
+        };
+        match &self.known {
+            Known::Distribution(table) => distribution_diff(&d, table.iter().copied()),
+            Known::Mean(mean) => {
+                let sum: i128 = d.occurrences().map(|(v, o)| v as i128 * o as i128).sum();
+                let got = Ratio::new(sum, d.total() as i128);
+                let want = Ratio::new(*mean.numer() as i128, *mean.denom() as i128);
+                (got != want).then(|| format!("mean: expected {want}, got {got}"))
+            }
+            Known::AtLeast(value, p) => {
+                let got = d.probability_where(|v| v >= *value);
+                (got != *p).then(|| format!("{value} or more: expected {p}, got {got}"))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.name, self.expression, self.known)
+    }
+}
+
+/// Every entry in the corpus.
+pub fn iter() -> impl Iterator<Item = Entry> {
+    let distribution = |name: &str, expression: &str, table: &[(i64, usize)], total| Entry {
+        name: name.to_owned(),
+        expression: expression.to_owned(),
+        known: Known::Distribution(
+            table
+                .iter()
+                .map(|(v, o)| (*v, Ratio::new(*o, total)))
+                .collect(),
+        ),
+    };
+    let fixed = [
+        distribution("two six-sided dice", "2d6", &TWO_D6, 36),
+        distribution("three six-sided dice", "3d6", &THREE_D6, 216),
+        distribution(
+            "ability score, 4d6 drop lowest",
+            "4d6kh3",
+            &FOUR_D6_KEEP_3,
+            1296,
+        ),
+        Entry {
+            name: "ability score, 4d6 drop lowest".to_owned(),
+            expression: "4d6kh3".to_owned(),
+            known: Known::Mean(Ratio::new(15869, 1296)),
+        },
+        distribution("Fate dice", "4dF", &FOUR_DF, 81),
+    ];
+    let advantage = ADVANTAGE.into_iter().flat_map(|(target, adv, dis)| {
+        [
+            ("advantage", "2d20kh", adv),
+            ("disadvantage", "2d20kl", dis),
+        ]
+        .map(|(name, expression, chances)| Entry {
+            name: format!("d20 with {name}, against {target}"),
+            expression: expression.to_owned(),
+            known: Known::AtLeast(target, Ratio::new(chances, 400)),
+        })
+    });
+    fixed.into_iter().chain(advantage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluator_agrees() {
+        for entry in iter() {
+            if let Some(diff) = entry.diff() {
+                panic!("{entry}:\n{diff}");
+            }
+        }
+        assert_eq!(iter().count(), 5 + 40);
+    }
+
+    #[test]
+    fn reports_differences() {
+        let entry = Entry {
+            name: "a wrong mean".to_owned(),
+            expression: "d6".to_owned(),
+            known: Known::Mean(Ratio::new(3, 1)),
+        };
+        assert_eq!(entry.to_string(), "a wrong mean (d6): mean 3");
+        assert_eq!(entry.diff().unwrap(), "mean: expected 3, got 7/2");
+        let entry = Entry {
+            expression: "d6 +".to_owned(),
+            ..entry
+        };
+        assert!(entry.diff().unwrap().starts_with("parse error"));
+
+        let hit = iter()
+            .find(|e| e.name == "d20 with advantage, against 11")
+            .unwrap();
+        assert_eq!(hit.known, Known::AtLeast(11, Ratio::new(3, 4)));
+    }
+}
@@ -0,0 +1,187 @@
+//! A live view of a file of named expressions, like a library of macros being edited.
+//!
+//! The file has the same format that [`lint`](crate::lint) checks: one named expression per line,
+//! `longsword: d20 + 5 >= 15`. [`rows`] evaluates each expression, exactly if it can
+//! and approximately otherwise, and [`table`] and [`page`] present them as a web page.
+//! `dicer watch FILE --serve PORT` serves that page, and updates it as the file changes.
+
+use maud::PreEscaped;
+
+use crate::{
+    Closed, Evaluation, html,
+    lint::{skipped, split_named},
+};
+
+/// The path of the server-sent events that update a [`page`].
+pub const EVENTS_PATH: &str = "/events";
+
+/// One named expression, evaluated.
+#[derive(Debug, Clone)]
+pub struct Row {
+    /// The line of the file, counting from 1.
+    pub line: usize,
+    /// The name of the expression; empty if the line doesn't have one.
+    pub name: String,
+    /// The expression and its evaluation, or what's wrong with the line.
+    pub result: Result<(Closed, Evaluation), String>,
+}
+
+impl std::fmt::Display for Row {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.line)?;
+        if !self.name.is_empty() {
+            write!(f, "{}: ", self.name)?;
+        }
+        match &self.result {
+            Ok((_, evaluation)) => {
+                let moments = evaluation.moments();
+                write!(
+                    f,
+                    "mean {:.2}, standard deviation {:.2}",
+                    moments.mean.value,
+                    moments.stddev()
+                )?;
+                match evaluation.range() {
+                    Some((min, max)) => write!(f, ", {min} to {max}"),
+                    None => write!(f, " (approximate)"),
+                }
+            }
+            Err(e) => write!(f, "error: {e}"),
+        }
+    }
+}
+
+/// Evaluate each named expression in the file, each within `budget` combinations
+/// as [`Closed::evaluate_auto`] counts them.
+pub fn rows(source: &str, budget: usize) -> Vec<Row> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !skipped(line))
+        .map(|(i, line)| {
+            let Some((name, expression)) = split_named(line) else {
+                return Row {
+                    line: i + 1,
+                    name: String::new(),
+                    result: Err("expected a name, a colon, and an expression".to_owned()),
+                };
+            };
+            let result = expression
+                .parse::<Closed>()
+                .and_then(|e| {
+                    let evaluation = e.evaluate_auto(budget)?;
+                    Ok((e, evaluation))
+                })
+                .map_err(|e| e.to_string());
+            Row {
+                line: i + 1,
+                name: name.to_owned(),
+                result,
+            }
+        })
+        .collect()
+}
+
+/// A table of the expressions and their statistics, with a chart of each exact distribution.
+pub fn table(rows: &[Row]) -> PreEscaped<String> {
+    maud::html! {
+        table {
+            thead {
+                tr {
+                    th { "Name" } th { "Expression" } th { "Mean" } th { "Standard deviation" }
+                    th { "Least" } th { "Greatest" }
+                }
+            }
+            @for row in rows {
+                tr {
+                    th scope="row" { (row.name) }
+                    @match &row.result {
+                        Ok((e, evaluation)) => {
+                            @let moments = evaluation.moments();
+                            td { (e.to_string()) }
+                            td { (format!("{:.2}", moments.mean.value)) }
+                            td { (format!("{:.2}", moments.stddev())) }
+                            @if let Some((min, max)) = evaluation.range() {
+                                td { (min) } td { (max) }
+                            } @else {
+                                td colspan="2" { "approximate" }
+                            }
+                        }
+                        Err(e) => {
+                            td colspan="5" { "Error on line " (row.line) ": " (e) }
+                        }
+                    }
+                }
+            }
+        }
+        @for row in rows {
+            @if let Ok((_, Evaluation::Exact(d))) = &row.result {
+                (html::table_multi_dist(&[(&row.name, (**d).clone())]))
+            }
+        }
+    }
+}
+
+/// A web page showing a [`table`] of the expressions, which replaces the table with each
+/// server-sent event from [`EVENTS_PATH`].
+pub fn page(table: PreEscaped<String>) -> PreEscaped<String> {
+    maud::html! {
+        (PreEscaped("<!DOCTYPE html>"))
+        html {
+            head {
+                meta charset="utf-8";
+                title { "dicer" }
+                link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/charts.css/dist/charts.min.css";
+            }
+            body {
+                div id="dashboard" { (table) }
+                script {
+                    (PreEscaped(format!(
+                        "new EventSource('{EVENTS_PATH}').onmessage = (e) => {{ \
+                         document.getElementById('dashboard').innerHTML = e.data; }};"
+                    )))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_rows() {
+        let source = "# Attacks\n\
+                      longsword: d8 + 3\n\
+                      \n\
+                      broken: 2d6 +\n\
+                      [X: d6] X\n\
+                      horde: 1000d100 * 1000d100\n";
+        let got = rows(source, 1000);
+        assert_eq!(got.len(), 4);
+        assert_eq!((got[0].line, got[0].name.as_str()), (2, "longsword"));
+        assert_eq!(
+            got[0].to_string(),
+            "2: longsword: mean 7.50, standard deviation 2.29, 4 to 11"
+        );
+        assert!(got[1].result.is_err());
+        assert_eq!(
+            got[2].to_string(),
+            "5: error: expected a name, a colon, and an expression"
+        );
+        assert!(matches!(got[3].result, Ok((_, Evaluation::Approximate(_)))));
+        assert!(got[3].to_string().ends_with("(approximate)"));
+    }
+
+    #[test]
+    fn html() {
+        let got = rows("longsword: d8 + 3\nbroken: 2d6 +\n", 1000);
+        let page = page(table(&got)).into_string();
+        assert!(page.contains("longsword"));
+        assert!(page.contains("Error on line 2"));
+        assert!(page.contains(EVENTS_PATH));
+        // One chart, for the one distribution.
+        assert_eq!(page.matches("charts-css").count(), 1);
+    }
+}
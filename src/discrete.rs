@@ -1,24 +1,90 @@
 //! Probability computation via discrete (integral) math and combinatorics.
 
+mod big;
+mod binary;
+mod bounded;
+mod checkpoint;
+mod counts;
+mod karatsuba;
+mod ntt;
+mod profile;
+mod provenance;
+mod restricted;
+
 use crate::{
-    Error,
+    Error, RandomVariable,
     analysis::Closed,
-    symbolic::{ComparisonOp, Constant, Die, ExpressionTree, ExpressionWrapper, Ranker, Symbol},
+    symbolic::{
+        ComparisonOp, Constant, CustomDie, Die, Explosion, ExpressionTree, ExpressionWrapper,
+        Function, Ranker, Reroll, Successes, Symbol, Trigger,
+    },
+};
+pub use big::BigDistribution;
+pub use bounded::Bounded;
+use counts::Counts;
+pub use profile::{NodeProfile, Profile};
+pub use provenance::Provenance;
+pub use restricted::Restricted;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Neg,
+    time::{Duration, Instant},
 };
-use std::{collections::HashMap, ops::Neg};
 
 use itertools::Itertools;
-use num::{ToPrimitive, rational::Ratio};
+use num::{CheckedAdd, CheckedMul, CheckedSub, ToPrimitive, rational::Ratio};
+
+/// The widest die we'll roll, or distribution we'll read, from lowest to highest value:
+/// its counts alone take 2GiB.
+const MAX_WIDTH: usize = 1 << 28;
 
 /// A computed distribution for a bounded dice expression.
-/// ("bounded": does not support exploding dice.)
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// ("bounded": exploding dice are truncated after a finite number of explosions.)
+///
+/// Distributions are equal when their probabilities are, however they were produced.
+#[derive(Debug, Clone)]
 pub struct Distribution {
     /// We track probabilities of each value using integers;
     /// all of these have an implied denominator of occurrence_by_value.sum().
-    occurrence_by_value: Vec<usize>,
+    occurrence_by_value: Counts,
     /// Index i in occurrence_by_value represents the number of occurrences of (i+offset).
-    offset: isize,
+    offset: i64,
+    /// How the distribution was produced.
+    provenance: Provenance,
+}
+
+impl PartialEq for Distribution {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.occurrence_by_value == other.occurrence_by_value
+    }
+}
+
+impl Eq for Distribution {}
+
+/// The result of an opposed roll; see [`Distribution::checked_margin`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Margin {
+    /// The first roll minus the second: positive when the first wins.
+    pub margin: Distribution,
+    /// The probability that the first roll is higher.
+    pub win: Ratio<usize>,
+    /// The probability that the rolls are equal.
+    pub tie: Ratio<usize>,
+    /// The probability that the second roll is higher.
+    pub lose: Ratio<usize>,
+}
+
+/// An exploding die, exploded deep enough to be close to the real thing;
+/// see [`Distribution::explode_until`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exploded {
+    /// The distribution of the die, exploding at most `depth` times.
+    pub distribution: Distribution,
+    /// How many times the die explodes, at most.
+    pub depth: usize,
+    /// The probability that the die would explode more than `depth` times. No probability in
+    /// `distribution`, or of any range of its values, is off by more than this.
+    pub residual: Ratio<usize>,
 }
 
 /// An evaluator: evaluates distributions for a closed expression.
@@ -28,14 +94,96 @@ pub struct Distribution {
 /// trading (some) memory for (some) processing.
 ///
 // TODO: Benchmark with and without memoization.
-#[derive(Default)]
 pub struct Evaluator {
     /// Memoization table.
     memo: HashMap<Closed, Distribution>,
     memoize: bool,
+    /// How many times an exploding die may explode.
+    explosion_depth: usize,
+    /// Storage for intermediate results, reused across evaluations.
+    scratch: Vec<usize>,
+    /// The sub-expression with the widest distribution evaluated so far, and that width.
+    largest: Option<(Closed, usize)>,
+    /// Combinations of values computed so far by the node being evaluated.
+    combinations: usize,
+    /// Measurements of each node evaluated, if profiling.
+    profile: Option<Vec<NodeProfile>>,
+    /// How deeply nested the node being evaluated is.
+    depth: usize,
+    /// The most combinations of values a single evaluation may compute.
+    budget: Option<usize>,
+    /// Combinations of values computed so far by the current evaluation, across all nodes.
+    spent: usize,
+    /// Memoized results used so far by the current evaluation.
+    cache_hits: usize,
+    /// How long a single evaluation may take.
+    timeout: Option<Duration>,
+    /// When the current evaluation runs out of time.
+    deadline: Option<Instant>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self {
+            memo: Default::default(),
+            memoize: false,
+            explosion_depth: Self::DEFAULT_EXPLOSION_DEPTH,
+            scratch: Vec::new(),
+            largest: None,
+            combinations: 0,
+            profile: None,
+            depth: 0,
+            budget: None,
+            spent: 0,
+            cache_hits: 0,
+            timeout: None,
+            deadline: None,
+        }
+    }
 }
 
 impl Evaluator {
+    /// How many times an exploding die may explode, unless configured otherwise.
+    pub const DEFAULT_EXPLOSION_DEPTH: usize = 2;
+
+    /// How deeply an expression may nest before evaluating it fails with
+    /// [`Error::RecursionLimit`], rather than overflowing the stack.
+    ///
+    /// Each level takes up to about 16KiB of stack in an unoptimized build, so this stays
+    /// within the 2MiB stack of a spawned thread.
+    pub const MAX_DEPTH: usize = 64;
+
+    /// Evaluate exploding dice with at most this many explosions.
+    ///
+    /// An exploding die that rolls its highest face on its last allowed roll
+    /// keeps that face, rather than rolling again.
+    /// Greater depths give more accurate tails, at the cost of larger distributions.
+    pub fn with_explosion_depth(mut self, depth: usize) -> Self {
+        self.explosion_depth = depth;
+        self.memo.clear();
+        self
+    }
+
+    /// Limit each evaluation to computing at most `combinations` combinations of values
+    /// (e.g. each value of one operand paired with each value of the other),
+    /// failing with [`Error::OverBudget`] rather than going over.
+    ///
+    /// Results already memoized don't count against the budget.
+    pub fn with_budget(mut self, combinations: usize) -> Self {
+        self.budget = Some(combinations);
+        self
+    }
+
+    /// Limit each evaluation to about `timeout` of wall time,
+    /// failing with [`Error::Timeout`] rather than going over.
+    ///
+    /// The time is checked before each step that combines values, so a single large step
+    /// may run past it.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Create a new Evaluator, with or without memoization enabled.
     pub fn new(memoize: bool) -> Self {
         Self {
@@ -44,76 +192,325 @@ impl Evaluator {
         }
     }
 
+    /// The sub-expression with the widest distribution this evaluator has computed,
+    /// along with its [`Distribution::support_len`].
+    ///
+    /// When an expression is slow to evaluate, this is usually the node responsible.
+    pub fn largest_intermediate(&self) -> Option<(&Closed, usize)> {
+        self.largest.as_ref().map(|(e, len)| (e, *len))
+    }
+
+    /// Evaluate the expression, recording its [`Distribution::provenance`].
     pub fn eval(&mut self, tree: &Closed) -> Result<Distribution, Error> {
+        if self.depth > 0 {
+            return self.eval_memoized(tree);
+        }
+        let start = Instant::now();
+        self.spent = 0;
+        self.cache_hits = 0;
+        self.deadline = self.timeout.map(|timeout| start + timeout);
+        let mut dist = self.eval_memoized(tree)?;
+        dist.provenance = Provenance {
+            explosion_depth: provenance::explodes(tree).then_some(self.explosion_depth),
+            trimmed: Ratio::default(),
+            elapsed: start.elapsed(),
+            cache_hits: self.cache_hits,
+        };
+        Ok(dist)
+    }
+
+    /// Evaluate a node, or retrieve it from the memo.
+    fn eval_memoized(&mut self, tree: &Closed) -> Result<Distribution, Error> {
+        if self.memoize
+            && let Some(dist) = self.memo.get(tree)
+        {
+            self.cache_hits += 1;
+            return Ok(dist.clone());
+        }
+        if self.depth >= Self::MAX_DEPTH {
+            return Err(Error::RecursionLimit(tree.to_string()));
+        }
+        let node = self.profile.as_mut().map(|nodes| {
+            nodes.push(NodeProfile {
+                expression: tree.clone(),
+                depth: self.depth,
+                elapsed: Default::default(),
+                support_len: 0,
+                combinations: 0,
+            });
+            (nodes.len() - 1, Instant::now())
+        });
+        // Count only this node's combinations, not its operands'.
+        let outer = std::mem::take(&mut self.combinations);
+        self.depth += 1;
+        let result = self.eval_node(tree);
+        self.depth -= 1;
+        let combinations = std::mem::replace(&mut self.combinations, outer);
+        let memo = result.map_err(|e| match e {
+            // Name the innermost node that went over.
+            Error::OverBudget(node) if node.is_empty() => Error::OverBudget(tree.to_string()),
+            Error::Timeout(node) if node.is_empty() => Error::Timeout(tree.to_string()),
+            e => e,
+        })?;
+
+        if let Some((index, start)) = node
+            && let Some(nodes) = &mut self.profile
+        {
+            let node = &mut nodes[index];
+            node.elapsed = start.elapsed();
+            node.support_len = memo.support_len();
+            node.combinations = combinations;
+        }
+        let len = memo.support_len();
+        if self
+            .largest
+            .as_ref()
+            .is_none_or(|(_, largest)| len > *largest)
+        {
+            self.largest = Some((tree.clone(), len));
+        }
         if self.memoize {
-            if let Some(dist) = self.memo.get(tree) {
-                return Ok(dist.clone());
-            }
+            self.memo.insert(tree.clone(), memo.clone());
         }
-        // We begin with native-stack recursion.
+        Ok(memo)
+    }
 
-        // Need to evaluate.
-        let memo = match tree.inner() {
-            ExpressionTree::Modifier(Constant(constant)) => Distribution::constant(*constant),
-            ExpressionTree::Die(Die(die)) => Distribution::die(*die),
-            ExpressionTree::Symbol(symbol) => {
-                panic!("unbound symbol {symbol} in closed expression")
-                // return Err(Error::UnboundSymbols([symbol].into()))
-            }
-            ExpressionTree::Negated(e) => {
-                let dist = self.eval(e.as_ref())?;
-                -dist
-            }
+    /// Record that `n` combinations of values are about to be computed at the current node.
+    ///
+    /// Fails if that would exceed the budget, or if the evaluation is out of time.
+    /// The error doesn't yet name the node: [`Evaluator::eval`] fills that in.
+    fn combined(&mut self, n: usize) -> Result<(), Error> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(Error::Timeout(String::new()));
+        }
+        self.combinations = self.combinations.saturating_add(n);
+        self.spent = self.spent.saturating_add(n);
+        match self.budget {
+            Some(budget) if self.spent > budget => Err(Error::OverBudget(String::new())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Evaluate a node that isn't memoized.
+    //
+    // This only dispatches: each node's locals live in the frame of its own function,
+    // so that deeply nested expressions don't exhaust the native stack.
+    fn eval_node(&mut self, tree: &Closed) -> Result<Distribution, Error> {
+        match tree.inner() {
+            ExpressionTree::Modifier(_)
+            | ExpressionTree::Die(_)
+            | ExpressionTree::CustomDie(_)
+            | ExpressionTree::Symbol(_) => leaf(tree),
+            ExpressionTree::Negated(e) => self.negate(tree, e),
             ExpressionTree::Repeated {
                 count,
                 value,
                 ranker,
-            } => self.repeat(tree, count, value, ranker)?,
-            ExpressionTree::Product(a, b) => self.product(a, b)?,
-            ExpressionTree::Floor(a, b) => self.floor(tree, a, b)?,
-            ExpressionTree::Sum(items) => {
-                let distrs: Result<Vec<_>, _> = items.iter().map(|e| self.eval(e)).collect();
-                let distrs = distrs?;
-                distrs.into_iter().sum()
-            }
-            ExpressionTree::Comparison { a, b, op } => self.comparison(a, b, *op)?,
+            } => self.repeat(tree, count, value, ranker),
+            ExpressionTree::Product(a, b) => self.product(tree, a, b),
+            ExpressionTree::Floor(a, b) => self.divide(tree, a, b, i64::checked_div),
+            ExpressionTree::Modulo(a, b) => self.divide(tree, a, b, i64::checked_rem_euclid),
+            ExpressionTree::Sum(items) => self.sum(tree, items),
+            ExpressionTree::Comparison { a, b, op } => self.comparison(tree, a, b, *op),
             ExpressionTree::Binding {
                 symbol,
                 value,
                 tail,
-            } => self.binding(symbol, value, tail)?,
+            } => self.binding(tree, symbol, value, tail),
+            ExpressionTree::Call { function, args } => self.call(tree, *function, args),
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => self.explode(tree, value, *explosion, trigger),
+            ExpressionTree::Reroll { value, reroll } => self.reroll(tree, value, reroll),
+            ExpressionTree::Lookup { value, bands } => {
+                Ok(self.eval(value)?.map_values(|v| bands.apply(v)))
+            }
+            ExpressionTree::Successes { value, successes } => {
+                self.successes(tree, value, successes)
+            }
+        }
+    }
+
+    fn negate(&mut self, e: &Closed, value: &Closed) -> Result<Distribution, Error> {
+        self.eval(value)?
+            .checked_neg()
+            .ok_or_else(|| Error::Overflow(e.to_string()))
+    }
+
+    fn sum(&mut self, e: &Closed, items: &[Closed]) -> Result<Distribution, Error> {
+        let distrs: Result<Vec<_>, _> = items.iter().map(|e| self.eval(e)).collect();
+        let distrs = distrs?;
+        countable(e, &distrs)?;
+        // Each term is combined with the sum of the terms before it.
+        let mut width = distrs[0].support_len();
+        for d in &distrs[1..] {
+            self.combined(width.saturating_mul(d.support_len()))?;
+            width = width.saturating_add(d.support_len() - 1);
+        }
+        let terms: Vec<_> = distrs.iter().map(|d| (1, d)).collect();
+        Distribution::linear_combination_with(&terms, &mut self.scratch)
+            .ok_or_else(|| Error::Overflow(e.to_string()))
+    }
+
+    fn explode(
+        &mut self,
+        e: &Closed,
+        value: &Closed,
+        explosion: Explosion,
+        trigger: &Trigger,
+    ) -> Result<Distribution, Error> {
+        let die = self.eval(value)?;
+        let face = match explosion {
+            Explosion::Imploding => die.min(),
+            _ => die.max(),
         };
-        if self.memoize {
-            self.memo.insert(tree.clone(), memo.clone());
+        let explodes = |v| trigger.explodes(face, v);
+        match explosion {
+            Explosion::Standard | Explosion::Compounding => {
+                die.checked_explode_when(self.explosion_depth, explodes)
+            }
+            Explosion::Penetrating => die.checked_penetrate_when(self.explosion_depth, explodes),
+            Explosion::Imploding => die.checked_implode_when(self.explosion_depth, explodes),
         }
-        Ok(memo)
+        .ok_or_else(|| Error::Overflow(e.to_string()))
     }
 
-    fn product(&mut self, a: &Closed, b: &Closed) -> Result<Distribution, Error> {
-        let a = self.eval(a)?;
-        let b = self.eval(b)?;
+    fn reroll(
+        &mut self,
+        e: &Closed,
+        value: &Closed,
+        reroll: &Reroll,
+    ) -> Result<Distribution, Error> {
+        let die = self.eval(value)?;
+        if reroll.recursive {
+            die.reroll_while(|v| reroll.applies(v))
+                .ok_or_else(|| Error::RerollsForever(e.to_string()))
+        } else {
+            die.checked_reroll_once(|v| reroll.applies(v))
+                .ok_or_else(|| Error::Overflow(e.to_string()))
+        }
+    }
 
-        let mut d = Distribution::empty();
+    fn successes(
+        &mut self,
+        e: &Closed,
+        value: &Closed,
+        successes: &Successes,
+    ) -> Result<Distribution, Error> {
+        // Each explosion of an exploding die is another die, which counts on its own.
+        let rolls = match value.inner() {
+            ExpressionTree::Explode {
+                value: die,
+                explosion: Explosion::Standard,
+                trigger,
+            } => {
+                let die = self.eval(die)?;
+                let max = die.max();
+                die.explosion_rolls(self.explosion_depth, |v| trigger.explodes(max, v))
+                    .ok_or_else(|| Error::Overflow(e.to_string()))?
+            }
+            _ => self
+                .eval(value)?
+                .occurrences()
+                .map(|(v, o)| (vec![v], o))
+                .collect(),
+        };
+        Ok(Distribution::from_occurrences(
+            rolls
+                .into_iter()
+                .map(|(rolls, o)| (rolls.iter().map(|v| successes.score(*v)).sum(), o)),
+        )
+        .expect("dice have at least one face"))
+    }
 
-        for ((v1, f1), (v2, f2)) in a.occurrences().cartesian_product(b.occurrences()) {
-            d.add_occurrences(v1 * v2, f1 * f2);
+    fn product(&mut self, e: &Closed, a: &Closed, b: &Closed) -> Result<Distribution, Error> {
+        let a = self.eval(a)?;
+        let b = self.eval(b)?;
+        countable(e, [&a, &b])?;
+        self.combined(a.support_len().saturating_mul(b.support_len()))?;
+        // The product's extremes are products of the operands' extremes; they may be too far
+        // apart to store, even when few values between them occur.
+        let corners = [a.min(), a.max()]
+            .into_iter()
+            .cartesian_product([b.min(), b.max()])
+            .map(|(x, y)| x.checked_mul(y))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Error::Overflow(e.to_string()))?;
+        let (lo, hi) = corners
+            .into_iter()
+            .minmax()
+            .into_option()
+            .expect("four corners");
+        if hi.abs_diff(lo) >= MAX_WIDTH as u64 {
+            return Err(Error::TooComplex(e.to_string()));
         }
-        Ok(d)
+
+        a.checked_combine(&b, i64::checked_mul)
+            .ok_or_else(|| Error::Overflow(e.to_string()))
     }
 
-    fn floor(&mut self, e: &Closed, a: &Closed, b: &Closed) -> Result<Distribution, Error> {
+    /// Divide, or take the remainder, by the divisor `b`, which must not be zero.
+    fn divide(
+        &mut self,
+        e: &Closed,
+        a: &Closed,
+        b: &Closed,
+        op: fn(i64, i64) -> Option<i64>,
+    ) -> Result<Distribution, Error> {
         let a = self.eval(a)?;
         let b = self.eval(b)?;
+        countable(e, [&a, &b])?;
+        self.combined(a.support_len().saturating_mul(b.support_len()))?;
 
         if *b.probability(0).numer() != 0 {
             return Err(Error::DivideByZero(e.to_string()));
         }
 
-        let mut d = Distribution::empty();
-        for ((v1, f1), (v2, f2)) in a.occurrences().cartesian_product(b.occurrences()) {
-            d.add_occurrences(v1 / v2, f1 * f2);
+        a.checked_combine(&b, op)
+            .ok_or_else(|| Error::Overflow(e.to_string()))
+    }
+
+    fn call(
+        &mut self,
+        e: &Closed,
+        function: Function,
+        args: &[Closed],
+    ) -> Result<Distribution, Error> {
+        let args: Vec<Distribution> = args
+            .iter()
+            .map(|a| self.eval(a))
+            .collect::<Result<_, _>>()?;
+        // A condition that always (or never) holds picks one branch, without combining them.
+        if function != Function::If {
+            countable(e, &args)?;
+        }
+        self.combined(
+            args.iter()
+                .fold(1usize, |acc, a| acc.saturating_mul(a.support_len())),
+        )?;
+        let overflow = || Error::Overflow(e.to_string());
+        match (function, args.as_slice()) {
+            (Function::Soak, [damage, armor]) => damage.checked_soak(armor).ok_or_else(overflow),
+            (Function::Tens, [x]) => Ok(x.map_values(|v| digit(v, 1))),
+            (Function::Units, [x]) => Ok(x.map_values(|v| digit(v, 0))),
+            (Function::Abs, [x]) => x.checked_abs().ok_or_else(overflow),
+            (Function::Max, [a, b]) => a.checked_max(b).ok_or_else(overflow),
+            (Function::Clamp, [x, lo, hi]) => x
+                .checked_max(lo)
+                .and_then(|x| x.checked_min(hi))
+                .ok_or_else(overflow),
+            (Function::Min, [a, b]) => a.checked_min(b).ok_or_else(overflow),
+            (Function::Save, [damage, saved]) => damage.checked_save(saved).ok_or_else(overflow),
+            (Function::If, [condition, then, otherwise]) => condition
+                .checked_choose(then, otherwise)
+                .ok_or_else(overflow),
+            _ => panic!("wrong number of arguments to {function} in {e}"),
         }
-        Ok(d)
     }
 
     fn repeat(
@@ -121,133 +518,551 @@ impl Evaluator {
         expression: &Closed,
         count: &Closed,
         value: &Closed,
-        ranker: &Ranker,
+        ranker: &Ranker<Box<Closed>>,
     ) -> Result<Distribution, Error> {
         let count_dist = self.eval(count)?;
         let value_dist = self.eval(value)?;
-
-        let mut result = Distribution::empty();
         if count_dist.min() < 0 {
             return Err(Error::NegativeCount(expression.to_string()));
         }
+        let Some(keep) = ranker.count() else {
+            let ranker = ranker.map(|_| 0);
+            return self.ranked(expression, value, &count_dist, &value_dist, &ranker);
+        };
+
+        // Each count kept (or dropped) gives the same total number of rolls:
+        // weight each by how often that count occurs.
+        let keep_dist = self.eval(keep)?;
+        if keep_dist.min() < 0 {
+            return Err(Error::NegativeCount(expression.to_string()));
+        }
+        let overflow = || Error::Overflow(expression.to_string());
+        let mut result = Distribution::empty();
+        for (keep, keep_frequency) in keep_dist.occurrences() {
+            let ranker = ranker.map(|_| keep as usize);
+            let ranked = self.ranked(expression, value, &count_dist, &value_dist, &ranker)?;
+            for (value, occurrences) in ranked.occurrences() {
+                occurrences
+                    .checked_mul(keep_frequency)
+                    .and_then(|o| result.checked_add_occurrences(value, o))
+                    .ok_or_else(overflow)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Roll `count_dist` of `value`, and keep those chosen by the ranker.
+    fn ranked(
+        &mut self,
+        expression: &Closed,
+        value: &Closed,
+        count_dist: &Distribution,
+        value_dist: &Distribution,
+        ranker: &Ranker,
+    ) -> Result<Distribution, Error> {
+        let mut result = Distribution::empty();
         if (count_dist.min() as usize) < ranker.min_count() {
-            return Err(Error::KeepTooFew(
-                ranker.min_count(),
-                expression.to_string(),
-            ));
+            return Err(ranker.too_few(expression.to_string()));
         }
 
         // We have to have the same type signature for each of these,
         // and we want to truncate in the other cases.
         #[allow(clippy::ptr_arg)]
-        fn keep_all(v: &mut [isize], _n: usize) -> &[isize] {
+        fn keep_all(v: &mut [i64], _n: usize) -> &[i64] {
             v
         }
-        fn keep_highest(v: &mut [isize], n: usize) -> &[isize] {
+        fn keep_highest(v: &mut [i64], n: usize) -> &[i64] {
             v.sort_by(|v1, v2| v2.cmp(v1));
             &v[..n]
         }
-        fn keep_lowest(v: &mut [isize], n: usize) -> &[isize] {
+        fn keep_lowest(v: &mut [i64], n: usize) -> &[i64] {
             v.sort();
             &v[..n]
         }
         let filter = match ranker {
-            Ranker::All => keep_all,
-            Ranker::Highest(_) => keep_highest,
-            Ranker::Lowest(_) => keep_lowest,
+            Ranker::All | Ranker::Matching => keep_all,
+            _ if ranker.keeps_highest() => keep_highest,
+            _ => keep_lowest,
         };
 
+        // Each count has a different number of possible rolls: value_dist.total() ^ count.
+        // Put them on equal footing by scaling each up to the rolls of the largest count.
+        let overflow = || Error::Overflow(expression.to_string());
+        let max_count = count_dist.max() as usize;
+        u32::try_from(max_count)
+            .ok()
+            .and_then(|n| value_dist.total().checked_pow(n))
+            .and_then(|rolls| rolls.checked_mul(count_dist.total()))
+            .ok_or_else(|| Error::TooComplex(expression.to_string()))?;
+        let weight = |count: usize, count_frequency: usize| {
+            u32::try_from(max_count - count)
+                .ok()
+                .and_then(|e| value_dist.total().checked_pow(e))
+                .and_then(|w| w.checked_mul(count_frequency))
+                .ok_or_else(overflow)
+        };
+
+        if *ranker == Ranker::All {
+            // Without a ranker, the result is just a sum: convolve, rather than enumerating
+            // every combination of rolls.
+            let mut partial = value_dist.clone();
+            let mut partial_count = 1;
+            for (count, count_frequency) in count_dist.occurrences() {
+                while partial_count < count {
+                    self.combined(
+                        partial
+                            .support_len()
+                            .saturating_mul(value_dist.support_len()),
+                    )?;
+                    partial
+                        .checked_add_assign(value_dist)
+                        .ok_or_else(overflow)?;
+                    partial_count += 1;
+                }
+                let weight = weight(count as usize, count_frequency)?;
+                for (value, occurrences) in partial.occurrences() {
+                    occurrences
+                        .checked_mul(weight)
+                        .and_then(|o| result.checked_add_occurrences(value, o))
+                        .ok_or_else(overflow)?;
+                }
+            }
+            return Ok(result);
+        }
+
+        let die_rolls = self.die_rolls(expression, value, value_dist)?;
+        let die_weights: Vec<usize> = die_rolls.iter().map(|(_, o)| *o).collect();
+
+        let mut values = Vec::new();
         for (count, count_frequency) in count_dist.occurrences() {
             let keep_count = ranker.keep(count) as usize;
+            let weight = weight(count as usize, count_frequency)?;
+            self.combined(multiset_count(die_rolls.len(), count as usize))?;
             // Assuming this count happens this often...
-            let dice = std::iter::repeat(&value_dist)
-                .map(|d| d.occurrences())
-                .take(count as usize);
-            for value_set in dice.multi_cartesian_product() {
-                let (mut values, frequencies): (Vec<isize>, Vec<usize>) =
-                    value_set.into_iter().unzip();
-                // We have to compute the overall frquency including the dice we dropped;
-                // in other universes (other combinations), we'd keep them.
-                let occurrences = frequencies.into_iter().product::<usize>() * count_frequency;
-                let value = filter(&mut values, keep_count).iter().sum();
-                result.add_occurrences(value, occurrences);
-            }
+            // The dice are interchangeable, so each multiset of rolls stands for every order
+            // of the same rolls.
+            multisets(
+                &die_weights,
+                count as usize,
+                overflow,
+                |counts, occurrences| {
+                    values.clear();
+                    for ((rolls, _), n) in die_rolls.iter().zip(counts) {
+                        for _ in 0..*n {
+                            values.extend_from_slice(rolls);
+                        }
+                    }
+                    // We have to compute the overall frquency including the dice we dropped;
+                    // in other universes (other combinations), we'd keep them.
+                    let occurrences = occurrences.checked_mul(weight).ok_or_else(overflow)?;
+                    let value = if *ranker == Ranker::Matching {
+                        largest_set(&mut values)
+                    } else {
+                        filter(&mut values, keep_count)
+                            .iter()
+                            .try_fold(0i64, |acc, v| acc.checked_add(*v))
+                            .ok_or_else(overflow)?
+                    };
+                    result
+                        .checked_add_occurrences(value, occurrences)
+                        .ok_or_else(overflow)
+                },
+            )?;
         }
         Ok(result)
     }
 
+    /// The rolls that a single die contributes to a pool, each with its occurrences.
+    ///
+    /// Modifiers on the die apply to each die in the pool separately, before the pool keeps its
+    /// highest or lowest rolls: `4d6r<3kh3` rerolls each low die, then keeps the highest three.
+    /// Most modifiers leave a single roll per die, so are already accounted for in `value_dist`;
+    /// each explosion of an exploding die (but not a compounding one) is another die.
+    fn die_rolls(
+        &mut self,
+        expression: &Closed,
+        value: &Closed,
+        value_dist: &Distribution,
+    ) -> Result<Vec<(Vec<i64>, usize)>, Error> {
+        match value.inner() {
+            ExpressionTree::Explode {
+                value: die,
+                explosion: Explosion::Standard,
+                trigger,
+            } => {
+                let die = self.eval(die)?;
+                let max = die.max();
+                die.explosion_rolls(self.explosion_depth, |v| trigger.explodes(max, v))
+                    .ok_or_else(|| Error::Overflow(expression.to_string()))
+            }
+            _ => Ok(value_dist
+                .occurrences()
+                .map(|(v, o)| (vec![v], o))
+                .collect()),
+        }
+    }
+
     fn comparison(
         &mut self,
+        expression: &Closed,
         a: &Closed,
         b: &Closed,
         op: ComparisonOp,
     ) -> Result<Distribution, Error> {
         let a = self.eval(a)?;
         let b = self.eval(b)?;
+        countable(expression, [&a, &b])?;
+        self.combined(a.support_len().saturating_mul(b.support_len()))?;
 
-        let mut dist = Distribution::empty();
-
-        for ((v1, o1), (v2, o2)) in a.occurrences().cartesian_product(b.occurrences()) {
-            let occurrences = o1 * o2;
-            let value = op.compare(v1, v2) as isize;
-            dist.add_occurrences(value, occurrences);
-        }
-        Ok(dist)
+        // The values can't overflow, but the number of ways to get them can.
+        a.checked_combine(&b, |v1, v2| Some(op.compare(v1, v2) as i64))
+            .ok_or_else(|| Error::Overflow(expression.to_string()))
     }
 
     fn binding(
         &mut self,
+        expression: &Closed,
         symbol: &Symbol,
         value: &Closed,
         tail: &Closed,
     ) -> Result<Distribution, Error> {
+        let too_complex = || Error::TooComplex(expression.to_string());
         let value = self.eval(value)?;
-        let mut acc = Distribution::empty();
+        let mut tables = Vec::with_capacity(value.support_len());
         for (value, occ) in value.occurrences() {
             let tree: Closed = tail.substitute(symbol, value);
-            let table = self.eval(&tree)?;
+            tables.push((occ, self.eval(&tree)?));
+        }
+        // The tail may have a different number of possible rolls for each value,
+        // e.g. `[N: d2] (N)d6`: put them over a common denominator.
+        let common = tables.iter().try_fold(1usize, |lcm, (_, table)| {
+            let total = table.total();
+            (lcm / num::integer::gcd(lcm, total)).checked_mul(total)
+        });
+        let common = common.ok_or_else(too_complex)?;
+        let mut acc = Distribution::empty();
+        for (occ, table) in tables {
+            self.combined(table.support_len())?;
+            let weight = occ
+                .checked_mul(common / table.total())
+                .ok_or_else(too_complex)?;
             for (v2, o2) in table.occurrences() {
-                acc.add_occurrences(v2, occ * o2);
+                o2.checked_mul(weight)
+                    .and_then(|o| acc.checked_add_occurrences(v2, o))
+                    .ok_or_else(too_complex)?;
             }
         }
         Ok(acc)
     }
 }
 
+/// The distribution of a node without operands: a constant, or a die.
+fn leaf(tree: &Closed) -> Result<Distribution, Error> {
+    let overflow = || Error::Overflow(tree.to_string());
+    let too_complex = || Error::TooComplex(tree.to_string());
+    Ok(match tree.inner() {
+        ExpressionTree::Modifier(Constant(constant)) => {
+            Distribution::constant(i64::try_from(*constant).map_err(|_| overflow())?)
+        }
+        ExpressionTree::Die(Die(die)) => {
+            i64::try_from(*die).map_err(|_| overflow())?;
+            Distribution::die(*die).ok_or_else(too_complex)?
+        }
+        // The parser only accepts nonempty ranges, so these are just too wide.
+        ExpressionTree::CustomDie(CustomDie::Range(lo, hi)) => {
+            Distribution::uniform(*lo, *hi).ok_or_else(too_complex)?
+        }
+        ExpressionTree::CustomDie(CustomDie::Named { faces, .. } | CustomDie::Faces(faces)) => {
+            Distribution::from_faces(faces.iter().copied()).ok_or_else(too_complex)?
+        }
+        ExpressionTree::CustomDie(CustomDie::Fudge) => Distribution::fudge(),
+        ExpressionTree::CustomDie(CustomDie::Weighted(faces)) => {
+            Distribution::from_weighted_faces(faces.iter().copied()).ok_or_else(overflow)?
+        }
+        ExpressionTree::CustomDie(CustomDie::Digits(sizes)) => {
+            Distribution::digits(sizes).ok_or_else(overflow)?
+        }
+        ExpressionTree::Symbol(symbol) => {
+            return Err(Error::UnboundSymbols(HashSet::from([symbol.clone()])));
+        }
+        _ => unreachable!("only leaves are passed to leaf()"),
+    })
+}
+
+/// Fail with [`Error::TooComplex`] if rolling all of the distributions together has too many
+/// possible outcomes to count.
+fn countable<'a>(
+    expression: &Closed,
+    distributions: impl IntoIterator<Item = &'a Distribution>,
+) -> Result<(), Error> {
+    distributions
+        .into_iter()
+        .try_fold(1usize, |rolls, d| rolls.checked_mul(d.total()))
+        .map(|_| ())
+        .ok_or_else(|| Error::TooComplex(expression.to_string()))
+}
+
+/// Visit each multiset of `count` draws from items with the given weights: the count drawn
+/// of each item, and the number of ordered draws that give it, each draw of an item counting
+/// as its weight.
+///
+/// Returns the error from `overflow` if the number of draws overflows, or the first error from `visit`.
+pub(crate) fn multisets<E>(
+    weights: &[usize],
+    count: usize,
+    overflow: impl Fn() -> E,
+    mut visit: impl FnMut(&[usize], usize) -> Result<(), E>,
+) -> Result<(), E> {
+    fn recurse<E>(
+        weights: &[usize],
+        counts: &mut Vec<usize>,
+        remaining: usize,
+        occurrences: usize,
+        overflow: &impl Fn() -> E,
+        visit: &mut impl FnMut(&[usize], usize) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let i = counts.len();
+        if i + 1 >= weights.len() {
+            // The last item takes the rest of the draws.
+            let last = match weights.last() {
+                Some(w) => u32::try_from(remaining)
+                    .ok()
+                    .and_then(|r| w.checked_pow(r))
+                    .and_then(|p| p.checked_mul(occurrences))
+                    .ok_or_else(overflow)?,
+                None if remaining == 0 => occurrences,
+                None => return Ok(()),
+            };
+            if !weights.is_empty() {
+                counts.push(remaining);
+            }
+            let result = visit(counts, last);
+            counts.truncate(i);
+            return result;
+        }
+        // The ways to choose which of the remaining draws are this item, times their weight.
+        let mut ways: u128 = 1;
+        let mut power: u128 = 1;
+        for n in 0..=remaining {
+            let o = ways
+                .checked_mul(power)
+                .and_then(|o| o.checked_mul(occurrences as u128))
+                .and_then(|o| usize::try_from(o).ok())
+                .ok_or_else(overflow)?;
+            counts.push(n);
+            let result = recurse(weights, counts, remaining - n, o, overflow, visit);
+            counts.pop();
+            result?;
+            if n < remaining {
+                ways = ways
+                    .checked_mul((remaining - n) as u128)
+                    .ok_or_else(overflow)?
+                    / (n as u128 + 1);
+                power = power.checked_mul(weights[i] as u128).ok_or_else(overflow)?;
+            }
+        }
+        Ok(())
+    }
+    recurse(
+        weights,
+        &mut Vec::with_capacity(weights.len()),
+        count,
+        1,
+        &overflow,
+        &mut visit,
+    )
+}
+
+/// The number of multisets of `count` draws from `kinds` items, saturating.
+pub(crate) fn multiset_count(kinds: usize, count: usize) -> usize {
+    if kinds == 0 {
+        return (count == 0) as usize;
+    }
+    // C(kinds - 1 + count, count), built up as C(kinds - 1 + k, k) for k up to count.
+    let mut c: u128 = 1;
+    for k in 1..=count as u128 {
+        c = c * (kinds as u128 - 1 + k) / k;
+        if c > usize::MAX as u128 {
+            return usize::MAX;
+        }
+    }
+    c as usize
+}
+
+/// The size of the largest set of equal values, sorting them in the process.
+pub(crate) fn largest_set(values: &mut [i64]) -> i64 {
+    values.sort_unstable();
+    values
+        .chunk_by(|a, b| a == b)
+        .map(|set| set.len() as i64)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The decimal digit of the value in the given place: 0 for units, 1 for tens, and so on.
+/// Negative values have the same digits as their absolute values.
+pub(crate) fn digit(value: i64, place: u32) -> i64 {
+    (value / 10i64.pow(place) % 10).abs()
+}
+
+/// The place value of each die in a roll of dice read as digits, like `d66`:
+/// each die's faces are shifted left past all the digits of the dice after it.
+///
+/// Returns None if a place value, or the largest possible roll, overflows.
+pub(crate) fn digit_places(sizes: &[usize]) -> Option<Vec<i64>> {
+    let mut places = Vec::with_capacity(sizes.len());
+    let mut place = Some(1i64);
+    let mut max = 0i64;
+    for size in sizes.iter().rev() {
+        let p = place?;
+        max = max.checked_add(i64::try_from(*size).ok()?.checked_mul(p)?)?;
+        places.push(p);
+        place = p.checked_mul(10i64.checked_pow(size.checked_ilog10()? + 1)?);
+    }
+    places.reverse();
+    Some(places)
+}
+
 impl Distribution {
     /// Generate a uniform distribution on the closed interval `[1, size]`;
     /// i.e. the distribution for rolling a die with the given number of faces.
-    fn die(size: usize) -> Distribution {
-        let mut v = Vec::new();
-        v.resize(size, 1);
-        Distribution {
-            occurrence_by_value: v,
+    ///
+    /// Returns None if the die is too wide to allocate.
+    fn die(size: usize) -> Option<Distribution> {
+        if size > MAX_WIDTH {
+            return None;
+        }
+        let mut counts = Counts::new();
+        counts.try_resize(size, 1).ok()?;
+        Some(Distribution {
+            occurrence_by_value: counts,
             offset: 1,
+            provenance: Provenance::default(),
+        })
+    }
+
+    /// Generate a uniform distribution on the closed interval `[lo, hi]`;
+    /// i.e. the distribution for rolling a die with faces `lo`, `lo + 1`, ... `hi`.
+    ///
+    /// Returns None if the interval is empty or too wide to represent.
+    pub fn uniform(lo: i64, hi: i64) -> Option<Distribution> {
+        let width = usize::try_from(hi.checked_sub(lo)?).ok()?.checked_add(1)?;
+        let mut d = Distribution::die(width)?;
+        d.offset = lo;
+        Some(d)
+    }
+
+    /// Generate the distribution for rolling a Fudge (Fate) die:
+    /// -1, 0, and +1, each equally likely.
+    pub fn fudge() -> Distribution {
+        Distribution::uniform(-1, 1).expect("fudge die range is valid")
+    }
+
+    /// Generate the distribution for rolling a die with the given faces, each equally likely.
+    /// Faces may repeat, to make some values more likely than others.
+    ///
+    /// Returns None if there are no faces, or they're too far apart to store.
+    pub fn from_faces(faces: impl IntoIterator<Item = i64>) -> Option<Distribution> {
+        Distribution::from_occurrences(faces.into_iter().map(|face| (face, 1)))
+    }
+
+    /// Generate the distribution for rolling a die with the given (face, weight) entries:
+    /// each face is rolled in proportion to its weight.
+    ///
+    /// Returns None if the total weight is zero, or too large to represent.
+    pub fn from_weighted_faces(
+        faces: impl IntoIterator<Item = (i64, usize)> + Clone,
+    ) -> Option<Distribution> {
+        faces
+            .clone()
+            .into_iter()
+            .try_fold(0usize, |total, (_, weight)| total.checked_add(weight))?;
+        Distribution::from_occurrences(faces)
+    }
+
+    /// Generate the distribution for rolling dice of the given sizes and reading them as digits
+    /// of one number, rather than adding them: `[6, 6]` rolls 11 through 66, like a `d66`.
+    /// A die with ten or more faces takes up as many digits as its largest face.
+    ///
+    /// Returns None if there are no dice, a die has no faces or is too wide, or the result
+    /// overflows.
+    pub fn digits(sizes: &[usize]) -> Option<Distribution> {
+        if sizes.is_empty() || sizes.contains(&0) {
+            return None;
+        }
+        let places = digit_places(sizes)?;
+        let dice: Vec<Distribution> = sizes
+            .iter()
+            .map(|n| Distribution::die(*n))
+            .collect::<Option<_>>()?;
+        let terms: Vec<(i64, &Distribution)> = places.into_iter().zip(&dice).collect();
+        Distribution::linear_combination(&terms)
+    }
+
+    /// Generate the distribution with the given (value, occurrences) entries.
+    /// Values may repeat; their occurrences are summed.
+    ///
+    /// Returns None if there are no occurrences, or they don't fit in a distribution:
+    /// the values are too far apart, or the counts overflow.
+    pub(crate) fn from_occurrences(
+        occurrences: impl IntoIterator<Item = (i64, usize)>,
+    ) -> Option<Distribution> {
+        let mut d = Distribution::empty();
+        for (value, occurrences) in occurrences {
+            if occurrences != 0 {
+                d.checked_add_occurrences(value, occurrences)?;
+            }
+        }
+        if d.occurrence_by_value.is_empty() {
+            None
+        } else {
+            Some(d)
         }
     }
 
     /// Generate a "modifier" distribution, which has probability 1 of producing the given value.
-    fn constant(value: usize) -> Distribution {
+    fn constant(value: i64) -> Distribution {
         Distribution {
-            occurrence_by_value: vec![1],
-            offset: value as isize,
+            occurrence_by_value: Counts::filled(1, 1),
+            offset: value,
+            provenance: Provenance::default(),
         }
     }
 
     /// Give the probability of this value occurring in this distribution.
-    pub fn probability(&self, value: isize) -> Ratio<usize> {
-        let index = value - self.offset;
-        if (0..(self.occurrence_by_value.len() as isize)).contains(&index) {
-            Ratio::new(self.occurrence_by_value[index as usize], self.total())
-        } else {
-            Ratio::new(0, 1)
+    pub fn probability(&self, value: i64) -> Ratio<usize> {
+        match self.occurrences_of(value) {
+            0 => Ratio::new(0, 1),
+            n => Ratio::new(n, self.total()),
         }
     }
 
-    pub fn probability_f64(&self, value: isize) -> f64 {
+    pub fn probability_f64(&self, value: i64) -> f64 {
         Ratio::to_f64(&self.probability(value)).expect("should convert probability to f64")
     }
 
+    /// Give the natural logarithm of the probability of this value occurring in this distribution.
+    ///
+    /// This is computed as the difference of the logarithms of the occurrence count and the total,
+    /// rather than the logarithm of the (possibly tiny) ratio, so it does not underflow for
+    /// unlikely values. Values that cannot occur have log-probability of negative infinity.
+    pub fn log_probability(&self, value: i64) -> f64 {
+        let occurrences = self.occurrences_of(value);
+        if occurrences == 0 {
+            return f64::NEG_INFINITY;
+        }
+        (occurrences as f64).ln() - (self.total() as f64).ln()
+    }
+
+    /// The number of occurrences of this value in the distribution.
+    fn occurrences_of(&self, value: i64) -> usize {
+        value
+            .checked_sub(self.offset)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| self.occurrence_by_value.get(index).copied())
+            .unwrap_or(0)
+    }
+
     /// Report the total number of occurrences in this expression, i.e. the number of possible
     /// rolls (rather than the number of distinct values).
     pub fn total(&self) -> usize {
@@ -256,23 +1071,41 @@ impl Distribution {
         v
     }
 
+    /// How this distribution was produced: whether it's exact, and, if an [`Evaluator`]
+    /// computed it, how long that took.
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// The number of values from the minimum to the maximum, inclusive,
+    /// whether or not they can occur: the number of entries this distribution stores.
+    pub fn support_len(&self) -> usize {
+        self.occurrence_by_value.len()
+    }
+
+    /// Approximately how much memory this distribution uses, in bytes,
+    /// including its counts when they are too many to store inline.
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.occurrence_by_value.heap_bytes()
+    }
+
     /// Iterator over (value, occurrences) tuples in this distribution.
     /// Reports values with nonzero occurrence in ascending order of value.
-    pub fn occurrences(&self) -> Occurrences {
+    pub fn occurrences(&self) -> Occurrences<'_> {
         Occurrences {
             distribution: self,
-            current: self.offset,
+            index: 0,
         }
     }
 
     /// The minimum value with nonzero occurrence in this distribution.
-    pub fn min(&self) -> isize {
+    pub fn min(&self) -> i64 {
         self.offset
     }
 
     /// The minimum value with nonzero occurrence in this distribution (note: inclusive)
-    pub fn max(&self) -> isize {
-        self.offset + (self.occurrence_by_value.len() as isize) - 1
+    pub fn max(&self) -> i64 {
+        self.offset + (self.occurrence_by_value.len() as i64 - 1)
     }
 
     /// The average value (expected value) from this distribution.
@@ -283,52 +1116,807 @@ impl Distribution {
             .sum()
     }
 
-    /// Clean up the distribution by removing extraneous zero-valued entries.
-    fn clean(&mut self) {
-        let leading_zeros = self
-            .occurrence_by_value
+    /// The variance of this distribution: the expected squared distance from the mean.
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.occurrences()
+            .map(|(v, _)| (v as f64 - mean).powi(2) * self.probability_f64(v))
+            .sum()
+    }
+
+    /// The standard deviation of this distribution: the square root of its variance.
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// The median of this distribution: the lowest value that at least half of the rolls are
+    /// at or below.
+    pub fn median(&self) -> i64 {
+        self.quantile(Ratio::new(1, 2))
+    }
+
+    /// The `p` quantile of this distribution: the lowest value that at least a fraction `p`
+    /// of the rolls are at or below. `quantile(Ratio::new(9, 10))` is the 90th percentile.
+    ///
+    /// Panics if `p` is more than 1.
+    pub fn quantile(&self, p: Ratio<usize>) -> i64 {
+        assert!(p <= Ratio::from_integer(1), "quantile {p} is more than 1");
+        // Compare at_or_below / total >= p without rounding; the products fit in 128 bits.
+        let total = self.total() as u128;
+        let (numer, denom) = (*p.numer() as u128, *p.denom() as u128);
+        let mut at_or_below = 0u128;
+        for (v, o) in self.occurrences() {
+            at_or_below += o as u128;
+            if at_or_below * denom >= numer * total {
+                return v;
+            }
+        }
+        unreachable!("every roll is at or below the maximum")
+    }
+
+    /// The mean of this distribution, as an exact fraction.
+    ///
+    /// Returns None if the sum over all possible rolls overflows.
+    pub fn mean_exact(&self) -> Option<Ratio<i128>> {
+        let (n, sum, _) = self.moment_sums()?;
+        Ratio::new(sum, n).checked_add(&Ratio::from_integer(i128::from(self.min())))
+    }
+
+    /// The variance of this distribution, as an exact fraction.
+    ///
+    /// Returns None if the sums over all possible rolls overflow.
+    pub fn variance_exact(&self) -> Option<Ratio<i128>> {
+        let (n, sum, squares) = self.moment_sums()?;
+        let mean = Ratio::new(sum, n);
+        Ratio::new(squares, n).checked_sub(&mean.checked_mul(&mean)?)
+    }
+
+    /// The standard deviation of this distribution, from its exact variance: the standard
+    /// deviation itself is usually irrational, but this is as close as an f64 gets.
+    ///
+    /// Returns None if [`Distribution::variance_exact`] does.
+    pub fn stddev_exact(&self) -> Option<f64> {
+        let variance = self.variance_exact()?;
+        Some((*variance.numer() as f64 / *variance.denom() as f64).sqrt())
+    }
+
+    /// The number of possible rolls, and the sums of each roll's distance above the minimum
+    /// and of its square. Measuring from the minimum keeps the sums small; the variance
+    /// doesn't depend on it, and the mean just shifts by it.
+    fn moment_sums(&self) -> Option<(i128, i128, i128)> {
+        let (mut n, mut sum, mut squares) = (0i128, 0i128, 0i128);
+        for (i, o) in self.occurrence_by_value.iter().enumerate() {
+            let (d, o) = (i128::try_from(i).ok()?, i128::try_from(*o).ok()?);
+            n = n.checked_add(o)?;
+            sum = sum.checked_add(d.checked_mul(o)?)?;
+            squares = squares.checked_add(d.checked_mul(d)?.checked_mul(o)?)?;
+        }
+        Some((n, sum, squares))
+    }
+
+    /// The average value of the rolls where the predicate holds: e.g. the average damage on hits.
+    ///
+    /// Returns None if the predicate never holds.
+    pub fn mean_given(&self, predicate: impl Fn(i64) -> bool) -> Option<f64> {
+        let (weight, sum) = self
+            .occurrences()
+            .filter(|(v, _)| predicate(*v))
+            .fold((0.0, 0.0), |(weight, sum), (v, o)| {
+                (weight + o as f64, sum + v as f64 * o as f64)
+            });
+        (weight > 0.0).then(|| sum / weight)
+    }
+
+    /// The variance of the rolls where the predicate holds, about their own mean.
+    ///
+    /// Returns None if the predicate never holds.
+    pub fn variance_given(&self, predicate: impl Fn(i64) -> bool) -> Option<f64> {
+        let mean = self.mean_given(&predicate)?;
+        let (weight, sum) = self.occurrences().filter(|(v, _)| predicate(*v)).fold(
+            (0.0, 0.0),
+            |(weight, sum), (v, o)| {
+                (
+                    weight + o as f64,
+                    sum + (v as f64 - mean).powi(2) * o as f64,
+                )
+            },
+        );
+        Some(sum / weight)
+    }
+
+    /// The distribution of `X + Y`, where `X` and `Y` are independently drawn from
+    /// this distribution and the other.
+    ///
+    /// Returns None if any sum, or the number of possible rolls, overflows.
+    pub fn checked_add(&self, rhs: &Distribution) -> Option<Distribution> {
+        let mut result = self.clone();
+        result.checked_add_assign(rhs)?;
+        debug_assert_eq!(self.total() * rhs.total(), result.total(), "{result:?}");
+        Some(result)
+    }
+
+    /// The distribution of `k1 * X1 + k2 * X2 + ...`, where each `Xi` is drawn independently
+    /// from the corresponding distribution.
+    ///
+    /// Equivalent to scaling each distribution and summing the results,
+    /// but accumulates in place rather than allocating for each intermediate result.
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn linear_combination(terms: &[(i64, &Distribution)]) -> Option<Distribution> {
+        Self::linear_combination_with(terms, &mut Vec::new())
+    }
+
+    /// Compute a linear combination, using (and leaving behind) `scratch` for scaled terms.
+    pub(crate) fn linear_combination_with(
+        terms: &[(i64, &Distribution)],
+        scratch: &mut Vec<usize>,
+    ) -> Option<Distribution> {
+        let mut acc = Distribution::constant(0);
+        for (k, d) in terms {
+            let (a, b) = (d.min().checked_mul(*k)?, d.max().checked_mul(*k)?);
+            let lo = acc.offset.checked_add(a.min(b))?;
+            acc.max().checked_add(a.max(b))?;
+
+            if *k == 1 {
+                convolve_in_place(&mut acc.occurrence_by_value, &d.occurrence_by_value)?;
+            } else if *k == 0 {
+                convolve_in_place(&mut acc.occurrence_by_value, &[d.total()])?;
+            } else {
+                // Spread the occurrences out by |k|, reversing them if k is negative.
+                let stride = k.unsigned_abs() as usize;
+                let len = (d.occurrence_by_value.len() - 1) * stride + 1;
+                scratch.clear();
+                scratch.resize(len, 0);
+                for (i, o) in d.occurrence_by_value.iter().enumerate() {
+                    let j = if *k > 0 {
+                        i * stride
+                    } else {
+                        len - 1 - i * stride
+                    };
+                    scratch[j] = *o;
+                }
+                convolve_in_place(&mut acc.occurrence_by_value, scratch)?;
+            }
+            acc.offset = lo;
+        }
+        Some(acc)
+    }
+
+    /// Add an independent roll from the other distribution to this one, in place:
+    /// `X += Y`, reusing this distribution's storage.
+    ///
+    /// Returns None, leaving this distribution unchanged, if any sum
+    /// or the number of possible rolls overflows.
+    pub fn checked_add_assign(&mut self, rhs: &Distribution) -> Option<()> {
+        let offset = self.offset.checked_add(rhs.offset)?;
+        self.max().checked_add(rhs.max())?;
+        convolve_in_place(&mut self.occurrence_by_value, &rhs.occurrence_by_value)?;
+        self.offset = offset;
+        Some(())
+    }
+
+    /// The distribution of `-X`, where `X` is drawn from this distribution.
+    ///
+    /// Returns None if the negation overflows.
+    pub fn checked_neg(&self) -> Option<Distribution> {
+        self.offset.checked_neg()?;
+        // Negation reverses the order of the values: the highest becomes the new offset.
+        let magnitude = self.max();
+        let occurrence_by_value = self.occurrence_by_value.iter().rev().copied().collect();
+        Some(Distribution {
+            offset: magnitude.checked_neg()?,
+            occurrence_by_value,
+            provenance: Provenance::default(),
+        })
+    }
+
+    /// The distribution of `f(X, Y)`, where `X` and `Y` are independently drawn from
+    /// this distribution and the other.
+    ///
+    /// Returns None if `f` returns None for any pair of values,
+    /// if the number of possible rolls overflows, or if the results are too far apart to store.
+    fn checked_combine(
+        &self,
+        other: &Distribution,
+        f: impl Fn(i64, i64) -> Option<i64>,
+    ) -> Option<Distribution> {
+        self.total().checked_mul(other.total())?;
+        let mut result = Distribution::empty();
+        for ((v1, o1), (v2, o2)) in self.occurrences().cartesian_product(other.occurrences()) {
+            // o1 and o2 each represent the numerator of a fraction, o1/total1 and
+            // o2/total2: the probability that the given value will turn up on a roll.
+            //
+            // The events are independent, so we can combine the probabilities by
+            // multiplying them. This represents _only one way_ to get this value;
+            // accumulate from different rolls.
+            result.checked_add_occurrences(f(v1, v2)?, o1 * o2)?;
+        }
+        Some(result)
+    }
+
+    /// The distribution of an exploding die with this distribution's faces:
+    /// whenever it rolls its highest face, roll again and add the result.
+    ///
+    /// The die explodes at most `depth` times; the last roll keeps its highest face
+    /// rather than rolling again.
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_explode(&self, depth: usize) -> Option<Distribution> {
+        let max = self.max();
+        self.checked_explode_when(depth, |v| v == max)
+    }
+
+    /// The distribution of an exploding die with this distribution's faces, as with
+    /// [`Distribution::checked_explode`], at the least depth where the chance of exploding
+    /// any further is less than `epsilon`.
+    ///
+    /// Returns None if that depth overflows, or if the die always explodes.
+    pub fn explode_until(&self, epsilon: f64) -> Option<Exploded> {
+        let total = self.total();
+        let explodes = self.occurrences_of(self.max());
+        if explodes == total {
+            return None;
+        }
+        for depth in 0.. {
+            // Exploding once more than `depth` takes that many of the highest face in a row.
+            let rolls = u32::try_from(depth + 1).ok()?;
+            let residual = Ratio::new(explodes.checked_pow(rolls)?, total.checked_pow(rolls)?);
+            if Ratio::to_f64(&residual)? < epsilon {
+                let mut distribution = self.checked_explode(depth)?;
+                distribution.provenance.explosion_depth = Some(depth);
+                return Some(Exploded {
+                    distribution,
+                    depth,
+                    residual,
+                });
+            }
+        }
+        unreachable!("the residual shrinks with each explosion")
+    }
+
+    /// The mean of an exploding die with this distribution's faces, exploding without limit:
+    /// the sum of a geometric series, rather than the mean of a distribution cut off at
+    /// some depth.
+    ///
+    /// Returns None if the die always explodes, or if the sum overflows.
+    pub fn explode_mean_exact(&self) -> Option<f64> {
+        let (mean, _) = self.explode_moments_exact()?;
+        Some(*mean.numer() as f64 / *mean.denom() as f64)
+    }
+
+    /// The variance of an exploding die with this distribution's faces, exploding without limit;
+    /// see [`Distribution::explode_mean_exact`].
+    pub fn explode_variance_exact(&self) -> Option<f64> {
+        let (_, variance) = self.explode_moments_exact()?;
+        Some(*variance.numer() as f64 / *variance.denom() as f64)
+    }
+
+    /// The mean and variance of an exploding die, exploding without limit.
+    fn explode_moments_exact(&self) -> Option<(Ratio<i128>, Ratio<i128>)> {
+        // X = F + [F explodes] X', with X' another roll of X. With n rolls of F, of which e
+        // explode, the series sum to
+        //   E[X] = Σ f / (n - e)
+        //   Var[X] = (Σ f² (n - e) + 2 (Σ f, exploding) (Σ f) - (Σ f)²) / (n - e)²
+        let max = self.max();
+        let (mut n, mut e, mut sum, mut squares, mut exploding) =
+            (0i128, 0i128, 0i128, 0i128, 0i128);
+        for (v, o) in self.occurrences() {
+            let (v, o) = (i128::from(v), i128::try_from(o).ok()?);
+            let f = v.checked_mul(o)?;
+            n = n.checked_add(o)?;
+            sum = sum.checked_add(f)?;
+            squares = squares.checked_add(f.checked_mul(v)?)?;
+            if v == i128::from(max) {
+                e = e.checked_add(o)?;
+                exploding = exploding.checked_add(f)?;
+            }
+        }
+        let stops = n - e;
+        if stops == 0 {
+            return None;
+        }
+        let mean = Ratio::new(sum, stops);
+        let variance = Ratio::new(
+            squares
+                .checked_mul(stops)?
+                .checked_add(exploding.checked_mul(sum)?.checked_mul(2)?)?
+                .checked_sub(sum.checked_mul(sum)?)?,
+            stops.checked_mul(stops)?,
+        );
+        Some((mean, variance))
+    }
+
+    /// The distribution of an exploding die with this distribution's faces,
+    /// which rolls again and adds the result whenever it rolls a value matching the predicate.
+    ///
+    /// The die explodes at most `depth` times, as with [`Distribution::checked_explode`].
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_explode_when(
+        &self,
+        depth: usize,
+        explodes: impl Fn(i64) -> bool,
+    ) -> Option<Distribution> {
+        self.checked_explode_with(depth, Some, explodes)
+    }
+
+    /// The distribution of a penetrating die with this distribution's faces:
+    /// like an exploding die, but each roll after the first counts one less.
+    /// The die still explodes on a roll of its highest face, before subtracting.
+    ///
+    /// The die explodes at most `depth` times, as with [`Distribution::checked_explode`].
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_penetrate(&self, depth: usize) -> Option<Distribution> {
+        let max = self.max();
+        self.checked_penetrate_when(depth, |v| v == max)
+    }
+
+    /// The distribution of a penetrating die with this distribution's faces,
+    /// which explodes on a roll matching the predicate (before subtracting).
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_penetrate_when(
+        &self,
+        depth: usize,
+        explodes: impl Fn(i64) -> bool,
+    ) -> Option<Distribution> {
+        self.checked_explode_with(depth, |v| v.checked_sub(1), explodes)
+    }
+
+    /// The distribution of an imploding die with this distribution's faces: the mirror of
+    /// an exploding die, which rolls again and subtracts the result whenever it rolls its
+    /// lowest face. Each roll subtracted implodes in turn, if it is the lowest face too.
+    ///
+    /// The die implodes at most `depth` times, as with [`Distribution::checked_explode`].
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_implode(&self, depth: usize) -> Option<Distribution> {
+        let min = self.min();
+        self.checked_implode_when(depth, |v| v == min)
+    }
+
+    /// The distribution of an imploding die with this distribution's faces,
+    /// which rolls again and subtracts the result whenever it rolls a value matching the predicate.
+    ///
+    /// Returns None if any value, or the number of possible rolls, overflows.
+    pub fn checked_implode_when(
+        &self,
+        depth: usize,
+        explodes: impl Fn(i64) -> bool,
+    ) -> Option<Distribution> {
+        self.checked_explode_with(depth, i64::checked_neg, explodes)
+    }
+
+    /// Explode on the values matching the predicate,
+    /// counting each roll after the first as `later` gives it.
+    fn checked_explode_with(
+        &self,
+        depth: usize,
+        later: impl Fn(i64) -> Option<i64>,
+        explodes: impl Fn(i64) -> bool,
+    ) -> Option<Distribution> {
+        let counted = |roll: usize, value: i64| if roll == 0 { Some(value) } else { later(value) };
+        // Build up from the last roll, which doesn't explode.
+        let mut result = Distribution::empty();
+        for (value, occurrences) in self.occurrences() {
+            result.add_occurrences(counted(depth, value)?, occurrences);
+        }
+        for roll in (0..depth).rev() {
+            // Every roll of this die is followed by all the possibilities of the remaining rolls;
+            // the faces that don't explode stop there, so count them once for each.
+            let total = result.total();
+            self.total().checked_mul(total)?;
+            let mut next = Distribution::empty();
+            for (value, occurrences) in self.occurrences() {
+                let counted = counted(roll, value)?;
+                if explodes(value) {
+                    for (rest, rest_occurrences) in result.occurrences() {
+                        next.add_occurrences(
+                            counted.checked_add(rest)?,
+                            occurrences * rest_occurrences,
+                        );
+                    }
+                } else {
+                    next.add_occurrences(counted, occurrences * total);
+                }
+            }
+            result = next;
+        }
+        Some(result)
+    }
+
+    /// The individual rolls of an exploding die with this distribution's faces,
+    /// each with its occurrences; a roll matching the predicate is followed by another roll.
+    ///
+    /// Occurrences are on the same footing as [`Distribution::checked_explode`].
+    fn explosion_rolls(
+        &self,
+        depth: usize,
+        explodes: impl Fn(i64) -> bool,
+    ) -> Option<Vec<(Vec<i64>, usize)>> {
+        let mut result = Vec::new();
+        // The sequences of exploding rolls so far, with their occurrences.
+        let mut exploding = vec![(Vec::new(), 1usize)];
+        for explosions in 0..=depth {
+            // This sequence of rolls stands in for all the possibilities of the rolls it didn't make.
+            let weight = self
+                .total()
+                .checked_pow(u32::try_from(depth - explosions).ok()?)?;
+            let mut next = Vec::new();
+            for (rolls, rolls_occurrences) in &exploding {
+                for (value, occurrences) in self.occurrences() {
+                    let mut rolls = rolls.clone();
+                    rolls.push(value);
+                    let occurrences = occurrences.checked_mul(*rolls_occurrences)?;
+                    if explodes(value) && explosions < depth {
+                        next.push((rolls, occurrences));
+                    } else {
+                        result.push((rolls, occurrences.checked_mul(weight)?));
+                    }
+                }
+            }
+            exploding = next;
+        }
+        Some(result)
+    }
+
+    /// The distribution of a die with this distribution's faces that is rerolled once
+    /// when it shows a value matching the predicate. The reroll is kept, even if it matches too.
+    ///
+    /// Returns None if the number of possible rolls overflows.
+    pub fn checked_reroll_once(&self, predicate: impl Fn(i64) -> bool) -> Option<Distribution> {
+        let total = self.total();
+        total.checked_mul(total)?;
+        // Each face that is kept stands in for all the possibilities of the reroll it didn't make.
+        let rerolled: usize = self
+            .occurrences()
+            .filter(|(v, _)| predicate(*v))
+            .map(|(_, o)| o)
+            .sum();
+        let mut result = Distribution::empty();
+        for (value, occurrences) in self.occurrences() {
+            let kept = if predicate(value) { 0 } else { total };
+            result.add_occurrences(value, occurrences * (kept + rerolled));
+        }
+        Some(result)
+    }
+
+    /// The distribution of a die with this distribution's faces that is rerolled for as long as
+    /// it shows a value matching the predicate: the same as never having those faces.
+    ///
+    /// Returns None if every value matches, so the die would be rerolled forever.
+    pub fn reroll_while(&self, predicate: impl Fn(i64) -> bool) -> Option<Distribution> {
+        Distribution::from_occurrences(self.occurrences().filter(|(v, _)| !predicate(*v)))
+    }
+
+    /// The distribution of `max(0, X - Y)`, where `X` is drawn from this distribution
+    /// and `Y` independently from the other: damage, reduced by armor or soak.
+    ///
+    /// Damage never goes negative, even if the damage roll itself can be negative.
+    ///
+    /// Returns None if any difference overflows.
+    pub fn checked_soak(&self, armor: &Distribution) -> Option<Distribution> {
+        self.checked_combine(armor, |damage, armor| {
+            if damage <= armor {
+                Some(0)
+            } else {
+                damage.checked_sub(armor)
+            }
+        })
+    }
+
+    /// The distribution of the absolute value: the negative values fold onto the positive ones.
+    ///
+    /// Returns None if the absolute value of some value overflows.
+    pub fn checked_abs(&self) -> Option<Distribution> {
+        self.min().checked_abs()?;
+        Some(self.map_values(i64::abs))
+    }
+
+    /// The distribution of the higher of independent rolls from this distribution and the other.
+    ///
+    /// Returns None if the number of possible rolls overflows.
+    pub fn checked_max(&self, other: &Distribution) -> Option<Distribution> {
+        self.checked_combine(other, |a, b| Some(a.max(b)))
+    }
+
+    /// The distribution of the lower of independent rolls from this distribution and the other.
+    ///
+    /// Returns None if the number of possible rolls overflows.
+    pub fn checked_min(&self, other: &Distribution) -> Option<Distribution> {
+        self.checked_combine(other, |a, b| Some(a.min(b)))
+    }
+
+    /// An opposed roll of this distribution against the other: the distribution of the margin,
+    /// this roll minus the other, with the chances of winning, tying, and losing.
+    ///
+    /// Returns None if any margin, or the number of possible rolls, overflows.
+    pub fn checked_margin(&self, other: &Distribution) -> Option<Margin> {
+        let margin = self.checked_combine(other, i64::checked_sub)?;
+        let win = margin.probability_where(|v| v > 0);
+        let tie = margin.probability(0);
+        Some(Margin {
+            lose: Ratio::from_integer(1) - win - tie,
+            win,
+            tie,
+            margin,
+        })
+    }
+
+    /// The damage dealt by this distribution against a saving throw that halves it,
+    /// truncated toward zero, where `saved` is nonzero (a success): "half damage on a successful save".
+    ///
+    /// Returns None if the number of possible rolls overflows.
+    pub fn checked_save(&self, saved: &Distribution) -> Option<Distribution> {
+        saved.checked_choose(&self.map_values(|v| v / 2), self)
+    }
+
+    /// The distribution of a value composed from a roll of this distribution and a roll of
+    /// `part`, `compose(roll, part)`, where `part` is rolled `rolls` times and the highest
+    /// (or lowest) composed value is kept: a reroll of only one part of the value, like the
+    /// bonus and penalty tens dice of Call of Cthulhu.
+    ///
+    /// Returns None if `compose` returns None for any pair of values,
+    /// or if the number of possible rolls overflows.
+    /// Panics if `rolls` is zero.
+    pub fn checked_reroll_part(
+        &self,
+        part: &Distribution,
+        rolls: u32,
+        highest: bool,
+        compose: impl Fn(i64, i64) -> Option<i64>,
+    ) -> Option<Distribution> {
+        assert!(rolls > 0, "the part must be rolled at least once");
+        self.total().checked_mul(part.total().checked_pow(rolls)?)?;
+        let mut result = Distribution::empty();
+        for (v, o) in self.occurrences() {
+            let composed = Distribution::from_occurrences(
+                part.occurrences()
+                    .map(|(p, n)| Some((compose(v, p)?, n)))
+                    .collect::<Option<Vec<_>>>()?,
+            )
+            .expect("distributions have at least one value");
+            let mut composed: Vec<(i64, usize)> = composed.occurrences().collect();
+            if !highest {
+                composed.reverse();
+            }
+            // From the worst value to the best: each is kept when no roll is better,
+            // but not every roll is worse.
+            let mut worse = 0usize;
+            for (w, n) in composed {
+                let ways = (worse + n).pow(rolls) - worse.pow(rolls);
+                result.add_occurrences(w, o * ways);
+                worse += n;
+            }
+        }
+        Some(result)
+    }
+
+    /// The distribution of a roll from `then` where this distribution is nonzero,
+    /// or from `otherwise` where it is zero.
+    ///
+    /// Returns None if the number of possible rolls overflows.
+    pub fn checked_choose(
+        &self,
+        then: &Distribution,
+        otherwise: &Distribution,
+    ) -> Option<Distribution> {
+        let (t, f) = self
+            .occurrences()
+            .fold((0usize, 0usize), |(t, f), (v, o)| match v {
+                0 => (t, f + o),
+                _ => (t + o, f),
+            });
+        if f == 0 {
+            return Some(then.clone());
+        }
+        if t == 0 {
+            return Some(otherwise.clone());
+        }
+        // Over the common denominator of all three distributions; no sum can exceed the total.
+        then.total()
+            .checked_mul(otherwise.total())?
+            .checked_mul(t + f)?;
+        let mut d = Distribution::empty();
+        for (weight, branch) in [(t * otherwise.total(), then), (f * then.total(), otherwise)] {
+            for (v, o) in branch.occurrences() {
+                d.add_occurrences(v, o * weight);
+            }
+        }
+        Some(d)
+    }
+
+    /// The distribution of how many of the thresholds a value meets or exceeds:
+    /// 0 below the first threshold, 1 from the first up to (but not including) the second,
+    /// and so on.
+    ///
+    /// Panics if the thresholds are not in ascending order.
+    pub fn thresholds(&self, thresholds: &[i64]) -> Distribution {
+        assert!(
+            thresholds.is_sorted(),
+            "thresholds {thresholds:?} are not in ascending order"
+        );
+        self.map_values(|v| thresholds.partition_point(|t| *t <= v) as i64)
+    }
+
+    /// The probability of each band of values, labeled: each band runs from its lower bound
+    /// up to (but not including) the next band's. Values below the first band's lower bound
+    /// are in no band; start it at `i64::MIN` to take in everything.
+    ///
+    /// Powered by the Apocalypse moves are `[("miss", i64::MIN), ("weak hit", 7), ("strong hit", 10)]`.
+    ///
+    /// Panics if the lower bounds are not in ascending order.
+    pub fn banded<L: Clone>(&self, bands: &[(L, i64)]) -> Vec<(L, Ratio<usize>)> {
+        let bounds: Vec<i64> = bands.iter().map(|(_, lo)| *lo).collect();
+        let d = self.thresholds(&bounds);
+        bands
             .iter()
-            .take_while(|&&f| f == 0)
-            .count();
-        if leading_zeros > 0 {
-            self.occurrence_by_value = self.occurrence_by_value[leading_zeros..].into();
-            self.offset += leading_zeros as isize;
+            .enumerate()
+            .map(|(i, (label, _))| (label.clone(), d.probability(i as i64 + 1)))
+            .collect()
+    }
+
+    /// Remove the least likely values from either end of the distribution, as long as their
+    /// total probability is at most `epsilon`, and return that probability.
+    ///
+    /// What remains is scaled up to make up for the removed values, so each probability read
+    /// from the trimmed distribution is off by at most the returned amount. The most likely
+    /// value is never removed. The amount is also added to [`Provenance::trimmed`].
+    pub fn trim_tail(&mut self, epsilon: f64) -> Ratio<usize> {
+        let total = self.total();
+        let counts = &self.occurrence_by_value;
+        let (mut lo, mut hi) = (0, counts.len() - 1);
+        let mut trimmed = 0;
+        while lo < hi {
+            // Trim the less likely end, or the less trimmed one if they're alike;
+            // if that doesn't fit, neither does the other.
+            let low = (counts[lo], lo) <= (counts[hi], counts.len() - 1 - hi);
+            let count = if low { counts[lo] } else { counts[hi] };
+            if (trimmed + count) as f64 > epsilon * total as f64 {
+                break;
+            }
+            trimmed += count;
+            if low {
+                lo += 1;
+            } else {
+                hi -= 1;
+            }
+        }
+        let offset = self.offset;
+        let provenance = std::mem::take(&mut self.provenance);
+        *self = Distribution::from_occurrences(
+            (lo..=hi).map(|i| (offset + i as i64, self.occurrence_by_value[i])),
+        )
+        .expect("should keep the most likely value");
+        let trimmed = Ratio::new(trimmed, total);
+        self.provenance = Provenance {
+            trimmed: provenance.trimmed + trimmed,
+            ..provenance
+        };
+        trimmed
+    }
+
+    /// The distribution of `k * X`, where `X` is drawn from this distribution.
+    ///
+    /// Returns None if any product overflows.
+    pub fn checked_scale(&self, k: i64) -> Option<Distribution> {
+        self.checked_combine(&Distribution::constant(k), i64::checked_mul)
+    }
+
+    /// The distribution of `f(X)`, where `X` is drawn from this distribution.
+    pub(crate) fn map_values(&self, mut f: impl FnMut(i64) -> i64) -> Distribution {
+        let mut d = Distribution::empty();
+        for (v, o) in self.occurrences() {
+            d.add_occurrences(f(v), o);
         }
-        let trailing_zeros = self
-            .occurrence_by_value
-            .iter()
-            .rev()
-            .take_while(|&&f| f == 0)
-            .count();
-        self.occurrence_by_value
-            .truncate(self.occurrence_by_value.len() - trailing_zeros);
+        d
     }
 
     /// Add the given occurrences to the values table.
-    fn add_occurrences(&mut self, value: isize, occurrences: usize) {
+    fn add_occurrences(&mut self, value: i64, occurrences: usize) {
+        self.checked_add_occurrences(value, occurrences)
+            .expect("occurrences overflowed");
+    }
+
+    /// Add the given occurrences to the values table, or return None if the count
+    /// for the value overflows, or the table would be too wide to allocate.
+    fn checked_add_occurrences(&mut self, value: i64, occurrences: usize) -> Option<()> {
+        if self.occurrence_by_value.is_empty() {
+            self.offset = value;
+        }
         if value < self.offset {
-            let diff = (self.offset - value) as usize;
-            let new_len = self.occurrence_by_value.len() + diff;
-            self.occurrence_by_value.resize(new_len, 0);
+            let diff = usize::try_from(self.offset.abs_diff(value)).ok()?;
+            let new_len = self.occurrence_by_value.len().checked_add(diff)?;
+            if new_len > MAX_WIDTH {
+                return None;
+            }
+            self.occurrence_by_value.try_resize(new_len, 0).ok()?;
             // Swap "upwards", starting from the newly long end
             for i in (diff..self.occurrence_by_value.len()).rev() {
                 self.occurrence_by_value.swap(i, i - diff);
             }
             self.offset = value;
         }
-        let index = (value - self.offset) as usize;
+        let index = usize::try_from(value.abs_diff(self.offset)).ok()?;
+        if index >= MAX_WIDTH {
+            return None;
+        }
         if index >= self.occurrence_by_value.len() {
-            self.occurrence_by_value.resize(index + 1, 0);
+            self.occurrence_by_value.try_resize(index + 1, 0).ok()?;
         }
-        self.occurrence_by_value[index] += occurrences;
+        let count = &mut self.occurrence_by_value[index];
+        *count = count.checked_add(occurrences)?;
+        Some(())
     }
 
     fn empty() -> Self {
         Self {
-            occurrence_by_value: vec![],
+            occurrence_by_value: Counts::new(),
             offset: 0,
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+/// Below this length, direct convolution is faster than Karatsuba multiplication.
+const KARATSUBA_MIN_LEN: usize = 12;
+
+/// Below this length, Karatsuba multiplication is faster than the NTT: in a release build,
+/// Karatsuba takes three quarters of the time at 4096 entries, and the NTT two thirds at 8192.
+///
+/// Few sums of dice are this wide and still countable in a `usize`: wide ranges, like
+/// `d[1..10000] + d[1..10000]`, are. Pools with more rolls than that, like `200d100`,
+/// are [`BigDistribution`]s, which always use the NTT.
+const NTT_MIN_LEN: usize = 6144;
+
+/// Convolve `a` with `b`, in place: replace `a` with its (polynomial) product with `b`.
+///
+/// Only grows `a`, so a reused buffer doesn't need to reallocate once it's large enough.
+///
+/// Returns None, leaving `a` unchanged, if the number of occurrences overflows.
+fn convolve_in_place(a: &mut Counts, b: &[usize]) -> Option<()> {
+    // No entry of the result can exceed the product of the totals.
+    a.iter()
+        .sum::<usize>()
+        .checked_mul(b.iter().try_fold(0usize, |acc, v| acc.checked_add(*v))?)?;
+
+    let n = a.len();
+    let out_len = n + b.len() - 1;
+    let nonzero = |v: &[usize]| v.iter().filter(|o| **o != 0).count();
+    if nonzero(a) * nonzero(b) * 2 < n * b.len() {
+        // Mostly zeros, e.g. after scaling: only visit the nonzero pairs.
+        let a_nonzero: Vec<(usize, usize)> = a
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, o)| *o != 0)
+            .collect();
+        a.clear();
+        a.resize(out_len, 0);
+        for (j, ob) in b.iter().enumerate().filter(|(_, o)| **o != 0) {
+            for (i, oa) in &a_nonzero {
+                a[i + j] += oa * ob;
+            }
+        }
+    } else if n.min(b.len()) >= KARATSUBA_MIN_LEN {
+        let product = if n.min(b.len()) >= NTT_MIN_LEN {
+            ntt::convolve(a, b)
+        } else {
+            karatsuba::convolve(a, b)
+        };
+        a.resize(out_len, 0);
+        a.copy_from_slice(&product);
+    } else {
+        a.resize(out_len, 0);
+        // Fill from the top down: each output entry k only reads a[..=k], which isn't yet overwritten.
+        for k in (0..out_len).rev() {
+            let lo = k.saturating_sub(n - 1);
+            let hi = k.min(b.len() - 1);
+            a[k] = (lo..=hi).map(|j| a[k - j] * b[j]).sum();
         }
     }
+    Some(())
 }
 
 /// An iterator over the occurrences in a distribution.
@@ -337,23 +1925,22 @@ impl Distribution {
 #[derive(Debug, Clone)]
 pub struct Occurrences<'a> {
     distribution: &'a Distribution,
-    current: isize,
+    index: usize,
 }
 
 impl Iterator for Occurrences<'_> {
-    type Item = (isize, usize);
+    type Item = (i64, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let value = self.current;
-            let index = (value - self.distribution.offset) as usize;
+            let index = self.index;
             if index < self.distribution.occurrence_by_value.len() {
-                self.current += 1;
+                self.index += 1;
                 let occ = self.distribution.occurrence_by_value[index];
                 if occ == 0 {
                     continue;
                 } else {
-                    break Some((value, occ));
+                    break Some((self.distribution.offset + index as i64, occ));
                 }
             } else {
                 break None;
@@ -365,51 +1952,36 @@ impl Iterator for Occurrences<'_> {
 impl std::ops::Add<&Distribution> for &Distribution {
     type Output = Distribution;
 
+    /// Panics if any sum overflows; see [`Distribution::checked_add`].
     fn add(self, rhs: &Distribution) -> Self::Output {
-        let a = self;
-        let b = rhs;
-
-        let mut result = Distribution::empty();
-
-        for ((v1, o1), (v2, o2)) in a.occurrences().cartesian_product(b.occurrences()) {
-            let val = v1 + v2;
-            // aocc and bocc each represent the numerator of a fraction, aocc/atotal and
-            // bocc/btotal. That fraction is the probability that the given value will turn up
-            // on a roll.
-            //
-            // The events are independent, so we can combine the probabilities by summing them.
-            let occ = o1 * o2;
-            // This represents _only one way_ to get this value: this roll from A, this roll
-            // from B.
-            // Accumulate from different rolls:
-            result.add_occurrences(val, occ);
-        }
-
-        debug_assert_eq!(a.total() * b.total(), result.total(), "{result:?}");
+        self.checked_add(rhs).expect("overflow in distribution sum")
+    }
+}
 
-        result
+impl std::ops::AddAssign<&Distribution> for Distribution {
+    /// Panics if any sum overflows; see [`Distribution::checked_add_assign`].
+    fn add_assign(&mut self, rhs: &Distribution) {
+        self.checked_add_assign(rhs)
+            .expect("overflow in distribution sum")
     }
 }
 
 impl std::ops::Add<Distribution> for Distribution {
     type Output = Distribution;
 
-    fn add(self, rhs: Distribution) -> Self::Output {
-        (&self) + (&rhs)
+    fn add(mut self, rhs: Distribution) -> Self::Output {
+        self += &rhs;
+        self
     }
 }
 
 impl Neg for &Distribution {
     type Output = Distribution;
 
+    /// Panics if the negation overflows; see [`Distribution::checked_neg`].
     fn neg(self) -> Self::Output {
-        // The largest magnitude entry has
-        let magnitude = (self.occurrence_by_value.len() - 1) as isize + self.offset;
-        let occurrence_by_value = self.occurrence_by_value.iter().rev().copied().collect();
-        Distribution {
-            offset: -magnitude,
-            occurrence_by_value,
-        }
+        self.checked_neg()
+            .expect("overflow in distribution negation")
     }
 }
 
@@ -423,8 +1995,11 @@ impl Neg for Distribution {
 
 impl std::iter::Sum for Distribution {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.reduce(|a, b| a + b)
-            .unwrap_or_else(|| Distribution::constant(0))
+        iter.reduce(|mut a, b| {
+            a += &b;
+            a
+        })
+        .unwrap_or_else(|| Distribution::constant(0))
     }
 }
 
@@ -434,11 +2009,25 @@ impl Closed {
         let mut eval = Evaluator::default();
         eval.eval(self)
     }
+
+    /// Retrieve the distribution for the expression, along with measurements of how long
+    /// each part of the expression took to evaluate and how large its result was.
+    pub fn profile(&self) -> Result<Profile, Error> {
+        let mut eval = Evaluator {
+            profile: Some(Vec::new()),
+            ..Default::default()
+        };
+        let distribution = eval.eval(self)?;
+        Ok(Profile {
+            distribution,
+            nodes: eval.profile.unwrap_or_default(),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parse::RawExpression;
+    use crate::{Parser, parse::RawExpression};
 
     use super::*;
 
@@ -458,7 +2047,7 @@ mod tests {
     fn d20() {
         let d = distribution_of("d20").unwrap();
 
-        for i in 1..=20isize {
+        for i in 1..=20i64 {
             assert_eq!(d.probability(i), Ratio::new(1, 20));
         }
 
@@ -471,7 +2060,7 @@ mod tests {
     fn d20_plus1() {
         let d = distribution_of("d20 + 1").unwrap();
 
-        for i in 2..=21isize {
+        for i in 2..=21i64 {
             assert_eq!(d.probability(i), Ratio::new(1, 20));
         }
 
@@ -480,13 +2069,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn opposed() {
+        let d6 = Distribution::die(6).unwrap();
+        let m = d6.checked_margin(&d6).unwrap();
+        assert_eq!(
+            (m.win, m.tie, m.lose),
+            (Ratio::new(5, 12), Ratio::new(1, 6), Ratio::new(5, 12))
+        );
+        assert_eq!((m.margin.min(), m.margin.max()), (-5, 5));
+        assert_eq!(m.margin.probability(3), Ratio::new(3, 36));
+
+        // A bonus shifts the margin, and ties become wins.
+        let plus_one = distribution_of("d6 + 1").unwrap();
+        let m = plus_one.checked_margin(&d6).unwrap();
+        assert_eq!(m.win, Ratio::new(21, 36));
+        assert_eq!(m.tie, Ratio::new(5, 36));
+        assert_eq!(m.win + m.tie + m.lose, Ratio::from_integer(1));
+        assert_eq!(m.margin, distribution_of("d6 + 1 - d6").unwrap());
+
+        let extreme = Distribution::constant(i64::MIN);
+        assert_eq!(extreme.checked_margin(&d6), None);
+    }
+
     #[test]
     fn two_d4() {
         let d = distribution_of("2d4").unwrap();
+        crate::assert_distribution_eq!(d, {
+            2: 1/16, 3: 2/16, 4: 3/16, 5: 4/16, 6: 3/16, 7: 2/16, 8: 1/16
+        });
+    }
 
-        for (v, p) in [(2, 1), (3, 2), (4, 3), (5, 4), (6, 3), (7, 2), (8, 1)] {
-            assert_eq!(d.probability(v), Ratio::new(p, 16));
-        }
+    #[test]
+    fn variable_count() {
+        // One die half the time, two dice the other half.
+        let d = distribution_of("(d2)d2").unwrap();
+        crate::assert_distribution_eq!(d, {1: 2/8, 2: 3/8, 3: 2/8, 4: 1/8});
+
+        let d = distribution_of("(d2)d4kh").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1, 8) + Ratio::new(1, 32));
+    }
+
+    #[test]
+    fn large_pool() {
+        let d = distribution_of("12d6").unwrap();
+        assert_eq!((d.min(), d.max()), (12, 72));
+        assert_eq!(d.total(), 6usize.pow(12));
+        assert!((d.mean() - 42.0).abs() < 1e-9);
     }
 
     #[test]
@@ -515,6 +2144,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn drop() {
+        assert_eq!(
+            distribution_of("4d6dl").unwrap(),
+            distribution_of("4d6kh3").unwrap()
+        );
+        assert_eq!(
+            distribution_of("3d20dh2").unwrap(),
+            distribution_of("3d20kl").unwrap()
+        );
+        // The number kept depends on how many dice are rolled.
+        let d = distribution_of("(d2 + 1)d4dl").unwrap();
+        // Two dice keep one; three keep two.
+        assert_eq!(d.min(), 1);
+        assert_eq!(d.max(), 8);
+        // Dropping every die leaves nothing.
+        assert_eq!(distribution_of("2d6dh2").unwrap().max(), 0);
+
+        let e = distribution_of("(d3)d6dl2").unwrap_err();
+        assert!(matches!(e, Error::DropTooMany(2, _)));
+    }
+
+    #[test]
+    fn keep_expression() {
+        // Keeping one or two, equally often.
+        let d = distribution_of("3d6kh(d2)").unwrap();
+        let one = distribution_of("3d6kh").unwrap();
+        let two = distribution_of("3d6kh2").unwrap();
+        for v in 1..=12 {
+            assert_eq!(
+                d.probability(v),
+                (one.probability(v) + two.probability(v)) / 2
+            );
+        }
+        assert_eq!(d, distribution_of("[N: d2] 3d6kh(N)").unwrap());
+        // A constant count is the same as a number.
+        assert_eq!(
+            distribution_of("4d6dl(3 - 2)").unwrap(),
+            distribution_of("4d6dl").unwrap()
+        );
+
+        let e = distribution_of("2d6kh(d3)").unwrap_err();
+        assert!(matches!(e, Error::KeepTooFew(3, _)));
+        let e = distribution_of("2d6kh(1 - 2)").unwrap_err();
+        assert!(matches!(e, Error::NegativeCount(_)));
+    }
+
+    #[test]
+    fn matching() {
+        let d = distribution_of("2d6m").unwrap();
+        assert_eq!(d.probability(2), Ratio::new(1, 6));
+        assert_eq!(d.probability(1), Ratio::new(5, 6));
+        let d = distribution_of("3d6m").unwrap();
+        assert_eq!(d.probability(3), Ratio::new(6, 216));
+        // Three ways to pick the odd one out; six values for the pair, and five for the other.
+        assert_eq!(d.probability(2), Ratio::new(3 * 6 * 5, 216));
+        assert_eq!(d.probability(1), Ratio::new(6 * 5 * 4, 216));
+        // More dice than faces always match.
+        assert_eq!(distribution_of("3d2m").unwrap().min(), 2);
+        assert_eq!(largest_set(&mut [3, 1, 3, 2, 1, 3]), 3);
+    }
+
     #[test]
     fn require_dice_to_keep() {
         for expr in ["2d4kh3", "(1d4)(4)kl2"] {
@@ -526,19 +2217,1052 @@ mod tests {
     #[test]
     fn negative_modifier() {
         let d = distribution_of("1d4 + -1").unwrap();
-        for i in 0..3isize {
+        for i in 0..3i64 {
             assert_eq!(d.probability(i), Ratio::new(1, 4));
         }
     }
 
     #[test]
     fn negative_die() {
-        let d = -Distribution::die(4) + Distribution::constant(1);
-        for i in -3..=0isize {
+        let d = -Distribution::die(4).unwrap() + Distribution::constant(1);
+        for i in -3..=0i64 {
             assert_eq!(d.probability(i), Ratio::new(1, 4), "{d:?}");
         }
     }
 
+    #[test]
+    fn min_max() {
+        let d = distribution_of("d20 + 1").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 21));
+        let d = distribution_of("1d4 * 3").unwrap();
+        assert_eq!((d.min(), d.max()), (3, 12));
+    }
+
+    #[test]
+    fn log_probability() {
+        let d = distribution_of("2d4").unwrap();
+        for (v, p) in [(2, 1), (3, 2), (4, 3), (5, 4), (6, 3), (7, 2), (8, 1)] {
+            let want = (p as f64 / 16.0).ln();
+            assert!((d.log_probability(v) - want).abs() < 1e-12, "value {v}");
+        }
+        assert_eq!(d.log_probability(1), f64::NEG_INFINITY);
+        assert_eq!(d.log_probability(9), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn range_die() {
+        let d = distribution_of("d[-5..5]").unwrap();
+        assert_eq!((d.min(), d.max()), (-5, 5));
+        for i in -5..=5 {
+            assert_eq!(d.probability(i), Ratio::new(1, 11));
+        }
+        assert_eq!(
+            distribution_of("d[1..6]").unwrap(),
+            distribution_of("d6").unwrap()
+        );
+        assert_eq!(Distribution::uniform(3, 2), None);
+
+        // Too wide to roll, but not to parse: these fail, rather than exhausting memory.
+        for e in [
+            "d[1..1000000000000]",
+            "d1000000000000",
+            "3d[0..1000000000000] + 1",
+        ] {
+            let got = distribution_of(e);
+            assert!(matches!(got, Err(Error::TooComplex(_))), "{e}: {got:?}");
+        }
+        assert_eq!(Distribution::uniform(i64::MIN, i64::MAX), None);
+    }
+
+    #[test]
+    fn faces_die() {
+        let d = distribution_of("d{1,1,3,5,8}").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(2, 5));
+        assert_eq!(d.probability(2), Ratio::new(0, 1));
+        assert_eq!(d.probability(8), Ratio::new(1, 5));
+        assert_eq!(
+            distribution_of("d{1,2,3,4,5,6}").unwrap(),
+            distribution_of("d6").unwrap()
+        );
+
+        // Custom faces work with pools, rerolls, and explosions like any other die.
+        let d = distribution_of("2d{0,0,1,2}kh").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(1, 4));
+        let d = distribution_of("d{-1,5}!").unwrap();
+        assert_eq!(d.max(), 15);
+    }
+
+    #[test]
+    fn digits_die() {
+        let d = distribution_of("d66").unwrap();
+        assert_eq!((d.min(), d.max()), (11, 66));
+        assert_eq!(d.probability(11), Ratio::new(1, 36));
+        assert_eq!(d.probability(17), Ratio::new(0, 1));
+        assert_eq!(d.probability(20), Ratio::new(0, 1));
+        let d = distribution_of("d666").unwrap();
+        assert_eq!((d.min(), d.max()), (111, 666));
+        assert_eq!(d.probability(345), Ratio::new(1, 216));
+
+        // A die with ten faces takes two digits.
+        let d = distribution_of("d(2,10)").unwrap();
+        assert_eq!((d.min(), d.max()), (101, 210));
+        assert_eq!(d.probability(110), Ratio::new(1, 20));
+        assert_eq!(d.probability(111), Ratio::new(0, 1));
+
+        // Still a die with 66 faces, when written out.
+        let d = distribution_of("d[1..66]").unwrap();
+        assert_eq!(d.probability(17), Ratio::new(1, 66));
+
+        assert!(matches!(
+            distribution_of("d(10,10,10,10,10,10,10,10,10,10)"),
+            Err(Error::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn weighted_die() {
+        let d = distribution_of("d{1:3,2:1,3:1}").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(3, 5));
+        assert_eq!(d.probability(3), Ratio::new(1, 5));
+        assert_eq!(
+            distribution_of("d{1:2,3:1,1:1}").unwrap(),
+            distribution_of("d{1,1,1,3}").unwrap()
+        );
+        let d = distribution_of("2d{0:1,1:3}").unwrap();
+        assert_eq!(d.probability(2), Ratio::new(9, 16));
+
+        // Weights that can't be counted are an error, not a wrong answer.
+        let huge = usize::MAX / 2 + 1;
+        assert!(matches!(
+            distribution_of(&format!("d{{1:{huge},2:{huge}}}")),
+            Err(Error::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn fudge_die() {
+        let d = distribution_of("4dF").unwrap();
+        assert_eq!((d.min(), d.max()), (-4, 4));
+        assert_eq!(d.probability(0), Ratio::new(19, 81));
+        assert_eq!(d.probability(4), Ratio::new(1, 81));
+        assert_eq!(d.mean(), 0.0);
+        assert_eq!(
+            distribution_of("dF").unwrap(),
+            distribution_of("d[-1..1]").unwrap()
+        );
+    }
+
+    #[test]
+    fn max_min() {
+        let d = distribution_of("max(d6, d6)").unwrap();
+        assert_eq!(d, distribution_of("2d6kh").unwrap());
+        let d = distribution_of("min(d4, d6)").unwrap();
+        assert_eq!((d.min(), d.max()), (1, 4));
+        assert_eq!(d.probability(4), Ratio::new(3, 24));
+        let d = distribution_of("max(d4, d6)").unwrap();
+        assert_eq!(d.probability(6), Ratio::new(1, 6));
+        assert_eq!(d.probability(1), Ratio::new(1, 24));
+
+        // The higher of 1d8 or 2d4: over each pair of rolls, not the higher of the two averages.
+        let d = distribution_of("max(1d8, 2d4)").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 8));
+        // 2 only if the d8 rolls at most 2 and the 2d4 rolls exactly 2.
+        assert_eq!(d.probability(2), Ratio::new(2, 8 * 16));
+        assert_eq!(
+            d.probability(8),
+            Ratio::new(1, 8) + Ratio::new(7, 8) * Ratio::new(1, 16)
+        );
+        // Nested, for more than two.
+        assert_eq!(
+            distribution_of("max(max(d6, d6), d6)").unwrap(),
+            distribution_of("3d6kh").unwrap()
+        );
+    }
+
+    #[test]
+    fn abs() {
+        // The difference between two rolls.
+        let d = distribution_of("abs(1d6 - 1d6)").unwrap();
+        assert_eq!((d.min(), d.max()), (0, 5));
+        assert_eq!(d.probability(0), Ratio::new(6, 36));
+        assert_eq!(d.probability(1), Ratio::new(10, 36));
+        assert_eq!(d.probability(5), Ratio::new(2, 36));
+        assert_eq!(
+            distribution_of("abs(d6)").unwrap(),
+            distribution_of("d6").unwrap()
+        );
+        assert!(Distribution::constant(i64::MIN).checked_abs().is_none());
+    }
+
+    #[test]
+    fn clamp() {
+        let d = distribution_of("clamp(1d6 - 3, 0, 6)").unwrap();
+        assert_eq!((d.min(), d.max()), (0, 3));
+        // All the mass below the floor lands on it.
+        assert_eq!(d.probability(0), Ratio::new(3, 6));
+        assert_eq!(d.probability(3), Ratio::new(1, 6));
+        let d = distribution_of("clamp(2d6, 4, 9)").unwrap();
+        assert_eq!((d.min(), d.max()), (4, 9));
+        assert_eq!(d.probability(4), Ratio::new(6, 36));
+        assert_eq!(d.probability(9), Ratio::new(10, 36));
+        // Minimum 1.
+        let d = distribution_of("max(d4 - 2, 1)").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(3, 4));
+    }
+
+    #[test]
+    fn lookup() {
+        // B/X ability score modifiers.
+        let d = distribution_of(
+            "3d6 -> {3: -3, 4..5: -2, 6..8: -1, 9..12: 0, 13..15: 1, 16..17: 2, 18: 3}",
+        )
+        .unwrap();
+        assert_eq!((d.min(), d.max()), (-3, 3));
+        assert_eq!(d.probability(3), Ratio::new(1, 216));
+        assert_eq!(d.probability(-2), Ratio::new(3 + 6, 216));
+        assert_eq!(d.probability(0), Ratio::new(25 + 27 + 27 + 25, 216));
+        assert_eq!(d.probability(-1), d.probability(1));
+        // Values outside every band are unchanged.
+        let d = distribution_of("d6 -> {1: 0}").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(1, 6));
+        assert_eq!(d.probability(1), Ratio::new(0, 1));
+        assert_eq!(d.probability(6), Ratio::new(1, 6));
+        // Open-ended bands.
+        let d = distribution_of("d20 -> {..10: 0, 11..: 1}").unwrap();
+        assert_eq!(d, distribution_of("d20 >= 11").unwrap());
+    }
+
+    #[test]
+    fn reroll_part() {
+        // A d66 whose tens die is rolled twice, keeping the higher.
+        let d6 = Distribution::die(6).unwrap();
+        let d = d6
+            .checked_reroll_part(&d6, 2, true, |units, tens| Some(tens * 10 + units))
+            .unwrap();
+        assert_eq!(d.total(), 6 * 36);
+        assert_eq!(d.probability(61), Ratio::new(11, 216));
+        assert_eq!(d.probability(11), Ratio::new(1, 216));
+        // Rolling once is no reroll at all.
+        let once = d6
+            .checked_reroll_part(&d6, 1, false, |units, tens| Some(tens * 10 + units))
+            .unwrap();
+        assert_eq!(once, distribution_of("d66").unwrap());
+        // The same as writing out the alternatives with a binding.
+        let lowest = d6
+            .checked_reroll_part(&d6, 2, false, |a, b| Some(a - b))
+            .unwrap();
+        let want = distribution_of("[A: d6] min(A - d6, A - d6)").unwrap();
+        for v in -5..=5 {
+            assert_eq!(lowest.probability(v), want.probability(v), "{v}");
+        }
+        assert!(
+            d6.checked_reroll_part(&d6, 30, true, |a, b| Some(a + b))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn save() {
+        // Fails on 1-11, and takes the full damage; succeeds on 12-20, and takes half.
+        let d = distribution_of("save(3d6, d20 + 3 >= 15)").unwrap();
+        assert_eq!((d.min(), d.max()), (1, 18));
+        assert_eq!(d.probability(18), Ratio::new(11, 20 * 216));
+        // Half truncates toward zero, but 3d6 can't roll 19: a 9 is a failed save against 9,
+        // or a successful save against 18.
+        assert_eq!(
+            d.probability(9),
+            Ratio::new(11 * 25, 20 * 216) + Ratio::new(9, 20 * 216)
+        );
+        // The same as picking between the full and halved damage.
+        assert_eq!(
+            d,
+            distribution_of("if d20 + 3 >= 15 then 3d6 / 2 else 3d6").unwrap()
+        );
+        // Always saving is always half.
+        let d = distribution_of("save(d6, 1)").unwrap();
+        assert_eq!(d, distribution_of("d6 / 2").unwrap());
+    }
+
+    #[test]
+    fn conditional() {
+        // Hits on 10 or more: 11 in 20.
+        let d = distribution_of("if (1d20+5 >= 15) then 2d6 else 1d4").unwrap();
+        assert_eq!((d.min(), d.max()), (1, 12));
+        assert_eq!(d.probability(12), Ratio::new(11, 20 * 36));
+        assert_eq!(
+            d.probability(2),
+            Ratio::new(11, 20 * 36) + Ratio::new(9, 20 * 4)
+        );
+        let total: Ratio<usize> = d.occurrences().map(|(v, _)| d.probability(v)).sum();
+        assert_eq!(total, Ratio::new(1, 1));
+
+        // Any nonzero condition is true.
+        let d = distribution_of("if(d3 - 2, 10, 20)").unwrap();
+        assert_eq!(d.probability(10), Ratio::new(2, 3));
+        // A branch that's never taken doesn't matter.
+        let d = distribution_of("if(d6 > 0, d4, d1000)").unwrap();
+        assert_eq!(d, distribution_of("d4").unwrap());
+        // Bound values are shared between the condition and the branches.
+        let d = distribution_of("[X: d20] if X >= 15 then X else 0").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(14, 20));
+        assert_eq!(d.probability(15), Ratio::new(1, 20));
+    }
+
+    #[test]
+    fn soak() {
+        let d = distribution_of("soak(d6, 2)").unwrap();
+        assert_eq!(d.min(), 0);
+        assert_eq!(d.max(), 4);
+        assert_eq!(d.probability(0), Ratio::new(2, 6));
+        assert_eq!(d.probability(3), Ratio::new(1, 6));
+
+        // Negative damage is clamped, not "healing".
+        let d = distribution_of("soak(d6 - 4, d2)").unwrap();
+        assert_eq!(d.min(), 0);
+        assert_eq!(d.probability(0), Ratio::new(11, 12));
+
+        // Negative armor adds damage.
+        let d = distribution_of("soak(d4, -1)").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 5));
+    }
+
+    #[test]
+    fn digits() {
+        // A percentile die is a d100, where 100 reads as "00".
+        let d = distribution_of("tens(d%)").unwrap();
+        assert_eq!((d.min(), d.max()), (0, 9));
+        for v in 0..=9 {
+            assert_eq!(d.probability(v), Ratio::new(1, 10));
+        }
+        let d = distribution_of("units(d%)").unwrap();
+        assert_eq!((d.min(), d.max()), (0, 9));
+        assert_eq!(d.probability(0), Ratio::new(1, 10));
+
+        // Doubles: 11, 22, ..., 99, and 00.
+        let d = distribution_of("[R: d%] tens(R) = units(R)").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1, 10));
+
+        // Negative values have the digits of their absolute values.
+        let d = distribution_of("units(-d[12..13])").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 3));
+        assert_eq!(
+            distribution_of("tens(-9223372036854775807 - 1)").unwrap(),
+            Distribution::constant(0)
+        );
+    }
+
+    #[test]
+    fn soak_overflow() {
+        let e = distribution_of("soak(9223372036854775807, -1)").unwrap_err();
+        assert!(matches!(e, Error::Overflow(_)), "{e}");
+        // Only differences that survive clamping can overflow.
+        let d = distribution_of("soak(-9223372036854775807, 9223372036854775807)").unwrap();
+        assert_eq!(d.max(), 0);
+    }
+
+    #[test]
+    fn thresholds() {
+        let d = distribution_of("d10").unwrap().thresholds(&[4, 8, 8]);
+        assert_eq!(d.probability(0), Ratio::new(3, 10));
+        assert_eq!(d.probability(1), Ratio::new(4, 10));
+        assert_eq!(d.probability(2), Ratio::new(0, 1));
+        assert_eq!(d.probability(3), Ratio::new(3, 10));
+    }
+
+    #[test]
+    fn explode_until() {
+        let d6 = Distribution::die(6).unwrap();
+        let explosion = d6.explode_until(0.001).unwrap();
+        // 1/6^4 is 0.00077.
+        assert_eq!(explosion.depth, 3);
+        assert_eq!(explosion.residual, Ratio::new(1, 1296));
+        assert_eq!(explosion.distribution, d6.checked_explode(3).unwrap());
+        // The truncated die only misplaces rolls past its depth.
+        let deeper = d6.checked_explode(10).unwrap();
+        let beyond = deeper.probability_where(|v| v > 24);
+        assert!(beyond <= explosion.residual);
+
+        assert_eq!(d6.explode_until(1.0).unwrap().depth, 0);
+        assert_eq!(Distribution::constant(3).explode_until(0.1), None);
+        assert_eq!(Distribution::die(2).unwrap().explode_until(0.0), None);
+    }
+
+    #[test]
+    fn explode_exact() {
+        // A d6 explodes a sixth of the time: 3.5 / (1 - 1/6).
+        let d6 = Distribution::die(6).unwrap();
+        assert_eq!(d6.explode_mean_exact(), Some(4.2));
+        // A d2 is 1, or 2 and another d2: E[X] = 3 and E[X²] = 17.
+        assert_eq!(
+            Distribution::die(2).unwrap().explode_mean_exact(),
+            Some(3.0)
+        );
+        assert_eq!(
+            Distribution::die(2).unwrap().explode_variance_exact(),
+            Some(8.0)
+        );
+        // The truncated die comes ever closer.
+        let deep = d6.checked_explode(12).unwrap();
+        assert!((deep.mean() - 4.2).abs() < 1e-8);
+        let variance = d6.explode_variance_exact().unwrap();
+        assert!((deep.variance() - variance).abs() < 1e-6, "{variance}");
+        assert!(d6.checked_explode(2).unwrap().mean() < 4.2);
+        assert_eq!(Distribution::constant(3).explode_mean_exact(), None);
+    }
+
+    #[test]
+    fn trim_tail() {
+        let mut d = Distribution::die(20).unwrap();
+        assert_eq!(d.trim_tail(0.1), Ratio::new(2, 20));
+        assert_eq!((d.min(), d.max()), (2, 19));
+        assert_eq!(d.probability(10), Ratio::new(1, 18));
+
+        let mut d = "3d6".parse::<Closed>().unwrap().distribution().unwrap();
+        // 3 and 18 are 1/216 each; 4 and 17 are 3/216.
+        assert_eq!(d.trim_tail(0.02), Ratio::new(2, 216));
+        assert_eq!((d.min(), d.max()), (4, 17));
+        assert_eq!(d.trim_tail(0.0), Ratio::new(0, 1));
+        assert_eq!(d.support_len(), 14);
+
+        // The most likely value stays, however large epsilon is.
+        let mut d = Distribution::die(6).unwrap();
+        assert_eq!(d.trim_tail(1.0), Ratio::new(5, 6));
+        assert_eq!(d.occurrences().count(), 1);
+    }
+
+    #[test]
+    fn provenance() {
+        let mut e = Evaluator::new(true);
+        let d = e.eval(&"3d6 + 2".parse().unwrap()).unwrap();
+        assert!(d.provenance().is_exact());
+        assert_eq!(d.provenance().cache_hits, 0);
+
+        // 3d6 is memoized from the last evaluation.
+        let mut d = e.eval(&"3d6 * 4".parse().unwrap()).unwrap();
+        assert_eq!(d.provenance().cache_hits, 1);
+        d.trim_tail(0.01);
+        assert_eq!(d.provenance().trimmed, Ratio::new(2, 216));
+        assert!(!d.provenance().is_exact());
+
+        let d = e.eval(&"d6! + 1".parse().unwrap()).unwrap();
+        assert_eq!(
+            d.provenance().explosion_depth,
+            Some(Evaluator::DEFAULT_EXPLOSION_DEPTH)
+        );
+        let d = Distribution::die(6)
+            .unwrap()
+            .explode_until(0.001)
+            .unwrap()
+            .distribution;
+        assert_eq!(d.provenance().explosion_depth, Some(3));
+
+        // Provenance doesn't affect equality.
+        assert_eq!(
+            e.eval(&"3d6 + 2".parse().unwrap()).unwrap(),
+            "3d6 + 2".parse::<Closed>().unwrap().distribution().unwrap()
+        );
+    }
+
+    #[test]
+    fn banded() {
+        let moves = [("miss", i64::MIN), ("weak hit", 7), ("strong hit", 10)];
+        let d = distribution_of("2d6 + 1").unwrap();
+        assert_eq!(
+            d.banded(&moves),
+            vec![
+                ("miss", Ratio::new(10, 36)),
+                ("weak hit", Ratio::new(16, 36)),
+                ("strong hit", Ratio::new(10, 36)),
+            ]
+        );
+        // Values below the first band, and empty bands, have no probability.
+        let d = distribution_of("d10").unwrap();
+        assert_eq!(
+            d.banded(&[('a', 4), ('b', 8), ('c', 8)]),
+            vec![
+                ('a', Ratio::new(4, 10)),
+                ('b', Ratio::new(0, 1)),
+                ('c', Ratio::new(3, 10))
+            ]
+        );
+    }
+
+    #[test]
+    fn given() {
+        // Average damage on hits only: a miss does 0.
+        let d = distribution_of("(d20 >= 11) * (d8 + 2)").unwrap();
+        assert_eq!(d.mean_given(|v| v > 0), Some(6.5));
+        assert!((d.variance_given(|v| v > 0).unwrap() - 63.0 / 12.0).abs() < 1e-12);
+        assert_eq!(d.mean_given(|_| true), Some(d.mean()));
+        assert!((d.variance_given(|_| true).unwrap() - d.variance()).abs() < 1e-12);
+        assert_eq!(d.mean_given(|v| v > 10), None);
+        assert_eq!(d.variance_given(|v| v > 10), None);
+    }
+
+    #[test]
+    fn moments() {
+        let d = distribution_of("3d6").unwrap();
+        assert_eq!(d.mean_exact(), Some(Ratio::new(21, 2)));
+        assert_eq!(d.variance_exact(), Some(Ratio::new(35, 4)));
+        assert!((d.variance() - 8.75).abs() < 1e-12);
+        assert!((d.stddev() - 8.75f64.sqrt()).abs() < 1e-12);
+        assert_eq!(d.stddev_exact(), Some(8.75f64.sqrt()));
+
+        // Negative values, and values far from zero.
+        let d = distribution_of("4dF - 1000000").unwrap();
+        assert_eq!(d.mean_exact(), Some(Ratio::from_integer(-1000000)));
+        assert_eq!(d.variance_exact(), Some(Ratio::new(8, 3)));
+        let d = Distribution::constant(i64::MIN);
+        assert_eq!(
+            d.mean_exact(),
+            Some(Ratio::from_integer(i128::from(i64::MIN)))
+        );
+        assert_eq!(d.variance_exact(), Some(Ratio::from_integer(0)));
+        assert_eq!(d.stddev(), 0.0);
+    }
+
+    #[test]
+    fn quantiles() {
+        // Exactly half of the rolls of 3d6 are 10 or less.
+        let d = distribution_of("3d6").unwrap();
+        assert_eq!(d.median(), 10);
+        assert_eq!(d.quantile(Ratio::new(0, 1)), 3);
+        assert_eq!(d.quantile(Ratio::new(1, 1)), 18);
+        assert_eq!(d.quantile(Ratio::new(1, 216)), 3);
+        assert_eq!(d.quantile(Ratio::new(2, 216)), 4);
+
+        let d = distribution_of("d20").unwrap();
+        assert_eq!(d.quantile(Ratio::new(9, 10)), 18);
+        assert_eq!(d.quantile(Ratio::new(91, 100)), 19);
+        // Values that can't occur are skipped.
+        let d = distribution_of("d{1,1,1,5}").unwrap();
+        assert_eq!(d.median(), 1);
+        assert_eq!(d.quantile(Ratio::new(4, 5)), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn quantile_over_one() {
+        Distribution::die(6).unwrap().quantile(Ratio::new(3, 2));
+    }
+
+    #[test]
+    fn linear_combination() {
+        let d4 = distribution_of("d4").unwrap();
+        let d6 = distribution_of("d6").unwrap();
+        let got =
+            Distribution::linear_combination(&[(3, &d4), (-2, &d6), (0, &d6), (1, &d4)]).unwrap();
+        let want = distribution_of("3 * d4 - 2 * d6 + 0 * d6 + d4").unwrap();
+        assert_eq!(got, want);
+
+        assert_eq!(
+            Distribution::linear_combination(&[]).unwrap(),
+            distribution_of("0").unwrap()
+        );
+        assert!(Distribution::linear_combination(&[(i64::MAX, &d4)]).is_none());
+    }
+
+    #[test]
+    fn add_assign() {
+        let d4 = distribution_of("d4").unwrap();
+        let d6 = distribution_of("d6 - 3").unwrap();
+        let mut acc = d4.clone();
+        acc += &d6;
+        acc += &d4;
+        assert_eq!(acc, distribution_of("d4 + (d6 - 3) + d4").unwrap());
+        assert_eq!(
+            [d4.clone(), d6.clone(), d4]
+                .into_iter()
+                .sum::<Distribution>(),
+            acc
+        );
+
+        let mut big = distribution_of("9223372036854775806").unwrap();
+        let before = big.clone();
+        assert!(big.checked_add_assign(&d6).is_none());
+        assert_eq!(big, before);
+    }
+
+    #[test]
+    fn reused_evaluator() {
+        let mut eval = Evaluator::default();
+        for e in ["3d6 + d4 - 2 * d8", "d20 + 5", "3d6 + d4 - 2 * d8"] {
+            let closed = e.parse::<Closed>().unwrap();
+            assert_eq!(eval.eval(&closed).unwrap(), closed.distribution().unwrap());
+        }
+    }
+
+    #[test]
+    fn explode() {
+        let d = distribution_of("d6!").unwrap();
+        assert_eq!(d.min(), 1);
+        assert_eq!(d.max(), 18);
+        assert_eq!(d.probability(5), Ratio::new(1, 6));
+        assert_eq!(d.probability(6), Ratio::new(0, 1));
+        assert_eq!(d.probability(8), Ratio::new(1, 36));
+        assert_eq!(d.probability(17), Ratio::new(1, 216));
+        assert_eq!(d.probability(18), Ratio::new(1, 216));
+        assert_eq!(d.total(), 216);
+
+        let deep = "d6!".parse::<Closed>().unwrap();
+        let deep = Evaluator::default()
+            .with_explosion_depth(10)
+            .eval(&deep)
+            .unwrap();
+        assert_eq!(deep.max(), 66);
+        // Expected value of an exploding die converges to 3.5 * 6/5.
+        assert!((deep.mean() - 4.2).abs() < 1e-6, "{}", deep.mean());
+
+        let none = "d6!".parse::<Closed>().unwrap();
+        let none = Evaluator::default()
+            .with_explosion_depth(0)
+            .eval(&none)
+            .unwrap();
+        assert_eq!(none, distribution_of("d6").unwrap());
+
+        // Each die explodes independently.
+        let d = distribution_of("2d4!").unwrap();
+        assert_eq!(d.max(), 24);
+        assert_eq!(d.probability(5), Ratio::new(2, 16));
+    }
+
+    #[test]
+    fn explode_on_comparison() {
+        let d = distribution_of("d4!>=3").unwrap();
+        assert_eq!(d.probability(2), Ratio::new(1, 4));
+        // A 3 or 4 always rolls again.
+        assert_eq!(d.probability(3), Ratio::new(0, 1));
+        assert_eq!(d.probability(4), Ratio::new(1, 16));
+        assert_eq!(d.probability(5), Ratio::new(2, 16));
+        // Two explosions of 4, then a 4 that stops.
+        assert_eq!(d.max(), 12);
+        assert_eq!(d.probability(12), Ratio::new(1, 64));
+        assert_eq!(
+            distribution_of("d6!>=6").unwrap(),
+            distribution_of("d6!").unwrap()
+        );
+
+        // Each explosion is its own die in a pool.
+        let d = distribution_of("2d4!>=3kh").unwrap();
+        assert_eq!(d.max(), 4);
+        let d = distribution_of("d4!>=3s>=3").unwrap();
+        assert_eq!(d.max(), 3);
+
+        // Penetrating dice compare before subtracting.
+        let d = distribution_of("d4!p>=3").unwrap();
+        assert_eq!(d.probability(3), Ratio::new(1, 16));
+    }
+
+    #[test]
+    fn explode_pool() {
+        // Summing, there's no difference between exploding and compounding.
+        assert_eq!(
+            distribution_of("3d6!").unwrap(),
+            distribution_of("3d6!!").unwrap()
+        );
+
+        // Each explosion is its own die: the highest single die is at most 6.
+        let d = distribution_of("2d6!kh").unwrap();
+        assert_eq!(d.max(), 6);
+        assert_eq!(d.probability(6), Ratio::new(11, 36));
+        // Compounding dice keep their whole total.
+        let d = distribution_of("2d6!!kh").unwrap();
+        assert_eq!(d.max(), 18);
+        assert_eq!(d.probability(6), Ratio::new(0, 1));
+
+        // A 6 followed by a 1 puts a 1 in the pool, too.
+        let d = distribution_of("1d6!kl").unwrap();
+        assert_eq!(d.max(), 6);
+        assert_eq!(
+            d.probability(1),
+            Ratio::new(1, 6) + Ratio::new(1, 36) + Ratio::new(1, 216)
+        );
+        assert_eq!(d.total(), distribution_of("d6!").unwrap().total());
+    }
+
+    #[test]
+    fn roll_and_keep() {
+        // Keeping the highest of compounded tens is the maximum of the compounded dice.
+        let die = distribution_of("d10!!").unwrap();
+        let d = distribution_of("2k1").unwrap();
+        assert_eq!(d, die.checked_max(&die).unwrap());
+        assert_eq!((d.min(), d.max()), (1, 30));
+
+        let d = distribution_of("3k2").unwrap();
+        assert_eq!(d.max(), 60);
+        assert_eq!(d.probability(2), Ratio::new(1, 1000));
+        assert!(distribution_of("2k3").is_err());
+        if let Err(m) = crate::brute_force::check(&"3k2".parse().unwrap()) {
+            panic!("{m}");
+        }
+    }
+
+    #[test]
+    fn multisets_of_rolls() {
+        // Two draws from weights 1, 2, 3: every ordered pair, with its product of weights.
+        let mut seen = Vec::new();
+        multisets(
+            &[1, 2, 3],
+            2,
+            || (),
+            |counts, o| {
+                seen.push((counts.to_vec(), o));
+                Ok(())
+            },
+        )
+        .unwrap();
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![
+                (vec![0, 0, 2], 9),
+                (vec![0, 1, 1], 12),
+                (vec![0, 2, 0], 4),
+                (vec![1, 0, 1], 6),
+                (vec![1, 1, 0], 4),
+                (vec![2, 0, 0], 1),
+            ]
+        );
+        assert_eq!(seen.iter().map(|(_, o)| o).sum::<usize>(), 36);
+        assert_eq!(multiset_count(3, 2), 6);
+        assert_eq!(multiset_count(10, 10), 92378);
+        assert_eq!((multiset_count(0, 0), multiset_count(0, 3)), (1, 0));
+        assert_eq!(multiset_count(100, 200), usize::MAX);
+        assert!(multisets(&[usize::MAX, 2], 2, || (), |_, _| Ok(())).is_err());
+    }
+
+    #[test]
+    fn wide_sum() {
+        // Wide enough to take the NTT, then Karatsuba multiplication.
+        let d = distribution_of("d[1..7000] + d[1..7000] + d[1..300]").unwrap();
+        assert_eq!((d.min(), d.max()), (3, 14300));
+        assert_eq!(d.total(), 7000 * 7000 * 300);
+        assert_eq!(d.occurrences().next(), Some((3, 1)));
+        // Two d7000s make a total of s in s - 1 ways, for s up to 7001.
+        assert_eq!(
+            d.probability(7002),
+            Ratio::new((1..=300).map(|z| 7002 - z - 1).sum(), 7000 * 7000 * 300)
+        );
+    }
+
+    #[test]
+    fn too_many_rolls() {
+        // 100^200 possible rolls is too many to count.
+        let e = distribution_of("200d100").unwrap_err();
+        assert!(matches!(e, Error::TooComplex(_)), "{e}");
+        let e = distribution_of("(11d10) * (11d10)").unwrap_err();
+        assert!(matches!(e, Error::TooComplex(_)), "{e}");
+        let e = distribution_of("12d20 >= 12d20").unwrap_err();
+        assert!(matches!(e, Error::TooComplex(_)), "{e}");
+        let e = distribution_of("[N: 12d10] N + 12d10").unwrap_err();
+        assert!(matches!(e, Error::TooComplex(_)), "{e}");
+        // Only one branch is rolled.
+        assert!(distribution_of("if 1 then 19d10 else 19d10").is_ok());
+    }
+
+    #[test]
+    fn too_deep() {
+        let nested = |depth, wrap: fn(RawExpression) -> RawExpression| -> Closed {
+            (0..depth)
+                .fold(RawExpression::from(ExpressionTree::Die(Die(6))), |e, _| {
+                    wrap(e)
+                })
+                .try_into()
+                .unwrap()
+        };
+        let negate = |e| ExpressionTree::Negated(Box::new(e)).into();
+        // Repetitions take the most stack of any node.
+        let repeat = |e| {
+            ExpressionTree::Repeated {
+                count: Box::new(Constant(1).into()),
+                value: Box::new(e),
+                ranker: Ranker::All,
+            }
+            .into()
+        };
+        for wrap in [negate, repeat] {
+            let e = nested(Evaluator::MAX_DEPTH, wrap)
+                .distribution()
+                .unwrap_err();
+            assert!(matches!(e, Error::RecursionLimit(_)), "{e}");
+            assert!(
+                nested(Evaluator::MAX_DEPTH - 1, wrap)
+                    .distribution()
+                    .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let e = Evaluator::default()
+            .with_timeout(Duration::ZERO)
+            .eval(&"3d6 + 1".parse().unwrap())
+            .unwrap_err();
+        assert!(matches!(e, Error::Timeout(_)), "{e}");
+        assert!(
+            Evaluator::default()
+                .with_timeout(Duration::from_secs(60))
+                .eval(&"3d6 + 1".parse().unwrap())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn penetrate() {
+        let d = distribution_of("d6!p").unwrap();
+        // 6, then 5 - 1 = 4: 10.
+        assert_eq!(d.probability(10), Ratio::new(1, 36));
+        // 6 then 6 - 1, then the last roll - 1.
+        assert_eq!(d.max(), 6 + 5 + 5);
+        // A 6 followed by a 1 adds nothing: 6 is possible, unlike with exploding dice.
+        assert_eq!(d.probability(6), Ratio::new(1, 36));
+        assert_eq!(
+            distribution_of("d6!").unwrap().probability(6),
+            Ratio::new(0, 1)
+        );
+        assert_eq!(d.total(), 216);
+        // Lower than the exploding die: every reroll costs 1.
+        assert!(d.mean() < distribution_of("d6!").unwrap().mean());
+
+        let none = "d6!p".parse::<Closed>().unwrap();
+        let none = Evaluator::default()
+            .with_explosion_depth(0)
+            .eval(&none)
+            .unwrap();
+        assert_eq!(none, distribution_of("d6").unwrap());
+    }
+
+    #[test]
+    fn implode() {
+        let d = distribution_of("d6!i").unwrap();
+        // 1, then 4: -3; or 1, 1, then 3.
+        assert_eq!(d.probability(-3), Ratio::new(7, 216));
+        // 1, then 1, then the last roll at 6: 1 - 1 - 6.
+        assert_eq!(d.min(), -6);
+        assert_eq!(d.max(), 6);
+        // A 1 always subtracts at least as much, so the total is never 0.
+        assert_eq!(d.probability(0), Ratio::new(0, 1));
+        assert_eq!(d.total(), 216);
+        // The mirror of the exploding die, with its lowest face in place of its highest.
+        assert!(d.mean() < distribution_of("d6").unwrap().mean());
+        assert_eq!(d, Distribution::die(6).unwrap().checked_implode(2).unwrap());
+        let d = distribution_of("d6!i<=2").unwrap();
+        assert_eq!(d.probability(2), Ratio::new(0, 1));
+    }
+
+    #[test]
+    fn size_report() {
+        let small = distribution_of("d20").unwrap();
+        assert_eq!(small.support_len(), 20);
+        assert_eq!(small.memory_bytes(), std::mem::size_of::<Distribution>());
+
+        // Values that can't occur still take up space.
+        let sparse = distribution_of("d2 * 100").unwrap();
+        assert_eq!(sparse.support_len(), 101);
+        assert!(sparse.memory_bytes() > small.memory_bytes());
+
+        let mut eval = Evaluator::default();
+        assert_eq!(eval.largest_intermediate(), None);
+        let e = "d4 + 10d6 + d20".parse::<Closed>().unwrap();
+        eval.eval(&e).unwrap();
+        let (node, len) = eval.largest_intermediate().unwrap();
+        assert_eq!(node, &e);
+        assert_eq!(len, (4 + 60 + 20) - (1 + 10 + 1) + 1);
+
+        // The widest node need not be the root.
+        let e = "[X: 10d6] X > 30".parse::<Closed>().unwrap();
+        let mut eval = Evaluator::default();
+        eval.eval(&e).unwrap();
+        let (node, len) = eval.largest_intermediate().unwrap();
+        assert_eq!(node.to_string(), "10d6");
+        assert_eq!(len, 51);
+    }
+
+    #[test]
+    fn budget() {
+        let e = "d6 + 8d100".parse::<Closed>().unwrap();
+        let mut eval = Evaluator::default().with_budget(10_000);
+        // The error names the node that went over.
+        match eval.eval(&e) {
+            Err(Error::OverBudget(node)) => assert_eq!(node, "8d100"),
+            other => panic!("unexpected result {other:?}"),
+        }
+
+        // The budget applies to each evaluation, not the evaluator's lifetime.
+        let small = "4d6kh3".parse::<Closed>().unwrap();
+        for _ in 0..10 {
+            eval.eval(&small).unwrap();
+        }
+        let mut eval = Evaluator::default().with_budget(usize::MAX);
+        assert_eq!(eval.eval(&e).unwrap(), e.distribution().unwrap());
+    }
+
+    #[test]
+    fn profile() {
+        let e = "[X: 2d6] X + d20 > 10".parse::<Closed>().unwrap();
+        let profile = e.profile().unwrap();
+        assert_eq!(profile.distribution, e.distribution().unwrap());
+
+        let root = &profile.nodes[0];
+        assert_eq!(&root.expression, &e);
+        assert_eq!(root.depth, 0);
+        assert_eq!(profile.elapsed(), root.elapsed);
+        // The binding combines each of 11 values with the outcomes of the comparison;
+        // when X is at least 10, the comparison is always true.
+        assert_eq!(root.combinations, 8 * 2 + 3);
+
+        // The tail is evaluated for each value of X.
+        let tails = profile
+            .nodes
+            .iter()
+            .filter(|n| n.depth == 1 && n.expression.to_string().ends_with("> 10"))
+            .count();
+        assert_eq!(tails, 11);
+        for node in &profile.nodes[1..] {
+            assert!(node.depth > 0);
+            assert!(node.elapsed <= root.elapsed);
+        }
+
+        // Ties go to the first node evaluated.
+        let widest = profile.widest().unwrap();
+        assert_eq!(widest.expression.to_string(), "2+d20");
+        assert_eq!(widest.support_len, 20);
+        // Rolling 2d6 pairs each face of one die with each face of the other.
+        let busiest = profile.busiest().unwrap();
+        assert_eq!(busiest.expression.to_string(), "2d6");
+        assert_eq!(busiest.combinations, 36);
+
+        let text = profile.to_string();
+        assert_eq!(text.lines().count(), profile.nodes.len());
+        assert!(text.lines().nth(1).unwrap().starts_with("  2d6: "));
+    }
+
+    #[test]
+    fn reroll_once() {
+        let d = distribution_of("d20ro1").unwrap();
+        // A 1 only comes up if it's rolled twice.
+        assert_eq!(d.probability(1), Ratio::new(1, 400));
+        assert_eq!(d.probability(2), Ratio::new(21, 400));
+        assert_eq!(d.total(), 400);
+
+        // Rerolling low values, kept even if they're low again.
+        let d = distribution_of("d6ro<3").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(2, 36));
+        assert_eq!(d.probability(6), Ratio::new(8, 36));
+
+        // Each die in a pool is rerolled on its own.
+        assert_eq!(
+            distribution_of("2d4ro1").unwrap(),
+            distribution_of("d4ro1 + d4ro1").unwrap()
+        );
+        let d = distribution_of("2d4ro1kh").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1, 16 * 16));
+        // A lucky halfling with disadvantage rerolls each d20 on a 1.
+        let d = distribution_of("2d20ro1kl").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1 + 2 * 399, 400 * 400));
+        assert_eq!(d.probability(20), Ratio::new(21 * 21, 400 * 400));
+
+        // Great Weapon Fighting rerolls 1s and 2s.
+        assert_eq!(
+            distribution_of("2d6gwf").unwrap(),
+            distribution_of("2d6ro<=2").unwrap()
+        );
+        let d = distribution_of("2d6gwf").unwrap();
+        assert!((d.mean() - 2.0 * 25.0 / 6.0).abs() < 1e-9);
+
+        // Nothing to reroll.
+        assert_eq!(
+            distribution_of("d6ro>6").unwrap().probability(6),
+            Ratio::new(1, 6)
+        );
+    }
+
+    #[test]
+    fn reroll_recursive() {
+        let d = distribution_of("d20rr1").unwrap();
+        assert_eq!(d, distribution_of("d[2..20]").unwrap());
+
+        let d = distribution_of("d6rr<3").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(0, 1));
+        assert_eq!(d.probability(3), Ratio::new(1, 4));
+        // Better than rerolling once.
+        assert!(d.mean() > distribution_of("d6ro<3").unwrap().mean());
+
+        // Weights are kept for uneven dice.
+        let d = Parser::new()
+            .with_die("fate", [-1, 0, 0, 1])
+            .parse("dfaterr=-1")
+            .unwrap()
+            .distribution()
+            .unwrap();
+        assert_eq!(d.probability(0), Ratio::new(2, 3));
+
+        let e = "4d6rr<=6".parse::<Closed>().unwrap();
+        assert!(matches!(e.distribution(), Err(Error::RerollsForever(_))));
+    }
+
+    #[test]
+    fn reroll_pool() {
+        // Each die is rerolled before keeping the highest.
+        assert_eq!(
+            distribution_of("2d6r<3kh").unwrap(),
+            distribution_of("2d[3..6]kh").unwrap()
+        );
+        assert_eq!(
+            distribution_of("3d8r>=7kl2").unwrap(),
+            distribution_of("3d6kl2").unwrap()
+        );
+        let d = distribution_of("2d4ro=4kl").unwrap();
+        // Both dice roll 4 twice.
+        assert_eq!(d.probability(4), Ratio::new(1, 16 * 16));
+        // Rerolled dice can explode, and each explosion joins the pool.
+        let d = distribution_of("2d6r1!kh").unwrap();
+        assert_eq!(d.min(), 2);
+        assert_eq!(d.max(), 6);
+    }
+
+    #[test]
+    fn successes() {
+        let d = distribution_of("d10s>=6f1").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1, 2));
+        assert_eq!(d.probability(0), Ratio::new(2, 5));
+        assert_eq!(d.probability(-1), Ratio::new(1, 10));
+
+        // Botches cancel successes, and can leave a negative total.
+        let d = distribution_of("2d10s>=6f1").unwrap();
+        assert_eq!(d.probability(-2), Ratio::new(1, 100));
+        assert_eq!(d.probability(0), Ratio::new(26, 100));
+        assert_eq!(d.probability(2), Ratio::new(25, 100));
+        assert_eq!(
+            distribution_of("5d10s>=6f1").unwrap(),
+            distribution_of("5([D: d10] (D >= 6) - (D = 1))").unwrap()
+        );
+
+        // Without botches, this counts dice that meet the target.
+        assert_eq!(
+            distribution_of("4d6s>4").unwrap(),
+            distribution_of("4(d6 > 4)").unwrap()
+        );
+
+        // Each explosion is another die, with its own chance of success.
+        let d = distribution_of("d10!s>=8").unwrap();
+        assert_eq!(d.min(), 0);
+        assert_eq!(d.max(), 3);
+        assert_eq!(d.probability(0), Ratio::new(7, 10));
+        assert_eq!(d.probability(3), Ratio::new(3, 1000));
+    }
+
+    #[test]
+    fn explode_overflow() {
+        let e = "d20!".parse::<Closed>().unwrap();
+        let e = Evaluator::default()
+            .with_explosion_depth(100)
+            .eval(&e)
+            .unwrap_err();
+        assert!(matches!(e, Error::Overflow(_)), "{e}");
+    }
+
     #[test]
     fn product() {
         let d = distribution_of("1d4 * 3").unwrap();
@@ -546,6 +3270,22 @@ mod tests {
         assert_eq!(&ps, &vec![(3, 1), (6, 1), (9, 1), (12, 1)])
     }
 
+    #[test]
+    fn overflow() {
+        for expr in [
+            "9223372036854775807 + 1",
+            "2 * 9223372036854775807",
+            "9223372036854775808",
+            "-9223372036854775807 - 2",
+            "2(4611686018427387904)",
+        ] {
+            let e = distribution_of(expr).unwrap_err();
+            assert!(matches!(e, Error::Overflow(_)), "{expr}: {e}");
+        }
+        let d = distribution_of("-9223372036854775807 - 1").unwrap();
+        assert_eq!(d.min(), i64::MIN);
+    }
+
     #[test]
     fn never() {
         distribution_of("0d3").unwrap_err();
@@ -647,10 +3387,43 @@ mod tests {
         assert_eq!(&ps, &vec![(0, 40), (1, 10), (2, 10), (3, 10), (4, 10)])
     }
 
+    #[test]
+    fn binding_weights_each_value() {
+        // One die or two, equally often, though two dice have more ways to roll.
+        let d = distribution_of("[N: d2] (N)d6").unwrap();
+        assert_eq!(d.probability(1), Ratio::new(1, 12));
+        assert_eq!(d.probability(12), Ratio::new(1, 72));
+        assert!((d.mean() - 5.25).abs() < 1e-9);
+        let d = distribution_of("[R: d20] if R = 20 then 2 * d8 else 0").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(19, 20));
+        assert_eq!(d.probability(16), Ratio::new(1, 160));
+    }
+
     #[test]
     fn floor_div() {
         let d = distribution_of("1d4 / 2").unwrap();
         let ps: Vec<_> = d.occurrences().collect();
         assert_eq!(&ps, &vec![(0, 1), (1, 2), (2, 1)])
     }
+
+    #[test]
+    fn modulo() {
+        let d = distribution_of("1d6 % 3").unwrap();
+        let ps: Vec<_> = d.occurrences().collect();
+        assert_eq!(&ps, &vec![(0, 2), (1, 2), (2, 2)]);
+        // The remainder is never negative, whatever the signs.
+        let d = distribution_of("d[-7..-1] % 3").unwrap();
+        assert_eq!((d.min(), d.max()), (0, 2));
+        assert_eq!(d.probability(2), Ratio::new(3, 7));
+        let d = distribution_of("-7 % -3").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 2));
+        // Parity.
+        let d = distribution_of("3d6 % 2").unwrap();
+        assert_eq!(d.probability(0), Ratio::new(1, 2));
+
+        assert!(matches!(
+            distribution_of("d6 % (d3 - 1)"),
+            Err(Error::DivideByZero(_))
+        ));
+    }
 }
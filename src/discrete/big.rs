@@ -0,0 +1,186 @@
+//! Distributions with too many possible rolls to count in a `usize`, like `200d100`'s 100^200.
+
+use num::{BigUint, ToPrimitive, Zero, rational::Ratio};
+
+use super::{Distribution, ntt};
+
+/// A distribution whose occurrence counts are arbitrary-precision integers:
+/// the exact distribution of a sum of rolls with too many possible outcomes
+/// for a [`Distribution`] to count.
+///
+/// [`Closed::evaluate_auto`](crate::Closed::evaluate_auto) produces these for sums of large
+/// pools, like `200d100`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigDistribution {
+    /// Index i in occurrence_by_value represents the number of occurrences of (i+offset).
+    occurrence_by_value: Vec<BigUint>,
+    offset: i64,
+    total: BigUint,
+}
+
+impl BigDistribution {
+    /// The distribution of the sum of independent rolls: `n` rolls of each distribution.
+    ///
+    /// Returns None if a value is out of range, or computing it would cost more than `budget`
+    /// (see [`ntt::power_product`]).
+    pub(crate) fn sum_of_rolls(rolls: &[(Distribution, usize)], budget: usize) -> Option<Self> {
+        let offset = rolls.iter().try_fold(0i64, |offset, (d, n)| {
+            offset.checked_add(d.min().checked_mul(i64::try_from(*n).ok()?)?)
+        })?;
+        rolls.iter().try_fold(0i64, |max, (d, n)| {
+            max.checked_add(d.max().checked_mul(i64::try_from(*n).ok()?)?)
+        })?;
+        let factors: Vec<(&[usize], usize)> = rolls
+            .iter()
+            .filter(|(_, n)| *n != 0)
+            .map(|(d, n)| (&d.occurrence_by_value[..], *n))
+            .collect();
+        let occurrence_by_value = ntt::power_product(&factors, budget)?;
+        let total = occurrence_by_value.iter().sum();
+        Some(BigDistribution {
+            occurrence_by_value,
+            offset,
+            total,
+        })
+    }
+
+    /// The minimum value with nonzero occurrence in this distribution.
+    pub fn min(&self) -> i64 {
+        self.offset
+    }
+
+    /// The maximum value with nonzero occurrence in this distribution.
+    pub fn max(&self) -> i64 {
+        self.offset + (self.occurrence_by_value.len() as i64 - 1)
+    }
+
+    /// The total number of occurrences: the number of possible rolls.
+    pub fn total(&self) -> &BigUint {
+        &self.total
+    }
+
+    /// Iterator over (value, occurrences) tuples in this distribution.
+    /// Reports values with nonzero occurrence in ascending order of value.
+    pub fn occurrences(&self) -> impl Iterator<Item = (i64, &BigUint)> {
+        (self.offset..)
+            .zip(&self.occurrence_by_value)
+            .filter(|(_, o)| !o.is_zero())
+    }
+
+    /// The number of occurrences of this value in the distribution.
+    fn occurrences_of(&self, value: i64) -> Option<&BigUint> {
+        value
+            .checked_sub(self.offset)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| self.occurrence_by_value.get(index))
+    }
+
+    /// Give the probability of this value occurring in this distribution.
+    pub fn probability(&self, value: i64) -> Ratio<BigUint> {
+        match self.occurrences_of(value) {
+            Some(n) if !n.is_zero() => Ratio::new(n.clone(), self.total.clone()),
+            _ => Ratio::new(BigUint::zero(), 1u32.into()),
+        }
+    }
+
+    /// The probability of this value occurring, as a float.
+    /// Probabilities too small for a float are 0.
+    pub fn probability_f64(&self, value: i64) -> f64 {
+        let Some(n) = self.occurrences_of(value).filter(|n| !n.is_zero()) else {
+            return 0.0;
+        };
+        // Both may be too large for a float: divide them as integers, to the leading 64 bits of
+        // the probability, then scale that down.
+        let shift = 64 + self.total.bits() - n.bits();
+        let leading = (n << shift) / &self.total;
+        leading.to_f64().expect("a u64 is within range") * 2f64.powi(-(shift as i32))
+    }
+
+    /// The average value (expected value) from this distribution.
+    pub fn mean(&self) -> f64 {
+        self.occurrences()
+            .map(|(v, _)| (v as f64) * self.probability_f64(v))
+            .sum()
+    }
+
+    /// The variance of this distribution: the expected squared distance from the mean.
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.occurrences()
+            .map(|(v, _)| (v as f64 - mean).powi(2) * self.probability_f64(v))
+            .sum()
+    }
+
+    /// The standard deviation of this distribution: the square root of its variance.
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution_of(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn matches_distribution() {
+        let rolls = [(distribution_of("d6"), 3), (distribution_of("-d4"), 2)];
+        let big = BigDistribution::sum_of_rolls(&rolls, usize::MAX).unwrap();
+        let want = distribution_of("3d6 - 2d4");
+        assert_eq!((big.min(), big.max()), (want.min(), want.max()));
+        assert_eq!(*big.total(), BigUint::from(want.total()));
+        for (v, o) in want.occurrences() {
+            assert_eq!(
+                big.probability(v),
+                Ratio::new(o.into(), want.total().into())
+            );
+        }
+        assert!((big.mean() - want.mean()).abs() < 1e-9);
+        assert!((big.variance() - want.variance()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn too_many_to_count() {
+        let d100 = distribution_of("d100");
+        let big = BigDistribution::sum_of_rolls(&[(d100, 30)], usize::MAX).unwrap();
+        assert_eq!((big.min(), big.max()), (30, 3000));
+        assert_eq!(*big.total(), BigUint::from(100u32).pow(30));
+        // Only one roll is all 1s; 30 rolls have a single 2.
+        assert_eq!(
+            big.probability(30),
+            Ratio::new(1u32.into(), big.total().clone())
+        );
+        assert_eq!(
+            big.probability(31),
+            Ratio::new(30u32.into(), big.total().clone())
+        );
+        assert!((big.mean() - 30.0 * 50.5).abs() < 1e-6);
+        assert!((big.variance() - 30.0 * 9999.0 / 12.0).abs() < 1e-6);
+        // The middle is about as likely as the middle of a normal distribution with the same
+        // variance.
+        let peak = 1.0 / (2.0 * std::f64::consts::PI * big.variance()).sqrt();
+        assert!((big.probability_f64(1515) / peak - 1.0).abs() < 1e-2);
+
+        // 2^-2000 is too small for a float, and the total too large; the middle isn't either.
+        let d2 = distribution_of("d2");
+        let big = BigDistribution::sum_of_rolls(&[(d2, 2000)], usize::MAX).unwrap();
+        assert_eq!(big.probability_f64(2000), 0.0);
+        assert!((big.mean() - 3000.0).abs() < 1e-6);
+        assert!((big.variance() - 500.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn over_budget() {
+        let d100 = distribution_of("d100");
+        assert_eq!(BigDistribution::sum_of_rolls(&[(d100, 200)], 1000), None);
+        let huge = distribution_of("d[9223372036854775806..9223372036854775807]");
+        assert_eq!(
+            BigDistribution::sum_of_rolls(&[(huge, 2)], usize::MAX),
+            None
+        );
+    }
+}
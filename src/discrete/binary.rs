@@ -0,0 +1,263 @@
+//! A compact binary format for storing distributions.
+//!
+//! A stored distribution is a header, then a payload:
+//!
+//! - The magic bytes `DICD`, a version byte (1), and a flags byte: bit 0 is set if the payload
+//!   is compressed with zstd.
+//! - The payload: the number of values that occur, then each of those values with its
+//!   occurrences, in increasing order. The first value is stored as a signed (zigzag) varint;
+//!   each later value as its (positive) difference from the one before.
+//!   Occurrences are unsigned varints.
+//!
+//! All varints are LEB128. Values that never occur take no space, so sparse distributions
+//! (like thresholds or `d66`) are as small as dense ones.
+
+use std::io::{self, Read, Write};
+
+use super::{Counts, Distribution, MAX_WIDTH, Provenance};
+
+const MAGIC: &[u8; 4] = b"DICD";
+const VERSION: u8 = 1;
+/// Flag: the payload is compressed with zstd.
+const ZSTD: u8 = 1;
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
+
+fn write_varint(out: &mut impl Write, mut v: u64) -> io::Result<()> {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        let bits = u64::from(byte[0] & 0x7f);
+        if shift == 63 && bits > 1 {
+            return Err(invalid("varint overflows 64 bits"));
+        }
+        v |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid("varint overflows 64 bits"))
+}
+
+impl Distribution {
+    /// Write the distribution in dicer's binary format, uncompressed.
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, 0])?;
+        self.write_payload(out)
+    }
+
+    /// Write the distribution in dicer's binary format, compressed with zstd at the given level
+    /// (1 through 22; 0 for zstd's default).
+    #[cfg(feature = "zstd")]
+    pub fn write_compressed_to(&self, out: &mut impl Write, level: i32) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, ZSTD])?;
+        let mut encoder = zstd::stream::Encoder::new(out, level)?;
+        self.write_payload(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Read a distribution in dicer's binary format, as written by [`Distribution::write_to`].
+    ///
+    /// Reading a compressed distribution requires the `zstd` feature.
+    pub fn read_from(r: &mut impl Read) -> io::Result<Distribution> {
+        let mut header = [0; 6];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a stored distribution"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        match header[5] {
+            0 => Distribution::read_payload(r),
+            #[cfg(feature = "zstd")]
+            ZSTD => Distribution::read_payload(&mut zstd::stream::Decoder::new(r)?),
+            #[cfg(not(feature = "zstd"))]
+            ZSTD => Err(invalid(
+                "compressed distribution; requires the zstd feature",
+            )),
+            _ => Err(invalid("unknown flags")),
+        }
+    }
+
+    fn write_payload(&self, out: &mut impl Write) -> io::Result<()> {
+        write_varint(out, self.occurrences().count() as u64)?;
+        let mut last = None;
+        for (value, occurrences) in self.occurrences() {
+            match last {
+                None => write_varint(out, ((value << 1) ^ (value >> 63)) as u64)?,
+                Some(last) => write_varint(out, value.abs_diff(last))?,
+            }
+            write_varint(out, occurrences as u64)?;
+            last = Some(value);
+        }
+        Ok(())
+    }
+
+    fn read_payload(r: &mut impl Read) -> io::Result<Distribution> {
+        let len = read_varint(r)?;
+        if len == 0 {
+            return Err(invalid("empty distribution"));
+        }
+        let mut counts = Counts::new();
+        let mut offset = 0i64;
+        let mut total = 0usize;
+        for i in 0..len {
+            let position = read_varint(r)?;
+            let occurrences = usize::try_from(read_varint(r)?)
+                .ok()
+                .filter(|o| *o != 0)
+                .ok_or_else(|| invalid("invalid occurrences"))?;
+            total = total
+                .checked_add(occurrences)
+                .ok_or_else(|| invalid("total occurrences overflow"))?;
+            let index = if i == 0 {
+                offset = (position >> 1) as i64 ^ -((position & 1) as i64);
+                0
+            } else {
+                if position == 0 {
+                    return Err(invalid("values out of order"));
+                }
+                // The value must also fit in an i64.
+                let index = usize::try_from(position)
+                    .ok()
+                    .and_then(|p| (counts.len() - 1).checked_add(p))
+                    .filter(|index| offset.checked_add_unsigned(*index as u64).is_some())
+                    .ok_or_else(|| invalid("value overflows"))?;
+                if index >= MAX_WIDTH {
+                    return Err(invalid("distribution too wide"));
+                }
+                counts
+                    .try_resize(index, 0)
+                    .map_err(|_| invalid("distribution too wide"))?;
+                index
+            };
+            counts
+                .try_resize(index + 1, occurrences)
+                .map_err(|_| invalid("distribution too wide"))?;
+        }
+        Ok(Distribution {
+            occurrence_by_value: counts,
+            offset,
+            provenance: Provenance::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn round_trip(d: &Distribution) -> Vec<u8> {
+        let mut out = Vec::new();
+        d.write_to(&mut out).unwrap();
+        assert_eq!(&Distribution::read_from(&mut out.as_slice()).unwrap(), d);
+        out
+    }
+
+    #[test]
+    fn varints() {
+        for v in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, v).unwrap();
+            assert_eq!(read_varint(&mut out.as_slice()).unwrap(), v);
+        }
+        assert_eq!(
+            read_varint(&mut [0xff; 10].as_slice()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn distributions() {
+        for e in ["3", "-5", "d6", "3d6 - 20", "d66", "d20 >= 15", "20d6"] {
+            round_trip(&e.parse::<Closed>().unwrap().distribution().unwrap());
+        }
+        for (lo, hi) in [(i64::MIN, i64::MIN + 3), (i64::MAX - 3, i64::MAX)] {
+            round_trip(&Distribution::uniform(lo, hi).unwrap());
+        }
+    }
+
+    #[test]
+    fn compact() {
+        // Six values, one byte each, with one-byte differences and counts.
+        let d6 = round_trip(&Distribution::die(6).unwrap());
+        assert_eq!(d6.len(), 6 + 1 + 6 * 2);
+        // Sparse values take no more space than dense ones.
+        let d66 = round_trip(&Distribution::digits(&[6, 6]).unwrap());
+        assert_eq!(d66.len(), 6 + 1 + 36 * 2);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let stored = round_trip(&Distribution::die(6).unwrap());
+        let read = |bytes: &[u8]| Distribution::read_from(&mut &bytes[..]);
+        let mut bad = stored.clone();
+        bad[0] = b'X';
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // Truncated.
+        assert_eq!(
+            read(&stored[..stored.len() - 1]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        // Zero occurrences.
+        let mut bad = stored.clone();
+        bad[8] = 0;
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // Values out of order.
+        let mut bad = stored.clone();
+        bad[9] = 0;
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // Empty.
+        assert_eq!(
+            read(b"DICD\x01\x00\x00").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        // Values too far apart to store.
+        assert_eq!(
+            read(b"DICD\x01\x00\x02\x00\x01\x80\x80\x80\x80\x80\x80\x80\x80\x40\x01")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressed() {
+        let d = Distribution::uniform(0, 9999).unwrap();
+        let plain = round_trip(&d);
+        let mut compressed = Vec::new();
+        d.write_compressed_to(&mut compressed, 0).unwrap();
+        assert!(compressed.len() < plain.len());
+        assert_eq!(
+            Distribution::read_from(&mut compressed.as_slice()).unwrap(),
+            d
+        );
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn compressed_requires_feature() {
+        let err = Distribution::read_from(&mut b"DICD\x01\x01".as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
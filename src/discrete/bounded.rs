@@ -0,0 +1,229 @@
+//! Distributions with a bound on how far they are from the exact answer.
+
+use num::ToPrimitive;
+
+use super::{Distribution, Evaluator};
+use crate::{
+    Error, RandomVariable,
+    analysis::Closed,
+    symbolic::{Explosion, ExpressionTree, ExpressionWrapper},
+};
+
+/// A distribution that may have been approximated, with a bound on its error.
+///
+/// Exploding dice are truncated after some number of explosions, and a distribution may have
+/// its tails trimmed; either moves some probability away from where it belongs. `error` bounds
+/// how much: the probability of any value, or any set of values, is within `error` of the
+/// exact one.
+///
+/// Produced by [`Evaluator::eval_bounded`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bounded {
+    /// The distribution as computed.
+    pub distribution: Distribution,
+    /// The most any probability from `distribution` can be off by; 0 if it's exact.
+    pub error: f64,
+}
+
+impl Bounded {
+    /// Whether the distribution is exact: no approximation was made anywhere.
+    pub fn is_exact(&self) -> bool {
+        self.error == 0.0
+    }
+
+    /// The interval that the exact probability of the value is in.
+    pub fn probability(&self, value: i64) -> (f64, f64) {
+        self.interval(self.distribution.probability_f64(value))
+    }
+
+    /// The interval that the exact probability of any value matching the predicate is in.
+    pub fn probability_where(&self, predicate: impl FnMut(i64) -> bool) -> (f64, f64) {
+        let p = self.distribution.probability_where(predicate);
+        self.interval(p.to_f64().expect("should convert probability to f64"))
+    }
+
+    /// Trim the distribution's tails, as [`Distribution::trim_tail`] does, and add the trimmed
+    /// probability to the error.
+    pub fn trim_tail(&mut self, epsilon: f64) {
+        let trimmed = self.distribution.trim_tail(epsilon);
+        self.error =
+            (self.error + trimmed.to_f64().expect("should convert probability to f64")).min(1.0);
+    }
+
+    fn interval(&self, p: f64) -> (f64, f64) {
+        ((p - self.error).max(0.0), (p + self.error).min(1.0))
+    }
+}
+
+impl Evaluator {
+    /// Evaluate the expression, along with a bound on how far the result is from exact.
+    pub fn eval_bounded(&mut self, tree: &Closed) -> Result<Bounded, Error> {
+        Ok(Bounded {
+            distribution: self.eval(tree)?,
+            error: self.truncation(tree)?,
+        })
+    }
+
+    /// How much probability a roll of the expression may have out of place, from
+    /// truncating its exploding dice.
+    ///
+    /// Each approximate part of the expression agrees with its exact roll except with at most
+    /// its own error, so the errors of all the rolls it's made of add up.
+    fn truncation(&mut self, tree: &Closed) -> Result<f64, Error> {
+        let error = match tree.inner() {
+            ExpressionTree::Modifier(_)
+            | ExpressionTree::Die(_)
+            | ExpressionTree::CustomDie(_)
+            | ExpressionTree::Symbol(_) => 0.0,
+            ExpressionTree::Negated(e)
+            | ExpressionTree::Successes { value: e, .. }
+            | ExpressionTree::Lookup { value: e, .. } => self.truncation(e)?,
+            ExpressionTree::Product(a, b)
+            | ExpressionTree::Floor(a, b)
+            | ExpressionTree::Modulo(a, b)
+            | ExpressionTree::Comparison { a, b, .. } => {
+                self.truncation(a)? + self.truncation(b)?
+            }
+            ExpressionTree::Sum(items) | ExpressionTree::Call { args: items, .. } => {
+                let mut error = 0.0;
+                for item in items {
+                    error += self.truncation(item)?;
+                }
+                error
+            }
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker,
+            } => {
+                // Where the count is right, it's no more than its computed maximum.
+                let rolls = self.eval(count)?.max().max(0) as f64;
+                let ranked = match ranker.count() {
+                    Some(n) => self.truncation(n)?,
+                    None => 0.0,
+                };
+                self.truncation(count)? + ranked + rolls * self.truncation(value)?
+            }
+            ExpressionTree::Binding {
+                symbol,
+                value,
+                tail,
+            } => {
+                let mut worst: f64 = 0.0;
+                for (v, _) in self.eval(value)?.occurrences() {
+                    worst = worst.max(self.truncation(&tail.substitute(symbol, v))?);
+                }
+                self.truncation(value)? + worst
+            }
+            ExpressionTree::Reroll { value, reroll } => {
+                let error = self.truncation(value)?;
+                if !reroll.recursive {
+                    // Rolled at most twice.
+                    2.0 * error
+                } else if error == 0.0 {
+                    0.0
+                } else {
+                    // Conditioning on keeping the roll scales up the error.
+                    let kept = self
+                        .eval(value)?
+                        .probability_where(|v| !reroll.applies(v))
+                        .to_f64()
+                        .expect("should convert probability to f64");
+                    if kept > error {
+                        2.0 * error / (kept - error)
+                    } else {
+                        1.0
+                    }
+                }
+            }
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => {
+                let error = self.truncation(value)?;
+                let die = self.eval(value)?;
+                let face = match explosion {
+                    Explosion::Imploding => die.min(),
+                    _ => die.max(),
+                };
+                let explodes = die
+                    .probability_where(|v| trigger.explodes(face, v))
+                    .to_f64()
+                    .expect("should convert probability to f64");
+                let rolls = self.explosion_depth + 1;
+                // The truncated die is only wrong when every roll it makes explodes.
+                let residual = (explodes + error).min(1.0).powi(rolls as i32);
+                rolls as f64 * error + residual
+            }
+        };
+        Ok(error.min(1.0))
+    }
+}
+
+impl Closed {
+    /// Retrieve the distribution for the expression, with a bound on its error;
+    /// see [`Bounded`].
+    pub fn bounded_distribution(&self) -> Result<Bounded, Error> {
+        Evaluator::default().eval_bounded(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounded(s: &str) -> Bounded {
+        s.parse::<Closed>().unwrap().bounded_distribution().unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        for s in ["3d6 + 2", "4d6kh3", "[X: d6] X * X", "d20rr1", "2d4ro<2"] {
+            let b = bounded(s);
+            assert!(b.is_exact(), "{s}: {}", b.error);
+            assert_eq!(
+                b.distribution,
+                s.parse::<Closed>().unwrap().distribution().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn explosions() {
+        // A d6 explodes past the default depth of 2 with probability 1/6³.
+        let b = bounded("d6!");
+        assert!((b.error - 1.0 / 216.0).abs() < 1e-12, "{}", b.error);
+        assert!(!b.is_exact());
+        // Each of three dice may be off.
+        let b = bounded("3d6! + 1");
+        assert!((b.error - 3.0 / 216.0).abs() < 1e-12, "{}", b.error);
+        let b = bounded("d6!i");
+        assert!((b.error - 1.0 / 216.0).abs() < 1e-12, "{}", b.error);
+
+        // The exact probability is in the interval.
+        let deep = Evaluator::default()
+            .with_explosion_depth(12)
+            .eval(&"d6! >= 15".parse().unwrap())
+            .unwrap()
+            .probability_f64(1);
+        let (lo, hi) = bounded("d6!").probability_where(|v| v >= 15);
+        assert!(lo <= deep && deep <= hi, "{lo} {deep} {hi}");
+
+        // Deeper explosions are closer.
+        let b = Evaluator::default()
+            .with_explosion_depth(5)
+            .eval_bounded(&"d6!".parse().unwrap())
+            .unwrap();
+        assert!((b.error - 1.0 / 46656.0).abs() < 1e-12, "{}", b.error);
+    }
+
+    #[test]
+    fn trimmed() {
+        let mut b = bounded("3d6");
+        b.trim_tail(0.01);
+        assert!((b.error - 2.0 / 216.0).abs() < 1e-12, "{}", b.error);
+        let (lo, hi) = b.probability(10);
+        assert!(lo < 27.0 / 216.0 && 27.0 / 216.0 < hi);
+    }
+}
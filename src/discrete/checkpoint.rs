@@ -0,0 +1,193 @@
+//! Saving and restoring an evaluator's memoized results, so a long evaluation can be paused
+//! (e.g. when it goes over budget) and resumed later, possibly in another process.
+//!
+//! A checkpoint is text: a header, then one line per memoized sub-expression, with the
+//! expression, the distribution's lowest value, and its occurrence counts, separated by tabs.
+
+use std::io::{BufRead, Write};
+
+use super::{Counts, Distribution, Evaluator, Provenance};
+use crate::{Error, Parser};
+
+/// The first line of every checkpoint, identifying the format.
+const HEADER: &str = "dicer checkpoint 1";
+
+impl Evaluator {
+    /// Write the memoized results of this evaluator, to be restored with [`Evaluator::restore`].
+    ///
+    /// Only results of completed sub-expressions are memoized, so pausing an evaluation loses
+    /// the work on the sub-expressions in progress, but no more.
+    pub fn checkpoint(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "{HEADER}")?;
+        writeln!(out, "explosion-depth {}", self.explosion_depth)?;
+        // Sort, so the same results always give the same checkpoint.
+        let mut entries: Vec<(String, &Distribution)> =
+            self.memo.iter().map(|(e, d)| (e.to_string(), d)).collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (expression, d) in entries {
+            write!(out, "{expression}\t{}\t", d.offset)?;
+            for (i, count) in d.occurrence_by_value.iter().enumerate() {
+                if i > 0 {
+                    write!(out, " ")?;
+                }
+                write!(out, "{count}")?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Restore memoized results from a checkpoint written by [`Evaluator::checkpoint`],
+    /// and enable memoization so they're used. Returns how many results were restored.
+    ///
+    /// Expressions in the checkpoint are parsed with `parser`, so any custom dice they use
+    /// must be registered with it. The checkpoint must have been written by an evaluator
+    /// with the same explosion depth as this one.
+    pub fn restore(&mut self, parser: &Parser, checkpoint: impl BufRead) -> Result<usize, Error> {
+        let invalid =
+            |line: usize, why: &str| Error::InvalidCheckpoint(format!("line {}: {why}", line + 1));
+        let mut lines = checkpoint.lines().enumerate();
+        let mut next = || match lines.next() {
+            Some((i, Ok(line))) => Ok(Some((i, line))),
+            Some((i, Err(e))) => Err(invalid(i, &e.to_string())),
+            None => Ok(None),
+        };
+        match next()? {
+            Some((_, header)) if header == HEADER => (),
+            _ => return Err(invalid(0, "not a checkpoint")),
+        }
+        match next()? {
+            Some((i, depth)) => {
+                let want = format!("explosion-depth {}", self.explosion_depth);
+                if depth != want {
+                    return Err(invalid(i, &format!("expected {want:?}, found {depth:?}")));
+                }
+            }
+            None => return Err(invalid(1, "missing explosion depth")),
+        }
+
+        let mut restored = Vec::new();
+        while let Some((i, line)) = next()? {
+            let [expression, offset, counts] = line
+                .split('\t')
+                .collect::<Vec<_>>()
+                .try_into()
+                .map_err(|_| invalid(i, "expected three tab-separated fields"))?;
+            let expression = parser.parse(expression)?;
+            let offset: i64 = offset.parse().map_err(|_| invalid(i, "invalid offset"))?;
+            let counts = counts
+                .split(' ')
+                .map(|c| c.parse::<usize>())
+                .collect::<Result<Counts, _>>()
+                .map_err(|_| invalid(i, "invalid count"))?;
+            // Distributions are trimmed: their first and last values occur.
+            if counts.first().is_none_or(|c| *c == 0) || counts.last().is_none_or(|c| *c == 0) {
+                return Err(invalid(i, "distribution isn't trimmed"));
+            }
+            counts
+                .iter()
+                .try_fold(0usize, |total, c| total.checked_add(*c))
+                .ok_or_else(|| invalid(i, "counts overflow"))?;
+            offset
+                .checked_add_unsigned(counts.len() as u64 - 1)
+                .ok_or_else(|| invalid(i, "values overflow"))?;
+            restored.push((
+                expression,
+                Distribution {
+                    occurrence_by_value: counts,
+                    offset,
+                    provenance: Provenance::default(),
+                },
+            ));
+        }
+        // Only change the evaluator once the whole checkpoint is known to be valid.
+        let n = restored.len();
+        self.memo.extend(restored);
+        self.memoize = true;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Closed, Error, Evaluator, Parser};
+
+    fn checkpoint(evaluator: &Evaluator) -> Vec<u8> {
+        let mut out = Vec::new();
+        evaluator.checkpoint(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn resume_over_budget() {
+        let e: Closed = "4d20 + 4d12 * d4 + 4d10 - 4d8".parse().unwrap();
+        let want = e.distribution().unwrap();
+        // Enough for any one node, but not the whole expression.
+        let profile = e.profile().unwrap();
+        let budget = profile.busiest().unwrap().combinations;
+        let total: usize = profile.nodes.iter().map(|n| n.combinations).sum();
+        assert!(budget < total);
+
+        let mut saved = Vec::new();
+        let mut rounds = 0;
+        let got = loop {
+            rounds += 1;
+            assert!(rounds < 20, "no progress");
+            // A fresh evaluator each time, as if in a new process.
+            let mut evaluator = Evaluator::new(true).with_budget(budget);
+            evaluator.restore(&Parser::new(), saved.as_slice()).ok();
+            match evaluator.eval(&e) {
+                Ok(d) => break d,
+                Err(Error::OverBudget(_)) => saved = checkpoint(&evaluator),
+                Err(e) => panic!("{e}"),
+            }
+        };
+        assert!(rounds > 1);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn round_trip() {
+        let parser = Parser::new().with_die("hitloc", [1, 1, 2, 3]);
+        let e = parser.parse("2dhitloc + d{-3:1,5:2}! * 3dF").unwrap();
+        let mut evaluator = Evaluator::new(true);
+        let want = evaluator.eval(&e).unwrap();
+        let saved = checkpoint(&evaluator);
+
+        let mut restored = Evaluator::new(false);
+        let n = restored.restore(&parser, saved.as_slice()).unwrap();
+        assert!(n > 1);
+        assert_eq!(checkpoint(&restored), saved);
+        // Evaluating from the restored results doesn't need to combine anything.
+        let mut restored = restored.with_budget(0);
+        assert_eq!(restored.eval(&e).unwrap(), want);
+    }
+
+    #[test]
+    fn invalid() {
+        let saved = {
+            let mut evaluator = Evaluator::new(true);
+            evaluator.eval(&"2d6".parse().unwrap()).unwrap();
+            String::from_utf8(checkpoint(&evaluator)).unwrap()
+        };
+        let restore = |s: &str| Evaluator::new(true).restore(&Parser::new(), s.as_bytes());
+        assert!(restore(&saved).is_ok());
+
+        for bad in [
+            String::new(),
+            "2d6\t2\t1 2 3".to_owned(),
+            saved.replace("explosion-depth 2", "explosion-depth 3"),
+            saved.replace("\t2\t", "\tx\t"),
+            saved + "d4\t1\t1 1 0\n",
+        ] {
+            assert!(
+                matches!(restore(&bad), Err(Error::InvalidCheckpoint(_))),
+                "{bad:?}"
+            );
+        }
+        assert!(matches!(
+            restore("dicer checkpoint 1\nexplosion-depth 2\nd(\t1\t1\n"),
+            Err(Error::ParseError(..))
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! Storage for occurrence counts, inline when small.
+//!
+//! Most distributions are small: a modifier has one entry, a d20 has twenty.
+//! Storing those inline in the Distribution saves an allocation for each
+//! intermediate result; wider distributions spill over to the heap.
+
+use std::{
+    collections::TryReserveError,
+    ops::{Deref, DerefMut},
+};
+
+/// How many counts can be stored without allocating.
+const INLINE: usize = 24;
+
+/// A growable list of counts, like `Vec<usize>`, which stores short lists inline.
+#[derive(Clone)]
+pub(crate) enum Counts {
+    Inline { len: usize, counts: [usize; INLINE] },
+    Heap(Vec<usize>),
+}
+
+impl Counts {
+    /// An empty list.
+    pub fn new() -> Self {
+        Counts::Inline {
+            len: 0,
+            counts: [0; INLINE],
+        }
+    }
+
+    /// A list of `len` copies of `value`.
+    pub fn filled(value: usize, len: usize) -> Self {
+        let mut counts = Counts::new();
+        counts.resize(len, value);
+        counts
+    }
+
+    /// Resize the list, filling any new entries with `value`.
+    pub fn resize(&mut self, new_len: usize, value: usize) {
+        match self {
+            Counts::Inline { len, counts } if new_len <= INLINE => {
+                if new_len > *len {
+                    counts[*len..new_len].fill(value);
+                }
+                *len = new_len;
+            }
+            Counts::Inline { len, counts } => {
+                let mut v = Vec::with_capacity(new_len);
+                v.extend_from_slice(&counts[..*len]);
+                v.resize(new_len, value);
+                *self = Counts::Heap(v);
+            }
+            Counts::Heap(v) => v.resize(new_len, value),
+        }
+    }
+
+    /// Resize the list, as [`Counts::resize`] does, or fail if the memory for it can't be
+    /// allocated.
+    pub fn try_resize(&mut self, new_len: usize, value: usize) -> Result<(), TryReserveError> {
+        match self {
+            Counts::Inline { .. } if new_len <= INLINE => (),
+            Counts::Inline { len, counts } => {
+                let mut v = Vec::new();
+                v.try_reserve_exact(new_len)?;
+                v.extend_from_slice(&counts[..*len]);
+                *self = Counts::Heap(v);
+            }
+            Counts::Heap(v) => v.try_reserve(new_len.saturating_sub(v.len()))?,
+        }
+        self.resize(new_len, value);
+        Ok(())
+    }
+
+    /// Remove all entries, keeping any allocated capacity.
+    pub fn clear(&mut self) {
+        self.resize(0, 0)
+    }
+
+    /// How many bytes are allocated on the heap for these counts.
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            Counts::Inline { .. } => 0,
+            Counts::Heap(v) => v.capacity() * std::mem::size_of::<usize>(),
+        }
+    }
+}
+
+impl Default for Counts {
+    fn default() -> Self {
+        Counts::new()
+    }
+}
+
+impl Deref for Counts {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        match self {
+            Counts::Inline { len, counts } => &counts[..*len],
+            Counts::Heap(v) => v,
+        }
+    }
+}
+
+impl DerefMut for Counts {
+    fn deref_mut(&mut self) -> &mut [usize] {
+        match self {
+            Counts::Inline { len, counts } => &mut counts[..*len],
+            Counts::Heap(v) => v,
+        }
+    }
+}
+
+impl FromIterator<usize> for Counts {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut counts = Counts::new();
+        for (i, v) in iter.into_iter().enumerate() {
+            counts.resize(i + 1, v);
+        }
+        counts
+    }
+}
+
+impl PartialEq for Counts {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Counts {}
+
+impl std::fmt::Debug for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill() {
+        let mut c = Counts::filled(1, 3);
+        assert!(matches!(c, Counts::Inline { .. }));
+        assert_eq!(c.heap_bytes(), 0);
+        c.resize(INLINE + 1, 2);
+        assert!(matches!(c, Counts::Heap(_)));
+        assert!(c.heap_bytes() >= (INLINE + 1) * std::mem::size_of::<usize>());
+        assert_eq!(&c[..4], &[1, 1, 1, 2]);
+        assert_eq!(c.len(), INLINE + 1);
+
+        // Equality doesn't depend on where the counts are stored.
+        c.resize(3, 0);
+        assert_eq!(c, Counts::filled(1, 3));
+        c.clear();
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn shrink_and_regrow() {
+        let mut c: Counts = [5, 6, 7].into_iter().collect();
+        c.resize(1, 0);
+        c.resize(3, 9);
+        assert_eq!(&*c, &[5, 9, 9]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Sub-quadratic convolution via Karatsuba multiplication.
+//!
+//! Convolving occurrence counts is polynomial multiplication. Karatsuba's method splits each
+//! polynomial in half and uses three half-size multiplications instead of four.
+//! The intermediate sums and differences use wrapping arithmetic: every result is exact
+//! modulo 2^64, so any result that fits in a `usize` is exact.
+
+/// Below this length, direct convolution is faster than splitting further.
+const DIRECT_MAX_LEN: usize = 32;
+
+/// Convolve `a` and `b`: `out[k]` is the sum of `a[i] * b[j]` over `i + j = k`.
+///
+/// The caller must ensure that every result fits in a `usize`,
+/// e.g. by checking that the product of the sums of `a` and `b` does.
+pub(crate) fn convolve(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut out = vec![0; a.len() + b.len() - 1];
+    accumulate(a, b, &mut out);
+    out
+}
+
+/// Add the convolution of `a` and `b` into `out`.
+fn accumulate(a: &[usize], b: &[usize], out: &mut [usize]) {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    if short.len() <= DIRECT_MAX_LEN {
+        for (i, x) in long.iter().enumerate() {
+            for (j, y) in short.iter().enumerate() {
+                out[i + j] = out[i + j].wrapping_add(x.wrapping_mul(*y));
+            }
+        }
+        return;
+    }
+    if long.len() != short.len() {
+        // Multiply by pieces of the long one that are no longer than the short one.
+        for (i, chunk) in long.chunks(short.len()).enumerate() {
+            let offset = i * short.len();
+            accumulate(
+                chunk,
+                short,
+                &mut out[offset..offset + chunk.len() + short.len() - 1],
+            );
+        }
+        return;
+    }
+
+    // a = a0 + x^m a1, b = b0 + x^m b1; then
+    // ab = a0 b0 + x^m ((a0 + a1)(b0 + b1) - a0 b0 - a1 b1) + x^2m a1 b1.
+    let m = a.len() / 2;
+    let (a0, a1) = a.split_at(m);
+    let (b0, b1) = b.split_at(m);
+    let z0 = convolve(a0, b0);
+    let z2 = convolve(a1, b1);
+    let sum = |lo: &[usize], hi: &[usize]| -> Vec<usize> {
+        let mut s = hi.to_vec();
+        for (s, v) in s.iter_mut().zip(lo) {
+            *s = s.wrapping_add(*v);
+        }
+        s
+    };
+    let mut z1 = convolve(&sum(a0, a1), &sum(b0, b1));
+    for (i, v) in z0.iter().enumerate() {
+        z1[i] = z1[i].wrapping_sub(*v);
+    }
+    for (i, v) in z2.iter().enumerate() {
+        z1[i] = z1[i].wrapping_sub(*v);
+    }
+
+    for (i, v) in z0.iter().enumerate() {
+        out[i] = out[i].wrapping_add(*v);
+    }
+    for (i, v) in z1.iter().enumerate() {
+        out[m + i] = out[m + i].wrapping_add(*v);
+    }
+    for (i, v) in z2.iter().enumerate() {
+        out[2 * m + i] = out[2 * m + i].wrapping_add(*v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direct(a: &[usize], b: &[usize]) -> Vec<usize> {
+        let mut out = vec![0; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += x * y;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn matches_direct() {
+        for (la, lb) in [(1, 1), (33, 33), (100, 100), (257, 40), (40, 500), (99, 98)] {
+            let a: Vec<usize> = (0..la).map(|i| (i * 7919) % 1000 + 1).collect();
+            let b: Vec<usize> = (0..lb).map(|i| (i * 104729) % 5000).collect();
+            assert_eq!(convolve(&a, &b), direct(&a, &b), "{la} x {lb}");
+        }
+    }
+
+    #[test]
+    fn large_values() {
+        // Intermediate sums wrap, but results that fit are exact.
+        let a: Vec<usize> = (0..64)
+            .map(|i| if i % 2 == 0 { 1 << 40 } else { 1 })
+            .collect();
+        let b: Vec<usize> = (0..64)
+            .map(|i| if i % 3 == 0 { 1 << 20 } else { 3 })
+            .collect();
+        assert_eq!(convolve(&a, &b), direct(&a, &b));
+    }
+}
@@ -0,0 +1,353 @@
+//! Exact convolution via the number-theoretic transform (NTT).
+//!
+//! Direct convolution of two sequences takes time proportional to the product of their lengths;
+//! for wide distributions (large pools, like `100d100`) that's prohibitive.
+//! The NTT is an FFT over integers modulo a prime, so it is exact: we convolve modulo two primes
+//! and reconstruct each result with the Chinese remainder theorem.
+//! The product of the primes exceeds `u64::MAX`, so any result that fits in a `usize` is exact.
+//!
+//! Results that don't fit, like the counts of `200d100`'s 100^200 rolls, are reconstructed as
+//! arbitrary-precision integers from their residues modulo as many smaller primes as they need:
+//! see [`power_product`].
+
+use std::sync::OnceLock;
+
+use num::BigUint;
+
+/// A prime modulus `c * 2^k + 1`, with a primitive root.
+struct Prime {
+    modulus: u64,
+    /// The largest power of two dividing `modulus - 1`: the longest supported transform.
+    max_log_len: u32,
+    primitive_root: u64,
+}
+
+const P1: Prime = Prime {
+    modulus: 29 << 57 | 1,
+    max_log_len: 57,
+    primitive_root: 3,
+};
+
+const P2: Prime = Prime {
+    modulus: 27 << 56 | 1,
+    max_log_len: 56,
+    primitive_root: 5,
+};
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    if m <= 1 << 32 {
+        // Both are less than the modulus, so their product fits in a u64.
+        a * b % m
+    } else {
+        ((a as u128 * b as u128) % m as u128) as u64
+    }
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+impl Prime {
+    /// Transform `values` in place; its length must be a power of two.
+    fn transform(&self, values: &mut [u64], inverse: bool) {
+        let n = values.len();
+        let m = self.modulus;
+        debug_assert!(n.is_power_of_two() && n.trailing_zeros() <= self.max_log_len);
+
+        // Bit-reversal permutation.
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                values.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        let mut twiddles = Vec::with_capacity(n / 2);
+        while len <= n {
+            let mut root = pow_mod(self.primitive_root, (m - 1) / len as u64, m);
+            if inverse {
+                root = pow_mod(root, m - 2, m);
+            }
+            // The powers of the root, shared by every chunk.
+            twiddles.clear();
+            twiddles.extend(
+                std::iter::successors(Some(1), |w| Some(mul_mod(*w, root, m))).take(len / 2),
+            );
+            for chunk in values.chunks_mut(len) {
+                let (lo, hi) = chunk.split_at_mut(len / 2);
+                for ((x, y), w) in lo.iter_mut().zip(hi.iter_mut()).zip(&twiddles) {
+                    let u = *x;
+                    let v = mul_mod(*y, *w, m);
+                    *x = if u + v >= m { u + v - m } else { u + v };
+                    *y = if u >= v { u - v } else { u + m - v };
+                }
+            }
+            len <<= 1;
+        }
+
+        if inverse {
+            let n_inv = pow_mod(n as u64, m - 2, m);
+            for v in values.iter_mut() {
+                *v = mul_mod(*v, n_inv, m);
+            }
+        }
+    }
+
+    /// Convolve `a` and `b` modulo this prime.
+    fn convolve(&self, a: &[usize], b: &[usize], len: usize) -> Vec<u64> {
+        let m = self.modulus;
+        let mut fa: Vec<u64> = a.iter().map(|v| *v as u64 % m).collect();
+        let mut fb: Vec<u64> = b.iter().map(|v| *v as u64 % m).collect();
+        fa.resize(len, 0);
+        fb.resize(len, 0);
+        self.transform(&mut fa, false);
+        self.transform(&mut fb, false);
+        for (x, y) in fa.iter_mut().zip(fb.iter()) {
+            *x = mul_mod(*x, *y, m);
+        }
+        self.transform(&mut fa, true);
+        fa
+    }
+}
+
+/// Convolve `a` and `b`: `out[k]` is the sum of `a[i] * b[j]` over `i + j = k`.
+///
+/// The caller must ensure that every result fits in a `usize`,
+/// e.g. by checking that the product of the sums of `a` and `b` does.
+pub(crate) fn convolve(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let out_len = a.len() + b.len() - 1;
+    let len = out_len.next_power_of_two();
+    assert!(
+        len.trailing_zeros() <= P2.max_log_len,
+        "convolution of length {out_len} is too long for the NTT"
+    );
+    let r1 = P1.convolve(a, b, len);
+    let r2 = P2.convolve(a, b, len);
+
+    // Chinese remainder theorem (Garner's form):
+    // x = r1 + p1 * ((r2 - r1) * p1^-1 mod p2), which is exact for x < p1 * p2.
+    let (p1, p2) = (P1.modulus, P2.modulus);
+    let p1_inv = pow_mod(p1 % p2, p2 - 2, p2);
+    r1.into_iter()
+        .zip(r2)
+        .take(out_len)
+        .map(|(x1, x2)| {
+            let diff = (x2 + p2 - x1 % p2) % p2;
+            let t = mul_mod(diff, p1_inv, p2);
+            (x1 as u128 + p1 as u128 * t as u128) as usize
+        })
+        .collect()
+}
+
+/// The longest transform [`power_product`] supports, as a power of two.
+const SMALL_LOG_LEN: u32 = 20;
+
+/// Whether `n`, less than 2^32, is prime: a deterministic Miller-Rabin test.
+fn is_prime(n: u64) -> bool {
+    if n < 2 || n.is_multiple_of(2) {
+        return n == 2;
+    }
+    let (mut d, mut s) = (n - 1, 0);
+    while d.is_multiple_of(2) {
+        d /= 2;
+        s += 1;
+    }
+    // These witnesses are enough for every n below 2^32.
+    [2, 7, 61].into_iter().all(|a| {
+        if a % n == 0 {
+            return true;
+        }
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..s).any(|_| {
+            x = mul_mod(x, x, n);
+            x == n - 1
+        })
+    })
+}
+
+/// The primes `c * 2^20 + 1` below 2^32, largest first, with their primitive roots.
+///
+/// Their products fit in a u64, so they're quicker to multiply with than [`P1`] and [`P2`].
+fn small_primes() -> &'static [Prime] {
+    static PRIMES: OnceLock<Vec<Prime>> = OnceLock::new();
+    PRIMES.get_or_init(|| {
+        (1..1 << (32 - SMALL_LOG_LEN))
+            .rev()
+            .map(|c: u64| c << SMALL_LOG_LEN | 1)
+            .filter(|p| is_prime(*p))
+            .map(|modulus| {
+                // The prime factors of modulus - 1 are 2 and those of c.
+                let mut factors = vec![2];
+                let mut c = modulus >> SMALL_LOG_LEN;
+                let mut f = 2;
+                while f * f <= c {
+                    if c.is_multiple_of(f) {
+                        factors.push(f);
+                        while c.is_multiple_of(f) {
+                            c /= f;
+                        }
+                    }
+                    f += 1;
+                }
+                if c > 1 {
+                    factors.push(c);
+                }
+                let primitive_root = (2..)
+                    .find(|g| {
+                        factors
+                            .iter()
+                            .all(|f| pow_mod(*g, (modulus - 1) / f, modulus) != 1)
+                    })
+                    .expect("every prime has a primitive root");
+                Prime {
+                    modulus,
+                    max_log_len: SMALL_LOG_LEN,
+                    primitive_root,
+                }
+            })
+            .collect()
+    })
+}
+
+/// Multiply the polynomials `a`, each raised to the power `n`: the counts of the sum of `n` rolls
+/// of each distribution with counts `a`, exactly, however large they are.
+///
+/// Each power is taken value by value in the transform, so the cost doesn't depend on `n`:
+/// about the length of the result, times the number of primes its counts need, times the
+/// logarithm of its length.
+///
+/// Returns None if that cost is more than `budget`, or the result is too long to transform.
+pub(crate) fn power_product(factors: &[(&[usize], usize)], budget: usize) -> Option<Vec<BigUint>> {
+    let out_len = factors.iter().try_fold(1usize, |len, (a, n)| {
+        len.checked_add((a.len() - 1).checked_mul(*n)?)
+    })?;
+    let len = out_len.next_power_of_two();
+    if len.trailing_zeros() > SMALL_LOG_LEN {
+        return None;
+    }
+
+    // No count can exceed the product of the totals: take primes until theirs does.
+    let bits: f64 = factors
+        .iter()
+        .map(|(a, n)| (a.iter().sum::<usize>() as f64).log2() * *n as f64)
+        .sum();
+    let mut primes = Vec::new();
+    let mut primes_bits = 0.0;
+    for p in small_primes() {
+        if primes_bits > bits + 1.0 {
+            break;
+        }
+        primes.push(p);
+        primes_bits += (p.modulus as f64).log2();
+    }
+    if primes_bits <= bits + 1.0 || out_len.saturating_mul(primes.len()) > budget {
+        return None;
+    }
+
+    let residues: Vec<Vec<u64>> = primes
+        .iter()
+        .map(|p| {
+            let m = p.modulus;
+            let mut product = vec![1; len];
+            for (a, n) in factors {
+                let mut fa: Vec<u64> = a.iter().map(|v| *v as u64 % m).collect();
+                fa.resize(len, 0);
+                p.transform(&mut fa, false);
+                for (x, y) in product.iter_mut().zip(fa) {
+                    *x = mul_mod(*x, pow_mod(y, *n as u64, m), m);
+                }
+            }
+            p.transform(&mut product, true);
+            product
+        })
+        .collect();
+
+    // The Chinese remainder theorem: with M the product of the primes, and M_i = M / p_i,
+    // x = sum(r_i * (M_i^-1 mod p_i) * M_i) mod M.
+    // Each term is summed digit by digit, without carrying: a digit of M_i times a residue is
+    // less than 2^64, so a u128 holds the sum of billions of them.
+    let product: BigUint = primes.iter().map(|p| p.modulus).product();
+    let bases: Vec<(Vec<u32>, u64)> = primes
+        .iter()
+        .map(|p| {
+            let base = &product / p.modulus;
+            let m = p.modulus;
+            let inverse = pow_mod((&base % m).try_into().expect("less than m"), m - 2, m);
+            (base.to_u32_digits(), inverse)
+        })
+        .collect();
+    let mut sums = vec![0u128; product.to_u32_digits().len() + 1];
+    Some(
+        (0..out_len)
+            .map(|k| {
+                sums.fill(0);
+                for ((base, inverse), (p, r)) in bases.iter().zip(primes.iter().zip(&residues)) {
+                    let y = mul_mod(r[k], *inverse, p.modulus) as u128;
+                    for (sum, digit) in sums.iter_mut().zip(base) {
+                        *sum += *digit as u128 * y;
+                    }
+                }
+                let mut carry = 0;
+                let digits = sums
+                    .iter()
+                    .map(|sum| {
+                        let v = sum + carry;
+                        carry = v >> 32;
+                        v as u32
+                    })
+                    .collect();
+                debug_assert_eq!(carry, 0);
+                BigUint::new(digits) % &product
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direct(a: &[usize], b: &[usize]) -> Vec<usize> {
+        let mut out = vec![0; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += x * y;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn matches_direct() {
+        let a: Vec<usize> = (0..300).map(|i| (i * 7919) % 1000).collect();
+        let b: Vec<usize> = (0..77).map(|i| (i * 104729) % 5000 + 1).collect();
+        assert_eq!(convolve(&a, &b), direct(&a, &b));
+        assert_eq!(convolve(&[3], &[5]), vec![15]);
+    }
+
+    #[test]
+    fn large_values() {
+        // Results past either modulus, but within a usize, are exact.
+        let a = [u32::MAX as usize, 1 << 31];
+        let b = [u32::MAX as usize, 3];
+        assert_eq!(convolve(&a, &b), direct(&a, &b));
+    }
+}
@@ -0,0 +1,78 @@
+//! Measurements of where evaluation spends its time.
+
+use std::time::Duration;
+
+use crate::{Distribution, analysis::Closed};
+
+/// Measurements from evaluating one node of an expression.
+#[derive(Debug, Clone)]
+pub struct NodeProfile {
+    /// The sub-expression evaluated at this node.
+    pub expression: Closed,
+    /// How deeply the node is nested: 0 for the whole expression, 1 for its operands, and so on.
+    pub depth: usize,
+    /// Wall time spent evaluating this node, including its operands.
+    pub elapsed: Duration,
+    /// The [`Distribution::support_len`] of the node's result.
+    pub support_len: usize,
+    /// How many combinations of values were computed at this node, not counting its operands:
+    /// e.g. each value of one operand paired with each value of the other.
+    pub combinations: usize,
+}
+
+/// The result of evaluating an expression, along with measurements of each node.
+///
+/// Produced by [`Closed::profile`].
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// The distribution of the expression.
+    pub distribution: Distribution,
+    /// Each node that was evaluated, in evaluation order: a node precedes its operands.
+    ///
+    /// A sub-expression evaluated more than once, such as the tail of a binding,
+    /// appears once for each evaluation.
+    pub nodes: Vec<NodeProfile>,
+}
+
+impl Profile {
+    /// Wall time spent evaluating the whole expression.
+    pub fn elapsed(&self) -> Duration {
+        self.nodes.first().map(|n| n.elapsed).unwrap_or_default()
+    }
+
+    /// The node that computed the most combinations of values, i.e. did the most work itself.
+    /// Ties go to the node evaluated first.
+    pub fn busiest(&self) -> Option<&NodeProfile> {
+        self.first_max_by_key(|n| n.combinations)
+    }
+
+    /// The node with the widest result. Ties go to the node evaluated first.
+    pub fn widest(&self) -> Option<&NodeProfile> {
+        self.first_max_by_key(|n| n.support_len)
+    }
+
+    fn first_max_by_key(&self, key: impl Fn(&NodeProfile) -> usize) -> Option<&NodeProfile> {
+        self.nodes
+            .iter()
+            .reduce(|best, n| if key(n) > key(best) { n } else { best })
+    }
+}
+
+impl std::fmt::Display for Profile {
+    /// One line per node, indented by depth.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            writeln!(
+                f,
+                "{:indent$}{}: {:?}, {} values, {} combinations",
+                "",
+                node.expression,
+                node.elapsed,
+                node.support_len,
+                node.combinations,
+                indent = 2 * node.depth
+            )?;
+        }
+        Ok(())
+    }
+}
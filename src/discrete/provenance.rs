@@ -0,0 +1,66 @@
+//! How a distribution was produced: whether it's exact, and what it took to compute.
+
+use std::time::Duration;
+
+use num::rational::Ratio;
+
+use crate::{
+    analysis::Closed,
+    symbolic::{ExpressionTree, ExpressionWrapper},
+};
+
+/// How a distribution was produced; see [`crate::Distribution::provenance`].
+///
+/// An [`crate::Evaluator`] records the provenance of each expression it evaluates.
+/// Distributions built or combined by hand start out exact, with no record of evaluation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// The most times an exploding die may explode, if the distribution has any:
+    /// their tails are cut off after that many explosions.
+    pub explosion_depth: Option<usize>,
+    /// The probability trimmed from the distribution's tails by [`crate::Distribution::trim_tail`].
+    pub trimmed: Ratio<usize>,
+    /// Wall time spent evaluating the expression.
+    pub elapsed: Duration,
+    /// How many sub-expressions were found among the evaluator's memoized results,
+    /// rather than computed.
+    pub cache_hits: usize,
+}
+
+impl Provenance {
+    /// Whether the distribution is exact: no exploding die was truncated, and nothing trimmed.
+    pub fn is_exact(&self) -> bool {
+        self.explosion_depth.is_none() && *self.trimmed.numer() == 0
+    }
+}
+
+impl std::fmt::Display for Provenance {
+    /// Whether the distribution is exact, and why not, then the time and cache hits:
+    /// `approximate, explosions truncated at depth 2, 1.2ms, 0 cache hits`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_exact() {
+            write!(f, "exact")?;
+        } else {
+            write!(f, "approximate")?;
+        }
+        if let Some(depth) = self.explosion_depth {
+            write!(f, ", explosions truncated at depth {depth}")?;
+        }
+        if *self.trimmed.numer() != 0 {
+            write!(f, ", {} trimmed", self.trimmed)?;
+        }
+        write!(f, ", {:?}, {} cache hits", self.elapsed, self.cache_hits)
+    }
+}
+
+/// Whether the expression has an exploding (or imploding) die.
+pub(super) fn explodes(e: &Closed) -> bool {
+    match e.inner() {
+        ExpressionTree::Explode { .. } => true,
+        node => {
+            let mut found = false;
+            node.map_children(|child| found |= explodes(child));
+            found
+        }
+    }
+}
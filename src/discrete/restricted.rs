@@ -0,0 +1,193 @@
+//! Views of part of a distribution, without copying it.
+
+use std::{
+    borrow::Cow,
+    ops::{Bound, RangeBounds},
+};
+
+use num::rational::Ratio;
+
+use crate::{Distribution, RandomVariable};
+
+/// A distribution truncated to a range of values: the parent distribution,
+/// conditioned on the roll falling in the range.
+///
+/// The view borrows the parent's occurrence counts rather than copying them,
+/// so analyses of a small part of a huge support only pay for that part.
+/// Operations that produce a new distribution, like [`RandomVariable::convolve`],
+/// copy the range into a distribution of its own.
+#[derive(Debug, Clone)]
+pub struct Restricted<'a> {
+    parent: Cow<'a, Distribution>,
+    /// Indices into the parent's counts, of the least and greatest values in the range
+    /// that occur.
+    first: usize,
+    last: usize,
+    /// The occurrences of all values in the range.
+    total: usize,
+}
+
+impl Distribution {
+    /// A view of this distribution truncated to the range:
+    /// the probability of each value is its probability given that the roll is in the range.
+    ///
+    /// Returns None if no value in the range occurs.
+    pub fn restrict_to(&self, range: impl RangeBounds<i64>) -> Option<Restricted<'_>> {
+        Restricted::new(Cow::Borrowed(self), range)
+    }
+}
+
+impl<'a> Restricted<'a> {
+    fn new(parent: Cow<'a, Distribution>, range: impl RangeBounds<i64>) -> Option<Self> {
+        let lo = match range.start_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v.checked_add(1)?,
+            Bound::Unbounded => i64::MIN,
+        };
+        let hi = match range.end_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v.checked_sub(1)?,
+            Bound::Unbounded => i64::MAX,
+        };
+        let (lo, hi) = (lo.max(parent.min()), hi.min(parent.max()));
+        if lo > hi {
+            return None;
+        }
+        let index = |v: i64| (v - parent.offset) as usize;
+        let counts = &parent.occurrence_by_value[index(lo)..=index(hi)];
+        let first = index(lo) + counts.iter().position(|o| *o != 0)?;
+        let last = index(lo) + counts.iter().rposition(|o| *o != 0)?;
+        let total = counts.iter().sum();
+        Some(Restricted {
+            parent,
+            first,
+            last,
+            total,
+        })
+    }
+
+    /// The occurrence counts of the range, from the least value that occurs to the greatest.
+    fn counts(&self) -> &[usize] {
+        &self.parent.occurrence_by_value[self.first..=self.last]
+    }
+
+    /// The least value in the range that occurs.
+    pub fn min(&self) -> i64 {
+        self.parent.offset + self.first as i64
+    }
+
+    /// The greatest value in the range that occurs.
+    pub fn max(&self) -> i64 {
+        self.parent.offset + self.last as i64
+    }
+
+    /// The number of possible rolls of the parent that fall in the range.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Iterator over the values in the range that occur, and their occurrences.
+    pub fn occurrences(&self) -> impl Iterator<Item = (i64, usize)> + '_ {
+        let min = self.min();
+        self.counts()
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| **o != 0)
+            .map(move |(i, o)| (min + i as i64, *o))
+    }
+
+    /// The average value in the range.
+    pub fn mean(&self) -> f64 {
+        let total = self.total as f64;
+        self.occurrences()
+            .map(|(v, o)| v as f64 * (o as f64 / total))
+            .sum()
+    }
+
+    /// Copy the range into a distribution of its own.
+    pub fn to_distribution(&self) -> Distribution {
+        Distribution::from_occurrences(self.occurrences()).expect("some value in the range occurs")
+    }
+
+    /// A view of a whole distribution that this view owns.
+    fn owned(d: Distribution) -> Restricted<'static> {
+        Restricted::new(Cow::Owned(d), ..).expect("distributions have at least one value")
+    }
+}
+
+impl RandomVariable for Restricted<'_> {
+    type Value = i64;
+
+    fn support(&self) -> impl Iterator<Item = i64> + '_ {
+        self.occurrences().map(|(v, _)| v)
+    }
+
+    fn probability(&self, value: i64) -> Ratio<usize> {
+        if value < self.min() || value > self.max() {
+            return Ratio::new(0, 1);
+        }
+        match self.counts()[(value - self.min()) as usize] {
+            0 => Ratio::new(0, 1),
+            n => Ratio::new(n, self.total),
+        }
+    }
+
+    /// The result owns its distribution, rather than borrowing either parent.
+    fn convolve(&self, other: &Self) -> Self {
+        Restricted::owned(&self.to_distribution() + &other.to_distribution())
+    }
+
+    /// The result owns its distribution, rather than borrowing the parent.
+    fn map(&self, f: impl FnMut(i64) -> i64) -> Self {
+        Restricted::owned(self.to_distribution().map_values(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution_of(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn truncated() {
+        let d = distribution_of("2d6");
+        let r = d.restrict_to(10..).unwrap();
+        assert_eq!((r.min(), r.max(), r.total()), (10, 12, 6));
+        assert_eq!(r.probability(12), Ratio::new(1, 6));
+        assert_eq!(r.probability(9), Ratio::new(0, 1));
+        assert_eq!(r.probability_where(|v| v >= 11), Ratio::new(3, 6));
+        assert!((r.mean() - d.mean_given(|v| v >= 10).unwrap()).abs() < 1e-12);
+
+        let r = d.restrict_to(..=3).unwrap();
+        assert_eq!(r.support().collect::<Vec<_>>(), vec![2, 3]);
+        let r = d.restrict_to(-5..7).unwrap();
+        assert_eq!(r.total(), 15);
+        assert_eq!(r.to_distribution().probability(6), Ratio::new(5, 15));
+    }
+
+    #[test]
+    fn skips_gaps() {
+        let d = distribution_of("d{1,5,9}");
+        let r = d.restrict_to(2..=8).unwrap();
+        assert_eq!((r.min(), r.max()), (5, 5));
+        assert!(d.restrict_to(2..5).is_none());
+        assert!(d.restrict_to(10..).is_none());
+        assert!(d.restrict_to(i64::MAX..).is_none());
+    }
+
+    #[test]
+    fn convolve_and_map() {
+        let d = distribution_of("d6");
+        let low = d.restrict_to(..=2).unwrap();
+        let high = d.restrict_to(5..).unwrap();
+        let sum = low.convolve(&high);
+        assert_eq!(sum.to_distribution(), distribution_of("d2 + d[5..6]"));
+        assert_eq!(sum.probability(6), Ratio::new(1, 4));
+        let doubled = high.map(|v| v * 2);
+        assert_eq!(doubled.support().collect::<Vec<_>>(), vec![10, 12]);
+    }
+}
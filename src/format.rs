@@ -0,0 +1,160 @@
+//! Text formatting for values of dice expressions.
+
+use crate::sample::RolledDie;
+
+/// How to present the values of a distribution.
+///
+/// Treasure and other large-number rolls are often written as a small roll times a scale,
+/// e.g. "3d6 × 10 gp". Evaluating `3d6` and presenting it with a scale of 10 keeps the
+/// distribution small while still displaying the values players expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueFormat {
+    /// Multiply each value by this before presenting it.
+    pub scale: i64,
+    /// Group digits by thousands with commas, e.g. `12,345`.
+    pub thousands_separator: bool,
+    /// A unit to present after each value, e.g. `gp`.
+    pub unit: Option<String>,
+}
+
+impl Default for ValueFormat {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            thousands_separator: false,
+            unit: None,
+        }
+    }
+}
+
+impl ValueFormat {
+    /// A format for amounts of currency (or any other unit): scaled, grouped by thousands,
+    /// and followed by the unit.
+    pub fn currency(scale: i64, unit: impl Into<String>) -> Self {
+        Self {
+            scale,
+            thousands_separator: true,
+            unit: Some(unit.into()),
+        }
+    }
+
+    /// Present the value in this format.
+    pub fn format(&self, value: i64) -> String {
+        // Widen, so scaling cannot overflow.
+        let value = value as i128 * self.scale as i128;
+        let digits = value.unsigned_abs().to_string();
+        let mut s = String::new();
+        if value < 0 {
+            s.push('-');
+        }
+        if self.thousands_separator {
+            for (i, c) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                    s.push(',');
+                }
+                s.push(c);
+            }
+        } else {
+            s.push_str(&digits);
+        }
+        if let Some(unit) = &self.unit {
+            s.push(' ');
+            s.push_str(unit);
+        }
+        s
+    }
+}
+
+/// How to mark dice that were rolled, but don't count toward the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DroppedStyle {
+    /// Struck through, as Markdown: `~~⚀~~`. Discord and most Markdown chat clients render this.
+    #[default]
+    Strikethrough,
+    /// In parentheses: `(⚀)`, for clients without Markdown.
+    Parentheses,
+}
+
+/// Present rolled dice for chat: d6s as the Unicode die faces ⚀ through ⚅,
+/// other dice as their numbers, with dropped dice marked in the given style.
+pub fn emoji_dice(dice: &[RolledDie], dropped: DroppedStyle) -> String {
+    dice.iter()
+        .map(|die| {
+            let face = match (die.sides, die.face) {
+                (6, face @ 1..=6) => char::from_u32(0x267f + face as u32)
+                    .expect("die faces are characters")
+                    .to_string(),
+                (_, face) => face.to_string(),
+            };
+            match (die.kept, dropped) {
+                (true, _) => face,
+                (false, DroppedStyle::Strikethrough) => format!("~~{face}~~"),
+                (false, DroppedStyle::Parentheses) => format!("({face})"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain() {
+        let f = ValueFormat::default();
+        assert_eq!(f.format(12345), "12345");
+        assert_eq!(f.format(-7), "-7");
+    }
+
+    #[test]
+    fn thousands() {
+        let f = ValueFormat {
+            thousands_separator: true,
+            ..Default::default()
+        };
+        for (v, want) in [
+            (0, "0"),
+            (999, "999"),
+            (1000, "1,000"),
+            (-1234, "-1,234"),
+            (12345678, "12,345,678"),
+            (i64::MIN, "-9,223,372,036,854,775,808"),
+        ] {
+            assert_eq!(f.format(v), want);
+        }
+    }
+
+    #[test]
+    fn emoji() {
+        let die = |sides, face, kept| RolledDie { sides, face, kept };
+        let dice = [
+            die(6, 1, true),
+            die(6, 6, true),
+            die(6, 3, false),
+            die(20, 17, true),
+            die(8, 2, false),
+        ];
+        assert_eq!(
+            emoji_dice(&dice, DroppedStyle::Strikethrough),
+            "⚀ ⚅ ~~⚂~~ 17 ~~2~~"
+        );
+        assert_eq!(
+            emoji_dice(&dice, DroppedStyle::Parentheses),
+            "⚀ ⚅ (⚂) 17 (2)"
+        );
+        assert_eq!(emoji_dice(&[], DroppedStyle::default()), "");
+    }
+
+    #[test]
+    fn currency() {
+        let f = ValueFormat::currency(10, "gp");
+        assert_eq!(f.format(3), "30 gp");
+        assert_eq!(f.format(1800), "18,000 gp");
+        // Scaling widens rather than overflowing.
+        assert_eq!(
+            ValueFormat::currency(10, "cp").format(i64::MAX),
+            "92,233,720,368,547,758,070 cp"
+        );
+    }
+}
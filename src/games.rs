@@ -0,0 +1,16 @@
+//! Presets for the mechanics of specific games.
+
+pub mod call_of_cthulhu;
+pub mod cortex;
+pub mod dnd;
+pub mod genesys;
+pub mod gurps;
+pub mod hit_location;
+pub mod ironsworn;
+pub mod one_roll_engine;
+pub mod savage_worlds;
+pub mod shadowrun;
+pub mod thirteenth_age;
+pub mod treasure;
+pub mod wounds;
+pub mod year_zero;
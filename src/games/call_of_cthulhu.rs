@@ -0,0 +1,154 @@
+//! Call of Cthulhu (7th edition) skill checks.
+//!
+//! A check is a d100 roll under the skill, read from a tens die (00 to 90) and a units die
+//! (0 to 9); 00 and 0 together read as 100. Rolling at or under half the skill is a hard
+//! success, and at or under a fifth of it an extreme success. 01 is a critical success,
+//! and 100 a fumble, as is any roll of 96 or more for a skill under 50.
+//!
+//! Bonus and penalty dice are extra tens dice: the roll uses the tens die that gives the
+//! best result (for bonus dice) or the worst (for penalty dice).
+
+use num::rational::Ratio;
+
+use crate::Distribution;
+
+/// The most bonus or penalty dice a check can have.
+pub const MAX_EXTRA_DICE: i64 = 2;
+
+/// The result of a check, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    Fumble,
+    Failure,
+    Regular,
+    Hard,
+    Extreme,
+    Critical,
+}
+
+impl Outcome {
+    /// All outcomes, from worst to best.
+    pub const ALL: [Outcome; 6] = [
+        Outcome::Fumble,
+        Outcome::Failure,
+        Outcome::Regular,
+        Outcome::Hard,
+        Outcome::Extreme,
+        Outcome::Critical,
+    ];
+}
+
+/// The outcome of a d100 roll (1 to 100) against the skill.
+pub fn outcome(skill: i64, roll: i64) -> Outcome {
+    let fumble = if skill < 50 { 96 } else { 100 };
+    match roll {
+        1 => Outcome::Critical,
+        r if r >= fumble => Outcome::Fumble,
+        r if r <= skill / 5 => Outcome::Extreme,
+        r if r <= skill / 2 => Outcome::Hard,
+        r if r <= skill => Outcome::Regular,
+        _ => Outcome::Failure,
+    }
+}
+
+/// The distribution of a d100 roll with bonus dice (if `extra` is positive)
+/// or penalty dice (if negative).
+///
+/// Panics if there are more than [`MAX_EXTRA_DICE`] bonus or penalty dice.
+pub fn roll(extra: i64) -> Distribution {
+    assert!(
+        extra.abs() <= MAX_EXTRA_DICE,
+        "{extra} is more than {MAX_EXTRA_DICE} bonus or penalty dice"
+    );
+    let digit = Distribution::from_faces(0..10).expect("a d10 has faces");
+    // Roll the units die once and the tens die for each bonus or penalty die,
+    // keeping the lowest (best) result for bonus dice, or the highest for penalty dice.
+    digit
+        .checked_reroll_part(
+            &digit,
+            extra.unsigned_abs() as u32 + 1,
+            extra < 0,
+            |units, tens| {
+                Some(match tens * 10 + units {
+                    0 => 100,
+                    r => r,
+                })
+            },
+        )
+        .expect("d100 with bonus or penalty dice doesn't overflow")
+}
+
+/// The probability of each outcome of a check against the skill, from worst to best,
+/// with bonus dice (if `extra` is positive) or penalty dice (if negative).
+///
+/// Panics if there are more than [`MAX_EXTRA_DICE`] bonus or penalty dice.
+pub fn check(skill: i64, extra: i64) -> Vec<(Outcome, Ratio<usize>)> {
+    let outcomes = roll(extra).map_values(|r| outcome(skill, r) as i64);
+    Outcome::ALL
+        .iter()
+        .map(|o| (*o, outcomes.probability(*o as i64)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn probability(outcomes: &[(Outcome, Ratio<usize>)], outcome: Outcome) -> Ratio<usize> {
+        outcomes.iter().find(|(o, _)| *o == outcome).unwrap().1
+    }
+
+    #[test]
+    fn plain_roll() {
+        let d = roll(0);
+        assert_eq!(d, "d100".parse::<Closed>().unwrap().distribution().unwrap());
+    }
+
+    #[test]
+    fn categories() {
+        let c = check(60, 0);
+        assert_eq!(probability(&c, Outcome::Critical), Ratio::new(1, 100));
+        // 2 through 12.
+        assert_eq!(probability(&c, Outcome::Extreme), Ratio::new(11, 100));
+        // 13 through 30.
+        assert_eq!(probability(&c, Outcome::Hard), Ratio::new(18, 100));
+        assert_eq!(probability(&c, Outcome::Regular), Ratio::new(30, 100));
+        assert_eq!(probability(&c, Outcome::Failure), Ratio::new(39, 100));
+        assert_eq!(probability(&c, Outcome::Fumble), Ratio::new(1, 100));
+        let total: Ratio<usize> = c.iter().map(|(_, p)| p).sum();
+        assert_eq!(total, Ratio::new(1, 1));
+
+        // Low skills fumble on 96 or more.
+        let c = check(40, 0);
+        assert_eq!(probability(&c, Outcome::Fumble), Ratio::new(5, 100));
+        assert_eq!(outcome(49, 96), Outcome::Fumble);
+        assert_eq!(outcome(50, 96), Outcome::Failure);
+        assert_eq!(outcome(100, 100), Outcome::Fumble);
+        assert_eq!(outcome(0, 1), Outcome::Critical);
+    }
+
+    #[test]
+    fn bonus_and_penalty() {
+        // A roll of 100 needs every tens die to show 00, with a units 0.
+        let d = roll(1);
+        assert_eq!(d.probability(100), Ratio::new(1, 1000));
+        // 01 needs a units 1, and any tens die showing 00.
+        assert_eq!(d.probability(1), Ratio::new(19, 1000));
+        let d = roll(-1);
+        assert_eq!(d.probability(1), Ratio::new(1, 1000));
+        // With a units 0, any 00 makes 100.
+        assert_eq!(d.probability(100), Ratio::new(19, 1000));
+
+        let plain = probability(&check(50, 0), Outcome::Failure);
+        let bonus = probability(&check(50, 2), Outcome::Failure);
+        let penalty = probability(&check(50, -2), Outcome::Failure);
+        assert!(bonus < plain && plain < penalty);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_dice() {
+        roll(3);
+    }
+}
@@ -0,0 +1,120 @@
+//! Cortex Prime dice pools.
+//!
+//! A Cortex pool mixes dice of different sizes, like a d8, two d6s, and a d10. Each 1 is a
+//! _hitch_, which counts for nothing. Of the rest, the two highest add up to the total, and the
+//! largest of the dice left over is the _effect die_: its size, not its roll, is what matters.
+//! With no dice left over, the effect die is a d4.
+//!
+//! Keeping the highest dice of a mixed pool isn't something an expression can say, and the
+//! effect die is a second result alongside the total, so a pool's result is a
+//! [`JointDistribution`] of the total, the effect die, and the hitches.
+
+use crate::{Error, JointDistribution, discrete::multisets};
+
+/// The index of the total in a pool's result.
+pub const TOTAL: usize = 0;
+/// The index of the size of the effect die in a pool's result.
+pub const EFFECT: usize = 1;
+/// The index of the number of hitches, 1s, in a pool's result.
+pub const HITCHES: usize = 2;
+
+/// The effect die when no dice are left over for it.
+pub const DEFAULT_EFFECT: i64 = 4;
+
+/// The total, effect die, and hitches of a roll of the pool, which lists each die's size.
+///
+/// The total is as high as it can be; among the ways to reach it, the effect die is as large
+/// as it can be.
+pub fn pool(dice: &[usize]) -> Result<JointDistribution<3>, Error> {
+    let overflow = || Error::Overflow(format!("{dice:?}"));
+    let mut sizes = dice.to_vec();
+    sizes.sort_unstable();
+    // Dice of the same size are interchangeable: roll each size as a multiset of faces.
+    let mut rolls: Vec<(Vec<(i64, i64)>, usize)> = vec![(Vec::new(), 1)];
+    for group in sizes.chunk_by(|a, b| a == b) {
+        let size = group[0];
+        if size == 0 {
+            return Err(Error::ZeroFacedDie());
+        }
+        let mut next = Vec::new();
+        multisets(&vec![1; size], group.len(), overflow, |counts, o| {
+            let faces: Vec<(i64, i64)> = counts
+                .iter()
+                .enumerate()
+                .flat_map(|(face, n)| std::iter::repeat_n((face as i64 + 1, size as i64), *n))
+                .collect();
+            for (roll, occurrences) in &rolls {
+                let mut roll = roll.clone();
+                roll.extend_from_slice(&faces);
+                next.push((roll, occurrences.checked_mul(o).ok_or_else(overflow)?));
+            }
+            Ok(())
+        })?;
+        rolls = next;
+    }
+    let results = rolls.into_iter().map(|(mut roll, occurrences)| {
+        let hitches = roll.iter().filter(|(face, _)| *face == 1).count() as i64;
+        roll.retain(|(face, _)| *face > 1);
+        // Highest faces first, and the smaller die of equal faces, to save the larger for effect.
+        roll.sort_by(|(f1, s1), (f2, s2)| f2.cmp(f1).then(s1.cmp(s2)));
+        let total = roll.iter().take(2).map(|(face, _)| face).sum();
+        let effect = roll
+            .iter()
+            .skip(2)
+            .map(|(_, size)| *size)
+            .max()
+            .unwrap_or(DEFAULT_EFFECT);
+        ([total, effect, hitches], occurrences)
+    });
+    Ok(JointDistribution::from_occurrences(results).expect("pools have rolls"))
+}
+
+#[cfg(test)]
+mod tests {
+    use num::rational::Ratio;
+
+    use super::*;
+    use crate::{Closed, RandomVariable};
+
+    #[test]
+    fn small_pool() {
+        let d = pool(&[6, 8]).unwrap();
+        assert_eq!(d.total(), 48);
+        // Both dice make the total, so the effect die is a d4.
+        assert!(d.occurrences().all(|([_, effect, _], _)| effect == 4));
+        assert_eq!(d.probability(&[0, 4, 2]), Ratio::new(1, 48));
+        // A 1 on the d6 leaves the d8 alone.
+        assert_eq!(d.probability(&[8, 4, 1]), Ratio::new(1, 48));
+    }
+
+    #[test]
+    fn effect_die() {
+        let d = pool(&[4, 6, 10]).unwrap();
+        // A 3, a 3, and a 2: the d4's 3 makes the total, leaving the larger die for effect.
+        assert!(d.probability(&[6, 10, 0]) > Ratio::new(0, 1));
+        assert_eq!(d.marginal(HITCHES).max(), 3);
+        assert_eq!(
+            d.probability_where(|[_, _, hitches]| hitches == 0),
+            Ratio::new(3 * 5 * 9, 4 * 6 * 10)
+        );
+    }
+
+    #[test]
+    fn totals_without_hitches() {
+        // With no 1s possible, the total is just the two highest dice.
+        let d = pool(&[6, 6, 6])
+            .unwrap()
+            .map_tuple(|[total, _, hitches]| [if hitches == 0 { total } else { -1 }]);
+        let kh = "3d6kh2".parse::<Closed>().unwrap().distribution().unwrap();
+        for t in 4..=12 {
+            // Rolls with no 1s are a subset of 3d6kh2's.
+            assert!(d.probability(&[t]) <= kh.probability(t), "{t}");
+        }
+        // Two 6s make 12, unless the third die is a hitch.
+        assert_eq!(
+            d.probability(&[12]),
+            kh.probability(12) - Ratio::new(3, 216)
+        );
+        assert!(matches!(pool(&[6, 0]), Err(Error::ZeroFacedDie())));
+    }
+}
@@ -0,0 +1,330 @@
+//! Dungeons & Dragons (5th edition) saving throws and attacks.
+//!
+//! Many spells and traps deal damage that the target can halve with a successful saving throw:
+//! a d20 plus the target's bonus, against the effect's difficulty class (DC).
+//!
+//! An attack is a d20 plus the attack bonus, against the target's armor class (AC).
+//! Critical hits and misses depend on the natural roll, before the bonus:
+//! a natural 20 always hits, and rolls the damage dice twice; a natural 1 always misses.
+//!
+//! Some damage, like a rogue's Sneak Attack, is added only once per turn, to the first attack
+//! that hits; [`once_per_turn`] totals the damage of several attacks with such a rider.
+
+use crate::{
+    Closed,
+    symbolic::{Constant, ExpressionTree, Ranker},
+};
+
+/// The damage from an effect that deals half damage (truncated toward zero) on a successful save:
+/// `save(DAMAGE, d20 + BONUS >= DC)`.
+///
+/// A fireball against a target with a +3 Dexterity save is
+/// `save_for_half(&"8d6".parse()?, 3, 15)`.
+pub fn save_for_half(damage: &Closed, bonus: i64, dc: i64) -> Closed {
+    format!("save({damage}, d20 + ({bonus}) >= ({dc}))")
+        .parse()
+        .expect("saving throw is a valid expression")
+}
+
+/// The damage from an attack with the given bonus against the armor class,
+/// with a critical hit on a natural 20.
+pub fn attack(bonus: &Closed, ac: i64, damage: &Closed) -> Closed {
+    attack_with_crit_range(bonus, ac, damage, 20)
+}
+
+/// The damage from an attack with the given bonus against the armor class,
+/// with a critical hit on a natural roll of `crit` or more: 19 for a Champion's improved critical.
+pub fn attack_with_crit_range(bonus: &Closed, ac: i64, damage: &Closed, crit: i64) -> Closed {
+    attack_with(bonus, ac, damage, &Critical::default().with_range(crit))
+}
+
+/// When an attack is a critical hit, and what extra damage it deals then.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Critical {
+    /// The lowest natural roll that's a critical hit.
+    pub range: i64,
+    /// Extra dice rolled only on a critical hit, on top of doubling the damage dice.
+    pub extra: Vec<Closed>,
+}
+
+impl Default for Critical {
+    /// A critical hit on a natural 20, with no extra dice.
+    fn default() -> Self {
+        Critical {
+            range: 20,
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl Critical {
+    /// Critical hits on a natural roll of `range` or more: 19 for a Champion's improved
+    /// critical, and 18 for their superior critical.
+    pub fn with_range(mut self, range: i64) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Extra dice on a critical hit: one weapon die for a half-orc's Savage Attacks, or one
+    /// to three for a barbarian's Brutal Critical. The extras add up if there are several.
+    pub fn with_extra(mut self, dice: Closed) -> Self {
+        self.extra.push(dice);
+        self
+    }
+}
+
+/// The damage from an attack with the given bonus against the armor class,
+/// with critical hits as given.
+pub fn attack_with(bonus: &Closed, ac: i64, damage: &Closed, crit: &Critical) -> Closed {
+    let range = crit.range;
+    let critical = critical_damage(damage, crit);
+    format!(
+        "[NATURAL: d20] if NATURAL >= ({range}) then {critical} \
+         else if (NATURAL > 1) * (NATURAL + ({bonus}) >= ({ac})) then {damage} else 0"
+    )
+    .parse()
+    .expect("attack is a valid expression")
+}
+
+/// One attack of several in a turn; see [`once_per_turn`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Attack {
+    /// The attack bonus, added to the d20.
+    pub bonus: Closed,
+    /// The target's armor class.
+    pub ac: i64,
+    /// The damage on a hit.
+    pub damage: Closed,
+    /// When the attack is a critical hit, and its extra damage then.
+    pub crit: Critical,
+}
+
+impl Attack {
+    /// An attack with the given bonus against the armor class, with a critical hit on a natural 20.
+    pub fn new(bonus: Closed, ac: i64, damage: Closed) -> Self {
+        Attack {
+            bonus,
+            ac,
+            damage,
+            crit: Critical::default(),
+        }
+    }
+
+    /// Critical hits as given, rather than only on a natural 20.
+    pub fn with_crit(mut self, crit: Critical) -> Self {
+        self.crit = crit;
+        self
+    }
+
+    /// 0 if the attack misses, 1 if it hits, and 2 if it's a critical hit.
+    fn outcome(&self) -> String {
+        let Attack {
+            bonus, ac, crit, ..
+        } = self;
+        format!(
+            "[NATURAL: d20] if NATURAL >= ({}) then 2 \
+             else (NATURAL > 1) * (NATURAL + ({bonus}) >= ({ac}))",
+            crit.range
+        )
+    }
+}
+
+/// The total damage of several attacks in a turn, with extra `rider` damage on the first
+/// of them that hits, like a rogue's Sneak Attack. If that hit is a critical hit, so is the rider:
+/// its dice are rolled twice.
+///
+/// Panics if there are more than 26 attacks.
+pub fn once_per_turn(attacks: &[Attack], rider: &Closed) -> Closed {
+    assert!(attacks.len() <= 26, "too many attacks: {}", attacks.len());
+    // Each attack's outcome is bound to a symbol, HITA, HITB, and so on, so that its damage
+    // and the rider both see the same roll.
+    let symbols: Vec<String> = (b'A'..)
+        .take(attacks.len())
+        .map(|c| format!("HIT{}", c as char))
+        .collect();
+    let mut terms: Vec<String> = attacks
+        .iter()
+        .zip(&symbols)
+        .map(|(attack, hit)| {
+            let critical = critical_damage(&attack.damage, &attack.crit);
+            format!(
+                "(if {hit} = 2 then {critical} else if {hit} = 1 then {} else 0)",
+                attack.damage
+            )
+        })
+        .collect();
+    let critical_rider = critical(rider);
+    let rider = symbols.iter().rev().fold("0".to_owned(), |otherwise, hit| {
+        format!(
+            "if {hit} = 2 then {critical_rider} else if {hit} = 1 then {rider} else {otherwise}"
+        )
+    });
+    terms.push(format!("({rider})"));
+    let bindings: String = attacks
+        .iter()
+        .zip(&symbols)
+        .map(|(attack, hit)| format!("[{hit}: {}] ", attack.outcome()))
+        .collect();
+    format!("{bindings}{}", terms.join(" + "))
+        .parse()
+        .expect("attacks are a valid expression")
+}
+
+/// The damage of a critical hit with the damage and critical, including its extra dice.
+fn critical_damage(damage: &Closed, crit: &Critical) -> String {
+    std::iter::once(critical(damage).to_string())
+        .chain(crit.extra.iter().map(|extra| format!("({extra})")))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// The damage of a critical hit: each of the damage dice is rolled twice, but modifiers
+/// are only added once. A critical hit with `2d6 + 3` deals `4d6 + 3`.
+pub fn critical(damage: &Closed) -> Closed {
+    damage.rewrite(&mut |node| match node {
+        ExpressionTree::Die(_) | ExpressionTree::CustomDie(_) => ExpressionTree::Repeated {
+            count: Box::new(Closed::new(ExpressionTree::Modifier(Constant(2)))),
+            value: Box::new(Closed::new(node)),
+            ranker: Ranker::All,
+        },
+        node => node,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use num::rational::Ratio;
+
+    use super::*;
+
+    #[test]
+    fn fireball() {
+        let damage: Closed = "8d6".parse().unwrap();
+        let d = save_for_half(&damage, 3, 15).distribution().unwrap();
+        // Only a failed save takes the full 48.
+        assert_eq!(d.max(), 48);
+        assert_eq!(d.probability(48), Ratio::new(11, 20 * 6usize.pow(8)));
+        // A successful save can take as little as 4.
+        assert_eq!(d.min(), 4);
+        let mean = d.mean();
+        assert!(mean > 28.0 * 0.5 && mean < 28.0, "{mean}");
+    }
+
+    #[test]
+    fn critical_dice() {
+        let damage: Closed = "2d6 + d4 + 3".parse().unwrap();
+        let d = critical(&damage).distribution().unwrap();
+        assert_eq!(d.min(), 4 + 2 + 3);
+        assert_eq!(d.max(), 24 + 8 + 3);
+        assert!((d.mean() - (14.0 + 5.0 + 3.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn attacks() {
+        let damage: Closed = "d8 + 3".parse().unwrap();
+        let bonus: Closed = "5".parse().unwrap();
+        // Hits on 10 through 19, and crits on 20 for 2d8 + 3.
+        let d = attack(&bonus, 15, &damage).distribution().unwrap();
+        assert_eq!(d.probability(0), Ratio::new(9, 20));
+        assert_eq!(d.max(), 19);
+        assert!((d.mean() - (10.0 * 7.5 + 12.0) / 20.0).abs() < 1e-9);
+
+        // The natural roll decides a critical hit, even when the bonus can't reach the AC...
+        let d = attack(&"-10".parse().unwrap(), 30, &"d6".parse().unwrap())
+            .distribution()
+            .unwrap();
+        assert_eq!(d.probability(0), Ratio::new(19, 20));
+        assert!((d.mean() - 7.0 / 20.0).abs() < 1e-9);
+        // ... and a critical miss, even when the total would hit.
+        let d = attack(&"30".parse().unwrap(), 10, &"4".parse().unwrap())
+            .distribution()
+            .unwrap();
+        assert_eq!(d.probability(0), Ratio::new(1, 20));
+
+        // The bonus may be rolled, as with bless: a natural 16 hits on a 4,
+        // and a 19 always hits, but only a natural 20 crits.
+        let blessed: Closed = "5 + d4".parse().unwrap();
+        let d = attack(&blessed, 25, &damage).distribution().unwrap();
+        assert_eq!(d.probability(0), Ratio::new(80 - 4 - (1 + 2 + 3 + 4), 80));
+        assert_eq!(d.max(), 19);
+
+        // A Champion crits on 19 or 20.
+        let d = attack_with_crit_range(&bonus, 15, &damage, 19)
+            .distribution()
+            .unwrap();
+        assert!((d.mean() - (9.0 * 7.5 + 2.0 * 12.0) / 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn extra_critical_dice() {
+        let bonus: Closed = "7".parse().unwrap();
+        let damage: Closed = "d12 + 4".parse().unwrap();
+        // A half-orc barbarian with Brutal Critical and Savage Attacks: 2d12 + 4 on a crit,
+        // plus a d12 for each.
+        let crit = Critical::default()
+            .with_extra("d12".parse().unwrap())
+            .with_extra("d12".parse().unwrap());
+        let d = attack_with(&bonus, 16, &damage, &crit)
+            .distribution()
+            .unwrap();
+        assert_eq!(d.max(), 4 * 12 + 4);
+        assert_eq!(d.probability(52), Ratio::new(1, 20 * 12usize.pow(4)));
+        // Hits on 9 through 19 for 10.5, and crits on 20 for 30.
+        assert!((d.mean() - (11.0 * 10.5 + 30.0) / 20.0).abs() < 1e-9);
+
+        // A Champion's superior critical, at 18 through 20.
+        let crit = Critical::default().with_range(18);
+        let d = attack_with(&bonus, 16, &damage, &crit)
+            .distribution()
+            .unwrap();
+        assert!((d.mean() - (9.0 * 10.5 + 3.0 * 17.0) / 20.0).abs() < 1e-9);
+        assert_eq!(
+            d,
+            attack_with_crit_range(&bonus, 16, &damage, 18)
+                .distribution()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sneak_attack() {
+        let bonus: Closed = "5".parse().unwrap();
+        let damage: Closed = "d6 + 3".parse().unwrap();
+        let rider: Closed = "2d6".parse().unwrap();
+        let attack = Attack::new(bonus.clone(), 15, damage.clone());
+        // With one attack, the rider is just more damage on a hit.
+        let one = once_per_turn(std::slice::from_ref(&attack), &rider)
+            .distribution()
+            .unwrap();
+        assert_eq!(
+            one,
+            attack_with(&bonus, 15, &"d6 + 3 + 2d6".parse().unwrap(), &attack.crit)
+                .distribution()
+                .unwrap()
+        );
+
+        let two = once_per_turn(&[attack.clone(), attack.clone()], &rider)
+            .distribution()
+            .unwrap();
+        // Each attack hits 11 times in 20, one of them critically. The rider lands unless both
+        // miss, and is doubled if the first hit is a critical hit.
+        let each = 10.0 / 20.0 * 6.5 + 1.0 / 20.0 * 10.0;
+        let rider_mean = (1.0 - 9.0 / 20.0 * 9.0 / 20.0) * 7.0 + (1.0 / 20.0 + 9.0 / 400.0) * 7.0;
+        assert!((two.mean() - (2.0 * each + rider_mean)).abs() < 1e-9);
+        assert_eq!(two.probability(0), Ratio::new(81, 400));
+        // Two critical hits, 2d6 + 3 each, and one critical rider, 4d6.
+        assert_eq!(two.max(), 15 + 15 + 24);
+    }
+
+    #[test]
+    fn negative_bonus() {
+        let damage: Closed = "2d6".parse().unwrap();
+        // Saves only on a natural 20.
+        let d = save_for_half(&damage, -5, 15).distribution().unwrap();
+        assert_eq!(d.probability(12), Ratio::new(19, 20 * 36));
+        assert_eq!(
+            d.probability(6),
+            Ratio::new(19 * 5, 20 * 36) + Ratio::new(1, 20 * 36)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Genesys (and Fantasy Flight's Star Wars) narrative dice.
+//!
+//! Instead of numbers, the faces of narrative dice show symbols. Positive dice show successes
+//! and advantages; negative dice show failures and threats. A pool is rolled together, and then
+//! each failure cancels a success, and each threat an advantage: a check passes if any
+//! successes are left. A triumph counts as a success that also has a special effect,
+//! and a despair as a failure; the effects aren't cancelled.
+//!
+//! A roll of a pool is a [`JointDistribution`] of the count of each symbol, in the order of
+//! [`SUCCESS`], [`FAILURE`], [`ADVANTAGE`], [`THREAT`], [`TRIUMPH`], and [`DESPAIR`].
+//! Its result after cancellation is a distribution of the [`NET_SUCCESSES`],
+//! [`NET_ADVANTAGES`], [`TRIUMPHS`], and [`DESPAIRS`].
+
+use num::rational::Ratio;
+
+use crate::{Error, JointDistribution, RandomVariable};
+
+/// The index of successes in a roll of symbols.
+pub const SUCCESS: usize = 0;
+/// The index of failures in a roll of symbols.
+pub const FAILURE: usize = 1;
+/// The index of advantages in a roll of symbols.
+pub const ADVANTAGE: usize = 2;
+/// The index of threats in a roll of symbols.
+pub const THREAT: usize = 3;
+/// The index of triumphs in a roll of symbols. Each triumph is counted as a success, too.
+pub const TRIUMPH: usize = 4;
+/// The index of despairs in a roll of symbols. Each despair is counted as a failure, too.
+pub const DESPAIR: usize = 5;
+
+/// The index of successes less failures in the result of a roll: negative when failures remain.
+pub const NET_SUCCESSES: usize = 0;
+/// The index of advantages less threats in the result of a roll: negative when threats remain.
+pub const NET_ADVANTAGES: usize = 1;
+/// The index of triumphs in the result of a roll.
+pub const TRIUMPHS: usize = 2;
+/// The index of despairs in the result of a roll.
+pub const DESPAIRS: usize = 3;
+
+/// The symbols on one face of a die: how many of each.
+type Face = [i64; 6];
+
+const BLANK: Face = [0, 0, 0, 0, 0, 0];
+const S: Face = [1, 0, 0, 0, 0, 0];
+const F: Face = [0, 1, 0, 0, 0, 0];
+const A: Face = [0, 0, 1, 0, 0, 0];
+const T: Face = [0, 0, 0, 1, 0, 0];
+const SS: Face = [2, 0, 0, 0, 0, 0];
+const FF: Face = [0, 2, 0, 0, 0, 0];
+const AA: Face = [0, 0, 2, 0, 0, 0];
+const TT: Face = [0, 0, 0, 2, 0, 0];
+const SA: Face = [1, 0, 1, 0, 0, 0];
+const FT: Face = [0, 1, 0, 1, 0, 0];
+const TRIUMPH_FACE: Face = [1, 0, 0, 0, 1, 0];
+const DESPAIR_FACE: Face = [0, 1, 0, 0, 0, 1];
+
+/// A kind of narrative die.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Die {
+    /// A d6 added for a situational benefit.
+    Boost,
+    /// A d6 added for a situational hindrance.
+    Setback,
+    /// A d8 for the character's skill or characteristic.
+    Ability,
+    /// A d8 for the difficulty of the check.
+    Difficulty,
+    /// A d12 for a skill the character is trained in; an upgraded ability die.
+    Proficiency,
+    /// A d12 for an especially hard check; an upgraded difficulty die.
+    Challenge,
+}
+
+impl Die {
+    /// The faces of the die.
+    pub fn faces(&self) -> &'static [Face] {
+        match self {
+            Die::Boost => &[BLANK, BLANK, S, SA, AA, A],
+            Die::Setback => &[BLANK, BLANK, F, F, T, T],
+            Die::Ability => &[BLANK, S, S, SS, A, A, SA, AA],
+            Die::Difficulty => &[BLANK, F, FF, T, T, T, TT, FT],
+            Die::Proficiency => &[BLANK, S, S, SS, SS, A, SA, SA, SA, AA, AA, TRIUMPH_FACE],
+            Die::Challenge => &[BLANK, F, F, FF, FF, T, T, FT, FT, TT, TT, DESPAIR_FACE],
+        }
+    }
+
+    /// The symbols rolled on the die.
+    pub fn symbols(&self) -> JointDistribution<6> {
+        JointDistribution::from_faces(self.faces()).expect("dice have faces")
+    }
+}
+
+/// A pool of narrative dice: how many of each kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Pool {
+    pub boost: usize,
+    pub setback: usize,
+    pub ability: usize,
+    pub difficulty: usize,
+    pub proficiency: usize,
+    pub challenge: usize,
+}
+
+impl Pool {
+    fn counts(&self) -> [(Die, usize); 6] {
+        [
+            (Die::Proficiency, self.proficiency),
+            (Die::Ability, self.ability),
+            (Die::Boost, self.boost),
+            (Die::Challenge, self.challenge),
+            (Die::Difficulty, self.difficulty),
+            (Die::Setback, self.setback),
+        ]
+    }
+
+    /// The symbols rolled on all the dice in the pool, before any cancel.
+    pub fn symbols(&self) -> Result<JointDistribution<6>, Error> {
+        self.counts()
+            .iter()
+            .try_fold(JointDistribution::constant(BLANK), |acc, (die, n)| {
+                die.symbols()
+                    .checked_repeat(*n)
+                    .and_then(|d| acc.checked_convolve(&d))
+            })
+            .ok_or_else(|| Error::Overflow(self.to_string()))
+    }
+
+    /// The result of rolling the pool: the net successes, net advantages, triumphs,
+    /// and despairs, once failures cancel successes and threats cancel advantages.
+    pub fn result(&self) -> Result<JointDistribution<4>, Error> {
+        Ok(self.symbols()?.map_tuple(|s| {
+            [
+                s[SUCCESS] - s[FAILURE],
+                s[ADVANTAGE] - s[THREAT],
+                s[TRIUMPH],
+                s[DESPAIR],
+            ]
+        }))
+    }
+
+    /// The probability that a check with the pool passes: at least one success is left.
+    pub fn success(&self) -> Result<Ratio<usize>, Error> {
+        Ok(self.result()?.probability_where(|r| r[NET_SUCCESSES] > 0))
+    }
+}
+
+/// The dice in the pool, e.g. "1 proficiency, 2 ability, 2 difficulty".
+impl std::fmt::Display for Pool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dice: Vec<String> = self
+            .counts()
+            .iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(die, n)| format!("{n} {}", format!("{die:?}").to_lowercase()))
+            .collect();
+        if dice.is_empty() {
+            write!(f, "no dice")
+        } else {
+            write!(f, "{}", dice.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_dice() {
+        // Half the faces of an ability die show a success.
+        let pool = Pool {
+            ability: 1,
+            ..Default::default()
+        };
+        assert_eq!(pool.success().unwrap(), Ratio::new(1, 2));
+        // A difficulty die alone never passes.
+        let pool = Pool {
+            difficulty: 1,
+            ..Default::default()
+        };
+        assert_eq!(pool.success().unwrap(), Ratio::new(0, 1));
+        let result = pool.result().unwrap();
+        assert_eq!(
+            result.marginal(NET_ADVANTAGES).probability(-1),
+            Ratio::new(4, 8)
+        );
+    }
+
+    #[test]
+    fn cancellation() {
+        let pool = Pool {
+            ability: 2,
+            proficiency: 1,
+            difficulty: 2,
+            challenge: 1,
+            ..Default::default()
+        };
+        let result = pool.result().unwrap();
+        assert_eq!(result.total(), 8 * 8 * 8 * 8 * 12 * 12);
+
+        // Roll every combination of faces, and cancel by hand.
+        let dice = [
+            Die::Ability,
+            Die::Ability,
+            Die::Proficiency,
+            Die::Difficulty,
+            Die::Difficulty,
+            Die::Challenge,
+        ];
+        let mut passes = 0;
+        let mut triumph_and_despair = 0;
+        let mut rolls = vec![[0i64; 6]];
+        for die in dice {
+            rolls = rolls
+                .iter()
+                .flat_map(|roll| {
+                    die.faces().iter().map(move |face| {
+                        let mut sum = *roll;
+                        for i in 0..6 {
+                            sum[i] += face[i];
+                        }
+                        sum
+                    })
+                })
+                .collect();
+        }
+        for roll in &rolls {
+            passes += (roll[SUCCESS] > roll[FAILURE]) as usize;
+            triumph_and_despair += (roll[TRIUMPH] > 0 && roll[DESPAIR] > 0) as usize;
+        }
+        assert_eq!(pool.success().unwrap(), Ratio::new(passes, rolls.len()));
+        // Triumphs and despairs don't cancel each other.
+        assert_eq!(
+            result.probability_where(|r| r[TRIUMPHS] > 0 && r[DESPAIRS] > 0),
+            Ratio::new(triumph_and_despair, rolls.len())
+        );
+        assert_eq!(
+            result.probability_where(|r| r[TRIUMPHS] > 0 && r[DESPAIRS] > 0),
+            Ratio::new(1, 144)
+        );
+    }
+
+    #[test]
+    fn upgrades_help() {
+        let base = Pool {
+            ability: 2,
+            difficulty: 2,
+            ..Default::default()
+        };
+        let upgraded = Pool {
+            ability: 1,
+            proficiency: 1,
+            ..base
+        };
+        let boosted = Pool { boost: 1, ..base };
+        let hindered = Pool { setback: 1, ..base };
+        let p = base.success().unwrap();
+        assert!(upgraded.success().unwrap() > p);
+        assert!(boosted.success().unwrap() > p);
+        assert!(hindered.success().unwrap() < p);
+    }
+
+    #[test]
+    fn display() {
+        let pool = Pool {
+            ability: 2,
+            proficiency: 1,
+            difficulty: 2,
+            ..Default::default()
+        };
+        assert_eq!(pool.to_string(), "1 proficiency, 2 ability, 2 difficulty");
+        assert_eq!(Pool::default().to_string(), "no dice");
+        assert_eq!(
+            Pool::default().result().unwrap(),
+            JointDistribution::constant([0; 4])
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! GURPS success rolls.
+//!
+//! A success roll is 3d6, rolled under the effective skill: a roll at or below the skill succeeds,
+//! and the difference is the margin of success (or, negative, the margin of failure).
+//! Some rolls are special regardless of skill: 3 and 4 are always critical successes, and
+//! 17 and 18 always fail. The thresholds for the other critical results depend on the skill.
+
+use num::rational::Ratio;
+
+use crate::{Closed, Distribution};
+
+/// The result of a success roll, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    CriticalFailure,
+    Failure,
+    Success,
+    CriticalSuccess,
+}
+
+/// The outcome of rolling `roll` on 3d6 against the effective skill (Basic Set, p. B348).
+pub fn outcome(skill: i64, roll: i64) -> Outcome {
+    match roll {
+        ..=4 => Outcome::CriticalSuccess,
+        // A 5 is critical at skill 15, and a 6 at skill 16.
+        5 if skill >= 15 => Outcome::CriticalSuccess,
+        6 if skill >= 16 => Outcome::CriticalSuccess,
+        18.. => Outcome::CriticalFailure,
+        17 if skill <= 15 => Outcome::CriticalFailure,
+        17 => Outcome::Failure,
+        // Missing by 10 or more is critical.
+        r if r >= skill.saturating_add(10) => Outcome::CriticalFailure,
+        r if r <= skill => Outcome::Success,
+        _ => Outcome::Failure,
+    }
+}
+
+/// The odds of a success roll against an effective skill.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuccessRoll {
+    /// The probability of succeeding, critically or not.
+    pub success: Ratio<usize>,
+    /// The probability of a critical success.
+    pub critical_success: Ratio<usize>,
+    /// The probability of a critical failure.
+    pub critical_failure: Ratio<usize>,
+    /// The distribution of the margin: the skill minus the roll.
+    /// Negative margins are failures, though not every nonnegative margin is a success.
+    pub margin: Distribution,
+}
+
+/// The odds of a success roll against the effective skill (skill plus modifiers).
+pub fn success_roll(skill: i64) -> SuccessRoll {
+    let roll = "3d6"
+        .parse::<Closed>()
+        .expect("3d6 is a valid expression")
+        .distribution()
+        .expect("3d6 has a distribution");
+    let probability = |wanted: &dyn Fn(Outcome) -> bool| {
+        roll.occurrences()
+            .filter(|(r, _)| wanted(outcome(skill, *r)))
+            .map(|(r, _)| roll.probability(r))
+            .sum()
+    };
+    SuccessRoll {
+        success: probability(&|o| o >= Outcome::Success),
+        critical_success: probability(&|o| o == Outcome::CriticalSuccess),
+        critical_failure: probability(&|o| o == Outcome::CriticalFailure),
+        margin: roll.map_values(|r| skill.saturating_sub(r)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_skill() {
+        let s = success_roll(10);
+        assert_eq!(s.success, Ratio::new(108, 216));
+        // 3 and 4.
+        assert_eq!(s.critical_success, Ratio::new(1 + 3, 216));
+        // 17 and 18.
+        assert_eq!(s.critical_failure, Ratio::new(3 + 1, 216));
+        assert_eq!((s.margin.min(), s.margin.max()), (-8, 7));
+        assert_eq!(s.margin.probability(0), Ratio::new(27, 216));
+    }
+
+    #[test]
+    fn high_skill() {
+        // 17 and 18 still fail, but only 18 is critical.
+        let s = success_roll(18);
+        assert_eq!(s.success, Ratio::new(216 - 4, 216));
+        assert_eq!(s.critical_failure, Ratio::new(1, 216));
+        // 3 through 6.
+        assert_eq!(s.critical_success, Ratio::new(1 + 3 + 6 + 10, 216));
+        assert_eq!(outcome(18, 17), Outcome::Failure);
+        assert_eq!(outcome(15, 17), Outcome::CriticalFailure);
+    }
+
+    #[test]
+    fn low_skill() {
+        // 3 and 4 always succeed, and missing by 10 is critical.
+        let s = success_roll(3);
+        assert_eq!(s.success, Ratio::new(4, 216));
+        assert_eq!(outcome(3, 12), Outcome::Failure);
+        assert_eq!(outcome(3, 13), Outcome::CriticalFailure);
+        let critical: Ratio<usize> = (13..=18).map(|r| s.margin.probability(3 - r)).sum();
+        assert_eq!(s.critical_failure, critical);
+    }
+}
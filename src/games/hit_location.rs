@@ -0,0 +1,209 @@
+//! Hit locations: where an attack strikes, together with the damage it deals.
+//!
+//! Systems like RuneQuest and Mythras roll a hit location alongside damage;
+//! a blow to the head is different from the same blow to an arm, so the two are reported together.
+
+use std::ops::RangeInclusive;
+
+use num::rational::Ratio;
+
+use crate::{Distribution, joint::Distribution2D};
+
+/// A table of hit locations: a roll, and the location struck for each range of its values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HitLocations {
+    roll: Distribution,
+    /// Distinct location names, in table order.
+    names: Vec<String>,
+    /// Range of roll values, and the index into names of the location struck.
+    rows: Vec<(RangeInclusive<i64>, usize)>,
+}
+
+impl HitLocations {
+    /// Create a table from a roll and the location for each range of its values.
+    /// A location may appear in more than one row.
+    ///
+    /// Returns None if some value of the roll has no location.
+    pub fn new<S: Into<String>>(
+        roll: Distribution,
+        locations: impl IntoIterator<Item = (RangeInclusive<i64>, S)>,
+    ) -> Option<Self> {
+        let mut names: Vec<String> = Vec::new();
+        let mut rows = Vec::new();
+        for (range, name) in locations {
+            let name = name.into();
+            let index = match names.iter().position(|n| *n == name) {
+                Some(i) => i,
+                None => {
+                    names.push(name);
+                    names.len() - 1
+                }
+            };
+            rows.push((range, index));
+        }
+        let table = Self { roll, names, rows };
+        if table
+            .roll
+            .occurrences()
+            .all(|(v, _)| table.index(v).is_some())
+        {
+            Some(table)
+        } else {
+            None
+        }
+    }
+
+    /// The humanoid hit location table from RuneQuest, rolled on a d20.
+    pub fn humanoid() -> Self {
+        Self::new(
+            Distribution::uniform(1, 20).expect("nonempty range"),
+            [
+                (1..=4, "right leg"),
+                (5..=8, "left leg"),
+                (9..=11, "abdomen"),
+                (12..=12, "chest"),
+                (13..=15, "right arm"),
+                (16..=18, "left arm"),
+                (19..=20, "head"),
+            ],
+        )
+        .expect("table covers d20")
+    }
+
+    fn index(&self, roll: i64) -> Option<usize> {
+        self.rows
+            .iter()
+            .find(|(range, _)| range.contains(&roll))
+            .map(|(_, i)| *i)
+    }
+
+    /// The location struck for the given roll, if any.
+    pub fn location(&self, roll: i64) -> Option<&str> {
+        self.index(roll).map(|i| self.names[i].as_str())
+    }
+
+    /// Pair the location struck with independently-rolled damage.
+    pub fn with_damage(&self, damage: &Distribution) -> LocatedDamage {
+        let joint = Distribution2D::independent(&self.roll, damage);
+        let joint =
+            Distribution2D::from_occurrences(joint.occurrences().map(|((roll, damage), o)| {
+                let index = self.index(roll).expect("all rolls have a location");
+                ((index as i64, damage), o)
+            }))
+            .expect("nonempty distribution");
+        LocatedDamage {
+            names: self.names.clone(),
+            joint,
+        }
+    }
+}
+
+/// Damage, together with the location it was dealt to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocatedDamage {
+    names: Vec<String>,
+    /// Joint distribution of (index into names, damage).
+    joint: Distribution2D,
+}
+
+impl LocatedDamage {
+    fn index(&self, location: &str) -> Option<i64> {
+        self.names
+            .iter()
+            .position(|n| n == location)
+            .map(|i| i as i64)
+    }
+
+    /// The joint distribution of (location, damage), where locations are numbered in table order.
+    pub fn joint(&self) -> &Distribution2D {
+        &self.joint
+    }
+
+    /// The probability of dealing exactly this damage to this location.
+    pub fn probability(&self, location: &str, damage: i64) -> Ratio<usize> {
+        match self.index(location) {
+            Some(i) => self.joint.probability(i, damage),
+            None => Ratio::new(0, 1),
+        }
+    }
+
+    /// The probability of striking this location.
+    pub fn location_probability(&self, location: &str) -> Ratio<usize> {
+        match self.index(location) {
+            Some(i) => self.joint.first().probability(i),
+            None => Ratio::new(0, 1),
+        }
+    }
+
+    /// The distribution of damage, given that the attack struck this location.
+    pub fn damage_at(&self, location: &str) -> Option<Distribution> {
+        self.joint.second_given_first(self.index(location)?)
+    }
+
+    /// Iterator over (location, damage, probability) outcomes, in table order.
+    pub fn outcomes(&self) -> impl Iterator<Item = (&str, i64, Ratio<usize>)> + '_ {
+        let total = self.joint.total();
+        self.joint.occurrences().map(move |((i, damage), o)| {
+            (
+                self.names[i as usize].as_str(),
+                damage,
+                Ratio::new(o, total),
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for LocatedDamage {
+    /// One line per outcome, e.g. "head: 12 damage (0.4%)".
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (location, damage, p) in self.outcomes() {
+            let percent = *p.numer() as f64 / *p.denom() as f64 * 100.0;
+            writeln!(f, "{location}: {damage} damage ({percent:.1}%)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    #[test]
+    fn humanoid_locations() {
+        let table = HitLocations::humanoid();
+        assert_eq!(table.location(1), Some("right leg"));
+        assert_eq!(table.location(12), Some("chest"));
+        assert_eq!(table.location(20), Some("head"));
+        assert_eq!(table.location(21), None);
+    }
+
+    #[test]
+    fn incomplete_table() {
+        let roll = Distribution::uniform(1, 6).unwrap();
+        assert!(HitLocations::new(roll.clone(), [(1..=5, "body")]).is_none());
+        assert!(HitLocations::new(roll, [(1..=5, "body"), (6..=6, "head")]).is_some());
+    }
+
+    #[test]
+    fn joint_damage() {
+        let damage = "1d8 + 1".parse::<Closed>().unwrap().distribution().unwrap();
+        let located = HitLocations::humanoid().with_damage(&damage);
+        assert_eq!(located.location_probability("head"), Ratio::new(1, 10));
+        assert_eq!(located.probability("head", 9), Ratio::new(1, 80));
+        assert_eq!(located.probability("tail", 9), Ratio::new(0, 1));
+        assert_eq!(located.damage_at("chest"), Some(damage));
+
+        let text = located.to_string();
+        assert!(text.contains("head: 9 damage (1.2%)"), "{text}");
+    }
+
+    #[test]
+    fn repeated_location() {
+        let roll = Distribution::uniform(1, 4).unwrap();
+        let table =
+            HitLocations::new(roll, [(1..=1, "arm"), (2..=3, "body"), (4..=4, "arm")]).unwrap();
+        let located = table.with_damage(&Distribution::uniform(1, 1).unwrap());
+        assert_eq!(located.location_probability("arm"), Ratio::new(1, 2));
+    }
+}
@@ -0,0 +1,75 @@
+//! Ironsworn (and Starforged) action rolls.
+//!
+//! An action roll is a d6 plus a stat and any adds: the _action score_, which counts as no more
+//! than 10. It's compared against two d10 _challenge dice_, separately: beating both is a
+//! strong hit, beating one a weak hit, and beating neither a miss. Ties go to the challenge die.
+//! Since the same action score meets both challenge dice, the two comparisons aren't independent,
+//! and the roll can't be written as a single comparison.
+
+use num::rational::Ratio;
+
+use crate::Closed;
+
+/// The most an action score counts for.
+pub const MAX_ACTION_SCORE: i64 = 10;
+
+/// The odds of an action roll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionRoll {
+    /// The probability of beating both challenge dice.
+    pub strong_hit: Ratio<usize>,
+    /// The probability of beating exactly one challenge die.
+    pub weak_hit: Ratio<usize>,
+    /// The probability of beating neither challenge die.
+    pub miss: Ratio<usize>,
+}
+
+/// An action roll with the given stat plus adds: the number of challenge dice it beats,
+/// 0 for a miss, 1 for a weak hit, and 2 for a strong hit.
+pub fn action_roll(modifier: i64) -> Closed {
+    format!("[ACTION: min(d6 + ({modifier}), {MAX_ACTION_SCORE})] (ACTION > d10) + (ACTION > d10)")
+        .parse()
+        .expect("action roll is a valid expression")
+}
+
+/// The odds of an action roll with the given stat plus adds.
+pub fn odds(modifier: i64) -> ActionRoll {
+    let d = action_roll(modifier)
+        .distribution()
+        .expect("action rolls have distributions");
+    ActionRoll {
+        strong_hit: d.probability(2),
+        weak_hit: d.probability(1),
+        miss: d.probability(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_two() {
+        // With an action score of s, each challenge die is beaten with probability (s - 1) / 10.
+        let beaten = |s: usize| Ratio::new(s - 1, 10);
+        let mut strong = Ratio::new(0, 1);
+        let mut miss = Ratio::new(0, 1);
+        for s in 3..=8 {
+            strong += beaten(s) * beaten(s) / 6;
+            miss += (Ratio::from_integer(1) - beaten(s)).pow(2) / 6;
+        }
+        let o = odds(2);
+        assert_eq!(o.strong_hit, strong);
+        assert_eq!(o.miss, miss);
+        assert_eq!(o.strong_hit + o.weak_hit + o.miss, Ratio::from_integer(1));
+    }
+
+    #[test]
+    fn capped() {
+        // An action score of 10 still loses ties on a 10.
+        let o = odds(9);
+        assert_eq!(o.strong_hit, Ratio::new(81, 100));
+        assert_eq!(odds(12), o);
+        assert_eq!(odds(-10).miss, Ratio::from_integer(1));
+    }
+}
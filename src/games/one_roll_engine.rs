@@ -0,0 +1,91 @@
+//! One-Roll Engine sets.
+//!
+//! The One-Roll Engine (Reign, Wild Talents) rolls a pool of d10s and looks for _sets_ of
+//! matching dice. A set's _width_ is how many dice match, its speed; its _height_ is the face
+//! they show, its quality. A roll of `3x7` is three 7s. [`best_set`] gives the joint distribution
+//! of the width and height of the widest set, rather than just the width that `5d10m` gives.
+
+use crate::{Error, JointDistribution, discrete::multisets};
+
+/// The index of the width of a set.
+pub const WIDTH: usize = 0;
+/// The index of the height of a set.
+pub const HEIGHT: usize = 1;
+
+/// The width and height of a roll with no set: every die different.
+pub const NO_SET: [i64; 2] = [0, 0];
+
+/// The faces of the dice in a pool.
+const FACES: usize = 10;
+
+/// The width and height of the widest set rolled on a pool of `dice` d10s,
+/// the tallest among sets of that width; or [`NO_SET`] if no two dice match.
+pub fn best_set(dice: usize) -> Result<JointDistribution<2>, Error> {
+    let overflow = || Error::Overflow(format!("{dice}d10"));
+    let mut occurrences = Vec::new();
+    multisets(&[1; FACES], dice, overflow, |counts, o| {
+        // Faces are in ascending order, so the last of the widest is the tallest.
+        let best = counts
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| **n >= 2)
+            .max_by_key(|(face, n)| (**n, *face))
+            .map_or(NO_SET, |(face, n)| [*n as i64, face as i64 + 1]);
+        occurrences.push((best, o));
+        Ok(())
+    })?;
+    Ok(JointDistribution::from_occurrences(occurrences).expect("pools have rolls"))
+}
+
+#[cfg(test)]
+mod tests {
+    use num::rational::Ratio;
+
+    use super::*;
+    use crate::{Closed, RandomVariable};
+
+    #[test]
+    fn small_pools() {
+        let two = best_set(2).unwrap();
+        assert_eq!(two.probability(&NO_SET), Ratio::new(9, 10));
+        assert_eq!(two.probability(&[2, 7]), Ratio::new(1, 100));
+
+        let three = best_set(3).unwrap();
+        assert_eq!(three.probability(&NO_SET), Ratio::new(720, 1000));
+        assert_eq!(three.probability(&[3, 1]), Ratio::new(1, 1000));
+        // A pair of 4s, and any of the other nine faces in any of three places.
+        assert_eq!(three.probability(&[2, 4]), Ratio::new(27, 1000));
+    }
+
+    #[test]
+    fn tallest_of_the_widest() {
+        // Two pairs count as the taller pair: 9s and 2s.
+        let four = best_set(4).unwrap();
+        let pairs_of_nines = four.probability(&[2, 9]);
+        let pairs_of_twos = four.probability(&[2, 2]);
+        assert!(pairs_of_nines > pairs_of_twos);
+        // A pair of 2s is the best set alongside two other, different faces, or a pair of 1s.
+        assert_eq!(pairs_of_twos, Ratio::new(6 * (9 * 8 + 1), 10000));
+    }
+
+    #[test]
+    fn width_matches_sets() {
+        // Outside of pools with no set, the width is the size of the largest set.
+        for dice in 2..=6 {
+            let width = best_set(dice)
+                .unwrap()
+                .map_tuple(|[w, _]| [w.max(1)])
+                .marginal(0);
+            let m = format!("{dice}d10m")
+                .parse::<Closed>()
+                .unwrap()
+                .distribution()
+                .unwrap();
+            for w in 1..=dice as i64 {
+                assert_eq!(width.probability(w), m.probability(w), "{dice}: {w}");
+            }
+        }
+        assert!(best_set(10).unwrap().probability_where(|[w, _]| w >= 2) > Ratio::new(99, 100));
+        assert!(best_set(30).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Savage Worlds trait rolls.
+//!
+//! A trait roll is a single die, from d4 to d12, that _aces_ (explodes).
+//! Wild Cards (player characters and important foes) also roll a wild die, a d6 that aces too,
+//! and keep the higher of the two. The roll succeeds if it meets the target number,
+//! usually 4, and each further 4 points is a _raise_.
+
+use crate::{Closed, Distribution};
+
+/// The die a Wild Card rolls alongside their trait die.
+pub const WILD_DIE: usize = 6;
+
+/// The usual target number for a trait roll.
+pub const TARGET: i64 = 4;
+
+/// The outcome of a failed trait roll in [`raises`], apart from any count of raises.
+pub const FAILURE: i64 = -1;
+
+/// A trait roll with the given trait die and modifier: `d8! + 1` for an Extra,
+/// or `max(d8!, d6!) + 1` for a Wild Card.
+///
+/// Panics if the trait die isn't a d4, d6, d8, d10, or d12.
+pub fn trait_roll(trait_die: usize, modifier: i64, wild_card: bool) -> Closed {
+    assert!(
+        matches!(trait_die, 4 | 6 | 8 | 10 | 12),
+        "d{trait_die} is not a trait die"
+    );
+    let roll = if wild_card {
+        format!("max(d{trait_die}!, d{WILD_DIE}!)")
+    } else {
+        format!("d{trait_die}!")
+    };
+    format!("{roll} + ({modifier})")
+        .parse()
+        .expect("trait roll is a valid expression")
+}
+
+/// The distribution of successes for a trait roll against the target number:
+/// 0 for a failure, 1 for a success, and one more for each raise.
+pub fn successes(roll: &Distribution, target: i64) -> Distribution {
+    let thresholds: Vec<i64> = std::iter::successors(Some(target), |t| t.checked_add(4))
+        .take_while(|t| *t <= roll.max())
+        .collect();
+    roll.thresholds(&thresholds)
+}
+
+/// The distribution of raises for a trait roll against the target number:
+/// `floor((roll - target) / 4)` on a success, so 0 for a plain success,
+/// and [`FAILURE`] for any failure, however far short it falls.
+pub fn raises(roll: &Distribution, target: i64) -> Distribution {
+    roll.map_values(|v| {
+        if v < target {
+            FAILURE
+        } else {
+            // Widen, so the difference cannot overflow; a quarter of it fits.
+            ((v as i128 - target as i128) / 4) as i64
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use num::rational::Ratio;
+
+    use super::*;
+
+    #[test]
+    fn extra() {
+        let d = trait_roll(4, 0, false).distribution().unwrap();
+        assert_eq!(d, "d4!".parse::<Closed>().unwrap().distribution().unwrap());
+        let d = trait_roll(8, -2, false).distribution().unwrap();
+        assert_eq!(d.min(), -1);
+    }
+
+    #[test]
+    fn wild_card() {
+        let d = trait_roll(4, 0, true).distribution().unwrap();
+        // Fails only if both dice roll under 4.
+        let failure = Ratio::new(3, 4) * Ratio::new(3, 6);
+        let s = successes(&d, TARGET);
+        assert_eq!(s.probability(0), failure);
+        // The wild die can ace to 12, for two raises.
+        assert!(s.probability(3) > Ratio::new(0, 1));
+
+        // The wild die helps most with small trait dice.
+        let extra = successes(&trait_roll(4, 0, false).distribution().unwrap(), TARGET);
+        assert!(extra.probability(0) > s.probability(0));
+    }
+
+    #[test]
+    fn raises() {
+        let d = "d20".parse::<Closed>().unwrap().distribution().unwrap();
+        let s = successes(&d, 4);
+        assert_eq!((s.min(), s.max()), (0, 5));
+        assert_eq!(s.probability(0), Ratio::new(3, 20));
+        assert_eq!(s.probability(1), Ratio::new(4, 20));
+        assert_eq!(s.probability(5), Ratio::new(1, 20));
+    }
+
+    #[test]
+    fn raises_with_failure() {
+        let d = "d20".parse::<Closed>().unwrap().distribution().unwrap();
+        crate::assert_distribution_eq!(super::raises(&d, 4), {-1: 3/20, 0: 4/20, 1: 4/20, 2: 4/20, 3: 4/20, 4: 1/20});
+        // Failing by more than 4 is still just a failure.
+        let d = "d6 - 10".parse::<Closed>().unwrap().distribution().unwrap();
+        crate::assert_distribution_eq!(super::raises(&d, 4), {-1: 1/1});
+
+        // Raises are successes less one.
+        let d = trait_roll(8, 1, true).distribution().unwrap();
+        let s = successes(&d, TARGET);
+        let r = super::raises(&d, TARGET);
+        for n in 0..=s.max() {
+            assert_eq!(r.probability(n - 1), s.probability(n));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_a_trait_die() {
+        trait_roll(20, 0, true);
+    }
+}
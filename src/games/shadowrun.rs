@@ -0,0 +1,111 @@
+//! Shadowrun (fifth and sixth editions) tests: hits, glitches, and critical glitches.
+//!
+//! A test rolls a pool of d6s, and each 5 or 6 is a hit. If more than half the dice show 1s,
+//! the test glitches, whatever the hits; a glitch with no hits at all is a critical glitch.
+//! Since glitches depend on the count of 1s, not on the hits, a pool's result is
+//! a [`JointDistribution`] of the hits and the 1s, rather than a single count.
+
+use num::rational::Ratio;
+
+use crate::{Error, JointDistribution, RandomVariable};
+
+/// The index of hits in a die's or a pool's roll.
+pub const HITS: usize = 0;
+/// The index of 1s in a die's or a pool's roll.
+pub const ONES: usize = 1;
+
+/// The index of hits in the result of a test.
+pub const TEST_HITS: usize = 0;
+/// The index of whether a test glitched, 1 or 0, in its result.
+/// Critical glitches are glitches too.
+pub const GLITCH: usize = 1;
+/// The index of whether a test glitched critically, 1 or 0, in its result.
+pub const CRITICAL_GLITCH: usize = 2;
+
+/// The hits and 1s rolled on each face of a d6.
+const FACES: [[i64; 2]; 6] = [[0, 1], [0, 0], [0, 0], [0, 0], [1, 0], [1, 0]];
+
+/// The hits and 1s rolled on a pool of `dice` d6s.
+pub fn pool(dice: usize) -> Result<JointDistribution<2>, Error> {
+    JointDistribution::from_faces(&FACES)
+        .expect("dice have faces")
+        .checked_repeat(dice)
+        .ok_or_else(|| Error::Overflow(format!("{dice}d6")))
+}
+
+/// The result of a test with a pool of `dice` d6s: its hits, whether it glitched,
+/// and whether it glitched critically.
+pub fn test(dice: usize) -> Result<JointDistribution<3>, Error> {
+    Ok(pool(dice)?.map_tuple(|roll| {
+        let glitch = roll[ONES] * 2 > dice as i64;
+        let critical = glitch && roll[HITS] == 0;
+        [roll[HITS], glitch as i64, critical as i64]
+    }))
+}
+
+/// The probability that a test with a pool of `dice` d6s glitches, critically or not.
+pub fn glitch(dice: usize) -> Result<Ratio<usize>, Error> {
+    Ok(test(dice)?.probability_where(|r| r[GLITCH] != 0))
+}
+
+/// The probability that a test with a pool of `dice` d6s glitches critically.
+pub fn critical_glitch(dice: usize) -> Result<Ratio<usize>, Error> {
+    Ok(test(dice)?.probability_where(|r| r[CRITICAL_GLITCH] != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_die() {
+        // A single 1 is more than half the pool, and has no hits.
+        assert_eq!(glitch(1).unwrap(), Ratio::new(1, 6));
+        assert_eq!(critical_glitch(1).unwrap(), Ratio::new(1, 6));
+        assert_eq!(
+            test(1).unwrap().marginal(TEST_HITS).probability(1),
+            Ratio::new(1, 3)
+        );
+    }
+
+    #[test]
+    fn glitches_with_hits() {
+        // Three 1s out of four dice glitch; the fourth may still be a hit.
+        let result = test(4).unwrap();
+        assert_eq!(
+            result.probability(&[1, 1, 0]),
+            Ratio::new(4 * 2, 6usize.pow(4))
+        );
+        assert_eq!(
+            result.probability(&[0, 1, 1]),
+            Ratio::new(4 * 3 + 1, 6usize.pow(4))
+        );
+        // Two 1s out of four is only half.
+        assert_eq!(glitch(4).unwrap(), Ratio::new(4 * 5 + 1, 6usize.pow(4)));
+    }
+
+    #[test]
+    fn hits_are_binomial() {
+        let hits = test(6).unwrap().marginal(TEST_HITS);
+        let expected = [1, 6, 15, 20, 15, 6, 1].iter().enumerate().map(|(k, c)| {
+            (
+                k as i64,
+                Ratio::new(c * 2usize.pow(6 - k as u32), 3usize.pow(6)),
+            )
+        });
+        crate::assert_distribution_eq!(hits, expected);
+    }
+
+    #[test]
+    fn bigger_pools_glitch_less() {
+        // Adding one die to an even pool doesn't raise the 1s needed, so compare every other.
+        for n in 1..=10 {
+            assert!(glitch(n + 2).unwrap() < glitch(n).unwrap(), "{n}");
+            assert!(
+                critical_glitch(n + 2).unwrap() < critical_glitch(n).unwrap(),
+                "{n}"
+            );
+        }
+        assert!(glitch(3).unwrap() > glitch(2).unwrap());
+    }
+}
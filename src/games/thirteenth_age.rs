@@ -0,0 +1,129 @@
+//! 13th Age attacks over the course of a fight.
+//!
+//! An attack is a d20 plus the attack bonus, against the target's defense (AC, PD, or MD).
+//! A natural 20 is a critical hit, which doubles the damage; a natural 1 always misses.
+//! Many attacks deal some damage on a miss, too.
+//!
+//! The _escalation die_ adds to each attack: it starts at 0, and goes up by 1 each round
+//! after the first, to at most 6.
+
+use crate::{Closed, Error};
+
+/// The highest the escalation die goes.
+pub const MAX_ESCALATION: i64 = 6;
+
+/// The escalation die in the given round of a fight, counting from 1.
+pub fn escalation(round: usize) -> i64 {
+    (round.saturating_sub(1) as i64).min(MAX_ESCALATION)
+}
+
+/// Whether an attack with the given bonus hits the defense: 1 for a hit, 0 for a miss.
+pub fn hit(bonus: i64, defense: i64) -> Closed {
+    format!("[R: d20] (R = 20) + (R > 1) * (R < 20) * (R + ({bonus}) >= ({defense}))")
+        .parse()
+        .expect("attack is a valid expression")
+}
+
+/// The damage from an attack with the given bonus against the defense:
+/// doubled on a critical hit, and the `miss` damage on a miss.
+pub fn attack(bonus: i64, defense: i64, damage: &Closed, miss: &Closed) -> Closed {
+    format!(
+        "[R: d20] if R = 20 then 2 * ({damage}) \
+         else if (R > 1) * (R + ({bonus}) >= ({defense})) then {damage} else {miss}"
+    )
+    .parse()
+    .expect("attack is a valid expression")
+}
+
+/// The expected results of attacking in one round of a fight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Round {
+    /// The escalation die this round.
+    pub escalation: i64,
+    /// The probability of hitting.
+    pub hit: f64,
+    /// The expected damage this round.
+    pub damage: f64,
+    /// The expected damage over this round and every round before it.
+    pub total_damage: f64,
+}
+
+/// The expected results of attacking once a round, for the given number of rounds,
+/// as the escalation die rises.
+pub fn fight(
+    bonus: i64,
+    defense: i64,
+    damage: &Closed,
+    miss: &Closed,
+    rounds: usize,
+) -> Result<Vec<Round>, Error> {
+    let mut total_damage = 0.0;
+    (1..=rounds)
+        .map(|round| {
+            let escalation = escalation(round);
+            let bonus = bonus.saturating_add(escalation);
+            let damage = attack(bonus, defense, damage, miss).distribution()?.mean();
+            total_damage += damage;
+            Ok(Round {
+                escalation,
+                hit: hit(bonus, defense).distribution()?.mean(),
+                damage,
+                total_damage,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escalation_die() {
+        let rises: Vec<i64> = (1..=9).map(escalation).collect();
+        assert_eq!(rises, [0, 1, 2, 3, 4, 5, 6, 6, 6]);
+    }
+
+    #[test]
+    fn hits() {
+        // Needs 11 or more: 10 in 20.
+        let d = hit(5, 16).distribution().unwrap();
+        assert_eq!(d.mean(), 0.5);
+        // A natural 1 misses, and a natural 20 hits.
+        let d = hit(30, 10).distribution().unwrap();
+        assert!((d.mean() - 0.95).abs() < 1e-9);
+        let d = hit(-30, 10).distribution().unwrap();
+        assert!((d.mean() - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn attacks() {
+        let damage: Closed = "d8 + 4".parse().unwrap();
+        let miss: Closed = "2".parse().unwrap();
+        let d = attack(5, 16, &damage, &miss).distribution().unwrap();
+        assert_eq!(d.max(), 24);
+        assert_eq!(d.min(), 2);
+        // 9 in 20 hit, 1 in 20 crits, and 10 in 20 miss.
+        let want = (9.0 * 8.5 + 17.0 + 10.0 * 2.0) / 20.0;
+        assert!((d.mean() - want).abs() < 1e-9, "{}", d.mean());
+    }
+
+    #[test]
+    fn rounds() {
+        let damage: Closed = "d8 + 4".parse().unwrap();
+        let miss: Closed = "0".parse().unwrap();
+        let got = fight(5, 16, &damage, &miss, 8).unwrap();
+        assert_eq!(got.len(), 8);
+        assert_eq!(got[0].escalation, 0);
+        assert_eq!(got[0].hit, 0.5);
+        // +3 in the fourth round.
+        assert!((got[3].hit - 0.65).abs() < 1e-9);
+        // Each round hits more often, until the escalation die tops out.
+        for pair in got.windows(2) {
+            assert!(pair[0].damage <= pair[1].damage);
+            assert!((pair[1].total_damage - pair[0].total_damage - pair[1].damage).abs() < 1e-9);
+        }
+        assert_eq!(got[6].escalation, MAX_ESCALATION);
+        assert_eq!(got[6].damage, got[7].damage);
+    }
+}
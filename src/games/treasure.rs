@@ -0,0 +1,176 @@
+//! Treasure hoards, following the coin rows of the treasure hoard tables
+//! in the 5th edition Dungeon Master's Guide.
+//!
+//! Each coin row is an expression table entry: a roll, a multiplier, and a kind of coin.
+//! For instance, a hoard for challenge 0-4 contains `6d6` × 100 copper pieces.
+//! The same rows produce both random hoards (via [`sample_hoard`])
+//! and exact analyses (via [`value_distribution`]).
+//!
+//! Gems, art objects, and magic items are not included.
+
+use crate::{Closed, Distribution, Error, sample::Rng};
+
+/// A kind of coin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Coin {
+    Copper,
+    Silver,
+    Electrum,
+    Gold,
+    Platinum,
+}
+
+impl Coin {
+    /// The value of one of this coin, in copper pieces.
+    pub fn value_in_copper(&self) -> i64 {
+        match self {
+            Coin::Copper => 1,
+            Coin::Silver => 10,
+            Coin::Electrum => 50,
+            Coin::Gold => 100,
+            Coin::Platinum => 1000,
+        }
+    }
+
+    /// The usual abbreviation for this coin, e.g. "gp".
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Coin::Copper => "cp",
+            Coin::Silver => "sp",
+            Coin::Electrum => "ep",
+            Coin::Gold => "gp",
+            Coin::Platinum => "pp",
+        }
+    }
+}
+
+/// The challenge rating of the monsters guarding a hoard.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Challenge {
+    /// Challenge 0-4.
+    Tier1,
+    /// Challenge 5-10.
+    Tier2,
+    /// Challenge 11-16.
+    Tier3,
+    /// Challenge 17 and up.
+    Tier4,
+}
+
+/// One row of coins in a hoard: `roll` × `multiplier` coins of the given kind.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CoinRow {
+    pub roll: &'static str,
+    pub multiplier: i64,
+    pub coin: Coin,
+}
+
+impl CoinRow {
+    /// The distribution of the roll for this row, before the multiplier.
+    pub fn roll_distribution(&self) -> Result<Distribution, Error> {
+        self.roll.parse::<Closed>()?.distribution()
+    }
+}
+
+const fn row(roll: &'static str, multiplier: i64, coin: Coin) -> CoinRow {
+    CoinRow {
+        roll,
+        multiplier,
+        coin,
+    }
+}
+
+const TIER1: &[CoinRow] = &[
+    row("6d6", 100, Coin::Copper),
+    row("3d6", 100, Coin::Silver),
+    row("2d6", 10, Coin::Gold),
+];
+const TIER2: &[CoinRow] = &[
+    row("2d6", 100, Coin::Copper),
+    row("2d6", 1000, Coin::Silver),
+    row("6d6", 100, Coin::Gold),
+    row("3d6", 10, Coin::Platinum),
+];
+const TIER3: &[CoinRow] = &[
+    row("4d6", 1000, Coin::Gold),
+    row("5d6", 100, Coin::Platinum),
+];
+const TIER4: &[CoinRow] = &[
+    row("12d6", 1000, Coin::Gold),
+    row("8d6", 1000, Coin::Platinum),
+];
+
+impl Challenge {
+    /// The coins in a hoard of this challenge.
+    pub fn coins(&self) -> &'static [CoinRow] {
+        match self {
+            Challenge::Tier1 => TIER1,
+            Challenge::Tier2 => TIER2,
+            Challenge::Tier3 => TIER3,
+            Challenge::Tier4 => TIER4,
+        }
+    }
+}
+
+/// Roll the coins for a random hoard: the number of each kind of coin.
+pub fn sample_hoard(challenge: Challenge, rng: &mut impl Rng) -> Result<Vec<(Coin, i64)>, Error> {
+    challenge
+        .coins()
+        .iter()
+        .map(|row| {
+            Ok((
+                row.coin,
+                row.roll_distribution()?.sample(rng) * row.multiplier,
+            ))
+        })
+        .collect()
+}
+
+/// The distribution of the total value of a hoard's coins, in gold pieces.
+pub fn value_distribution(challenge: Challenge) -> Result<Distribution, Error> {
+    let gold = Coin::Gold.value_in_copper();
+    let mut total = Distribution::uniform(0, 0).expect("nonempty range");
+    for row in challenge.coins() {
+        // All of the hoard tables' multiples work out to whole gold pieces.
+        let scale = row.multiplier * row.coin.value_in_copper() / gold;
+        let value = row
+            .roll_distribution()?
+            .checked_scale(scale)
+            .ok_or_else(|| Error::Overflow(row.roll.to_owned()))?;
+        total = total
+            .checked_add(&value)
+            .ok_or_else(|| Error::Overflow(row.roll.to_owned()))?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::SplitMix64;
+
+    #[test]
+    fn expected_values() {
+        for (challenge, want) in [
+            (Challenge::Tier1, 196.0),
+            (Challenge::Tier2, 3857.0),
+            (Challenge::Tier3, 31500.0),
+            (Challenge::Tier4, 322000.0),
+        ] {
+            let got = value_distribution(challenge).unwrap().mean();
+            assert!((got - want).abs() < 1e-6, "{challenge:?}: {got}");
+        }
+    }
+
+    #[test]
+    fn sampled_hoard() {
+        let mut rng = SplitMix64::new(3);
+        for _ in 0..100 {
+            let hoard = sample_hoard(Challenge::Tier1, &mut rng).unwrap();
+            let coins: Vec<_> = hoard.iter().map(|(coin, _)| *coin).collect();
+            assert_eq!(coins, vec![Coin::Copper, Coin::Silver, Coin::Gold]);
+            let (_, copper) = hoard[0];
+            assert!((600..=3600).contains(&copper) && copper % 100 == 0);
+        }
+    }
+}
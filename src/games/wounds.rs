@@ -0,0 +1,107 @@
+//! Wound tables: mapping damage onto a small number of ordered outcomes.
+//!
+//! Many games compare damage against thresholds rather than subtracting it from hit points.
+//! In Savage Worlds, damage that meets a character's Toughness leaves them Shaken,
+//! and every 4 points beyond that inflicts a wound; vehicle damage tables work similarly.
+
+use num::rational::Ratio;
+
+use crate::Distribution;
+
+/// A table of outcomes for damage, in order of severity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WoundTable {
+    /// Label for damage below every threshold.
+    unharmed: String,
+    /// The least damage that produces each outcome, and the label for that outcome.
+    levels: Vec<(i64, String)>,
+}
+
+impl WoundTable {
+    /// Create a table with a label for damage below every threshold,
+    /// and the least damage for each further outcome.
+    ///
+    /// Returns None if the thresholds are not in ascending order.
+    pub fn new<S: Into<String>>(
+        unharmed: impl Into<String>,
+        levels: impl IntoIterator<Item = (i64, S)>,
+    ) -> Option<Self> {
+        let levels: Vec<(i64, String)> = levels.into_iter().map(|(t, s)| (t, s.into())).collect();
+        if levels.is_sorted_by_key(|(t, _)| *t) {
+            Some(Self {
+                unharmed: unharmed.into(),
+                levels,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The Savage Worlds damage table for a character with the given Toughness:
+    /// Shaken on meeting Toughness, a wound for each raise (4 points) beyond it,
+    /// and Incapacitated beyond three wounds.
+    pub fn savage_worlds(toughness: i64) -> Self {
+        Self::new(
+            "unharmed",
+            [
+                (toughness, "shaken"),
+                (toughness + 4, "1 wound"),
+                (toughness + 8, "2 wounds"),
+                (toughness + 12, "3 wounds"),
+                (toughness + 16, "incapacitated"),
+            ],
+        )
+        .expect("thresholds are ascending")
+    }
+
+    /// The labels of the outcomes, from least to most severe.
+    pub fn labels(&self) -> impl Iterator<Item = &str> + '_ {
+        std::iter::once(self.unharmed.as_str()).chain(self.levels.iter().map(|(_, s)| s.as_str()))
+    }
+
+    /// The distribution of outcomes, numbered from 0 (below every threshold) in order of severity.
+    pub fn levels(&self, damage: &Distribution) -> Distribution {
+        let thresholds: Vec<i64> = self.levels.iter().map(|(t, _)| *t).collect();
+        damage.thresholds(&thresholds)
+    }
+
+    /// The probability of each outcome, from least to most severe.
+    pub fn outcomes<'a>(
+        &'a self,
+        damage: &Distribution,
+    ) -> impl Iterator<Item = (&'a str, Ratio<usize>)> + 'a {
+        let levels = self.levels(damage);
+        self.labels()
+            .enumerate()
+            .map(move |(i, label)| (label, levels.probability(i as i64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    #[test]
+    fn unordered() {
+        assert!(WoundTable::new("fine", [(5, "hurt"), (3, "dead")]).is_none());
+    }
+
+    #[test]
+    fn savage_worlds() {
+        let damage = "2d6".parse::<Closed>().unwrap().distribution().unwrap();
+        let table = WoundTable::savage_worlds(5);
+        let outcomes: Vec<_> = table.outcomes(&damage).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("unharmed", Ratio::new(6, 36)),
+                ("shaken", Ratio::new(20, 36)),
+                ("1 wound", Ratio::new(10, 36)),
+                ("2 wounds", Ratio::new(0, 1)),
+                ("3 wounds", Ratio::new(0, 1)),
+                ("incapacitated", Ratio::new(0, 1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Year Zero engine dice pools, from Mutant: Year Zero and its descendants.
+//!
+//! A pool mixes three kinds of d6: base dice for the attribute, skill dice, and gear dice.
+//! Every 6 is a success. A 1 on a base die is a bane that costs attribute points, and a 1 on a
+//! gear die is a bane that damages the gear; a 1 on a skill die is nothing.
+//!
+//! A roll can be _pushed_: every die that didn't show a 6 or a bane is rolled again, once.
+//! Each die's reroll depends on its own first roll, and on its kind, so a pushed pool isn't a
+//! sum of simple dice. A roll of a pool is a [`JointDistribution`] of its [`SUCCESSES`],
+//! [`ATTRIBUTE_BANES`], and [`GEAR_BANES`].
+
+use num::rational::Ratio;
+
+use crate::{Error, JointDistribution, RandomVariable};
+
+/// The index of successes in a roll.
+pub const SUCCESSES: usize = 0;
+/// The index of banes on base dice in a roll.
+pub const ATTRIBUTE_BANES: usize = 1;
+/// The index of banes on gear dice in a roll.
+pub const GEAR_BANES: usize = 2;
+
+/// A kind of Year Zero die.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Die {
+    /// A die for the character's attribute.
+    Base,
+    /// A die for the character's skill.
+    Skill,
+    /// A die for the character's gear.
+    Gear,
+}
+
+impl Die {
+    /// What a face of the die counts for.
+    fn outcome(&self, face: i64) -> [i64; 3] {
+        match (self, face) {
+            (_, 6) => [1, 0, 0],
+            (Die::Base, 1) => [0, 1, 0],
+            (Die::Gear, 1) => [0, 0, 1],
+            _ => [0, 0, 0],
+        }
+    }
+
+    /// What a roll of the die counts for, pushed or not.
+    pub fn roll(&self, pushed: bool) -> JointDistribution<3> {
+        let mut faces = Vec::new();
+        for first in 1..=6 {
+            let kept = self.outcome(first) != [0, 0, 0];
+            for reroll in 1..=6 {
+                // Each first roll is paired with every reroll, even if it's kept,
+                // so that all are equally likely.
+                let face = if pushed && !kept { reroll } else { first };
+                faces.push(self.outcome(face));
+            }
+        }
+        JointDistribution::from_faces(&faces).expect("dice have faces")
+    }
+}
+
+/// A pool of Year Zero dice: how many of each kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Pool {
+    pub base: usize,
+    pub skill: usize,
+    pub gear: usize,
+}
+
+impl Pool {
+    fn counts(&self) -> [(Die, usize); 3] {
+        [
+            (Die::Base, self.base),
+            (Die::Skill, self.skill),
+            (Die::Gear, self.gear),
+        ]
+    }
+
+    /// The successes and banes of a roll of the pool, pushed or not.
+    pub fn roll(&self, pushed: bool) -> Result<JointDistribution<3>, Error> {
+        self.counts()
+            .iter()
+            .try_fold(JointDistribution::constant([0; 3]), |acc, (die, n)| {
+                die.roll(pushed)
+                    .checked_repeat(*n)
+                    .and_then(|d| acc.checked_convolve(&d))
+            })
+            .ok_or_else(|| Error::Overflow(self.to_string()))
+    }
+
+    /// The probability that a roll of the pool, pushed or not, has at least one success.
+    pub fn success(&self, pushed: bool) -> Result<Ratio<usize>, Error> {
+        Ok(self.roll(pushed)?.probability_where(|r| r[SUCCESSES] > 0))
+    }
+}
+
+/// The dice in the pool, e.g. "3 base, 2 skill, 1 gear".
+impl std::fmt::Display for Pool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dice: Vec<String> = self
+            .counts()
+            .iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(die, n)| format!("{n} {}", format!("{die:?}").to_lowercase()))
+            .collect();
+        if dice.is_empty() {
+            write!(f, "no dice")
+        } else {
+            write!(f, "{}", dice.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_dice() {
+        let base = Die::Base.roll(false);
+        assert_eq!(base.probability(&[1, 0, 0]), Ratio::new(1, 6));
+        assert_eq!(base.probability(&[0, 1, 0]), Ratio::new(1, 6));
+        // Pushing rerolls 2 through 5, which may come up 6 or 1 in turn.
+        let base = Die::Base.roll(true);
+        assert_eq!(base.probability(&[1, 0, 0]), Ratio::new(10, 36));
+        assert_eq!(base.probability(&[0, 1, 0]), Ratio::new(10, 36));
+        // A skill die's 1 is rerolled too, and never a bane.
+        let skill = Die::Skill.roll(true);
+        assert_eq!(skill.probability(&[1, 0, 0]), Ratio::new(11, 36));
+        assert_eq!(skill.marginal(ATTRIBUTE_BANES).max(), 0);
+        assert_eq!(
+            Die::Gear.roll(true).probability(&[0, 0, 1]),
+            Ratio::new(10, 36)
+        );
+    }
+
+    #[test]
+    fn pools() {
+        let pool = Pool {
+            base: 3,
+            skill: 2,
+            gear: 1,
+        };
+        assert_eq!(pool.to_string(), "3 base, 2 skill, 1 gear");
+        assert_eq!(
+            pool.success(false).unwrap(),
+            Ratio::from_integer(1) - Ratio::new(5usize.pow(6), 6usize.pow(6))
+        );
+        assert!(pool.success(true).unwrap() > pool.success(false).unwrap());
+        let pushed = pool.roll(true).unwrap();
+        assert_eq!(pushed.marginal(ATTRIBUTE_BANES).max(), 3);
+        assert_eq!(pushed.marginal(GEAR_BANES).max(), 1);
+        assert_eq!(Pool::default().success(true).unwrap(), Ratio::new(0, 1));
+    }
+}
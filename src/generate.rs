@@ -0,0 +1,335 @@
+//! Random dice expressions within constraints: for fuzzing tools that take expressions,
+//! for teaching, and for inventing strange new dice mechanics.
+//!
+//! [`Closed::random`] makes up an expression using only the [`Operator`]s and dice its
+//! [`Constraints`] allow, and only returns one that evaluates within their budget.
+//! [`crate::quiz`] asks questions about such expressions.
+
+use crate::{
+    Closed, Error, Evaluator,
+    parse::RawExpression,
+    sample::{Rng, below, between},
+    symbolic::{
+        ComparisonOp, Constant, Die, Explosion, ExpressionTree, Function, Ranker, Reroll, Symbol,
+        Trigger,
+    },
+};
+
+/// How many expressions to make up, looking for one within the budget.
+const ATTEMPTS: usize = 100;
+
+/// The symbols bound by [`Operator::Bind`], by depth, so nested bindings never conflict.
+const SYMBOLS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// An operation a generated expression may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Subtract,
+    /// `a * b`
+    Multiply,
+    /// `a / b`, dividing by a roll, which is never zero.
+    Divide,
+    /// `(a >= b) * c`: `c` if the comparison holds, otherwise 0.
+    Compare,
+    /// `max(a, b)`
+    Max,
+    /// `min(a, b)`
+    Min,
+    /// `[A: a] (A >= b) * A`: a binding, used more than once.
+    Bind,
+    /// `4d6kh3` or `4d6kl3`: keep the highest or lowest of the dice rolled.
+    Keep,
+    /// `2d6!`: dice that explode on their highest face.
+    Explode,
+    /// `2d6r1`: dice that reroll their lowest face.
+    Reroll,
+}
+
+impl Operator {
+    /// Every operator, in the order listed.
+    pub const ALL: [Operator; 11] = [
+        Operator::Add,
+        Operator::Subtract,
+        Operator::Multiply,
+        Operator::Divide,
+        Operator::Compare,
+        Operator::Max,
+        Operator::Min,
+        Operator::Bind,
+        Operator::Keep,
+        Operator::Explode,
+        Operator::Reroll,
+    ];
+}
+
+/// What a generated expression may contain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraints {
+    /// How deeply operations may nest: 0 is a single roll, like `3d6`.
+    pub depth: usize,
+    /// The operations the expression may use. With none, it's a single roll.
+    pub operators: Vec<Operator>,
+    /// The sizes of dice the expression may roll; must not be empty.
+    pub faces: Vec<usize>,
+    /// The most dice in any one roll.
+    pub dice: usize,
+    /// The most combinations of values evaluating the expression may take,
+    /// as [`Evaluator::with_budget`] counts them.
+    pub budget: usize,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Constraints {
+            depth: 3,
+            operators: Operator::ALL.to_vec(),
+            faces: vec![4, 6, 8, 10, 12, 20],
+            dice: 4,
+            budget: 100_000,
+        }
+    }
+}
+
+impl Closed {
+    /// Make up an expression within the constraints.
+    ///
+    /// Fails if none of several expressions could be evaluated within the budget.
+    ///
+    /// # Panics
+    ///
+    /// If `constraints.faces` is empty, or contains 0.
+    pub fn random(rng: &mut impl Rng, constraints: &Constraints) -> Result<Closed, Error> {
+        assert!(
+            !constraints.faces.is_empty() && !constraints.faces.contains(&0),
+            "random expressions need dice to roll"
+        );
+        let mut attempt = 0;
+        loop {
+            // Simplified, as the parser does, so it's the same as the expression written out.
+            let expression: Closed = expression(rng, constraints, constraints.depth)
+                .simplify()
+                .try_into()?;
+            attempt += 1;
+            match Evaluator::default()
+                .with_budget(constraints.budget)
+                .eval(&expression)
+            {
+                Ok(_) => return Ok(expression),
+                Err(Error::OverBudget(_) | Error::Overflow(_) | Error::TooComplex(_))
+                    if attempt < ATTEMPTS =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A roll of `count` of `die`, like `3d6`.
+fn roll(count: usize, die: ExpressionTree<RawExpression>) -> RawExpression {
+    ExpressionTree::Repeated {
+        count: Box::new(Constant(count as u64).into()),
+        value: Box::new(die.into()),
+        ranker: Ranker::All,
+    }
+    .into()
+}
+
+/// `a >= b`, as 1 or 0.
+fn at_least(a: RawExpression, b: RawExpression) -> RawExpression {
+    ExpressionTree::Comparison {
+        a: Box::new(a),
+        b: Box::new(b),
+        op: ComparisonOp::Ge,
+    }
+    .into()
+}
+
+fn product(a: RawExpression, b: RawExpression) -> RawExpression {
+    ExpressionTree::Product(Box::new(a), Box::new(b)).into()
+}
+
+/// A random expression, nesting operations at most `depth` deep.
+fn expression(rng: &mut impl Rng, constraints: &Constraints, depth: usize) -> RawExpression {
+    let faces = constraints.faces[below(rng, constraints.faces.len() as u64) as usize];
+    let dice = between(rng, 1, constraints.dice.max(1) as i64) as usize;
+    let die = ExpressionTree::Die(Die(faces));
+    let operators = &constraints.operators;
+    if depth == 0 || operators.is_empty() {
+        return roll(dice, die);
+    }
+    let operator = operators[below(rng, operators.len() as u64) as usize];
+    match operator {
+        // There's nothing to keep, or drop, from a single die.
+        Operator::Keep if dice > 1 => {
+            let keep = Box::new(Constant(between(rng, 1, dice as i64 - 1) as u64).into());
+            ExpressionTree::Repeated {
+                count: Box::new(Constant(dice as u64).into()),
+                value: Box::new(die.into()),
+                ranker: if below(rng, 2) == 0 {
+                    Ranker::Highest(keep)
+                } else {
+                    Ranker::Lowest(keep)
+                },
+            }
+            .into()
+        }
+        // A d1 would explode, or reroll, forever.
+        Operator::Explode if faces > 1 => roll(
+            dice,
+            ExpressionTree::Explode {
+                value: Box::new(die.into()),
+                explosion: Explosion::Standard,
+                trigger: Trigger::Highest,
+            },
+        ),
+        Operator::Reroll if faces > 1 => roll(
+            dice,
+            ExpressionTree::Reroll {
+                value: Box::new(die.into()),
+                reroll: Reroll {
+                    op: ComparisonOp::Eq,
+                    threshold: 1,
+                    recursive: true,
+                },
+            },
+        ),
+        Operator::Keep | Operator::Explode | Operator::Reroll => roll(dice, die),
+        Operator::Divide => {
+            let a = expression(rng, constraints, depth - 1);
+            ExpressionTree::Floor(Box::new(a), Box::new(roll(dice, die))).into()
+        }
+        Operator::Compare => {
+            let (a, b) = operands(rng, constraints, depth);
+            let c = expression(rng, constraints, depth - 1);
+            product(at_least(a, b), c)
+        }
+        Operator::Bind => {
+            let symbol: Symbol = SYMBOLS[depth % SYMBOLS.len()..][..1]
+                .parse()
+                .expect("symbols are capital letters");
+            let (a, b) = operands(rng, constraints, depth);
+            let bound = || RawExpression::from(ExpressionTree::Symbol(symbol.clone()));
+            ExpressionTree::Binding {
+                symbol: symbol.clone(),
+                value: Box::new(a),
+                tail: Box::new(product(at_least(bound(), b), bound())),
+            }
+            .into()
+        }
+        Operator::Add => {
+            let (a, b) = operands(rng, constraints, depth);
+            ExpressionTree::Sum(vec![a, b]).into()
+        }
+        Operator::Subtract => {
+            let (a, b) = operands(rng, constraints, depth);
+            ExpressionTree::Sum(vec![a, ExpressionTree::Negated(Box::new(b)).into()]).into()
+        }
+        Operator::Multiply => {
+            let (a, b) = operands(rng, constraints, depth);
+            product(a, b)
+        }
+        Operator::Max | Operator::Min => {
+            let (a, b) = operands(rng, constraints, depth);
+            let function = if operator == Operator::Max {
+                Function::Max
+            } else {
+                Function::Min
+            };
+            ExpressionTree::Call {
+                function,
+                args: vec![a, b],
+            }
+            .into()
+        }
+    }
+}
+
+/// Two random operands for an operation at `depth`.
+fn operands(
+    rng: &mut impl Rng,
+    constraints: &Constraints,
+    depth: usize,
+) -> (RawExpression, RawExpression) {
+    let a = expression(rng, constraints, depth - 1);
+    (a, expression(rng, constraints, depth - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::SplitMix64;
+
+    #[test]
+    fn deterministic() {
+        let constraints = Constraints::default();
+        let a = Closed::random(&mut SplitMix64::new(7), &constraints).unwrap();
+        let b = Closed::random(&mut SplitMix64::new(7), &constraints).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn within_budget() {
+        let constraints = Constraints {
+            budget: 10_000,
+            ..Default::default()
+        };
+        let mut rng = SplitMix64::new(1);
+        for _ in 0..50 {
+            let e = Closed::random(&mut rng, &constraints).unwrap();
+            let mut evaluator = Evaluator::default().with_budget(constraints.budget);
+            assert!(evaluator.eval(&e).is_ok(), "{e}");
+            // It's an expression as it could have been written.
+            assert_eq!(e.to_string().parse::<Closed>().unwrap(), e);
+        }
+
+        // When every expression is over budget, there's nothing to make.
+        let constraints = Constraints {
+            operators: vec![Operator::Multiply],
+            budget: 10,
+            ..Default::default()
+        };
+        let got = Closed::random(&mut rng, &constraints);
+        assert!(matches!(got, Err(Error::OverBudget(_))), "{got:?}");
+    }
+
+    #[test]
+    fn within_constraints() {
+        let constraints = Constraints {
+            depth: 2,
+            operators: vec![Operator::Add, Operator::Max],
+            faces: vec![6],
+            dice: 2,
+            ..Default::default()
+        };
+        let mut rng = SplitMix64::new(2);
+        for _ in 0..50 {
+            let e = Closed::random(&mut rng, &constraints).unwrap().to_string();
+            let rest = e
+                .replace("1d6", "")
+                .replace("2d6", "")
+                .replace("max", "")
+                .replace(['+', '(', ')', ',', ' '], "");
+            assert!(rest.is_empty(), "{e}");
+        }
+
+        // Without operators, or depth, it's a single roll.
+        for constraints in [
+            Constraints {
+                operators: Vec::new(),
+                ..constraints.clone()
+            },
+            Constraints {
+                depth: 0,
+                ..constraints
+            },
+        ] {
+            let e = Closed::random(&mut rng, &constraints).unwrap().to_string();
+            assert!(["1d6", "2d6"].contains(&e.as_str()), "{e}");
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! House rules: changes to how dice are rolled, applied throughout an expression.
+//!
+//! [`Closed::variants_with`] applies each rule to an expression and evaluates the result,
+//! to compare each rule's impact against the expression as written;
+//! [`variants_of_all`] does the same for a whole library of expressions.
+
+use std::collections::HashSet;
+
+use crate::{
+    Closed, Distribution, Error,
+    symbolic::{
+        ComparisonOp, Constant, Die, Explosion, ExpressionTree, ExpressionWrapper, Ranker, Reroll,
+        Symbol, Trigger,
+    },
+};
+
+/// A change to how dice are rolled, applied to every matching die in an expression.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HouseRule {
+    /// Roll each die with this many faces twice, and keep the higher roll:
+    /// `Advantage(20)` gives advantage on every d20.
+    Advantage(usize),
+    /// Roll each die with this many faces twice, and keep the lower roll.
+    Disadvantage(usize),
+    /// Reroll each die once when it shows this value or less, keeping the reroll:
+    /// `RerollAtMost(1)` rerolls every 1.
+    RerollAtMost(i64),
+    /// Each die with this many faces explodes on its highest face.
+    Explode(usize),
+    /// Reroll each natural 1 on a d20 once, keeping the reroll, as a lucky halfling does.
+    /// With advantage or disadvantage, each of the two d20s is rerolled on its own.
+    HalflingLuck,
+    /// Add the bonus to every d20 test: each comparison of a d20 roll, or a symbol bound to one,
+    /// that's higher-is-better (`d20 + 5 >= 15`, or `15 <= d20 + 5`).
+    /// A bonus of `d4` is the spell _bless_.
+    ///
+    /// Equality comparisons, like `ATK = 20` for a critical hit, read the natural roll,
+    /// so they're left alone.
+    D20Bonus(Closed),
+}
+
+impl HouseRule {
+    /// The expression, with this rule applied to each of its dice.
+    pub fn apply(&self, expression: &Closed) -> Closed {
+        // Symbols that stand for a d20 roll count as d20 rolls in a test.
+        let mut d20_symbols = HashSet::new();
+        if let HouseRule::D20Bonus(_) = self {
+            expression.rewrite(&mut |node| {
+                if let ExpressionTree::Binding { symbol, value, .. } = &node
+                    && rolls_d20(value, &d20_symbols)
+                {
+                    d20_symbols.insert(symbol.clone());
+                }
+                node
+            });
+        }
+        expression.rewrite(&mut |node| match (self, node) {
+            (HouseRule::Advantage(n) | HouseRule::Disadvantage(n), ExpressionTree::Die(d))
+                if d == Die(*n) =>
+            {
+                let ranker = if matches!(self, HouseRule::Advantage(_)) {
+                    Ranker::Highest
+                } else {
+                    Ranker::Lowest
+                };
+                let constant = |c| Box::new(Closed::new(ExpressionTree::Modifier(Constant(c))));
+                ExpressionTree::Repeated {
+                    count: constant(2),
+                    value: Box::new(Closed::new(ExpressionTree::Die(d))),
+                    ranker: ranker(constant(1)),
+                }
+            }
+            // A die already rerolled or exploding, by an earlier rule, now has advantage:
+            // each of its rolls is rerolled or explodes.
+            (
+                HouseRule::Advantage(_) | HouseRule::Disadvantage(_),
+                ExpressionTree::Reroll { value, reroll },
+            ) => sink(*value, |die| ExpressionTree::Reroll { value: die, reroll }),
+            (
+                HouseRule::Advantage(_) | HouseRule::Disadvantage(_),
+                ExpressionTree::Explode {
+                    value,
+                    explosion,
+                    trigger,
+                },
+            ) => sink(*value, |die| ExpressionTree::Explode {
+                value: die,
+                explosion,
+                trigger,
+            }),
+            (HouseRule::HalflingLuck, ExpressionTree::Die(Die(20))) => ExpressionTree::Reroll {
+                value: Box::new(Closed::new(ExpressionTree::Die(Die(20)))),
+                reroll: Reroll {
+                    op: ComparisonOp::Eq,
+                    threshold: 1,
+                    recursive: false,
+                },
+            },
+            (HouseRule::RerollAtMost(threshold), node @ ExpressionTree::Die(_)) => {
+                ExpressionTree::Reroll {
+                    value: Box::new(Closed::new(node)),
+                    reroll: Reroll {
+                        op: ComparisonOp::Le,
+                        threshold: *threshold,
+                        recursive: false,
+                    },
+                }
+            }
+            (HouseRule::Explode(n), ExpressionTree::Die(d)) if d == Die(*n) => {
+                ExpressionTree::Explode {
+                    value: Box::new(Closed::new(ExpressionTree::Die(d))),
+                    explosion: Explosion::Standard,
+                    trigger: Trigger::Highest,
+                }
+            }
+            (HouseRule::D20Bonus(bonus), ExpressionTree::Comparison { a, b, op }) => {
+                let with_bonus = |side: Box<Closed>| {
+                    Box::new(Closed::new(ExpressionTree::Sum(vec![*side, bonus.clone()])))
+                };
+                let (a, b) = match op {
+                    ComparisonOp::Gt | ComparisonOp::Ge if rolls_d20(&a, &d20_symbols) => {
+                        (with_bonus(a), b)
+                    }
+                    ComparisonOp::Lt | ComparisonOp::Le if rolls_d20(&b, &d20_symbols) => {
+                        (a, with_bonus(b))
+                    }
+                    _ => (a, b),
+                };
+                ExpressionTree::Comparison { a, b, op }
+            }
+            (_, node) => node,
+        })
+    }
+}
+
+/// Apply `modifier` to a die: to each of its rolls, if advantage or disadvantage has
+/// already made it a pool.
+///
+/// Rerolls and explosions only apply to a single die in the notation,
+/// so a pool under one can only come from a rule.
+fn sink(
+    value: Closed,
+    modifier: impl FnOnce(Box<Closed>) -> ExpressionTree<Closed>,
+) -> ExpressionTree<Closed> {
+    match value.inner() {
+        ExpressionTree::Repeated {
+            count,
+            value: die,
+            ranker,
+        } => ExpressionTree::Repeated {
+            count: count.clone(),
+            value: Box::new(Closed::new(modifier(die.clone()))),
+            ranker: ranker.clone(),
+        },
+        _ => modifier(Box::new(value)),
+    }
+}
+
+/// Whether the expression rolls a d20, or uses one of the symbols.
+fn rolls_d20(e: &Closed, symbols: &HashSet<Symbol>) -> bool {
+    match e.inner() {
+        ExpressionTree::Die(Die(20)) => true,
+        ExpressionTree::Symbol(s) => symbols.contains(s),
+        node => {
+            let mut found = false;
+            node.map_children(|child| found |= rolls_d20(child, symbols));
+            found
+        }
+    }
+}
+
+impl std::fmt::Display for HouseRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HouseRule::Advantage(n) => write!(f, "advantage on d{n}"),
+            HouseRule::Disadvantage(n) => write!(f, "disadvantage on d{n}"),
+            HouseRule::RerollAtMost(v) => write!(f, "reroll {v}s and below"),
+            HouseRule::Explode(n) => write!(f, "exploding d{n}"),
+            HouseRule::HalflingLuck => write!(f, "halfling luck"),
+            HouseRule::D20Bonus(bonus) => write!(f, "bonus of {bonus} on d20 tests"),
+        }
+    }
+}
+
+/// An expression under a house rule (or as written), with its distribution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// The rule applied, or None for the expression as written.
+    pub rule: Option<HouseRule>,
+    /// The expression with the rule applied.
+    pub expression: Closed,
+    /// The distribution of the expression with the rule applied.
+    pub distribution: Distribution,
+    /// The change in the mean from the expression as written.
+    pub mean_change: f64,
+}
+
+impl Closed {
+    /// The expression as written, and with each of the house rules applied on its own,
+    /// each with its distribution.
+    pub fn variants_with(&self, rules: &[HouseRule]) -> Result<Vec<Variant>, Error> {
+        let baseline = self.distribution()?;
+        let mean = baseline.mean();
+        let mut variants = vec![Variant {
+            rule: None,
+            expression: self.clone(),
+            distribution: baseline,
+            mean_change: 0.0,
+        }];
+        for rule in rules {
+            let expression = rule.apply(self);
+            let distribution = expression.distribution()?;
+            variants.push(Variant {
+                rule: Some(rule.clone()),
+                mean_change: distribution.mean() - mean,
+                expression,
+                distribution,
+            });
+        }
+        Ok(variants)
+    }
+}
+
+/// The variants of each expression in a library under each of the house rules:
+/// [`Closed::variants_with`] for every expression, in order.
+pub fn variants_of_all(
+    library: &[Closed],
+    rules: &[HouseRule],
+) -> Result<Vec<Vec<Variant>>, Error> {
+    library.iter().map(|e| e.variants_with(rules)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use num::rational::Ratio;
+
+    use super::*;
+
+    fn apply(rule: HouseRule, e: &str) -> String {
+        rule.apply(&e.parse().unwrap()).to_string()
+    }
+
+    #[test]
+    fn rewrites_dice() {
+        assert_eq!(
+            apply(HouseRule::Advantage(20), "d20 + 5 >= 15"),
+            "2d20kh+5 ≥ 15"
+        );
+        // Only the dice with that many faces.
+        assert_eq!(apply(HouseRule::Disadvantage(20), "d20 + d6"), "2d20kl+d6");
+        assert_eq!(apply(HouseRule::RerollAtMost(2), "2d6 + 3"), "2d6ro≤2+3");
+        assert_eq!(apply(HouseRule::Explode(6), "4d6kh3"), "4d6!kh3");
+        // Symbols stay bound.
+        assert_eq!(
+            apply(HouseRule::RerollAtMost(1), "[X: d20] X * d4"),
+            "[X: d20ro≤1] X * d4ro≤1"
+        );
+    }
+
+    #[test]
+    fn compare_rules() {
+        let e: Closed = "d20 + 5 >= 15".parse().unwrap();
+        let got = e
+            .variants_with(&[HouseRule::Advantage(20), HouseRule::Disadvantage(20)])
+            .unwrap();
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].rule, None);
+        assert_eq!(got[0].distribution.probability(1), Ratio::new(11, 20));
+        // Missing needs both rolls to miss.
+        assert_eq!(got[1].distribution.probability(0), Ratio::new(81, 400));
+        assert!(got[1].mean_change > 0.0);
+        assert!(got[2].mean_change < 0.0);
+        assert_eq!(
+            got[1].rule.as_ref().unwrap().to_string(),
+            "advantage on d20"
+        );
+    }
+
+    #[test]
+    fn reroll_ones() {
+        let e: Closed = "d6".parse().unwrap();
+        let got = e.variants_with(&[HouseRule::RerollAtMost(1)]).unwrap();
+        assert_eq!(got[1].distribution.probability(1), Ratio::new(1, 36));
+        assert!((got[1].mean_change - 2.5 / 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn halfling_luck() {
+        assert_eq!(
+            apply(HouseRule::HalflingLuck, "d20 + 5 >= 15"),
+            "d20ro=1+5 ≥ 15"
+        );
+        assert_eq!(apply(HouseRule::HalflingLuck, "d20 + d6"), "d20ro=1+d6");
+
+        // With advantage, in either order, each d20 is rerolled on a 1.
+        let e: Closed = "d20 + 5 >= 15".parse().unwrap();
+        let lucky = HouseRule::HalflingLuck.apply(&HouseRule::Advantage(20).apply(&e));
+        let advantage = HouseRule::Advantage(20).apply(&HouseRule::HalflingLuck.apply(&e));
+        assert_eq!(lucky.to_string(), "2d20ro=1kh+5 ≥ 15");
+        assert_eq!(lucky, advantage);
+        let unlucky = HouseRule::Disadvantage(20).apply(&HouseRule::HalflingLuck.apply(&e));
+        assert_eq!(unlucky.to_string(), "2d20ro=1kl+5 ≥ 15");
+
+        // Each d20 hits on 10 or more, 11 faces each rolled with probability 21/400,
+        // and both must hit.
+        let d = unlucky.distribution().unwrap();
+        assert_eq!(d.probability(1), Ratio::new(231 * 231, 400 * 400));
+
+        let got = e.variants_with(&[HouseRule::HalflingLuck]).unwrap();
+        // A 1 in 20 chance of another 11 in 20 chance to hit.
+        assert!((got[1].mean_change - 0.55 / 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bless() {
+        let bless = HouseRule::D20Bonus("d4".parse().unwrap());
+        assert_eq!(apply(bless.clone(), "d20 + 5 >= 15"), "(d20+5)+d4 ≥ 15");
+        assert_eq!(apply(bless.clone(), "15 <= d20"), "15 ≤ d20+d4");
+        // Not a d20 test: the d20 is on the wrong side, or it's not a roll against a target.
+        assert_eq!(apply(bless.clone(), "15 >= d20"), "15 ≥ d20");
+        assert_eq!(apply(bless.clone(), "d6 >= 4"), "d6 ≥ 4");
+        assert_eq!(apply(bless.clone(), "d20 + 5"), "d20+5");
+        // A natural 20 is still a natural 20, but the attack roll gets the bonus.
+        assert_eq!(
+            apply(
+                bless.clone(),
+                "[ATK: d20] (ATK = 20) * 2d6 + (ATK + 5 >= 15) * d6"
+            ),
+            "[ATK: d20] (ATK = 20) * 2d6+((ATK+5)+d4 ≥ 15) * d6"
+        );
+    }
+
+    #[test]
+    fn library() {
+        let library: Vec<Closed> = ["d20 + 5 >= 15", "2d6 + 3", "[S: d20] S + 2 >= 12"]
+            .iter()
+            .map(|e| e.parse().unwrap())
+            .collect();
+        let bless = HouseRule::D20Bonus("d4".parse().unwrap());
+        let got = variants_of_all(&library, std::slice::from_ref(&bless)).unwrap();
+        assert_eq!(got.len(), 3);
+        for variants in &got {
+            assert_eq!(variants.len(), 2);
+            assert_eq!(variants[1].rule, Some(bless.clone()));
+        }
+        // +2.5 on average: 2.5 in 20 more hits.
+        assert!((got[0][1].mean_change - 0.125).abs() < 1e-9);
+        assert_eq!(got[1][1].mean_change, 0.0);
+        assert!((got[2][1].mean_change - 0.125).abs() < 1e-9);
+    }
+}
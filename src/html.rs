@@ -1,18 +1,27 @@
 //! HTML formatting for dice expressions and distributions, mostly distributions.
 
-use crate::Distribution;
+use crate::{Distribution, format::ValueFormat};
 
 /// A table showing the various distributions as bar charts.
 pub fn table_multi_dist(inputs: &[(impl AsRef<str>, Distribution)]) -> maud::PreEscaped<String> {
+    table_multi_dist_formatted(inputs, &ValueFormat::default())
+}
+
+/// A table showing the various distributions as bar charts,
+/// with values presented in the given format.
+pub fn table_multi_dist_formatted(
+    inputs: &[(impl AsRef<str>, Distribution)],
+    format: &ValueFormat,
+) -> maud::PreEscaped<String> {
     // We need to know the minimum value, maximum value, and maximum frequency.
     let min = inputs
         .iter()
-        .fold(isize::MAX, |acc, (_, dist)| std::cmp::min(acc, dist.min()));
+        .fold(i64::MAX, |acc, (_, dist)| std::cmp::min(acc, dist.min()));
     let max = inputs
         .iter()
-        .fold(isize::MIN, |acc, (_, dist)| std::cmp::max(acc, dist.max()));
+        .fold(i64::MIN, |acc, (_, dist)| std::cmp::max(acc, dist.max()));
     let rows = (min..=max)
-        .map(|value| -> (isize, Vec<f64>) {
+        .map(|value| -> (i64, Vec<f64>) {
             (
                 value,
                 inputs
@@ -35,7 +44,7 @@ pub fn table_multi_dist(inputs: &[(impl AsRef<str>, Distribution)]) -> maud::Pre
             }
             @for (value, row) in rows.into_iter() {
                 tr {
-                    th scope="row" { (value) }
+                    th scope="row" { (format.format(value)) }
                     @for freq in row {
                         @let size = freq / max;
                         td style=(format!("--size: {}", size)) { span class="data" { (format!("{:.1}%", freq * 100.0)) }}
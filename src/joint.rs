@@ -0,0 +1,323 @@
+//! Joint distributions of two or more values.
+
+use std::collections::BTreeMap;
+
+use num::{Integer, rational::Ratio};
+
+use crate::{Distribution, RandomVariable};
+
+/// A joint distribution of a pair of values, e.g. a hit location and the damage dealt there.
+///
+/// Like [`Distribution`], this tracks the number of occurrences of each pair of values;
+/// the probability of a pair is its occurrences over the total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Distribution2D {
+    /// Occurrences of each pair of values. Only pairs with nonzero occurrences are present.
+    occurrences: BTreeMap<(i64, i64), usize>,
+}
+
+impl Distribution2D {
+    /// The joint distribution of two independent values.
+    pub fn independent(first: &Distribution, second: &Distribution) -> Self {
+        let mut occurrences = BTreeMap::new();
+        for (v1, o1) in first.occurrences() {
+            for (v2, o2) in second.occurrences() {
+                occurrences.insert((v1, v2), o1 * o2);
+            }
+        }
+        Self { occurrences }
+    }
+
+    /// Generate the distribution with the given (pair, occurrences) entries.
+    /// Pairs may repeat; their occurrences are summed.
+    ///
+    /// Returns None if there are no occurrences.
+    pub(crate) fn from_occurrences(
+        entries: impl IntoIterator<Item = ((i64, i64), usize)>,
+    ) -> Option<Self> {
+        let mut occurrences = BTreeMap::new();
+        for (pair, o) in entries {
+            if o != 0 {
+                *occurrences.entry(pair).or_insert(0) += o;
+            }
+        }
+        if occurrences.is_empty() {
+            None
+        } else {
+            Some(Self { occurrences })
+        }
+    }
+
+    /// Report the total number of occurrences, i.e. the number of possible rolls.
+    pub fn total(&self) -> usize {
+        self.occurrences.values().sum()
+    }
+
+    /// Iterator over ((first, second), occurrences) tuples in this distribution.
+    /// Reports pairs with nonzero occurrence, in ascending order.
+    pub fn occurrences(&self) -> impl Iterator<Item = ((i64, i64), usize)> + '_ {
+        self.occurrences.iter().map(|(k, v)| (*k, *v))
+    }
+
+    /// Give the probability of this pair of values occurring.
+    pub fn probability(&self, first: i64, second: i64) -> Ratio<usize> {
+        match self.occurrences.get(&(first, second)) {
+            Some(o) => Ratio::new(*o, self.total()),
+            None => Ratio::new(0, 1),
+        }
+    }
+
+    /// The distribution of the first value, regardless of the second.
+    pub fn first(&self) -> Distribution {
+        reduced(self.occurrences().map(|((v, _), o)| (v, o))).expect("nonempty distribution")
+    }
+
+    /// The distribution of the second value, regardless of the first.
+    pub fn second(&self) -> Distribution {
+        reduced(self.occurrences().map(|((_, v), o)| (v, o))).expect("nonempty distribution")
+    }
+
+    /// The distribution of the second value, given that the first has the provided value.
+    ///
+    /// Returns None if the first value never has the provided value.
+    pub fn second_given_first(&self, first: i64) -> Option<Distribution> {
+        reduced(
+            self.occurrences
+                .range((first, i64::MIN)..=(first, i64::MAX))
+                .map(|((_, v), o)| (*v, *o)),
+        )
+    }
+}
+
+/// Build a distribution from (value, occurrences) entries, in lowest terms:
+/// a marginal of `d4` and `2d6` has the same occurrences as a plain `d4`.
+fn reduced(entries: impl IntoIterator<Item = (i64, usize)>) -> Option<Distribution> {
+    let mut occurrences = BTreeMap::new();
+    for (v, o) in entries {
+        *occurrences.entry(v).or_insert(0) += o;
+    }
+    let divisor = occurrences.values().fold(0, |acc, o| acc.gcd(o));
+    Distribution::from_occurrences(
+        occurrences
+            .into_iter()
+            .map(|(v, o)| (v, o / divisor.max(1))),
+    )
+}
+
+impl RandomVariable for Distribution2D {
+    type Value = (i64, i64);
+
+    fn support(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.occurrences.keys().copied()
+    }
+
+    fn probability(&self, (first, second): (i64, i64)) -> Ratio<usize> {
+        Distribution2D::probability(self, first, second)
+    }
+
+    /// Pairs are summed elementwise.
+    fn convolve(&self, other: &Self) -> Self {
+        Self::from_occurrences(self.occurrences().flat_map(|((a1, b1), o1)| {
+            other
+                .occurrences()
+                .map(move |((a2, b2), o2)| ((a1 + a2, b1 + b2), o1 * o2))
+        }))
+        .expect("nonempty distribution")
+    }
+
+    fn map(&self, mut f: impl FnMut((i64, i64)) -> (i64, i64)) -> Self {
+        Self::from_occurrences(self.occurrences().map(|(v, o)| (f(v), o)))
+            .expect("nonempty distribution")
+    }
+}
+
+/// A joint distribution of a tuple of values, e.g. the counts of each symbol on a pool of
+/// narrative dice.
+///
+/// Like [`Distribution`], this tracks the number of occurrences of each tuple of values;
+/// the probability of a tuple is its occurrences over the total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JointDistribution<const N: usize> {
+    /// Occurrences of each tuple of values. Only tuples with nonzero occurrences are present.
+    occurrences: BTreeMap<[i64; N], usize>,
+}
+
+impl<const N: usize> JointDistribution<N> {
+    /// A die with the given faces, each equally likely.
+    /// Faces may repeat, making them more likely.
+    ///
+    /// Returns None if there are no faces.
+    pub fn from_faces(faces: &[[i64; N]]) -> Option<Self> {
+        Self::from_occurrences(faces.iter().map(|face| (*face, 1)))
+    }
+
+    /// Generate the distribution with the given (tuple, occurrences) entries.
+    /// Tuples may repeat; their occurrences are summed.
+    ///
+    /// Returns None if there are no occurrences.
+    pub(crate) fn from_occurrences(
+        entries: impl IntoIterator<Item = ([i64; N], usize)>,
+    ) -> Option<Self> {
+        let mut occurrences = BTreeMap::new();
+        for (tuple, o) in entries {
+            if o != 0 {
+                *occurrences.entry(tuple).or_insert(0) += o;
+            }
+        }
+        if occurrences.is_empty() {
+            None
+        } else {
+            Some(Self { occurrences })
+        }
+    }
+
+    /// The distribution that is always the given tuple.
+    pub fn constant(value: [i64; N]) -> Self {
+        Self {
+            occurrences: BTreeMap::from([(value, 1)]),
+        }
+    }
+
+    /// Report the total number of occurrences, i.e. the number of possible rolls.
+    pub fn total(&self) -> usize {
+        self.occurrences.values().sum()
+    }
+
+    /// Iterator over (tuple, occurrences) pairs in this distribution.
+    /// Reports tuples with nonzero occurrence, in ascending order.
+    pub fn occurrences(&self) -> impl Iterator<Item = ([i64; N], usize)> + '_ {
+        self.occurrences.iter().map(|(k, v)| (*k, *v))
+    }
+
+    /// Give the probability of this tuple of values occurring.
+    pub fn probability(&self, value: &[i64; N]) -> Ratio<usize> {
+        match self.occurrences.get(value) {
+            Some(o) => Ratio::new(*o, self.total()),
+            None => Ratio::new(0, 1),
+        }
+    }
+
+    /// The distribution of the `i`th value, regardless of the others.
+    ///
+    /// Panics if `i` is out of range.
+    pub fn marginal(&self, i: usize) -> Distribution {
+        reduced(self.occurrences().map(|(v, o)| (v[i], o))).expect("nonempty distribution")
+    }
+
+    /// The distribution of `f(X)`, where `X` is this tuple; `f` may give a tuple of
+    /// a different length, such as a count of the symbols left after some cancel others.
+    pub fn map_tuple<const M: usize>(
+        &self,
+        mut f: impl FnMut([i64; N]) -> [i64; M],
+    ) -> JointDistribution<M> {
+        JointDistribution::from_occurrences(self.occurrences().map(|(v, o)| (f(v), o)))
+            .expect("nonempty distribution")
+    }
+
+    /// The distribution of the elementwise sum of this tuple and an independent other tuple.
+    ///
+    /// Returns None if a value or the number of occurrences is out of range.
+    pub fn checked_convolve(&self, other: &Self) -> Option<Self> {
+        let mut occurrences = BTreeMap::new();
+        for (a, o1) in self.occurrences() {
+            for (b, o2) in other.occurrences() {
+                let mut sum = [0; N];
+                for i in 0..N {
+                    sum[i] = a[i].checked_add(b[i])?;
+                }
+                let o = occurrences.entry(sum).or_insert(0usize);
+                *o = o.checked_add(o1.checked_mul(o2)?)?;
+            }
+        }
+        Some(Self { occurrences })
+    }
+
+    /// The distribution of the elementwise sum of `count` independent copies of this tuple.
+    ///
+    /// Returns None if a value or the number of occurrences is out of range.
+    pub fn checked_repeat(&self, count: usize) -> Option<Self> {
+        (0..count).try_fold(Self::constant([0; N]), |acc, _| acc.checked_convolve(self))
+    }
+}
+
+impl<const N: usize> RandomVariable for JointDistribution<N> {
+    type Value = [i64; N];
+
+    fn support(&self) -> impl Iterator<Item = [i64; N]> + '_ {
+        self.occurrences.keys().copied()
+    }
+
+    fn probability(&self, value: [i64; N]) -> Ratio<usize> {
+        JointDistribution::probability(self, &value)
+    }
+
+    /// Tuples are summed elementwise.
+    fn convolve(&self, other: &Self) -> Self {
+        self.checked_convolve(other)
+            .expect("overflow in joint distribution sum")
+    }
+
+    fn map(&self, f: impl FnMut([i64; N]) -> [i64; N]) -> Self {
+        self.map_tuple(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution_of(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn marginals() {
+        let a = distribution_of("d4");
+        let b = distribution_of("2d6");
+        let joint = Distribution2D::independent(&a, &b);
+        assert_eq!(joint.first(), a);
+        assert_eq!(joint.second(), b);
+        assert_eq!(joint.second_given_first(3), Some(b));
+        assert_eq!(joint.second_given_first(5), None);
+        assert_eq!(joint.probability(1, 2), Ratio::new(1, 144));
+    }
+
+    #[test]
+    fn convolve_elementwise() {
+        let d = Distribution2D::independent(&distribution_of("d2"), &distribution_of("d2"));
+        let sum = d.convolve(&d);
+        assert_eq!(sum.first(), distribution_of("2d2"));
+        assert_eq!(sum.second(), distribution_of("2d2"));
+        assert_eq!(
+            sum.probability_where(|(a, b)| a == 4 && b == 4),
+            Ratio::new(1, 16)
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        // A coin that shows heads, tails, or (rarely) both.
+        let coin =
+            JointDistribution::from_faces(&[[1, 0], [1, 0], [0, 1], [0, 1], [1, 1]]).unwrap();
+        let three = coin.checked_repeat(3).unwrap();
+        assert_eq!(three.total(), 125);
+        assert_eq!(three.probability(&[3, 3]), Ratio::new(1, 125));
+        assert_eq!(three.marginal(0), distribution_of("3d{0,0,1,1,1}"));
+        assert_eq!(three.convolve(&coin), coin.checked_repeat(4).unwrap());
+
+        let net = three.map_tuple(|[heads, tails]| [heads - tails]);
+        assert_eq!(net.probability(&[3]), Ratio::new(8, 125));
+        assert_eq!(
+            three.probability_where(|[heads, tails]| heads > tails),
+            net.probability_where(|[n]| n > 0)
+        );
+        assert_eq!(
+            coin.checked_repeat(0).unwrap(),
+            JointDistribution::constant([0, 0])
+        );
+
+        let huge = JointDistribution::from_faces(&[[i64::MAX]]).unwrap();
+        assert_eq!(huge.checked_repeat(2), None);
+    }
+}
@@ -7,25 +7,69 @@ use peg::{error::ParseError, str::LineCol};
 use symbolic::Symbol;
 
 mod analysis;
+mod arena;
 mod discrete;
+mod joint;
 mod parse;
+mod planner;
+mod random_variable;
+mod summary;
 mod symbolic;
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod batch;
+pub mod bounds;
+#[cfg(any(test, feature = "brute-force"))]
+pub mod brute_force;
+pub mod completions;
+#[cfg(any(test, feature = "corpus"))]
+pub mod corpus;
+pub mod dashboard;
+pub mod format;
+pub mod games;
+pub mod generate;
+pub mod house_rules;
 pub mod html;
+pub mod lint;
+pub mod locale;
+pub mod prelude;
+pub mod query;
+pub mod quiz;
+pub mod sample;
+pub mod session;
+pub mod testing;
+pub mod verbal;
 pub use analysis::Closed;
-pub use discrete::{Distribution, Evaluator};
+pub use arena::{ExprArena, ExpressionRef};
+pub use discrete::{
+    BigDistribution, Bounded, Distribution, Evaluator, Exploded, Margin, NodeProfile, Profile,
+    Provenance, Restricted,
+};
+pub use joint::{Distribution2D, JointDistribution};
+pub use parse::Parser;
+pub use planner::{Evaluation, Moments};
+pub use random_variable::RandomVariable;
+pub use summary::Summary;
 
 #[cfg(test)]
 mod properties;
 
+/// Everything that can go wrong parsing or evaluating an expression.
+///
+/// More kinds of error may be added; each has a stable [`ErrorKind::code`] for reporting it
+/// across FFI or HTTP boundaries.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error("parse error; in expression {0}; {1}")]
-    ParseError(String, ParseError<LineCol>),
+    ParseError(String, #[source] ParseError<LineCol>),
     #[error("count cannot be negative; in expression {0}")]
     NegativeCount(String),
     #[error("asked to keep {0} rolls, but the expression {1} may not generate that many")]
     KeepTooFew(usize, String),
+    #[error("asked to drop {0} rolls, but the expression {1} may not generate that many")]
+    DropTooMany(usize, String),
     #[error("denominator contains 0 in its range; in expression {0}")]
     DivideByZero(String),
     #[error("invalid character {0} in symbol; symbols may only contain A-Z")]
@@ -34,6 +78,124 @@ pub enum Error {
     UnboundSymbols(HashSet<Symbol>),
     #[error("d0 is not a valid die")]
     ZeroFacedDie(),
+    #[error("value out of range; in expression {0}")]
+    Overflow(String),
+    #[error("every roll would be rerolled; in expression {0}")]
+    RerollsForever(String),
+    #[error("evaluation exceeded its budget of combinations; in expression {0}")]
+    OverBudget(String),
+    #[error("invalid checkpoint; {0}")]
+    InvalidCheckpoint(String),
+    #[error("the condition never holds; in query {0}")]
+    ImpossibleCondition(String),
+    #[error("too many possible rolls to count; in expression {0}")]
+    TooComplex(String),
+    #[error("evaluation ran out of time; in expression {0}")]
+    Timeout(String),
+    #[error("expression is nested too deeply to evaluate; in expression {0}")]
+    RecursionLimit(String),
+}
+
+/// The kind of an [`Error`], without its details: for looking up messages in a
+/// [`locale::Locale`]'s catalog, or mapping to error codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    ParseError,
+    NegativeCount,
+    KeepTooFew,
+    DropTooMany,
+    DivideByZero,
+    InvalidSymbolCharacter,
+    UnboundSymbols,
+    ZeroFacedDie,
+    Overflow,
+    RerollsForever,
+    OverBudget,
+    InvalidCheckpoint,
+    ImpossibleCondition,
+    TooComplex,
+    Timeout,
+    RecursionLimit,
+}
+
+impl Error {
+    /// The kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::ParseError(..) => ErrorKind::ParseError,
+            Error::NegativeCount(_) => ErrorKind::NegativeCount,
+            Error::KeepTooFew(..) => ErrorKind::KeepTooFew,
+            Error::DropTooMany(..) => ErrorKind::DropTooMany,
+            Error::DivideByZero(_) => ErrorKind::DivideByZero,
+            Error::InvalidSymbolCharacter(_) => ErrorKind::InvalidSymbolCharacter,
+            Error::UnboundSymbols(_) => ErrorKind::UnboundSymbols,
+            Error::ZeroFacedDie() => ErrorKind::ZeroFacedDie,
+            Error::Overflow(_) => ErrorKind::Overflow,
+            Error::RerollsForever(_) => ErrorKind::RerollsForever,
+            Error::OverBudget(_) => ErrorKind::OverBudget,
+            Error::InvalidCheckpoint(_) => ErrorKind::InvalidCheckpoint,
+            Error::ImpossibleCondition(_) => ErrorKind::ImpossibleCondition,
+            Error::TooComplex(_) => ErrorKind::TooComplex,
+            Error::Timeout(_) => ErrorKind::Timeout,
+            Error::RecursionLimit(_) => ErrorKind::RecursionLimit,
+        }
+    }
+
+    /// The stable code for this kind of error; see [`ErrorKind::code`].
+    pub fn code(&self) -> &'static str {
+        self.kind().code()
+    }
+}
+
+impl ErrorKind {
+    /// A stable, kebab-case code for this kind of error, for reporting it across FFI or HTTP
+    /// boundaries: `parse-error`, `divide-by-zero`, and so on.
+    /// Codes don't change once they're assigned, even if the error's message does.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::ParseError => "parse-error",
+            ErrorKind::NegativeCount => "negative-count",
+            ErrorKind::KeepTooFew => "keep-too-few",
+            ErrorKind::DropTooMany => "drop-too-many",
+            ErrorKind::DivideByZero => "divide-by-zero",
+            ErrorKind::InvalidSymbolCharacter => "invalid-symbol-character",
+            ErrorKind::UnboundSymbols => "unbound-symbols",
+            ErrorKind::ZeroFacedDie => "zero-faced-die",
+            ErrorKind::Overflow => "overflow",
+            ErrorKind::RerollsForever => "rerolls-forever",
+            ErrorKind::OverBudget => "over-budget",
+            ErrorKind::InvalidCheckpoint => "invalid-checkpoint",
+            ErrorKind::ImpossibleCondition => "impossible-condition",
+            ErrorKind::TooComplex => "too-complex",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::RecursionLimit => "recursion-limit",
+        }
+    }
+
+    /// The HTTP status for a request that failed with this kind of error:
+    /// 400 for input that isn't a valid expression (or checkpoint),
+    /// 422 for a valid expression that can't be evaluated,
+    /// and 503 for one that might be, given more time or a larger budget.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorKind::ParseError
+            | ErrorKind::InvalidSymbolCharacter
+            | ErrorKind::UnboundSymbols
+            | ErrorKind::ZeroFacedDie
+            | ErrorKind::InvalidCheckpoint => 400,
+            ErrorKind::NegativeCount
+            | ErrorKind::KeepTooFew
+            | ErrorKind::DropTooMany
+            | ErrorKind::DivideByZero
+            | ErrorKind::Overflow
+            | ErrorKind::RerollsForever
+            | ErrorKind::ImpossibleCondition
+            | ErrorKind::TooComplex
+            | ErrorKind::RecursionLimit => 422,
+            ErrorKind::OverBudget | ErrorKind::Timeout => 503,
+        }
+    }
 }
 
 fn list_symbols(s: &HashSet<Symbol>) -> String {
@@ -75,6 +237,21 @@ pub fn distribution_table_inner(input: String) -> Result<PreEscaped<String>, Err
 mod tests {
     use super::*;
 
+    #[test]
+    fn error_codes() {
+        let e = "d6 / (d2 - 1)"
+            .parse::<Closed>()
+            .unwrap()
+            .distribution()
+            .unwrap_err();
+        assert_eq!((e.code(), e.kind().http_status()), ("divide-by-zero", 422));
+        let e = "3d6 +".parse::<Closed>().unwrap_err();
+        assert_eq!((e.code(), e.kind().http_status()), ("parse-error", 400));
+        assert!(std::error::Error::source(&e).is_some());
+        let e = Error::Timeout("3d6".to_owned());
+        assert_eq!((e.code(), e.kind().http_status()), ("timeout", 503));
+    }
+
     #[test]
     fn readme_examples() {
         // Manual rather than doctest, because it's also a normal .md file
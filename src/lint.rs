@@ -0,0 +1,379 @@
+//! Checks for a file of named expressions, like a library of macros shared by a group.
+//!
+//! Each line of the file names an expression: `longsword: d8 + 3`.
+//! Blank lines, and lines starting with `#`, are skipped.
+//!
+//! [`lint`] reports expressions that don't parse or can't be evaluated,
+//! expressions that are too expensive to evaluate exactly,
+//! and degenerate parts of expressions: dice that always roll the same value,
+//! rerolls that never happen, or keeps that keep every roll.
+
+use std::collections::HashMap;
+
+use crate::{
+    Closed, Error, Evaluator,
+    symbolic::{Constant, Die, ExpressionTree, ExpressionWrapper, Ranker, Symbol, Trigger},
+};
+
+/// The combinations of values an expression may take to evaluate, unless configured otherwise:
+/// enough for any expression that evaluates interactively.
+pub const DEFAULT_BUDGET: usize = 1_000_000;
+
+/// Something wrong with one line of a file of named expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// The line of the file, counting from 1.
+    pub line: usize,
+    /// The column of the line, counting from 1: where the problem is, if known,
+    /// or where the expression starts.
+    pub column: usize,
+    /// The name of the expression, if it has one.
+    pub name: String,
+    pub problem: Problem,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        if !self.name.is_empty() {
+            write!(f, "{}: ", self.name)?;
+        }
+        write!(f, "{}", self.problem)
+    }
+}
+
+/// A kind of [`Finding`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The line doesn't have a name, a colon, and an expression.
+    Unnamed,
+    /// An earlier line, given here, has the same name.
+    Duplicate(usize),
+    /// The expression doesn't parse; the parser expected one of these instead.
+    Syntax(String),
+    /// The expression parses, but can't be evaluated.
+    Invalid(String),
+    /// Evaluating the expression exactly takes more than this many combinations of values.
+    OverBudget(usize),
+    /// Part of the expression rolls dice, but always has the same value.
+    Constant { part: String, value: i64 },
+    /// Part of the expression rerolls or explodes a die on values it never rolls.
+    NeverApplies(String),
+    /// Part of the expression keeps, or drops, rolls without changing the result.
+    KeepsEverything(String),
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Unnamed => write!(f, "expected a name, a colon, and an expression"),
+            Problem::Duplicate(line) => write!(f, "name already used on line {line}"),
+            Problem::Syntax(expected) => write!(f, "syntax error; expected {expected}"),
+            Problem::Invalid(e) => write!(f, "{e}"),
+            Problem::OverBudget(budget) => {
+                write!(f, "takes more than {budget} combinations to evaluate")
+            }
+            Problem::Constant { part, value } => write!(f, "{part} always comes out {value}"),
+            Problem::NeverApplies(part) => write!(f, "{part} never rerolls or explodes"),
+            Problem::KeepsEverything(part) => write!(f, "{part} keeps every roll"),
+        }
+    }
+}
+
+/// Check each named expression in the file, evaluating each within `budget` combinations.
+///
+/// Findings are in order of the lines they're on.
+pub fn lint(source: &str, budget: usize) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut names = HashMap::new();
+    for (i, line) in source.lines().enumerate() {
+        if skipped(line) {
+            continue;
+        }
+        let line_number = i + 1;
+        let line = line.trim_end();
+        let mut finding = |column: usize, name: &str, problem| {
+            findings.push(Finding {
+                line: line_number,
+                column,
+                name: name.to_owned(),
+                problem,
+            })
+        };
+        let Some((name, expression)) = split_named(line) else {
+            let indent = line.len() - line.trim_start().len();
+            finding(indent + 1, "", Problem::Unnamed);
+            continue;
+        };
+        // The column, in characters, where the expression starts.
+        let start = line[..line.len() - expression.len()].chars().count() + 1;
+        if let Some(first) = names.insert(name.to_owned(), line_number) {
+            finding(start, name, Problem::Duplicate(first));
+            names.insert(name.to_owned(), first);
+        }
+        let e: Closed = match expression.parse() {
+            Ok(e) => e,
+            Err(Error::ParseError(_, e)) => {
+                finding(
+                    start + e.location.column - 1,
+                    name,
+                    Problem::Syntax(e.expected.to_string()),
+                );
+                continue;
+            }
+            Err(e) => {
+                finding(start, name, Problem::Invalid(e.to_string()));
+                continue;
+            }
+        };
+        let mut evaluator = Evaluator::new(true).with_budget(budget);
+        match evaluator.eval(&e) {
+            Ok(_) => (),
+            // An expression with too many rolls to count is over any budget.
+            Err(Error::OverBudget(_) | Error::TooComplex(_)) => {
+                finding(start, name, Problem::OverBudget(budget))
+            }
+            Err(e) => {
+                finding(start, name, Problem::Invalid(e.to_string()));
+                continue;
+            }
+        }
+        let mut problems = Vec::new();
+        degenerate(&e, &mut evaluator, &mut problems);
+        for problem in problems {
+            finding(start, name, problem);
+        }
+    }
+    findings
+}
+
+/// The name and expression on each line of the file that has one, in order.
+pub fn named(source: &str) -> impl Iterator<Item = (&str, &str)> {
+    source
+        .lines()
+        .filter(|line| !skipped(line))
+        .filter_map(split_named)
+}
+
+/// Whether the line of a file of named expressions is blank or a comment.
+pub(crate) fn skipped(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// The name and expression on a line of a file of named expressions,
+/// or None if the line doesn't name an expression.
+pub(crate) fn split_named(line: &str) -> Option<(&str, &str)> {
+    let (name, expression) = line.trim().split_once(':')?;
+    let name = name.trim();
+    // A binding has a colon too, but a name doesn't have brackets.
+    (!name.is_empty() && !name.contains('[')).then(|| (name, expression.trim_start()))
+}
+
+/// Find the degenerate parts of the expression, outermost first.
+fn degenerate(e: &Closed, evaluator: &mut Evaluator, problems: &mut Vec<Problem>) {
+    let part = || e.to_string();
+    match e.inner() {
+        ExpressionTree::Reroll { value, reroll } => {
+            if let ExpressionTree::Die(Die(n)) = value.inner()
+                && !(1..=*n as i64).any(|v| reroll.applies(v))
+            {
+                problems.push(Problem::NeverApplies(part()));
+            }
+        }
+        ExpressionTree::Explode {
+            value,
+            trigger: trigger @ Trigger::Compare(..),
+            ..
+        } => {
+            if let ExpressionTree::Die(Die(n)) = value.inner()
+                && !(1..=*n as i64).any(|v| trigger.explodes(*n as i64, v))
+            {
+                problems.push(Problem::NeverApplies(part()));
+            }
+        }
+        ExpressionTree::Repeated { count, ranker, .. } => {
+            if let ExpressionTree::Modifier(Constant(count)) = count.inner() {
+                let keeps_all = match ranker.fixed() {
+                    Some(Ranker::Highest(k) | Ranker::Lowest(k)) => k as u64 == *count,
+                    Some(Ranker::DropHighest(0) | Ranker::DropLowest(0)) => true,
+                    _ => false,
+                };
+                if keeps_all {
+                    problems.push(Problem::KeepsEverything(part()));
+                }
+            }
+        }
+        _ => (),
+    }
+    // Parts that use a symbol bound outside of them can't be evaluated on their own.
+    if rolls(e)
+        && !uses_free_symbol(e, &mut Vec::new())
+        && let Ok(d) = evaluator.eval(e)
+        && d.min() == d.max()
+    {
+        problems.push(Problem::Constant {
+            part: part(),
+            value: d.min(),
+        });
+        return;
+    }
+    e.inner()
+        .map_children(|child| degenerate(child, evaluator, problems));
+}
+
+/// Whether the expression rolls any dice.
+fn rolls(e: &Closed) -> bool {
+    match e.inner() {
+        ExpressionTree::Die(_) | ExpressionTree::CustomDie(_) => true,
+        node => {
+            let mut found = false;
+            node.map_children(|child| found |= rolls(child));
+            found
+        }
+    }
+}
+
+/// Whether the expression uses a symbol that isn't bound within it.
+fn uses_free_symbol(e: &Closed, bound: &mut Vec<Symbol>) -> bool {
+    match e.inner() {
+        ExpressionTree::Symbol(s) => !bound.contains(s),
+        ExpressionTree::Binding {
+            symbol,
+            value,
+            tail,
+        } => {
+            if uses_free_symbol(value, bound) {
+                return true;
+            }
+            bound.push(symbol.clone());
+            let free = uses_free_symbol(tail, bound);
+            bound.pop();
+            free
+        }
+        node => {
+            let mut found = false;
+            node.map_children(|child| found |= uses_free_symbol(child, bound));
+            found
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(source: &str) -> Vec<(usize, usize, Problem)> {
+        lint(source, DEFAULT_BUDGET)
+            .into_iter()
+            .map(|f| (f.line, f.column, f.problem))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        let source = "# Attacks\n\
+                      longsword: d20 + 5 >= 15\n\
+                      \n\
+                      sneak attack: [ATK: d20] (ATK + 7 >= 14) * 3d6\n\
+                      strength: 3\n";
+        assert_eq!(problems(source), []);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let source = "fine: d6\nbroken: 2d6 + \nno name\n[X: d6] X\n";
+        let got = lint(source, DEFAULT_BUDGET);
+        assert_eq!(got.len(), 3);
+        assert_eq!((got[0].line, got[0].name.as_str()), (2, "broken"));
+        assert!(matches!(got[0].problem, Problem::Syntax(_)));
+        // The error is after the "+".
+        assert_eq!(got[0].column, 14);
+        assert_eq!(got[1].problem, Problem::Unnamed);
+        assert_eq!(got[2].problem, Problem::Unnamed);
+        assert!(
+            got[0].to_string().starts_with("2:14: broken: syntax error"),
+            "{}",
+            got[0]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let source = "unbound: X + 1\nzero: d6 / (d2 - 1)\nagain: d6\nagain: d8\n";
+        let got = problems(source);
+        assert_eq!(got.len(), 3);
+        assert!(matches!(got[0], (1, 10, Problem::Invalid(_))));
+        assert!(matches!(got[1], (2, 7, Problem::Invalid(_))));
+        assert_eq!(got[2], (4, 8, Problem::Duplicate(3)));
+    }
+
+    #[test]
+    fn degenerate_parts() {
+        let source = "one: d1 + d6\n\
+                      same: d{2,2} + 3\n\
+                      always: (d6 <= 6) * d8\n\
+                      never: d6ro>6\n\
+                      all: 3d6kh3\n\
+                      none: 4d6dl0\n\
+                      bound: [X: d6] X - X\n";
+        let got = problems(source);
+        assert_eq!(
+            got,
+            [
+                (
+                    1,
+                    6,
+                    Problem::Constant {
+                        part: "d1".to_owned(),
+                        value: 1
+                    }
+                ),
+                (
+                    2,
+                    7,
+                    Problem::Constant {
+                        part: "d{2,2}+3".to_owned(),
+                        value: 5
+                    }
+                ),
+                (
+                    3,
+                    9,
+                    Problem::Constant {
+                        part: "d6 ≤ 6".to_owned(),
+                        value: 1
+                    }
+                ),
+                (4, 8, Problem::NeverApplies("d6ro>6".to_owned())),
+                (5, 6, Problem::KeepsEverything("3d6kh3".to_owned())),
+                (6, 7, Problem::KeepsEverything("4d6dl0".to_owned())),
+                (
+                    7,
+                    8,
+                    Problem::Constant {
+                        part: "[X: d6] X-X".to_owned(),
+                        value: 0
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn names() {
+        let source = "# Attacks\nlongsword: d20 + 5 >= 15\n\n[X: d6] X\n  great axe : d12\n";
+        let got: Vec<_> = named(source).collect();
+        assert_eq!(
+            got,
+            vec![("longsword", "d20 + 5 >= 15"), ("great axe", "d12")]
+        );
+    }
+
+    #[test]
+    fn over_budget() {
+        let got = lint("huge: 100d100 * 100d100\n", 1000);
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].problem, Problem::OverBudget(1000));
+    }
+}
@@ -0,0 +1,269 @@
+//! Dice notation and error messages in languages other than English.
+//!
+//! A [`Locale`] has letters that stand for "d" in dice, like the German `W` (for _Würfel_)
+//! in `3W6`, which [`Parser::with_locale`](crate::Parser::with_locale) accepts; and a catalog of
+//! error messages, keyed by [`ErrorKind`], which [`Locale::message`] fills in.
+//! Kinds missing from the catalog get the English message.
+//!
+//! A message is a template: `{0}`, `{1}` and so on stand for the error's details, in order:
+//!
+//! | Kind | Details |
+//! |------|---------|
+//! | [`ErrorKind::ParseError`] | the expression, the line, the column, and what was expected |
+//! | [`ErrorKind::KeepTooFew`], [`ErrorKind::DropTooMany`] | the count, and the expression |
+//! | [`ErrorKind::InvalidSymbolCharacter`] | the character |
+//! | [`ErrorKind::UnboundSymbols`] | the symbols |
+//! | [`ErrorKind::ZeroFacedDie`] | nothing |
+//! | any other | the expression, or (for checkpoints) what's wrong |
+
+use std::collections::HashMap;
+
+use crate::{Error, ErrorKind, list_symbols};
+
+/// Dice notation and error messages for a language.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Locale {
+    die_letters: Vec<String>,
+    messages: HashMap<ErrorKind, String>,
+}
+
+impl Locale {
+    /// English: just "d", and the standard messages.
+    pub fn english() -> Self {
+        Self::default()
+    }
+
+    /// German: `W` or `w` for _Würfel_, as in `3W6`, and German messages.
+    pub fn german() -> Self {
+        [
+            (
+                ErrorKind::ParseError,
+                "Syntaxfehler in {0}, Zeile {1}, Spalte {2}; erwartet: {3}",
+            ),
+            (
+                ErrorKind::NegativeCount,
+                "Anzahl darf nicht negativ sein; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::KeepTooFew,
+                "{0} Würfe sollen behalten werden, aber {1} ergibt womöglich nicht so viele",
+            ),
+            (
+                ErrorKind::DropTooMany,
+                "{0} Würfe sollen verworfen werden, aber {1} ergibt womöglich nicht so viele",
+            ),
+            (
+                ErrorKind::DivideByZero,
+                "der Nenner kann 0 sein; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::InvalidSymbolCharacter,
+                "ungültiges Zeichen {0} im Symbol; Symbole bestehen nur aus A-Z",
+            ),
+            (
+                ErrorKind::UnboundSymbols,
+                "Symbol(e) ohne Bindung verwendet: {0}",
+            ),
+            (ErrorKind::ZeroFacedDie, "W0 ist kein gültiger Würfel"),
+            (
+                ErrorKind::Overflow,
+                "Wert außerhalb des Wertebereichs; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::RerollsForever,
+                "jeder Wurf würde neu gewürfelt; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::OverBudget,
+                "die Auswertung hat ihr Budget an Kombinationen überschritten; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::InvalidCheckpoint,
+                "ungültiger Sicherungspunkt; {0}",
+            ),
+            (
+                ErrorKind::ImpossibleCondition,
+                "die Bedingung trifft nie zu; in der Abfrage {0}",
+            ),
+            (
+                ErrorKind::TooComplex,
+                "zu viele mögliche Würfe zum Zählen; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::Timeout,
+                "die Auswertung hat zu lange gedauert; im Ausdruck {0}",
+            ),
+            (
+                ErrorKind::RecursionLimit,
+                "der Ausdruck ist zu tief verschachtelt; im Ausdruck {0}",
+            ),
+        ]
+        .into_iter()
+        .fold(
+            Self::default().with_die_letter("W").with_die_letter("w"),
+            |locale, (kind, message)| locale.with_message(kind, message),
+        )
+    }
+
+    /// Accept the letter (or word) in place of "d" in dice.
+    ///
+    /// Panics if the letter is empty or has characters other than letters.
+    pub fn with_die_letter(mut self, letter: impl Into<String>) -> Self {
+        let letter = letter.into();
+        assert!(
+            !letter.is_empty() && letter.chars().all(char::is_alphabetic),
+            "die letter {letter:?} may only contain letters"
+        );
+        self.die_letters.push(letter);
+        self
+    }
+
+    /// Use the template for errors of this kind; see the [module](self) for its placeholders.
+    pub fn with_message(mut self, kind: ErrorKind, template: impl Into<String>) -> Self {
+        self.messages.insert(kind, template.into());
+        self
+    }
+
+    /// The letters accepted in place of "d" in dice.
+    pub fn die_letters(&self) -> &[String] {
+        &self.die_letters
+    }
+
+    /// The error's message in this locale.
+    pub fn message(&self, e: &Error) -> String {
+        let Some(template) = self.messages.get(&e.kind()) else {
+            return e.to_string();
+        };
+        fill(template, &details(e))
+    }
+}
+
+/// Replace each `{i}` in the template with the `i`th detail, in one pass, so placeholders
+/// in the details themselves are left alone. Other braces are kept as they are.
+fn fill(template: &str, details: &[String]) -> String {
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        message.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let detail = rest[1..]
+            .split_once('}')
+            .filter(|(i, _)| i.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|(i, _)| Some((i.len(), details.get(i.parse::<usize>().ok()?)?)));
+        match detail {
+            Some((digits, detail)) => {
+                message.push_str(detail);
+                rest = &rest[digits + 2..];
+            }
+            None => {
+                message.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// The details of the error, in the order its message template refers to them.
+fn details(e: &Error) -> Vec<String> {
+    match e {
+        Error::ParseError(expression, e) => vec![
+            expression.clone(),
+            e.location.line.to_string(),
+            e.location.column.to_string(),
+            e.expected.to_string(),
+        ],
+        Error::KeepTooFew(n, expression) | Error::DropTooMany(n, expression) => {
+            vec![n.to_string(), expression.clone()]
+        }
+        Error::InvalidSymbolCharacter(c) => vec![c.to_string()],
+        Error::UnboundSymbols(symbols) => vec![list_symbols(symbols)],
+        Error::ZeroFacedDie() => vec![],
+        Error::NegativeCount(s)
+        | Error::DivideByZero(s)
+        | Error::Overflow(s)
+        | Error::RerollsForever(s)
+        | Error::OverBudget(s)
+        | Error::InvalidCheckpoint(s)
+        | Error::ImpossibleCondition(s)
+        | Error::TooComplex(s)
+        | Error::Timeout(s)
+        | Error::RecursionLimit(s) => vec![s.clone()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Closed, Parser};
+
+    #[test]
+    fn german_dice() {
+        let parser = Parser::new().with_locale(&Locale::german());
+        for (german, english) in [
+            ("3W6", "3d6"),
+            ("2w20kh + 5", "2d20kh + 5"),
+            ("4WF", "4dF"),
+            ("W{1,1,2}", "d{1,1,2}"),
+            ("W%", "d%"),
+            ("[W: W6] W + 1", "[W: d6] W + 1"),
+        ] {
+            let got = parser.parse(german).unwrap();
+            assert_eq!(got, english.parse::<Closed>().unwrap(), "{german}");
+        }
+        // "d" still works, and other parsers don't know "W".
+        assert!(parser.parse("3d6").is_ok());
+        assert!(Parser::new().parse("3W6").is_err());
+    }
+
+    #[test]
+    fn german_messages() {
+        let german = Locale::german();
+        let e = "d6 / (d2 - 1)"
+            .parse::<Closed>()
+            .unwrap()
+            .distribution()
+            .unwrap_err();
+        assert_eq!(
+            german.message(&e),
+            "der Nenner kann 0 sein; im Ausdruck d6 / (d2-1)"
+        );
+        let e = "4d6kh5"
+            .parse::<Closed>()
+            .unwrap()
+            .distribution()
+            .unwrap_err();
+        assert_eq!(
+            german.message(&e),
+            "5 Würfe sollen behalten werden, aber 4d6kh5 ergibt womöglich nicht so viele"
+        );
+        let e = "3W6 +".parse::<Closed>().unwrap_err();
+        assert!(
+            german
+                .message(&e)
+                .starts_with("Syntaxfehler in 3W6 +, Zeile 1, Spalte 2; erwartet: "),
+            "{}",
+            german.message(&e)
+        );
+    }
+
+    #[test]
+    fn catalog() {
+        let e = Error::ZeroFacedDie();
+        assert_eq!(Locale::english().message(&e), e.to_string());
+        let custom = Locale::english().with_message(ErrorKind::Overflow, "too big: {0}!");
+        assert_eq!(
+            custom.message(&Error::Overflow("1d6".to_owned())),
+            "too big: 1d6!"
+        );
+        assert_eq!(custom.message(&e), e.to_string());
+    }
+
+    #[test]
+    fn placeholders_in_details() {
+        let custom = Locale::english().with_message(ErrorKind::KeepTooFew, "{1}: keep {0} {2}");
+        let e = Error::KeepTooFew(3, "{0}d6".to_owned());
+        assert_eq!(custom.message(&e), "{0}d6: keep 3 {2}");
+    }
+}
@@ -1,21 +1,112 @@
 //! The types and grammar for parsing dice expressions.
 
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
-use crate::symbolic::*;
+use crate::{analysis::Closed, locale::Locale, symbolic::*};
 
 type ExpressionTree = crate::symbolic::ExpressionTree<RawExpression>;
 
+/// Faces of the named dice available to the parser.
+type NamedDice = HashMap<String, Vec<i64>>;
+
 peg::parser! {
-    grammar dice_notation() for str {
+    grammar dice_notation(parser: &Parser) for str {
         rule number() -> usize
           = n:$(['0'..='9']+) {? n.parse().or(Err("usize")) }
 
+        rule constant() -> Constant
+          = n:$(['0'..='9']+) {? n.parse().map(Constant).or(Err("u64")) }
+
+        rule integer() -> i64
+          = n:$("-"? ['0'..='9']+) {? n.parse().or(Err("i64")) }
+
         rule die() -> RawExpression
-            = "d" n:number() { Die(n).into() }
+            = d:exploded_die() successes:successes() {
+                ExpressionTree::Successes { value: Box::new(d), successes }.into()
+            }
+            / exploded_die()
+
+        rule successes() -> Successes
+            = "s" op:compare_op()? threshold:integer()
+              botch:("f" op:compare_op()? threshold:integer() { (op.unwrap_or(ComparisonOp::Eq), threshold) })? {
+                Successes { op: op.unwrap_or(ComparisonOp::Eq), threshold, botch }
+            }
+
+        rule exploded_die() -> RawExpression
+            = d:rerolled_die() explosion:explosion() trigger:trigger() {
+                ExpressionTree::Explode { value: Box::new(d), explosion, trigger }.into()
+            }
+            / rerolled_die()
+
+        rule rerolled_die() -> RawExpression
+            = d:base_die() reroll:reroll() {
+                ExpressionTree::Reroll { value: Box::new(d), reroll }.into()
+            }
+            / base_die()
+
+        rule reroll() -> Reroll
+            // A bare "r" rerolls recursively, as "rr" does.
+            = "r" recursive:("o" { false } / "r" { true })? op:compare_op()? threshold:integer() {
+                Reroll { op: op.unwrap_or(ComparisonOp::Eq), threshold, recursive: recursive.unwrap_or(true) }
+            }
+            / "gwf" { Reroll::GREAT_WEAPON_FIGHTING }
+
+        rule explosion() -> Explosion
+            = "!!" { Explosion::Compounding }
+            / "!p" { Explosion::Penetrating }
+            / "!i" { Explosion::Imploding }
+            / "!" { Explosion::Standard }
+
+        // Without a comparison, a die explodes on its highest face.
+        rule trigger() -> Trigger
+            = op:compare_op() threshold:integer() { Trigger::Compare(op, threshold) }
+            / "" { Trigger::Highest }
+
+        // "d", or a letter standing for it in the parser's locale, like the German "W" in `3W6`.
+        rule die_letter()
+            = "d" / #{|input, pos| die_letter(&parser.die_letters, input, pos)}
+
+        rule base_die() -> RawExpression
+            = die_letter() digits:$("666" / "66") !['0'..='9'] {
+                CustomDie::Digits(vec![6; digits.len()]).into()
+            }
+            / die_letter() "(" space() sizes:(number() ++ (space() "," space())) space() ")" {?
+                if sizes.contains(&0) {
+                    Err("dice with at least one face")
+                } else {
+                    Ok(CustomDie::Digits(sizes).into())
+                }
+            }
+            / die_letter() n:number() { Die(n).into() }
+            / die_letter() "[" space() lo:integer() space() ".." space() hi:integer() space() "]" {?
+                if lo <= hi {
+                    Ok(CustomDie::Range(lo, hi).into())
+                } else {
+                    Err("range with lower bound no greater than upper bound")
+                }
+            }
+            / die_letter() "F" { CustomDie::Fudge.into() }
+            / die_letter() "{" space() faces:(weighted_face() ++ (space() "," space())) space() "}" {
+                CustomDie::Weighted(faces).into()
+            }
+            / die_letter() "{" space() faces:(integer() ++ (space() "," space())) space() "}" {
+                CustomDie::Faces(faces).into()
+            }
+            // Percentile dice: a tens die and a units die, the same as a d100.
+            / die_letter() "%" { Die(100).into() }
+            / die_letter() d:#{|input, pos| named_die(&parser.dice, input, pos)} { d.into() }
+
+        rule weighted_face() -> (i64, usize)
+            = face:integer() space() ":" space() weight:number() {?
+                if weight > 0 {
+                    Ok((face, weight))
+                } else {
+                    Err("positive weight")
+                }
+            }
 
         rule modifier() -> RawExpression
-            = "+"? n:number() { Constant(n).into() }
+            = "+"? n:constant() { n.into() }
 
         rule symbol_token() -> Symbol
             = s:$(['a'..='z'|'A'..='Z']+) {? s.parse().or(Err("symbol")) }
@@ -23,6 +114,7 @@ peg::parser! {
         rule symbol_expr() -> RawExpression
             = s:symbol_token() { s.into() }
 
+        #[cache]
         rule paren() -> RawExpression
             = "(" space() e:expression() space() ")" { e }
 
@@ -30,21 +122,83 @@ peg::parser! {
             = paren() / die()
 
         rule repetitions() -> RawExpression
-            = n:number() { Constant(n).into() }
+            = n:constant() { n.into() }
             / paren()
 
         rule repeat() -> RawExpression
-            = count:repetitions() space() expr:repeatable() rank:ranker()? {
+            // Roll-and-keep, as in Legend of the Five Rings: `7k3` rolls seven d10s,
+            // whose tens explode into the same die, and keeps the highest three.
+            = count:constant() "k" keep:constant() {
+                let die = ExpressionTree::Explode {
+                    value: Box::new(Die(10).into()),
+                    explosion: Explosion::Compounding,
+                    trigger: Trigger::Highest,
+                };
+                ExpressionTree::Repeated {
+                    count: Box::new(count.into()),
+                    value: Box::new(die.into()),
+                    ranker: Ranker::Highest(Box::new(keep.into())),
+                }.into()
+            }
+            / count:repetitions() space() expr:repeatable() rank:ranker()? {
                 ExpressionTree::Repeated{count: Box::new(count), value: Box::new(expr), ranker: rank.unwrap_or(Ranker::All)}.into() }
 
-        rule ranker() -> Ranker
-            = "kl" n:number()? { Ranker::Lowest(n.unwrap_or(1)) }
-            / "kh" n:number()? { Ranker::Highest(n.unwrap_or(1)) }
+        rule ranker() -> Ranker<Box<RawExpression>>
+            = "kl" n:rank_count() { Ranker::Lowest(n) }
+            / "kh" n:rank_count() { Ranker::Highest(n) }
+            / "dl" n:rank_count() { Ranker::DropLowest(n) }
+            / "dh" n:rank_count() { Ranker::DropHighest(n) }
+            / "m" { Ranker::Matching }
+
+        // The count kept or dropped: a number, an expression in parentheses, or 1 if omitted.
+        rule rank_count() -> Box<RawExpression>
+            = n:constant() { Box::new(n.into()) }
+            / e:paren() { Box::new(e) }
+            / "" { Box::new(Constant(1).into()) }
 
         rule space() = quiet!{[' ' | '\n' | '\r' | '\t']*}
 
+        rule call() -> RawExpression
+            // `adv(d20 + 5)` is `2d20kh + 5`: only the d20 is rolled twice.
+            = "adv(" e:expression() ")" {? advantage(&e, Ranker::Highest).ok_or("a d20 in adv()") }
+            / "dis(" e:expression() ")" {? advantage(&e, Ranker::Lowest).ok_or("a d20 in dis()") }
+            / name:$(['a'..='z']+) "(" args:(expression() ** ",") ")" {?
+                let function = Function::ALL.iter().find(|f| f.name() == name).ok_or("function name")?;
+                if args.len() == function.arity() {
+                    Ok(ExpressionTree::Call { function: *function, args }.into())
+                } else {
+                    Err("matching number of function arguments")
+                }
+            }
+
+        // A band without a lower (or upper) end extends as far as values go.
+        rule band() -> Band
+            = lo:integer()? ".." hi:integer()? space() ":" space() result:integer() {
+                Band { lo: lo.unwrap_or(i64::MIN), hi: hi.unwrap_or(i64::MAX), result }
+            }
+            / v:integer() space() ":" space() result:integer() {
+                Band { lo: v, hi: v, result }
+            }
+
+        rule bands() -> Bands
+            = "{" space() bands:(band() ++ (space() "," space())) space() "}" {?
+                Bands::new(bands).ok_or("non-empty, non-overlapping bands")
+            }
+
+        // Cached, like paren(): many alternatives start with the same operand, and
+        // re-parsing it for each would take time exponential in how deeply it nests.
+        #[cache]
+        rule lookup_value() -> RawExpression
+            = call() / repeat() / die() / modifier() / symbol_expr() / paren()
+
         rule pos_subterm() -> RawExpression
-            = repeat() / die() / modifier() / symbol_expr() / paren()
+            = value:lookup_value()
+              bands:(space() "->" space() ("bands" space())? b:bands() { b })? {
+                match bands {
+                    Some(bands) => ExpressionTree::Lookup { value: Box::new(value), bands }.into(),
+                    None => value,
+                }
+            }
 
         rule subterm() -> RawExpression
             = pos_subterm()
@@ -53,6 +207,7 @@ peg::parser! {
         rule term() -> RawExpression
             = e1:subterm() space() "*" space() e2:term() { ExpressionTree::Product(Box::new(e1), Box::new(e2)).into() }
             / e1:subterm() space() "/" space() e2:term() { ExpressionTree::Floor(Box::new(e1), Box::new(e2)).into() }
+            / e1:subterm() space() "%" space() e2:term() { ExpressionTree::Modulo(Box::new(e1), Box::new(e2)).into() }
             // e1:subterm() space() "/^" space() e2:subterm() { Expression::Ceiling(e1, e2) }
             / subterm()
 
@@ -82,16 +237,143 @@ peg::parser! {
         rule symbolic_expression() -> RawExpression
             = comparison() / space() e:sum() space() { e }
 
+        // `if C then A else B` is another way to write `if(C, A, B)`.
+        rule conditional() -> RawExpression
+            = "if" space() c:symbolic_expression() space() "then" space()
+                a:symbolic_expression() space() "else" space() b:binding() {
+                ExpressionTree::Call { function: Function::If, args: vec![c, a, b] }.into()
+            }
+
         rule binding() -> RawExpression
             = "[" space() symbol:symbol_token() space() ":" e:expression() "]" tail:expression() {
                 ExpressionTree::Binding{symbol, value: Box::new(e), tail: Box::new(tail) }.into()
-            } / symbolic_expression()
+            } / conditional() / symbolic_expression()
 
         pub(crate) rule expression() -> RawExpression
             = space() e:binding() space() { e }
     }
 }
 
+/// Match the longest of the letters standing for "d" at the given position.
+fn die_letter(letters: &[String], input: &str, pos: usize) -> peg::RuleResult<()> {
+    let rest = &input[pos..];
+    match letters
+        .iter()
+        .filter(|letter| rest.starts_with(letter.as_str()))
+        .max_by_key(|letter| letter.len())
+    {
+        Some(letter) => peg::RuleResult::Matched(pos + letter.len(), ()),
+        None => peg::RuleResult::Failed,
+    }
+}
+
+/// Match the longest registered die name at the given position.
+fn named_die(dice: &NamedDice, input: &str, pos: usize) -> peg::RuleResult<CustomDie> {
+    let rest = &input[pos..];
+    match dice
+        .iter()
+        .filter(|(name, _)| rest.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+    {
+        Some((name, faces)) => peg::RuleResult::Matched(
+            pos + name.len(),
+            CustomDie::Named {
+                name: name.clone(),
+                faces: faces.clone(),
+            },
+        ),
+        None => peg::RuleResult::Failed,
+    }
+}
+
+/// A parser for dice expressions, which may be extended with custom dice.
+///
+/// `str::parse` is equivalent to parsing with `Parser::default()`.
+#[derive(Debug, Default, Clone)]
+pub struct Parser {
+    dice: NamedDice,
+    /// Letters that stand for "d", from the parser's locale.
+    die_letters: Vec<String>,
+}
+
+impl Parser {
+    /// Create a new Parser, with only the standard dice.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a die with the given name and faces, rolled as `d<name>`.
+    /// Faces may repeat, to make some values more likely than others.
+    ///
+    /// For instance, after `with_die("hitloc", [1, 1, 2, 3])`, `2dhitloc` rolls two such dice.
+    ///
+    /// Panics if the name is empty or contains characters other than a-z,
+    /// or if the die has no faces.
+    pub fn with_die(
+        mut self,
+        name: impl Into<String>,
+        faces: impl IntoIterator<Item = i64>,
+    ) -> Self {
+        let name = name.into();
+        assert!(
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase()),
+            "die name {name:?} may only contain a-z"
+        );
+        let faces: Vec<i64> = faces.into_iter().collect();
+        assert!(!faces.is_empty(), "die {name:?} has no faces");
+        self.dice.insert(name, faces);
+        self
+    }
+
+    /// Accept the locale's letters for dice, as well as "d": with [`Locale::german`],
+    /// `3W6` is the same as `3d6`. Expressions are still displayed with "d".
+    pub fn with_locale(mut self, locale: &Locale) -> Self {
+        self.die_letters
+            .extend(locale.die_letters().iter().cloned());
+        self
+    }
+
+    /// Parse the expression, using any registered dice.
+    pub fn parse(&self, s: &str) -> Result<Closed, crate::Error> {
+        self.parse_raw(s)?.try_into()
+    }
+
+    fn parse_raw(&self, s: &str) -> Result<RawExpression, crate::Error> {
+        Ok(dice_notation::expression(s, self)
+            .map_err(|e| crate::Error::ParseError(s.to_owned(), e))?
+            .simplify())
+    }
+}
+
+/// The expression with each d20 rolled twice, keeping the highest or lowest with the ranker;
+/// or None if the expression doesn't roll a d20.
+fn advantage(
+    e: &RawExpression,
+    ranker: fn(Box<RawExpression>) -> Ranker<Box<RawExpression>>,
+) -> Option<RawExpression> {
+    fn rewrite(
+        e: &RawExpression,
+        ranker: fn(Box<RawExpression>) -> Ranker<Box<RawExpression>>,
+        found: &mut bool,
+    ) -> RawExpression {
+        match &e.0 {
+            ExpressionTree::Die(Die(20)) => {
+                *found = true;
+                ExpressionTree::Repeated {
+                    count: Box::new(Constant(2).into()),
+                    value: Box::new(e.clone()),
+                    ranker: ranker(Box::new(Constant(1).into())),
+                }
+                .into()
+            }
+            node => RawExpression(node.map_children(|child| rewrite(child, ranker, found))),
+        }
+    }
+    let mut found = false;
+    let e = rewrite(e, ranker, &mut found);
+    found.then_some(e)
+}
+
 /// An expression tree that has not been semantically analyzed.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -113,9 +395,7 @@ impl FromStr for RawExpression {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(dice_notation::expression(s)
-            .map_err(|e| crate::Error::ParseError(s.to_owned(), e))?
-            .simplify())
+        Parser::default().parse_raw(s)
     }
 }
 
@@ -135,6 +415,7 @@ impl RawExpression {
         match self.0 {
             ExpressionTree::Modifier(_) => self,
             ExpressionTree::Die(_) => self,
+            ExpressionTree::CustomDie(_) => self,
             ExpressionTree::Symbol(_) => self,
             ExpressionTree::Negated(inner) => {
                 let simpl = inner.simplify();
@@ -150,7 +431,7 @@ impl RawExpression {
             } => ExpressionTree::Repeated {
                 count: Box::new(count.simplify()),
                 value: Box::new(value.simplify()),
-                ranker,
+                ranker: ranker.map(|n| Box::new(n.clone().simplify())),
             }
             .into(),
             ExpressionTree::Product(a, b) => {
@@ -159,6 +440,9 @@ impl RawExpression {
             ExpressionTree::Floor(a, b) => {
                 ExpressionTree::Floor(Box::new(a.simplify()), Box::new(b.simplify())).into()
             }
+            ExpressionTree::Modulo(a, b) => {
+                ExpressionTree::Modulo(Box::new(a.simplify()), Box::new(b.simplify())).into()
+            }
             ExpressionTree::Sum(expressions) => {
                 let mut es = Vec::new();
                 for e in expressions.into_iter() {
@@ -194,6 +478,36 @@ impl RawExpression {
                 }
                 .into()
             }
+            ExpressionTree::Call { function, args } => ExpressionTree::Call {
+                function,
+                args: args.into_iter().map(|e| e.simplify()).collect(),
+            }
+            .into(),
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => ExpressionTree::Explode {
+                value: Box::new(value.simplify()),
+                explosion,
+                trigger,
+            }
+            .into(),
+            ExpressionTree::Reroll { value, reroll } => ExpressionTree::Reroll {
+                value: Box::new(value.simplify()),
+                reroll,
+            }
+            .into(),
+            ExpressionTree::Successes { value, successes } => ExpressionTree::Successes {
+                value: Box::new(value.simplify()),
+                successes,
+            }
+            .into(),
+            ExpressionTree::Lookup { value, bands } => ExpressionTree::Lookup {
+                value: Box::new(value.simplify()),
+                bands,
+            }
+            .into(),
         }
     }
 }
@@ -224,6 +538,20 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    #[test]
+    fn deeply_nested() {
+        // Each level of parentheses is parsed once, not once per way it might continue.
+        let depth = 40;
+        let nested = format!("{}1d6 + 1d6{}", "(".repeat(depth), ")".repeat(depth));
+        let start = std::time::Instant::now();
+        let got: RawExpression = nested.parse().unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(got, "1d6 + 1d6".parse().unwrap());
+        let nested = (0..depth).fold("d6".to_owned(), |e, _| format!("max(({e}) * 2, d4)"));
+        nested.parse::<RawExpression>().unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
     #[test]
     fn modifier() {
         let got: RawExpression = "3".parse().unwrap();
@@ -252,7 +580,7 @@ mod tests {
         let want = ExpressionTree::Repeated {
             count: Box::new(Constant(2).into()),
             value: Box::new(Die(20).into()),
-            ranker: Ranker::Lowest(1),
+            ranker: Ranker::Lowest(Box::new(Constant(1).into())),
         };
         assert_eq!(got, want.into());
     }
@@ -263,11 +591,22 @@ mod tests {
         let want = ExpressionTree::Repeated {
             count: Box::new(Constant(4).into()),
             value: Box::new(Die(6).into()),
-            ranker: Ranker::Highest(3),
+            ranker: Ranker::Highest(Box::new(Constant(3).into())),
         };
         assert_eq!(got, want.into());
     }
 
+    #[test]
+    fn roll_and_keep() {
+        let got: RawExpression = "7k3".parse().unwrap();
+        let want: RawExpression = "7d10!!kh3".parse().unwrap();
+        assert_eq!(got, want);
+        let got: RawExpression = "5k2 + 3".parse().unwrap();
+        let want: RawExpression = "5d10!!kh2 + 3".parse().unwrap();
+        assert_eq!(got, want);
+        assert!("7k".parse::<RawExpression>().is_err());
+    }
+
     #[test]
     fn negative() {
         let got: RawExpression = "-4".parse().unwrap();
@@ -301,6 +640,350 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    #[test]
+    fn range_die() {
+        let got: RawExpression = "d[3..18]".parse().unwrap();
+        assert_eq!(got, CustomDie::Range(3, 18).into());
+
+        let got: RawExpression = "2d[ -5 .. 5 ]".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(Constant(2).into()),
+            value: Box::new(CustomDie::Range(-5, 5).into()),
+            ranker: Ranker::All,
+        };
+        assert_eq!(got, want.into());
+
+        "d[5..3]".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn faces_die() {
+        let got: RawExpression = "d{1,1,3,5,8}".parse().unwrap();
+        assert_eq!(got, CustomDie::Faces(vec![1, 1, 3, 5, 8]).into());
+
+        let got: RawExpression = "2d{ -1, 0 ,2 }kh + 1".parse().unwrap();
+        assert_eq!(got.to_string(), "2d{-1,0,2}kh+1");
+
+        "d{}".parse::<RawExpression>().unwrap_err();
+        "d{1,}".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn digits_die() {
+        let got: RawExpression = "d66".parse().unwrap();
+        assert_eq!(got, CustomDie::Digits(vec![6, 6]).into());
+        let got: RawExpression = "2d666".parse().unwrap();
+        assert_eq!(got.to_string(), "2d666");
+        let got: RawExpression = "d( 3, 10 ) + d6".parse().unwrap();
+        assert_eq!(got.to_string(), "d(3,10)+d6");
+
+        // Other numbers are still the number of faces.
+        let got: RawExpression = "d6 + d60 + d6666".parse().unwrap();
+        assert_eq!(got.to_string(), "d6+d60+d6666");
+        let got: RawExpression = "d[1..66]".parse().unwrap();
+        assert_eq!(got.to_string(), "d[1..66]");
+
+        "d()".parse::<RawExpression>().unwrap_err();
+        "d(6,0)".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn weighted_die() {
+        let got: RawExpression = "d{1:3, 2:1, 3 : 1}".parse().unwrap();
+        assert_eq!(
+            got,
+            CustomDie::Weighted(vec![(1, 3), (2, 1), (3, 1)]).into()
+        );
+        assert_eq!(got.to_string(), "d{1:3,2:1,3:1}");
+
+        let got: RawExpression = "2d{-1:2,4:1}kh".parse().unwrap();
+        assert_eq!(got.to_string(), "2d{-1:2,4:1}kh");
+
+        // Weights are positive, and every face has one.
+        "d{1:0}".parse::<RawExpression>().unwrap_err();
+        "d{1:-1}".parse::<RawExpression>().unwrap_err();
+        "d{1:2,3}".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn fudge_die() {
+        let got: RawExpression = "4dF".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(Constant(4).into()),
+            value: Box::new(CustomDie::Fudge.into()),
+            ranker: Ranker::All,
+        };
+        assert_eq!(got, want.into());
+        assert_eq!(got.to_string(), "4dF");
+
+        let got: RawExpression = "4dF + 2".parse().unwrap();
+        assert_eq!(got.to_string(), "4dF+2");
+    }
+
+    #[test]
+    fn explode() {
+        let got: RawExpression = "d6!".parse().unwrap();
+        let want = ExpressionTree::Explode {
+            value: Box::new(Die(6).into()),
+            explosion: Explosion::Standard,
+            trigger: Trigger::Highest,
+        };
+        assert_eq!(got.0, want);
+
+        let got: RawExpression = "3d6!kh2 + d[0..3]!".parse().unwrap();
+        assert_eq!(got.to_string(), "3d6!kh2+d[0..3]!");
+
+        let got: RawExpression = "3d6!!kh2".parse().unwrap();
+        assert_eq!(got.to_string(), "3d6!!kh2");
+
+        let got: RawExpression = "2d6!p - 1".parse().unwrap();
+        assert_eq!(got.to_string(), "2d6!p-1");
+
+        let got: RawExpression = "d6!i + 3".parse().unwrap();
+        assert_eq!(got.to_string(), "d6!i+3");
+
+        let got: RawExpression = "d10!>=9".parse().unwrap();
+        let want = ExpressionTree::Explode {
+            value: Box::new(Die(10).into()),
+            explosion: Explosion::Standard,
+            trigger: Trigger::Compare(ComparisonOp::Ge, 9),
+        };
+        assert_eq!(got.0, want);
+        assert_eq!(got.to_string(), "d10!≥9");
+        let got: RawExpression = "4d6!!<2kh3".parse().unwrap();
+        assert_eq!(got.to_string(), "4d6!!<2kh3");
+        // With a space, it's a comparison.
+        let got: RawExpression = "d6! > 4".parse().unwrap();
+        assert!(matches!(got.0, ExpressionTree::Comparison { .. }));
+    }
+
+    #[test]
+    fn reroll() {
+        let got: RawExpression = "d20ro1".parse().unwrap();
+        let want = ExpressionTree::Reroll {
+            value: Box::new(Die(20).into()),
+            reroll: Reroll {
+                op: ComparisonOp::Eq,
+                threshold: 1,
+                recursive: false,
+            },
+        };
+        assert_eq!(got.0, want);
+
+        let got: RawExpression = "4d6ro<3kh3".parse().unwrap();
+        assert_eq!(got.to_string(), "4d6ro<3kh3");
+
+        let got: RawExpression = "d6ro<=2!".parse().unwrap();
+        assert_eq!(got.to_string(), "d6ro≤2!");
+
+        let got: RawExpression = "2d10rr>8".parse().unwrap();
+        assert_eq!(got.to_string(), "2d10rr>8");
+
+        for (input, want) in [
+            ("d10r>=9", "d10rr≥9"),
+            ("d10r=2", "d10rr=2"),
+            ("d10r2", "d10rr=2"),
+            ("4d6r<3kh3", "4d6rr<3kh3"),
+            ("2d6gwf", "2d6ro≤2"),
+            ("d12gwf!", "d12ro≤2!"),
+        ] {
+            let got: RawExpression = input.parse().unwrap();
+            assert_eq!(got.to_string(), want);
+        }
+
+        "d6ro".parse::<RawExpression>().unwrap_err();
+        "(d6+1)ro1".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn successes() {
+        let got: RawExpression = "5d10s>=6f1".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(Constant(5).into()),
+            value: Box::new(
+                ExpressionTree::Successes {
+                    value: Box::new(Die(10).into()),
+                    successes: Successes {
+                        op: ComparisonOp::Ge,
+                        threshold: 6,
+                        botch: Some((ComparisonOp::Eq, 1)),
+                    },
+                }
+                .into(),
+            ),
+            ranker: Ranker::All,
+        };
+        assert_eq!(got.0, want);
+
+        for (input, want) in [
+            ("d10s>=8", "d10s≥8"),
+            ("d6s6", "d6s=6"),
+            ("4d10s>7f<=2", "4d10s>7f≤2"),
+            ("6d10!s>=8 + 1", "6d10!s≥8+1"),
+            ("3d6ro1s>4", "3d6ro=1s>4"),
+        ] {
+            let got: RawExpression = input.parse().unwrap();
+            assert_eq!(got.to_string(), want);
+        }
+
+        "d10s".parse::<RawExpression>().unwrap_err();
+        "d10s>=6f".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn drop() {
+        let got: RawExpression = "4d6dl1".parse().unwrap();
+        assert_eq!(got.to_string(), "4d6dl");
+        let got: RawExpression = "5d10dh2".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(Constant(5).into()),
+            value: Box::new(Die(10).into()),
+            ranker: Ranker::DropHighest(Box::new(Constant(2).into())),
+        };
+        assert_eq!(got.0, want);
+    }
+
+    #[test]
+    fn keep_expression() {
+        let got: RawExpression = "(1d4+1)d6kh(1d2)".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(
+                ExpressionTree::Sum(vec![
+                    ExpressionTree::Repeated {
+                        count: Box::new(Constant(1).into()),
+                        value: Box::new(Die(4).into()),
+                        ranker: Ranker::All,
+                    }
+                    .into(),
+                    Constant(1).into(),
+                ])
+                .into(),
+            ),
+            value: Box::new(Die(6).into()),
+            ranker: Ranker::Highest(Box::new(
+                ExpressionTree::Repeated {
+                    count: Box::new(Constant(1).into()),
+                    value: Box::new(Die(2).into()),
+                    ranker: Ranker::All,
+                }
+                .into(),
+            )),
+        };
+        assert_eq!(got.0, want);
+        assert_eq!(got.to_string(), "(1d4+1)d6kh(1d2)");
+
+        // A constant in parentheses is a constant.
+        let got: RawExpression = "4d6dl(2)".parse().unwrap();
+        assert_eq!(got.to_string(), "4d6dl2");
+        let got: RawExpression = "[N: d3] 4d6kl(N)".parse().unwrap();
+        assert_eq!(got.to_string(), "[N: d3] 4d6kl(N)");
+    }
+
+    #[test]
+    fn matching() {
+        let got: RawExpression = "5d6m".parse().unwrap();
+        let want = ExpressionTree::Repeated {
+            count: Box::new(Constant(5).into()),
+            value: Box::new(Die(6).into()),
+            ranker: Ranker::Matching,
+        };
+        assert_eq!(got.0, want);
+        assert_eq!(got.to_string(), "5d6m");
+    }
+
+    #[test]
+    fn advantage_sugar() {
+        for (input, want) in [
+            ("adv(d20+5)", "2d20kh + 5"),
+            ("dis( d20 + 5 >= 15 )", "2d20kl + 5 >= 15"),
+            ("adv(d20 + d4 + 3) + d6", "2d20kh + d4 + 3 + d6"),
+            ("adv([X: d20] X + 5)", "[X: 2d20kh] X + 5"),
+            ("3 * dis(d20)", "3 * 2d20kl"),
+        ] {
+            let got: RawExpression = input.parse().unwrap();
+            let want: RawExpression = want.parse().unwrap();
+            assert_eq!(got, want, "{input}");
+        }
+        // Nothing to roll with advantage.
+        "adv(d6 + 2)".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn modulo() {
+        let got: RawExpression = "d%%10 + (2d6 - 7) % -4".parse().unwrap();
+        assert_eq!(got.to_string(), "d100 % 10+(2d6-7) % -4");
+    }
+
+    #[test]
+    fn call() {
+        let got: RawExpression = "soak( 2d6 + 1 ,d4 )".parse().unwrap();
+        assert_eq!(got.to_string(), "soak(2d6+1, d4)");
+        let got: RawExpression = "2 * soak(d6, 2)".parse().unwrap();
+        assert_eq!(got.to_string(), "2 * soak(d6, 2)");
+
+        let got: RawExpression = "max(d8!,d6!) + min( d4, 2 )".parse().unwrap();
+        assert_eq!(got.to_string(), "max(d8!, d6!)+min(d4, 2)");
+
+        let got: RawExpression = "clamp(1d6 - 3,0 , 6)".parse().unwrap();
+        assert_eq!(got.to_string(), "clamp(1d6-3, 0, 6)");
+        "clamp(d6, 1)".parse::<RawExpression>().unwrap_err();
+
+        let got: RawExpression = "if (1d20+5 >= 15) then 2d6 else 1d4".parse().unwrap();
+        let want: RawExpression = "if(1d20 + 5 >= 15, 2d6, 1d4)".parse().unwrap();
+        assert_eq!(got, want);
+        assert_eq!(got.to_string(), "if(1d20+5 ≥ 15, 2d6, 1d4)");
+        let got: RawExpression = "if d20 = 20 then 2d6 else if d20 >= 11 then d6 else 0"
+            .parse()
+            .unwrap();
+        assert_eq!(got.to_string(), "if(d20 = 20, 2d6, if(d20 ≥ 11, d6, 0))");
+        "if d20 then d6".parse::<RawExpression>().unwrap_err();
+
+        let got: RawExpression = "(3d6) -> bands {3..5: -1, 6..8: 0, 9..12: 1, 13..18: 2}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            got.to_string(),
+            "3d6 -> {3..5: -1, 6..8: 0, 9..12: 1, 13..18: 2}"
+        );
+        let got: RawExpression = "d20->{20:1,..19:0} + 1".parse().unwrap();
+        assert_eq!(got.to_string(), "d20 -> {..19: 0, 20: 1}+1");
+        let got: RawExpression = "-(d6 + 1) -> {-3..-1: 0, 7..: 6}".parse().unwrap();
+        assert_eq!(got.to_string(), "-(d6+1) -> {-3..-1: 0, 7..: 6}");
+        for overlapping in ["d6 -> {1..3: 0, 3..6: 1}", "d6 -> {4..2: 0}", "d6 -> {}"] {
+            overlapping.parse::<RawExpression>().unwrap_err();
+        }
+
+        let got: RawExpression = "save(8d6, d20+3 >= 15)".parse().unwrap();
+        assert_eq!(got.to_string(), "save(8d6, d20+3 ≥ 15)");
+        "save(8d6)".parse::<RawExpression>().unwrap_err();
+        let got: RawExpression = "abs( d6-d6 )".parse().unwrap();
+        assert_eq!(got.to_string(), "abs(d6-d6)");
+
+        "soak(d6)".parse::<RawExpression>().unwrap_err();
+        "soak(d6, 1, 2)".parse::<RawExpression>().unwrap_err();
+        "sock(d6, 1)".parse::<RawExpression>().unwrap_err();
+
+        let got: RawExpression = "[R: d%] tens(R) = units(R)".parse().unwrap();
+        assert_eq!(got.to_string(), "[R: d100] tens(R) = units(R)");
+        "tens(d6, 1)".parse::<RawExpression>().unwrap_err();
+    }
+
+    #[test]
+    fn named_die() {
+        let parser = Parser::new()
+            .with_die("hitloc", [1, 1, 2, 3])
+            .with_die("hit", [7]);
+        let got = parser.parse("2dhitlockh + dhit").unwrap();
+        assert_eq!(got.to_string(), "2dhitlockh+dhit");
+
+        let d = got.distribution().unwrap();
+        assert_eq!(d.min(), 8);
+        assert_eq!(d.max(), 10);
+
+        "dhitloc".parse::<RawExpression>().unwrap_err();
+        parser.parse("dmiss").unwrap_err();
+    }
+
     #[test]
     fn explicit_modifier_sign() {
         for (i, e) in ["[CHA: +2] CHA", "+3", "-3", "1 - +3", "1 + -+3", "-+3"]
@@ -314,9 +997,45 @@ mod tests {
     /// Generate a symbolic Expression.
     fn symbolic_expression() -> impl Strategy<Value = RawExpression> {
         let leaf = proptest::prop_oneof![
-            any::<usize>().prop_map(|v| Die(v).into()),
-            any::<usize>().prop_map(|v| Constant(v).into()),
+            // d66 and d666 are written as ranges, which parse as custom dice.
+            any::<usize>()
+                .prop_filter("digit dice", |v| !matches!(v, 66 | 666))
+                .prop_map(|v| Die(v).into()),
+            any::<u64>().prop_map(|v| Constant(v).into()),
+            (any::<i64>(), any::<i64>())
+                .prop_map(|(a, b)| CustomDie::Range(a.min(b), a.max(b)).into()),
+            Just(CustomDie::Fudge.into()),
+            proptest::collection::vec(any::<i64>(), 1..8)
+                .prop_map(|faces| CustomDie::Faces(faces).into()),
+            proptest::collection::vec((any::<i64>(), 1..usize::MAX), 1..8)
+                .prop_map(|faces| CustomDie::Weighted(faces).into()),
+            proptest::collection::vec(1..usize::MAX, 1..4)
+                .prop_map(|sizes| CustomDie::Digits(sizes).into()),
             properties::symbol().prop_map(|s| s.into()),
+            (any::<usize>(), any::<Explosion>(), any::<Trigger>()).prop_map(
+                |(v, explosion, trigger)| {
+                    ExpressionTree::Explode {
+                        value: Box::new(Die(v).into()),
+                        explosion,
+                        trigger,
+                    }
+                    .into()
+                }
+            ),
+            (any::<usize>(), any::<Reroll>()).prop_map(|(v, reroll)| {
+                ExpressionTree::Reroll {
+                    value: Box::new(Die(v).into()),
+                    reroll,
+                }
+                .into()
+            }),
+            (any::<usize>(), any::<Successes>()).prop_map(|(v, successes)| {
+                ExpressionTree::Successes {
+                    value: Box::new(Die(v).into()),
+                    successes,
+                }
+                .into()
+            }),
         ];
         leaf.prop_recursive(3, 2, 3, |strat| {
             prop_oneof![
@@ -324,8 +1043,11 @@ mod tests {
                 properties::repeated(&strat),
                 properties::product(&strat),
                 properties::floor(&strat),
+                properties::modulo(&strat),
                 properties::sum(&strat),
                 properties::comparison(&strat),
+                properties::call(&strat),
+                properties::lookup(&strat),
                 // Binding:
                 (properties::symbol(), strat.clone(), strat.clone()).prop_map(
                     |(symbol, value, tail)| {
@@ -0,0 +1,421 @@
+//! Evaluation that falls back to approximation when exact evaluation is too expensive.
+//!
+//! The planner tries to evaluate each node exactly, within a budget of combinations of values.
+//! When a node is over budget, it looks at the node's operands instead:
+//! sums, negations, products, and plain repetitions of independent operands can be
+//! combined from the operands' moments, whether those are exact or approximate.
+//! Anything else that's over budget is sampled.
+//!
+//! Moments from exact distributions are exact; moments from samples carry their standard error,
+//! which is propagated (to first order) through the combinations.
+//!
+//! Before approximating, the planner tries one more exact evaluation: a sum of pools, like
+//! `200d100 + 3d6`, may have too many possible rolls to count in a `usize`, but its
+//! [`BigDistribution`] can still be computed within budget.
+
+use crate::{
+    BigDistribution, Closed, Distribution, Error, Evaluator,
+    sample::{Estimate, Program, SplitMix64},
+    symbolic::{ExpressionTree, ExpressionWrapper, Ranker},
+};
+
+/// Sample at least this many times, even with a small budget, so that estimates are meaningful.
+const MIN_SAMPLES: usize = 1_000;
+
+/// Sample at most this many times, even with a large budget: the budget counts combinations
+/// of values, and each sample costs many times more than a combination.
+const MAX_SAMPLES: usize = 1 << 20;
+
+/// The seed for sampling, so the same expression and budget always give the same result.
+const SEED: u64 = 0x5eed;
+
+/// The mean and variance of a distribution, with their uncertainty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moments {
+    /// The expected value.
+    pub mean: Estimate,
+    /// The expected squared distance from the mean.
+    pub variance: Estimate,
+}
+
+impl Moments {
+    /// The exact moments of the distribution.
+    fn of(d: &Distribution) -> Self {
+        Moments {
+            mean: Estimate::exact(d.mean()),
+            variance: Estimate::exact(d.variance()),
+        }
+    }
+
+    /// The standard deviation, from the estimated variance.
+    pub fn stddev(&self) -> f64 {
+        self.variance.value.max(0.0).sqrt()
+    }
+
+    /// Whether the moments are exact, rather than estimated from samples.
+    pub fn is_exact(&self) -> bool {
+        self.mean.stderr == 0.0 && self.variance.stderr == 0.0
+    }
+
+    /// The moments of the sum of independent variables.
+    fn sum(terms: &[Moments]) -> Self {
+        let add = |estimates: &mut dyn Iterator<Item = Estimate>| {
+            let (value, variance) = estimates.fold((0.0, 0.0), |(value, variance), e| {
+                (value + e.value, variance + e.stderr * e.stderr)
+            });
+            Estimate::normal(value, variance.sqrt())
+        };
+        Moments {
+            mean: add(&mut terms.iter().map(|m| m.mean)),
+            variance: add(&mut terms.iter().map(|m| m.variance)),
+        }
+    }
+
+    fn negated(self) -> Self {
+        Moments {
+            mean: Estimate::normal(-self.mean.value, self.mean.stderr),
+            variance: self.variance,
+        }
+    }
+
+    /// The moments of the product of independent variables.
+    fn product(a: Moments, b: Moments) -> Self {
+        let (ma, va) = (a.mean.value, a.variance.value);
+        let (mb, vb) = (b.mean.value, b.variance.value);
+        Moments {
+            mean: propagate(ma * mb, &[(mb, a.mean), (ma, b.mean)]),
+            // Var(AB) = E[A²]E[B²] - E[A]²E[B]².
+            variance: propagate(
+                (va + ma * ma) * (vb + mb * mb) - ma * ma * mb * mb,
+                &[
+                    (vb + mb * mb, a.variance),
+                    (va + ma * ma, b.variance),
+                    (2.0 * ma * vb, a.mean),
+                    (2.0 * mb * va, b.mean),
+                ],
+            ),
+        }
+    }
+
+    /// The moments of the sum of `count` independent rolls of `value` (Wald's identities).
+    fn repeat(count: Moments, value: Moments) -> Self {
+        let (mn, vn) = (count.mean.value, count.variance.value);
+        let (mx, vx) = (value.mean.value, value.variance.value);
+        Moments {
+            mean: propagate(mn * mx, &[(mx, count.mean), (mn, value.mean)]),
+            variance: propagate(
+                mn * vx + vn * mx * mx,
+                &[
+                    (vx, count.mean),
+                    (mx * mx, count.variance),
+                    (mn, value.variance),
+                    (2.0 * vn * mx, value.mean),
+                ],
+            ),
+        }
+    }
+}
+
+/// An estimate of `value`, a function of the given estimates, with the given partial derivatives:
+/// its standard error is propagated from theirs, assuming they're independent.
+fn propagate(value: f64, partials: &[(f64, Estimate)]) -> Estimate {
+    let variance: f64 = partials
+        .iter()
+        .filter(|(_, e)| e.stderr != 0.0)
+        .map(|(d, e)| (d * e.stderr).powi(2))
+        .sum();
+    Estimate::normal(value, variance.sqrt())
+}
+
+/// The result of [`Closed::evaluate_auto`].
+#[derive(Debug, Clone)]
+pub enum Evaluation {
+    /// The whole expression was within budget: this is its exact distribution.
+    Exact(Box<Distribution>),
+    /// The expression is a sum of rolls with too many outcomes to count in a `usize`, but within
+    /// budget with arbitrary precision: this is its exact distribution.
+    ExactBig(Box<BigDistribution>),
+    /// The expression was over budget: these are its moments, exact where possible
+    /// and estimated elsewhere.
+    Approximate(Moments),
+}
+
+impl Evaluation {
+    /// The moments of the result, whether exact or approximate.
+    pub fn moments(&self) -> Moments {
+        match self {
+            Evaluation::Exact(d) => Moments::of(d),
+            Evaluation::ExactBig(d) => Moments {
+                mean: Estimate::exact(d.mean()),
+                variance: Estimate::exact(d.variance()),
+            },
+            Evaluation::Approximate(m) => *m,
+        }
+    }
+
+    /// The least and greatest possible results, if the result is exact.
+    pub fn range(&self) -> Option<(i64, i64)> {
+        match self {
+            Evaluation::Exact(d) => Some((d.min(), d.max())),
+            Evaluation::ExactBig(d) => Some((d.min(), d.max())),
+            Evaluation::Approximate(_) => None,
+        }
+    }
+
+    /// Each possible result with its probability, in increasing order, if the result is exact.
+    pub fn probabilities(&self) -> Option<Vec<(i64, f64)>> {
+        match self {
+            Evaluation::Exact(d) => Some(
+                d.occurrences()
+                    .map(|(v, _)| (v, d.probability_f64(v)))
+                    .collect(),
+            ),
+            Evaluation::ExactBig(d) => Some(
+                d.occurrences()
+                    .map(|(v, _)| (v, d.probability_f64(v)))
+                    .collect(),
+            ),
+            Evaluation::Approximate(_) => None,
+        }
+    }
+}
+
+impl Closed {
+    /// Evaluate the expression exactly if it's within `budget` combinations of values,
+    /// as [`Evaluator::with_budget`] counts them, and its possible rolls can be counted;
+    /// otherwise, approximate its moments.
+    ///
+    /// A sum of rolls with too many outcomes to count, like `200d100`, is still exact if its
+    /// [`BigDistribution`] is within budget: about the number of values it can take,
+    /// times the number of 32-bit words its counts take.
+    ///
+    /// An approximation evaluates each part of the expression exactly where it can,
+    /// and samples the parts that are over budget, `budget` times (within limits).
+    /// Sampling is seeded, so the result is the same each time.
+    pub fn evaluate_auto(&self, budget: usize) -> Result<Evaluation, Error> {
+        let mut planner = Planner {
+            evaluator: Evaluator::new(true).with_budget(budget),
+            samples: budget.clamp(MIN_SAMPLES, MAX_SAMPLES),
+            rng: SplitMix64::new(SEED),
+        };
+        match planner.evaluator.eval(self) {
+            Ok(d) => Ok(Evaluation::Exact(Box::new(d))),
+            Err(Error::OverBudget(_) | Error::TooComplex(_)) => {
+                let mut rolls = Vec::new();
+                if planner.rolls(self, false, &mut rolls)?
+                    && let Some(d) = BigDistribution::sum_of_rolls(&rolls, budget)
+                {
+                    return Ok(Evaluation::ExactBig(Box::new(d)));
+                }
+                Ok(Evaluation::Approximate(planner.moments(self)?))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+struct Planner {
+    /// A memoizing evaluator, so results within budget are reused between attempts.
+    evaluator: Evaluator,
+    samples: usize,
+    rng: SplitMix64,
+}
+
+impl Planner {
+    /// The exact distribution of the expression, or None if it's over budget or has too many
+    /// possible rolls to count.
+    fn exact(&mut self, e: &Closed) -> Result<Option<Distribution>, Error> {
+        match self.evaluator.eval(e) {
+            Ok(d) => Ok(Some(d)),
+            Err(Error::OverBudget(_) | Error::TooComplex(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add the independent rolls the expression sums to `rolls`: each distribution, negated if
+    /// `negated`, and how many times it's rolled.
+    ///
+    /// Returns false if the expression isn't a sum of rolls of exact distributions.
+    fn rolls(
+        &mut self,
+        e: &Closed,
+        negated: bool,
+        rolls: &mut Vec<(Distribution, usize)>,
+    ) -> Result<bool, Error> {
+        let (value, count) = match e.inner() {
+            ExpressionTree::Sum(terms) => {
+                for term in terms {
+                    if !self.rolls(term, negated, rolls)? {
+                        return Ok(false);
+                    }
+                }
+                return Ok(true);
+            }
+            ExpressionTree::Negated(a) => return self.rolls(a, !negated, rolls),
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker: Ranker::All,
+            } => match self.exact(count)? {
+                Some(count) if count.min() == count.max() && count.min() >= 0 => {
+                    (self.exact(value)?, count.min() as usize)
+                }
+                _ => (self.exact(e)?, 1),
+            },
+            _ => (self.exact(e)?, 1),
+        };
+        let Some(value) = value else {
+            return Ok(false);
+        };
+        let value = if negated {
+            value
+                .checked_neg()
+                .ok_or_else(|| Error::Overflow(e.to_string()))?
+        } else {
+            value
+        };
+        rolls.push((value, count));
+        Ok(true)
+    }
+
+    fn moments(&mut self, e: &Closed) -> Result<Moments, Error> {
+        match self.evaluator.eval(e) {
+            Ok(d) => return Ok(Moments::of(&d)),
+            // Too many possible rolls to count is also too expensive, for exact evaluation.
+            // A value that's out of range would also be out of range when sampled: that's an
+            // error either way.
+            Err(Error::OverBudget(_) | Error::TooComplex(_)) => (),
+            Err(e) => return Err(e),
+        }
+        // The operands of a closed expression, outside of any binding, are closed and independent.
+        match e.inner() {
+            ExpressionTree::Sum(terms) => {
+                let terms = terms
+                    .iter()
+                    .map(|t| self.moments(t))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Moments::sum(&terms))
+            }
+            ExpressionTree::Negated(a) => Ok(self.moments(a)?.negated()),
+            ExpressionTree::Product(a, b) => {
+                Ok(Moments::product(self.moments(a)?, self.moments(b)?))
+            }
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker: Ranker::All,
+            } => {
+                if let Ok(d) = self.evaluator.eval(count)
+                    && d.min() < 0
+                {
+                    return Err(Error::NegativeCount(e.to_string()));
+                }
+                Ok(Moments::repeat(self.moments(count)?, self.moments(value)?))
+            }
+            _ => self.sample(e),
+        }
+    }
+
+    fn sample(&mut self, e: &Closed) -> Result<Moments, Error> {
+        let mut program = Program::new(e, Evaluator::DEFAULT_EXPLOSION_DEPTH)?;
+        let rolls = program.sample_n(&mut self.rng, self.samples)?;
+        let mean = Estimate::from_terms(rolls.iter().map(|v| *v as f64));
+        // Correct the sample variance's bias: divide by n - 1, rather than n.
+        let n = rolls.len() as f64;
+        let variance = Estimate::from_terms(
+            rolls
+                .iter()
+                .map(|v| (*v as f64 - mean.value).powi(2) * n / (n - 1.0)),
+        );
+        Ok(Moments { mean, variance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::BigUint;
+
+    #[test]
+    fn within_budget() {
+        let e: Closed = "4d6kh3 + 2".parse().unwrap();
+        let Evaluation::Exact(d) = e.evaluate_auto(10_000).unwrap() else {
+            panic!("expected an exact result");
+        };
+        assert_eq!(*d, e.distribution().unwrap());
+    }
+
+    #[test]
+    fn too_many_to_count() {
+        // 100^200 possible rolls, and more, don't fit in a usize, but they're still exact.
+        let e: Closed = "200d100 + 3d6 - 2".parse().unwrap();
+        let Evaluation::ExactBig(d) = e.evaluate_auto(1_000_000).unwrap() else {
+            panic!("expected an exact result");
+        };
+        assert_eq!((d.min(), d.max()), (201, 20_016));
+        assert_eq!(
+            *d.total(),
+            BigUint::from(100u32).pow(200) * BigUint::from(216u32)
+        );
+        assert!((d.mean() - (200.0 * 50.5 + 10.5 - 2.0)).abs() < 1e-6);
+        let want_variance = 200.0 * 9999.0 / 12.0 + 3.0 * 35.0 / 12.0;
+        assert!((d.variance() - want_variance).abs() < 1e-3);
+
+        // Over budget, it's approximate.
+        assert!(matches!(
+            e.evaluate_auto(10_000).unwrap(),
+            Evaluation::Approximate(_)
+        ));
+    }
+
+    #[test]
+    fn exact_moments() {
+        // Too wide to convolve within budget, but each die is exact, so the moments are too.
+        let e: Closed = "2 * 100d100 - d6".parse().unwrap();
+        let Evaluation::Approximate(m) = e.evaluate_auto(10_000).unwrap() else {
+            panic!("expected an approximate result");
+        };
+        assert!(m.is_exact());
+        assert!((m.mean.value - (2.0 * 5050.0 - 3.5)).abs() < 1e-6, "{m:?}");
+        let want_variance = 4.0 * 100.0 * (100.0 * 100.0 - 1.0) / 12.0 + 35.0 / 12.0;
+        assert!((m.variance.value - want_variance).abs() < 1e-3, "{m:?}");
+    }
+
+    #[test]
+    fn sampled_moments() {
+        // The binding can't be decomposed, so it's sampled.
+        let e: Closed = "[X: 50d20] X * X + 100d100".parse().unwrap();
+        let Evaluation::Approximate(m) = e.evaluate_auto(10_000).unwrap() else {
+            panic!("expected an approximate result");
+        };
+        assert!(!m.is_exact());
+        // E[X²] = Var(X) + E[X]².
+        let want = 50.0 * 399.0 / 12.0 + 525.0 * 525.0 + 5050.0;
+        assert!(
+            (m.mean.value - want).abs() < 4.0 * m.mean.stderr,
+            "{m:?}, want {want}"
+        );
+        assert!(m.mean.ci95.0 < m.mean.value && m.mean.value < m.mean.ci95.1);
+
+        // Sampling is deterministic.
+        assert_eq!(e.evaluate_auto(10_000).unwrap().moments(), m);
+    }
+
+    #[test]
+    fn errors() {
+        let e: Closed = "d6 / (d2 - 1)".parse().unwrap();
+        assert!(matches!(e.evaluate_auto(100), Err(Error::DivideByZero(_))));
+
+        let e: Closed = "(d3 - 2)d6 + 100d100".parse().unwrap();
+        assert!(matches!(e.evaluate_auto(100), Err(Error::NegativeCount(_))));
+
+        // Out of range, however it's evaluated.
+        for e in [
+            "9223372036854775807 + 1",
+            "9223372036854775807 * d2 + 100d100",
+        ] {
+            let got = e.parse::<Closed>().unwrap().evaluate_auto(100);
+            assert!(matches!(got, Err(Error::Overflow(_))), "{e}: {got:?}");
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! The types and traits most programs use, in one import: `use dicer::prelude::*;`.
+//!
+//! Parse an expression into a [`Closed`] (or with a [`Parser`], for custom dice),
+//! evaluate it to a [`Distribution`] (or with an [`Evaluator`], to memoize or limit the work),
+//! and ask it questions with [`query`], [`RandomVariable`], or [`Closed::summarize`].
+//!
+//! Names here keep working as the crate's modules are reorganized; prefer them to paths
+//! into the modules that define them.
+
+pub use crate::{
+    Bounded, Closed, Distribution, Distribution2D, Error, ErrorKind, Evaluation, Evaluator,
+    JointDistribution, Moments, Parser, Provenance, RandomVariable, Summary,
+    query::query,
+    sample::{Estimate, Rng, SplitMix64},
+};
+pub use num::rational::Ratio;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everyday_use() {
+        let attack: Closed = "d20 + 5 >= 15".parse().unwrap();
+        let hits = Evaluator::new(true).eval(&attack).unwrap();
+        assert_eq!(hits.probability(1), Ratio::new(11, 20));
+        assert!((query(&hits, "P(X = 1)").unwrap() - 0.55).abs() < 1e-12);
+    }
+}
@@ -2,7 +2,9 @@
 
 use proptest::prelude::*;
 
-use crate::symbolic::{ComparisonOp, ExpressionTree, ExpressionWrapper, Ranker, Symbol};
+use crate::symbolic::{
+    Band, Bands, ComparisonOp, ExpressionTree, ExpressionWrapper, Function, Ranker, Symbol,
+};
 
 /// Generate a valid Symbol.
 pub fn symbol() -> impl Strategy<Value = Symbol> {
@@ -24,13 +26,13 @@ pub fn repeated<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = Expression
 where
     W: ExpressionWrapper + std::fmt::Debug,
 {
-    (strat.clone(), strat.clone(), any::<Ranker>()).prop_map(|(count, value, ranker)| {
-        ExpressionTree::Repeated {
+    (strat.clone(), strat.clone(), any::<Ranker>(), strat.clone()).prop_map(
+        |(count, value, ranker, keep)| ExpressionTree::Repeated {
             count: Box::new(count),
             value: Box::new(value),
-            ranker,
-        }
-    })
+            ranker: ranker.map(|_| Box::new(keep)),
+        },
+    )
 }
 
 pub fn product<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = ExpressionTree<W>> + use<W>
@@ -49,6 +51,14 @@ where
         .prop_map(|(a, b)| ExpressionTree::Floor(Box::new(a), Box::new(b)))
 }
 
+pub fn modulo<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = ExpressionTree<W>> + use<W>
+where
+    W: ExpressionWrapper + std::fmt::Debug,
+{
+    (strat.clone(), strat.clone())
+        .prop_map(|(a, b)| ExpressionTree::Modulo(Box::new(a), Box::new(b)))
+}
+
 pub fn sum<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = ExpressionTree<W>> + use<W>
 where
     W: ExpressionWrapper + std::fmt::Debug,
@@ -68,3 +78,38 @@ where
         }
     })
 }
+
+pub fn call<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = ExpressionTree<W>> + use<W>
+where
+    W: ExpressionWrapper + std::fmt::Debug,
+{
+    any::<Function>().prop_flat_map({
+        let strat = strat.clone();
+        move |function| {
+            prop::collection::vec(strat.clone(), function.arity())
+                .prop_map(move |args| ExpressionTree::Call { function, args })
+        }
+    })
+}
+
+pub fn lookup<W>(strat: &BoxedStrategy<W>) -> impl Strategy<Value = ExpressionTree<W>> + use<W>
+where
+    W: ExpressionWrapper + std::fmt::Debug,
+{
+    let bands =
+        (prop::collection::vec(any::<i64>(), 1..8), any::<i64>()).prop_map(|(mut ends, result)| {
+            // Sorted, distinct ends pair up into bands that don't overlap.
+            ends.sort();
+            ends.dedup();
+            Bands::new(ends.chunks(2).enumerate().map(|(i, c)| Band {
+                lo: c[0],
+                hi: c[c.len() - 1],
+                result: result.wrapping_add(i as i64),
+            }))
+            .expect("bands are sorted and distinct")
+        });
+    (strat.clone(), bands).prop_map(|(value, bands)| ExpressionTree::Lookup {
+        value: Box::new(value),
+        bands,
+    })
+}
@@ -0,0 +1,156 @@
+//! A small language for asking questions of a computed distribution.
+//!
+//! A query is either a probability, `P(EVENT)`, or an expectation, `E[X]`;
+//! either may be conditioned on another event, `P(EVENT | GIVEN)` or `E[X | GIVEN]`.
+//! An event compares the result, `X`, with a constant: `X >= 15`, `X != 1`.
+//! Comparisons may be combined with `and`, all of which must hold: `X > 5 and X < 10`.
+
+use crate::{Distribution, Error, symbolic::ComparisonOp};
+
+/// One comparison of the result with a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Comparison {
+    op: ComparisonOp,
+    /// Whether the comparison is negated: `!=` is a negated `=`.
+    negated: bool,
+    value: i64,
+}
+
+/// Comparisons that must all hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event(Vec<Comparison>);
+
+impl Event {
+    fn holds(&self, x: i64) -> bool {
+        self.0.iter().all(|c| c.op.compare(x, c.value) != c.negated)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Query {
+    Probability { event: Event, given: Option<Event> },
+    Expectation { given: Option<Event> },
+}
+
+peg::parser! {
+    grammar query_language() for str {
+        rule space() = [' ' | '\t' | '\n']*
+
+        rule integer() -> i64
+            = n:$("-"? ['0'..='9']+) {? n.parse().or(Err("i64")) }
+
+        rule compare_op() -> (ComparisonOp, bool)
+            // Longer operators first.
+            = (">=" / "≥") { (ComparisonOp::Ge, false) }
+            / ("<=" / "≤") { (ComparisonOp::Le, false) }
+            / ("!=" / "≠") { (ComparisonOp::Eq, true) }
+            / ("==" / "=") { (ComparisonOp::Eq, false) }
+            / ">" { (ComparisonOp::Gt, false) }
+            / "<" { (ComparisonOp::Lt, false) }
+
+        rule comparison() -> Comparison
+            = "X" space() op:compare_op() space() value:integer() {
+                Comparison { op: op.0, negated: op.1, value }
+            }
+
+        rule event() -> Event
+            = c:(comparison() ++ (space() ("and" / "&&") space())) { Event(c) }
+
+        rule given() -> Event
+            = space() "|" space() e:event() { e }
+
+        pub rule query() -> Query
+            = space() "P(" space() event:event() given:given()? space() ")" space() {
+                Query::Probability { event, given }
+            }
+            / space() "E[" space() "X" given:given()? space() "]" space() {
+                Query::Expectation { given }
+            }
+    }
+}
+
+/// Answer the query about the distribution: a probability, or an expected value.
+///
+/// Fails if the query can't be parsed, or if it's conditioned on an event that never happens.
+pub fn query(d: &Distribution, q: &str) -> Result<f64, Error> {
+    let parsed = query_language::query(q).map_err(|e| Error::ParseError(q.to_owned(), e))?;
+    let given = match &parsed {
+        Query::Probability { given, .. } | Query::Expectation { given } => given.as_ref(),
+    };
+    let condition = |x: i64| given.is_none_or(|g| g.holds(x));
+    let impossible = || Error::ImpossibleCondition(q.to_owned());
+    match &parsed {
+        Query::Probability { event, .. } => {
+            // Occurrences of the values where the condition holds.
+            let total: usize = d
+                .occurrences()
+                .filter(|(v, _)| condition(*v))
+                .map(|(_, o)| o)
+                .sum();
+            if total == 0 {
+                return Err(impossible());
+            }
+            let hits: usize = d
+                .occurrences()
+                .filter(|(v, _)| condition(*v) && event.holds(*v))
+                .map(|(_, o)| o)
+                .sum();
+            Ok(hits as f64 / total as f64)
+        }
+        Query::Expectation { .. } => d.mean_given(condition).ok_or_else(impossible),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn probabilities() {
+        let d = distribution("d20");
+        for (q, want) in [
+            ("P(X >= 15)", 6.0 / 20.0),
+            ("P(X>=15|X>5)", 6.0 / 15.0),
+            ("P(X = 20)", 1.0 / 20.0),
+            ("P(X == 20)", 1.0 / 20.0),
+            ("P(X != 1)", 19.0 / 20.0),
+            ("P(X > 5 and X < 10)", 4.0 / 20.0),
+            ("P(X <= 10 | X >= 5 && X <= 14)", 6.0 / 10.0),
+            ("P(X < -3)", 0.0),
+            (" P( X ≥ 15 | X ≠ 20 ) ", 5.0 / 19.0),
+        ] {
+            assert_eq!(query(&d, q).unwrap(), want, "{q}");
+        }
+    }
+
+    #[test]
+    fn expectations() {
+        let d = distribution("d6 - 3");
+        for (q, want) in [
+            ("E[X]", d.mean()),
+            ("E[X | X > 0]", 2.0),
+            ("E[X | X != 0 and X < 3]", 0.0),
+            ("E[X | X < 0]", -1.5),
+        ] {
+            let got = query(&d, q).unwrap();
+            assert!((got - want).abs() < 1e-12, "{q}: {got} vs {want}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let d = distribution("d6");
+        for q in ["P(X > 3", "E[Y]", "P(X >> 3)", "Q(X > 3)", "E[X] + 1"] {
+            assert!(matches!(query(&d, q), Err(Error::ParseError(..))), "{q}");
+        }
+        assert!(matches!(
+            query(&d, "E[X | X > 6]"),
+            Err(Error::ImpossibleCondition(_))
+        ));
+    }
+}
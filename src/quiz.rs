@@ -0,0 +1,144 @@
+//! Random dice expressions with their answers, for quizzes and flashcards that teach probability.
+//!
+//! [`question`] makes up an expression within its [`Constraints`], as [`Closed::random`] does,
+//! and works out its key statistics exactly.
+
+use num::rational::Ratio;
+
+use crate::{
+    Closed, Error, RandomVariable,
+    generate::{Constraints, Operator},
+    sample::{Rng, between},
+};
+
+/// The constraints for questions simple enough to work out by hand:
+/// sums, differences, and comparisons of a few dice, and keeping the highest or lowest.
+pub fn simple() -> Constraints {
+    Constraints {
+        depth: 2,
+        operators: vec![
+            Operator::Add,
+            Operator::Subtract,
+            Operator::Max,
+            Operator::Keep,
+            Operator::Compare,
+        ],
+        ..Default::default()
+    }
+}
+
+/// An expression and its answers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub expression: Closed,
+    /// The value asked about.
+    pub target: i64,
+    /// The probability that the expression comes out at `target` or more.
+    pub at_least: Ratio<usize>,
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Question {
+    /// The question, as asked on the front of a flashcard.
+    pub fn front(&self) -> String {
+        format!(
+            "What is the probability that {} rolls {} or more?",
+            self.expression, self.target
+        )
+    }
+
+    /// The answers, as shown on the back of a flashcard.
+    pub fn back(&self) -> String {
+        format!(
+            "{} (about {:.1}%); mean {:.2}, standard deviation {:.2}, from {} to {}",
+            self.at_least,
+            *self.at_least.numer() as f64 / *self.at_least.denom() as f64 * 100.0,
+            self.mean,
+            self.stddev,
+            self.min,
+            self.max
+        )
+    }
+}
+
+/// The front and back of the flashcard, separated by a tab, as Anki and other flashcard
+/// programs import them.
+impl std::fmt::Display for Question {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\t{}", self.front(), self.back())
+    }
+}
+
+/// Make up a question about an expression within the constraints.
+///
+/// Fails if none of several expressions could be evaluated within the budget.
+pub fn question(rng: &mut impl Rng, constraints: &Constraints) -> Result<Question, Error> {
+    let expression = Closed::random(rng, constraints)?;
+    let d = expression.distribution()?;
+    let target = between(rng, d.min(), d.max());
+    Ok(Question {
+        target,
+        at_least: d.probability_where(|v| v >= target),
+        mean: d.mean(),
+        stddev: d.variance().sqrt(),
+        min: d.min(),
+        max: d.max(),
+        expression,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::SplitMix64;
+
+    #[test]
+    fn deterministic() {
+        let constraints = simple();
+        let a = question(&mut SplitMix64::new(7), &constraints).unwrap();
+        let b = question(&mut SplitMix64::new(7), &constraints).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn answers() {
+        let mut rng = SplitMix64::new(1);
+        for _ in 0..50 {
+            let q = question(&mut rng, &simple()).unwrap();
+            let d = q.expression.distribution().unwrap();
+            assert!(q.min <= q.target && q.target <= q.max, "{q}");
+            assert_eq!(q.at_least, d.probability_where(|v| v >= q.target), "{q}");
+            assert_eq!((q.min, q.max), (d.min(), d.max()));
+            // The card parses back to the same expression.
+            let card = q.to_string();
+            let (front, back) = card.split_once('\t').unwrap();
+            assert!(!back.contains('\t'));
+            let e = front
+                .strip_prefix("What is the probability that ")
+                .unwrap()
+                .split(" rolls ")
+                .next()
+                .unwrap();
+            assert_eq!(e.parse::<Closed>().unwrap(), q.expression);
+        }
+    }
+
+    #[test]
+    fn within_constraints() {
+        let constraints = Constraints {
+            depth: 0,
+            faces: vec![4, 6],
+            dice: 2,
+            ..simple()
+        };
+        let mut rng = SplitMix64::new(2);
+        for _ in 0..50 {
+            let q = question(&mut rng, &constraints).unwrap();
+            let e = q.expression.to_string();
+            assert!(["1d4", "2d4", "1d6", "2d6"].contains(&e.as_str()), "{e}");
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! A common interface over representations of probability distributions.
+
+use num::rational::Ratio;
+
+use crate::Distribution;
+
+/// A discrete random variable: a set of values, each with some probability.
+///
+/// Analyses written against this trait work for any representation of a distribution.
+pub trait RandomVariable: Sized {
+    /// The type of value the variable takes on.
+    type Value: Copy;
+
+    /// Iterator over the values with nonzero probability, in ascending order.
+    fn support(&self) -> impl Iterator<Item = Self::Value> + '_;
+
+    /// Give the probability of this value occurring.
+    fn probability(&self, value: Self::Value) -> Ratio<usize>;
+
+    /// The distribution of the sum of this variable and an independent copy of the other.
+    fn convolve(&self, other: &Self) -> Self;
+
+    /// The distribution of `f(X)`, where `X` is this variable.
+    fn map(&self, f: impl FnMut(Self::Value) -> Self::Value) -> Self;
+
+    /// The probability that the value satisfies the predicate.
+    fn probability_where(&self, mut predicate: impl FnMut(Self::Value) -> bool) -> Ratio<usize> {
+        self.support()
+            .filter(|v| predicate(*v))
+            .map(|v| self.probability(v))
+            .fold(Ratio::new(0, 1), |acc, p| acc + p)
+    }
+}
+
+impl RandomVariable for Distribution {
+    type Value = i64;
+
+    fn support(&self) -> impl Iterator<Item = i64> + '_ {
+        self.occurrences().map(|(v, _)| v)
+    }
+
+    fn probability(&self, value: i64) -> Ratio<usize> {
+        Distribution::probability(self, value)
+    }
+
+    fn convolve(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn map(&self, f: impl FnMut(i64) -> i64) -> Self {
+        self.map_values(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution_of(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    /// An analysis written once over any RandomVariable.
+    fn at_least<R: RandomVariable<Value = i64>>(r: &R, threshold: i64) -> Ratio<usize> {
+        r.probability_where(|v| v >= threshold)
+    }
+
+    #[test]
+    fn generic_analysis() {
+        let d = distribution_of("2d4");
+        assert_eq!(at_least(&d, 7), Ratio::new(3, 16));
+        assert_eq!(at_least(&d, 2), Ratio::new(1, 1));
+    }
+
+    #[test]
+    fn convolve_is_sum() {
+        let a = distribution_of("d4");
+        let b = distribution_of("d6");
+        assert_eq!(a.convolve(&b), distribution_of("d4 + d6"));
+    }
+
+    #[test]
+    fn map_parity() {
+        let d = distribution_of("d6").map(|v| v % 2);
+        let support: Vec<_> = d.support().collect();
+        assert_eq!(support, vec![0, 1]);
+        assert_eq!(RandomVariable::probability(&d, 0), Ratio::new(1, 2));
+    }
+}
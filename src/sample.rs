@@ -0,0 +1,216 @@
+//! Random sampling ("rolling") from distributions.
+
+mod estimate;
+mod program;
+mod rare;
+
+use crate::Distribution;
+pub use estimate::Estimate;
+pub use program::Program;
+
+/// A source of random numbers, for rolling dice.
+///
+/// dicer doesn't depend on any particular random number generator;
+/// implement this trait to use your own (e.g. by forwarding to `rand::RngCore::next_u64`).
+pub trait Rng {
+    /// Produce a uniformly random 64-bit value.
+    fn next_u64(&mut self) -> u64;
+}
+
+/// A small, fast, seedable random number generator (SplitMix64).
+///
+/// Suitable for simulations and games; not suitable for cryptography.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Create a generator from the given seed.
+    /// The same seed always produces the same sequence of values.
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+}
+
+impl Rng for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Produce a uniformly random value in `0..n`, without bias towards small values.
+pub(crate) fn below(rng: &mut (impl Rng + ?Sized), n: u64) -> u64 {
+    debug_assert_ne!(n, 0);
+    // Reject the values at the top of the range that would make some results more likely.
+    let zone = u64::MAX - (u64::MAX - n + 1) % n;
+    loop {
+        let v = rng.next_u64();
+        if v <= zone {
+            return v % n;
+        }
+    }
+}
+
+/// Produce a uniformly random value in `lo..=hi`.
+pub(crate) fn between(rng: &mut (impl Rng + ?Sized), lo: i64, hi: i64) -> i64 {
+    lo + below(rng, hi.abs_diff(lo) + 1) as i64
+}
+
+/// Fill `out` with uniformly random values in `0..n`, as [`below`] produces one at a time.
+///
+/// This scales each random value by multiplication rather than reducing it by division
+/// (Lemire's method), so the common case is the same arithmetic for every value.
+pub(crate) fn fill_below(rng: &mut (impl Rng + ?Sized), n: u64, out: &mut [u64]) {
+    debug_assert_ne!(n, 0);
+    for v in out.iter_mut() {
+        *v = rng.next_u64();
+    }
+    // Products whose low half falls below this would make some results more likely.
+    let threshold = n.wrapping_neg() % n;
+    for v in out.iter_mut() {
+        let mut m = u128::from(*v) * u128::from(n);
+        while (m as u64) < threshold {
+            m = u128::from(rng.next_u64()) * u128::from(n);
+        }
+        *v = (m >> 64) as u64;
+    }
+}
+
+/// A single die as rolled: its size, the face it showed, and whether it counts
+/// toward the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolledDie {
+    pub sides: usize,
+    pub face: i64,
+    pub kept: bool,
+}
+
+/// Roll `count` dice with `sides` sides each, all kept.
+///
+/// Panics if `sides` is 0.
+pub fn roll_dice(rng: &mut impl Rng, count: usize, sides: usize) -> Vec<RolledDie> {
+    assert_ne!(sides, 0, "dice need at least one side");
+    (0..count)
+        .map(|_| RolledDie {
+            sides,
+            face: below(rng, sides as u64) as i64 + 1,
+            kept: true,
+        })
+        .collect()
+}
+
+/// Keep only the `n` highest of the kept dice, dropping the rest; ties keep the earlier die.
+pub fn keep_highest(dice: &mut [RolledDie], n: usize) {
+    keep_ranked(dice, n, |a, b| b.cmp(&a))
+}
+
+/// Keep only the `n` lowest of the kept dice, dropping the rest; ties keep the earlier die.
+pub fn keep_lowest(dice: &mut [RolledDie], n: usize) {
+    keep_ranked(dice, n, |a, b| a.cmp(&b))
+}
+
+/// Keep the first `n` kept dice in the order, dropping the rest.
+fn keep_ranked(dice: &mut [RolledDie], n: usize, order: impl Fn(i64, i64) -> std::cmp::Ordering) {
+    let mut ranked: Vec<usize> = (0..dice.len()).filter(|i| dice[*i].kept).collect();
+    // A stable sort, so ties go to the earlier die.
+    ranked.sort_by(|a, b| order(dice[*a].face, dice[*b].face));
+    for i in ranked.into_iter().skip(n) {
+        dice[i].kept = false;
+    }
+}
+
+impl Distribution {
+    /// Roll: produce a random value, with the probabilities of this distribution.
+    pub fn sample(&self, rng: &mut impl Rng) -> i64 {
+        let mut target = below(rng, self.total() as u64) as usize;
+        for (value, occurrences) in self.occurrences() {
+            if target < occurrences {
+                return value;
+            }
+            target -= occurrences;
+        }
+        unreachable!("sample target exceeded total occurrences")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    #[test]
+    fn deterministic() {
+        let mut a = SplitMix64::new(17);
+        let mut b = SplitMix64::new(17);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn below_range() {
+        let mut rng = SplitMix64::new(1);
+        for n in [1, 2, 3, 6, 7, 100, u64::MAX] {
+            for _ in 0..100 {
+                assert!(below(&mut rng, n) < n);
+            }
+        }
+    }
+
+    #[test]
+    fn fill_below_range() {
+        let mut rng = SplitMix64::new(2);
+        let mut out = [0; 100];
+        for n in [1, 2, 3, 6, 7, 100, u64::MAX] {
+            fill_below(&mut rng, n, &mut out);
+            assert!(out.iter().all(|v| *v < n));
+        }
+        fill_below(&mut rng, 2, &mut out);
+        assert!(out.contains(&0) && out.contains(&1));
+    }
+
+    #[test]
+    fn rolled_dice() {
+        let mut rng = SplitMix64::new(3);
+        let mut dice = roll_dice(&mut rng, 100, 6);
+        assert!(dice.iter().all(|d| (1..=6).contains(&d.face) && d.kept));
+        keep_highest(&mut dice, 10);
+        let kept: Vec<i64> = dice.iter().filter(|d| d.kept).map(|d| d.face).collect();
+        assert_eq!(kept, vec![6; 10]);
+
+        let die = |face, kept| RolledDie {
+            sides: 20,
+            face,
+            kept,
+        };
+        let mut dice = vec![die(5, true), die(3, true), die(5, true), die(1, false)];
+        keep_lowest(&mut dice, 2);
+        assert_eq!(
+            dice,
+            vec![die(5, true), die(3, true), die(5, false), die(1, false)]
+        );
+        keep_highest(&mut dice, 5);
+        assert_eq!(dice.iter().filter(|d| d.kept).count(), 2);
+    }
+
+    #[test]
+    fn sample_frequencies() {
+        let d: Distribution = "2d4".parse::<Closed>().unwrap().distribution().unwrap();
+        let mut rng = SplitMix64::new(5);
+        let mut counts = [0usize; 9];
+        const N: usize = 160_000;
+        for _ in 0..N {
+            counts[d.sample(&mut rng) as usize] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert_eq!(counts[1], 0);
+        for (v, count) in counts.into_iter().enumerate().skip(2) {
+            let expected = d.probability_f64(v as i64) * N as f64;
+            let error = (count as f64 - expected).abs() / expected;
+            assert!(error < 0.05, "value {v}: {count} vs {expected}");
+        }
+    }
+}
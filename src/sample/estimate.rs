@@ -0,0 +1,181 @@
+//! Estimates from random samples, with their uncertainty.
+//!
+//! A sampled mean or probability is only approximately right; reporting it without
+//! its uncertainty invites reading noise as signal. Every estimate from sampling is
+//! an [`Estimate`], which carries its standard error and a confidence interval.
+
+use crate::Error;
+
+use super::{Program, Rng};
+
+/// The number of standard errors on either side of an estimate that cover 95% of
+/// a normal distribution.
+const Z95: f64 = 1.96;
+
+/// An estimate of some quantity from random samples, with its uncertainty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// The estimated value.
+    pub value: f64,
+    /// The standard error of the estimate: the standard deviation of `value` across repeated runs.
+    pub stderr: f64,
+    /// An approximate 95% confidence interval for the true value.
+    pub ci95: (f64, f64),
+}
+
+impl Estimate {
+    /// Estimate the mean of the distribution that produced these terms,
+    /// from their sample mean and variance.
+    pub(crate) fn from_terms(terms: impl IntoIterator<Item = f64>) -> Self {
+        // Welford's method, for numerical stability.
+        let (mut n, mut mean, mut m2) = (0.0, 0.0, 0.0);
+        for x in terms {
+            n += 1.0;
+            let delta = x - mean;
+            mean += delta / n;
+            m2 += delta * (x - mean);
+        }
+        let stderr = if n > 1.0 {
+            (m2 / (n - 1.0) / n).sqrt()
+        } else {
+            f64::INFINITY
+        };
+        Estimate::normal(mean, stderr)
+    }
+
+    /// An estimate that is normally distributed around the true value.
+    pub(crate) fn normal(value: f64, stderr: f64) -> Self {
+        Estimate {
+            value,
+            stderr,
+            ci95: (value - Z95 * stderr, value + Z95 * stderr),
+        }
+    }
+
+    /// A value known exactly.
+    pub(crate) fn exact(value: f64) -> Self {
+        Estimate::normal(value, 0.0)
+    }
+
+    /// Estimate a probability from the number of `hits` in `n` independent trials.
+    ///
+    /// The confidence interval is the Wilson score interval, which stays meaningful
+    /// when there are few (or no) hits: seeing no hits in 100 trials doesn't mean
+    /// the probability is exactly zero.
+    pub(crate) fn from_hits(hits: usize, n: usize) -> Self {
+        if n == 0 {
+            return Estimate {
+                value: 0.0,
+                stderr: f64::INFINITY,
+                ci95: (0.0, 1.0),
+            };
+        }
+        let (hits, n) = (hits as f64, n as f64);
+        let p = hits / n;
+        let z2 = Z95 * Z95;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let half = Z95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        Estimate {
+            value: p,
+            stderr: (p * (1.0 - p) / n).sqrt(),
+            ci95: ((center - half).max(0.0), (center + half).min(1.0)),
+        }
+    }
+
+    /// Limit the confidence interval to the possible values of a probability.
+    pub(crate) fn probability(self) -> Self {
+        Estimate {
+            ci95: (self.ci95.0.max(0.0), self.ci95.1.min(1.0)),
+            ..self
+        }
+    }
+}
+
+impl Program {
+    /// Estimate the mean of the expression from `n` rolls.
+    pub fn estimate_mean(&mut self, rng: &mut impl Rng, n: usize) -> Result<Estimate, Error> {
+        let rolls = self.sample_n(rng, n)?;
+        Ok(Estimate::from_terms(rolls.into_iter().map(|v| v as f64)))
+    }
+
+    /// Estimate the probability that a roll of the expression satisfies the predicate,
+    /// from `n` rolls.
+    ///
+    /// For very rare events, see [`Closed::estimate_at_least`](crate::Closed::estimate_at_least).
+    pub fn estimate_probability(
+        &mut self,
+        rng: &mut impl Rng,
+        n: usize,
+        predicate: impl Fn(i64) -> bool,
+    ) -> Result<Estimate, Error> {
+        let hits = self
+            .sample_n(rng, n)?
+            .into_iter()
+            .filter(|v| predicate(*v))
+            .count();
+        Ok(Estimate::from_hits(hits, n))
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.6} ± {:.6} (95% CI {:.6}..{:.6})",
+            self.value, self.stderr, self.ci95.0, self.ci95.1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Closed, sample::SplitMix64};
+
+    #[test]
+    fn from_terms() {
+        let e = Estimate::from_terms([1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(e.value, 0.5);
+        // Sample variance 1/3, over 4 terms.
+        assert!((e.stderr - (1.0f64 / 12.0).sqrt()).abs() < 1e-12);
+        assert!(e.ci95.0 < 0.0);
+
+        let p = e.probability();
+        assert_eq!(p.ci95.0, 0.0);
+        assert_eq!(p.ci95.1, 1.0);
+        assert_eq!(p.value, e.value);
+    }
+
+    #[test]
+    fn from_hits() {
+        let e = Estimate::from_hits(50, 100);
+        assert_eq!(e.value, 0.5);
+        assert!((e.stderr - 0.05).abs() < 1e-12);
+        assert!(e.ci95.0 > 0.39 && e.ci95.0 < 0.41, "{e}");
+        assert!(e.ci95.1 > 0.59 && e.ci95.1 < 0.61, "{e}");
+
+        // No hits is not certainty.
+        let e = Estimate::from_hits(0, 100);
+        assert_eq!(e.value, 0.0);
+        assert_eq!(e.ci95.0, 0.0);
+        assert!(e.ci95.1 > 0.03, "{e}");
+    }
+
+    #[test]
+    fn program_estimates() {
+        let e: Closed = "2d6 + 3".parse().unwrap();
+        let mut program = e.compile().unwrap();
+        let mut rng = SplitMix64::new(3);
+
+        let mean = program.estimate_mean(&mut rng, 10_000).unwrap();
+        assert!((mean.value - 10.0).abs() < 4.0 * mean.stderr, "{mean}");
+        assert!(mean.ci95.0 < 10.0 && 10.0 < mean.ci95.1, "{mean}");
+
+        let p = program
+            .estimate_probability(&mut rng, 10_000, |v| v >= 13)
+            .unwrap();
+        let d = e.distribution().unwrap();
+        let want: f64 = (13..=15).map(|v| d.probability_f64(v)).sum();
+        assert!((p.value - want).abs() < 4.0 * p.stderr, "{p}, want {want}");
+    }
+}
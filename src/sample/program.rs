@@ -0,0 +1,929 @@
+//! Expressions compiled for fast rolling.
+//!
+//! Computing an expression's distribution and then sampling from it is exact, but
+//! computing the distribution can be expensive. A [`Program`] instead rolls each die in the
+//! expression and combines the results, without allocating for each roll.
+//!
+//! Programs roll a batch of samples at once: each step of the expression runs over every
+//! sample in the batch before moving on to the next, so the inner loops are simple
+//! arithmetic over slices that the compiler can vectorize.
+
+use std::collections::HashSet;
+
+use crate::{
+    Closed, Distribution, Error, Evaluator,
+    discrete::{digit, digit_places, largest_set},
+    symbolic::{
+        Constant, CustomDie, Die, Explosion, ExpressionTree, ExpressionWrapper, Function, Ranker,
+        Symbol,
+    },
+};
+
+use super::{Rng, fill_below};
+
+/// How many samples to roll at once.
+const LANES: usize = 256;
+
+/// State for rolling a batch of samples.
+struct Machine<'a> {
+    rng: &'a mut dyn Rng,
+    /// The values of bound symbols, by slot, for each sample in the batch.
+    slots: &'a mut [Vec<i64>],
+    /// Buffers for intermediate values, reused across nodes and batches.
+    free: &'a mut Vec<Vec<i64>>,
+    /// Pools of rolls for each sample, reused across nodes and batches.
+    free_pools: &'a mut Vec<Vec<Vec<i64>>>,
+    /// Raw random values, before scaling to a die.
+    random: &'a mut Vec<u64>,
+    /// For each sample, the log of the likelihood ratio between the dice's own distributions
+    /// and the tilted distributions they were rolled from; see [`Program::tilted`].
+    log_weights: &'a mut [f64],
+}
+
+impl Machine<'_> {
+    /// A buffer of `len` zeroes. Return it with [`Machine::give`] when done.
+    fn take(&mut self, len: usize) -> Vec<i64> {
+        let mut v = self.free.pop().unwrap_or_default();
+        v.clear();
+        v.resize(len, 0);
+        v
+    }
+
+    fn give(&mut self, v: Vec<i64>) {
+        self.free.push(v)
+    }
+
+    /// Fill `out` with random values in `0..n`, transformed by `f`.
+    fn roll(&mut self, n: u64, out: &mut [i64], f: impl Fn(u64) -> i64) {
+        self.random.resize(out.len(), 0);
+        fill_below(self.rng, n, self.random);
+        for (o, r) in out.iter_mut().zip(self.random.iter()) {
+            *o = f(*r);
+        }
+    }
+}
+
+/// A compiled node: rolls a value for each sample in the batch.
+type Node = Box<dyn Fn(&mut Machine, &mut [i64]) -> Result<(), Error>>;
+
+/// A compiled die in a pool: adds one or more rolls to the pool of each sample
+/// that rolls at least this many dice.
+type PoolDie = Box<dyn Fn(&mut Machine, &mut [Vec<i64>], &[i64], i64) -> Result<(), Error>>;
+
+/// A dice expression, compiled for rolling many times.
+///
+/// Exploding dice explode at most as many times as they would when computing the
+/// distribution, so samples follow [`Closed::distribution`].
+pub struct Program {
+    root: Node,
+    slots: Vec<Vec<i64>>,
+    free: Vec<Vec<i64>>,
+    free_pools: Vec<Vec<Vec<i64>>>,
+    random: Vec<u64>,
+    log_weights: Vec<f64>,
+}
+
+impl Program {
+    /// Compile the expression, exploding dice at most `explosion_depth` times.
+    pub fn new(expression: &Closed, explosion_depth: usize) -> Result<Program, Error> {
+        Program::tilted(expression, explosion_depth, 0.0)
+    }
+
+    /// Compile the expression to roll each die from an exponentially tilted distribution:
+    /// each face's probability is scaled by `e^(tilt × face)`, then renormalized.
+    /// A positive tilt favors high rolls; a negative one, low rolls.
+    ///
+    /// Use [`Program::sample_weighted`] to recover the original probabilities.
+    /// Dice with more than [`TILT_MAX_FACES`] faces are rolled without tilting.
+    pub(crate) fn tilted(
+        expression: &Closed,
+        explosion_depth: usize,
+        tilt: f64,
+    ) -> Result<Program, Error> {
+        let mut compiler = Compiler {
+            explosion_depth,
+            tilt,
+            scopes: Vec::new(),
+            slots: 0,
+        };
+        let root = compiler.compile(expression)?;
+        Ok(Program {
+            root,
+            slots: vec![Vec::new(); compiler.slots],
+            free: Vec::new(),
+            free_pools: Vec::new(),
+            random: Vec::new(),
+            log_weights: Vec::new(),
+        })
+    }
+
+    /// Roll the expression once.
+    ///
+    /// Returns an error if this roll can't be completed: dividing by zero,
+    /// rolling a negative number of dice, or overflowing.
+    pub fn sample(&mut self, rng: &mut impl Rng) -> Result<i64, Error> {
+        let mut out = [0];
+        self.run(rng, &mut out)?;
+        Ok(out[0])
+    }
+
+    /// Roll the expression `n` times.
+    ///
+    /// This is faster than calling [`Program::sample`] `n` times.
+    /// Returns an error if any of the rolls can't be completed.
+    pub fn sample_n(&mut self, rng: &mut impl Rng, n: usize) -> Result<Vec<i64>, Error> {
+        let mut out = vec![0; n];
+        for batch in out.chunks_mut(LANES) {
+            self.run(rng, batch)?;
+        }
+        Ok(out)
+    }
+
+    /// Roll the expression `n` times, also reporting the log of each roll's weight:
+    /// the ratio of its probability under the dice's own distributions to its probability
+    /// as rolled. Weights are all 1 (log-weights 0) unless the program is [tilted](Program::tilted).
+    pub(crate) fn sample_weighted(
+        &mut self,
+        rng: &mut impl Rng,
+        n: usize,
+    ) -> Result<Vec<(i64, f64)>, Error> {
+        let mut out = vec![0; LANES.min(n)];
+        let mut result = Vec::with_capacity(n);
+        while result.len() < n {
+            let batch = &mut out[..LANES.min(n - result.len())];
+            self.run(rng, batch)?;
+            result.extend(batch.iter().copied().zip(self.log_weights.iter().copied()));
+        }
+        Ok(result)
+    }
+
+    fn run(&mut self, rng: &mut dyn Rng, out: &mut [i64]) -> Result<(), Error> {
+        self.log_weights.clear();
+        self.log_weights.resize(out.len(), 0.0);
+        let mut machine = Machine {
+            rng,
+            slots: &mut self.slots,
+            free: &mut self.free,
+            free_pools: &mut self.free_pools,
+            random: &mut self.random,
+            log_weights: &mut self.log_weights,
+        };
+        (self.root)(&mut machine, out)
+    }
+}
+
+impl Closed {
+    /// Compile the expression for rolling, with the default explosion depth.
+    pub fn compile(&self) -> Result<Program, Error> {
+        Program::new(self, Evaluator::DEFAULT_EXPLOSION_DEPTH)
+    }
+}
+
+/// Dice with more faces than this aren't tilted: the table of tilted probabilities
+/// would be too large.
+pub(crate) const TILT_MAX_FACES: usize = 1 << 16;
+
+/// A die with arbitrary (weighted) faces, ready to roll.
+struct Table {
+    values: Vec<i64>,
+    /// The running total of occurrences, through each value.
+    cumulative: Vec<u64>,
+    tilted: Option<Tilted>,
+}
+
+/// The faces of a [`Table`], reweighted by an exponential tilt.
+struct Tilted {
+    /// The running total of tilted probabilities, through each value.
+    cumulative: Vec<f64>,
+    /// For each value, the log of its original probability over its tilted probability.
+    log_ratios: Vec<f64>,
+}
+
+impl Table {
+    fn new(distribution: &Distribution, tilt: f64) -> Self {
+        let mut total = 0;
+        let (values, cumulative): (Vec<i64>, Vec<u64>) = distribution
+            .occurrences()
+            .map(|(v, o)| {
+                total += o as u64;
+                (v, total)
+            })
+            .unzip();
+        let tilted = (tilt != 0.0).then(|| {
+            // Shift exponents so the largest is zero, to avoid overflow.
+            let top = values
+                .iter()
+                .map(|v| tilt * *v as f64)
+                .fold(f64::NEG_INFINITY, f64::max);
+            let mut sum = 0.0;
+            let mut cumulative_tilted = Vec::with_capacity(values.len());
+            let mut shifts = Vec::with_capacity(values.len());
+            for (v, o) in distribution.occurrences() {
+                let shift = tilt * v as f64 - top;
+                sum += o as f64 * shift.exp();
+                cumulative_tilted.push(sum);
+                shifts.push(shift);
+            }
+            let log_normalizer = sum.ln() - (total as f64).ln();
+            Tilted {
+                cumulative: cumulative_tilted,
+                log_ratios: shifts.iter().map(|s| log_normalizer - s).collect(),
+            }
+        });
+        Table {
+            values,
+            cumulative,
+            tilted,
+        }
+    }
+
+    fn max(&self) -> i64 {
+        *self.values.last().expect("nonempty distribution")
+    }
+
+    fn roll(&self, m: &mut Machine, out: &mut [i64]) {
+        if let Some(tilted) = &self.tilted {
+            let total = *tilted.cumulative.last().expect("nonempty distribution");
+            for (o, w) in out.iter_mut().zip(m.log_weights.iter_mut()) {
+                // A uniform value in [0, 1), from the top 53 bits.
+                let u = (m.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+                let i = tilted
+                    .cumulative
+                    .partition_point(|c| *c <= u * total)
+                    .min(self.values.len() - 1);
+                *o = self.values[i];
+                *w += tilted.log_ratios[i];
+            }
+            return;
+        }
+        let total = *self.cumulative.last().expect("nonempty distribution");
+        m.roll(total, out, |target| {
+            self.values[self.cumulative.partition_point(|c| *c <= target)]
+        });
+    }
+}
+
+/// Combine `out` with `other`, lane by lane, reporting whether any lane overflowed.
+fn lanewise(out: &mut [i64], other: &[i64], f: impl Fn(i64, i64) -> (i64, bool)) -> bool {
+    let mut overflow = false;
+    for (a, b) in out.iter_mut().zip(other) {
+        let (v, o) = f(*a, *b);
+        *a = v;
+        overflow |= o;
+    }
+    overflow
+}
+
+struct Compiler {
+    explosion_depth: usize,
+    /// How to tilt the dice; see [`Program::tilted`].
+    tilt: f64,
+    /// Bound symbols, innermost last, with their slots.
+    scopes: Vec<(Symbol, usize)>,
+    /// How many slots are needed.
+    slots: usize,
+}
+
+impl Compiler {
+    fn compile(&mut self, e: &Closed) -> Result<Node, Error> {
+        let overflow = {
+            let e = e.to_string();
+            move || Error::Overflow(e.clone())
+        };
+        if self.tilt != 0.0 && self.tiltable(e) {
+            let table = Table::new(&self.exact(e)?, self.tilt);
+            return Ok(Box::new(move |m, out| {
+                table.roll(m, out);
+                Ok(())
+            }));
+        }
+        Ok(match e.inner() {
+            ExpressionTree::Modifier(Constant(c)) => {
+                let c = i64::try_from(*c).map_err(|_| overflow())?;
+                Box::new(move |_, out| {
+                    out.fill(c);
+                    Ok(())
+                })
+            }
+            ExpressionTree::Die(Die(n)) => {
+                i64::try_from(*n).map_err(|_| overflow())?;
+                let n = *n as u64;
+                Box::new(move |m, out| {
+                    m.roll(n, out, |r| r as i64 + 1);
+                    Ok(())
+                })
+            }
+            ExpressionTree::CustomDie(CustomDie::Range(lo, hi)) => {
+                let (lo, width) = (*lo, hi.abs_diff(*lo));
+                match width.checked_add(1) {
+                    Some(n) => Box::new(move |m, out| {
+                        m.roll(n, out, |r| lo.wrapping_add(r as i64));
+                        Ok(())
+                    }),
+                    // Every 64-bit value is a face.
+                    None => Box::new(|m, out| {
+                        for o in out.iter_mut() {
+                            *o = m.rng.next_u64() as i64;
+                        }
+                        Ok(())
+                    }),
+                }
+            }
+            ExpressionTree::CustomDie(CustomDie::Named { faces, .. } | CustomDie::Faces(faces)) => {
+                let faces = faces.clone();
+                Box::new(move |m, out| {
+                    m.roll(faces.len() as u64, out, |r| faces[r as usize]);
+                    Ok(())
+                })
+            }
+            ExpressionTree::CustomDie(CustomDie::Fudge) => Box::new(|m, out| {
+                m.roll(3, out, |r| r as i64 - 1);
+                Ok(())
+            }),
+            ExpressionTree::CustomDie(CustomDie::Digits(sizes)) => {
+                let places = digit_places(sizes).ok_or_else(overflow)?;
+                let dice: Vec<(u64, i64)> = sizes.iter().map(|n| *n as u64).zip(places).collect();
+                Box::new(move |m, out| {
+                    let mut rolls = m.take(out.len());
+                    out.fill(0);
+                    for (size, place) in &dice {
+                        m.roll(*size, &mut rolls, |r| (r as i64 + 1) * place);
+                        for (o, r) in out.iter_mut().zip(&rolls) {
+                            *o += r;
+                        }
+                    }
+                    m.give(rolls);
+                    Ok(())
+                })
+            }
+            ExpressionTree::CustomDie(CustomDie::Weighted(_)) => {
+                let table = Table::new(&self.exact(e)?, self.tilt);
+                Box::new(move |m, out| {
+                    table.roll(m, out);
+                    Ok(())
+                })
+            }
+            ExpressionTree::Symbol(symbol) => {
+                let (_, slot) = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find(|(s, _)| s == symbol)
+                    .ok_or_else(|| Error::UnboundSymbols(HashSet::from([symbol.clone()])))?;
+                let slot = *slot;
+                Box::new(move |m, out| {
+                    out.copy_from_slice(&m.slots[slot]);
+                    Ok(())
+                })
+            }
+            ExpressionTree::Negated(a) => {
+                let a = self.compile(a)?;
+                Box::new(move |m, out| {
+                    a(m, out)?;
+                    let mut overflowed = false;
+                    for v in out.iter_mut() {
+                        let (n, o) = v.overflowing_neg();
+                        *v = n;
+                        overflowed |= o;
+                    }
+                    if overflowed { Err(overflow()) } else { Ok(()) }
+                })
+            }
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker,
+            } => self.repeat(e, count, value, ranker)?,
+            ExpressionTree::Product(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |m, out| {
+                    a(m, out)?;
+                    let mut other = m.take(out.len());
+                    b(m, &mut other)?;
+                    let overflowed = lanewise(out, &other, i64::overflowing_mul);
+                    m.give(other);
+                    if overflowed { Err(overflow()) } else { Ok(()) }
+                })
+            }
+            ExpressionTree::Floor(a, b) | ExpressionTree::Modulo(a, b) => {
+                let op: fn(i64, i64) -> (i64, bool) =
+                    if matches!(e.inner(), ExpressionTree::Floor(..)) {
+                        i64::overflowing_div
+                    } else {
+                        i64::overflowing_rem_euclid
+                    };
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                let divide_by_zero = e.to_string();
+                Box::new(move |m, out| {
+                    a(m, out)?;
+                    let mut other = m.take(out.len());
+                    b(m, &mut other)?;
+                    if other.contains(&0) {
+                        return Err(Error::DivideByZero(divide_by_zero.clone()));
+                    }
+                    let overflowed = lanewise(out, &other, op);
+                    m.give(other);
+                    if overflowed { Err(overflow()) } else { Ok(()) }
+                })
+            }
+            ExpressionTree::Sum(items) => {
+                let items: Vec<Node> = items
+                    .iter()
+                    .map(|i| self.compile(i))
+                    .collect::<Result<_, _>>()?;
+                Box::new(move |m, out| {
+                    items[0](m, out)?;
+                    let mut other = m.take(out.len());
+                    let mut overflowed = false;
+                    for item in &items[1..] {
+                        item(m, &mut other)?;
+                        overflowed |= lanewise(out, &other, i64::overflowing_add);
+                    }
+                    m.give(other);
+                    if overflowed { Err(overflow()) } else { Ok(()) }
+                })
+            }
+            ExpressionTree::Comparison { a, b, op } => {
+                let (a, b, op) = (self.compile(a)?, self.compile(b)?, *op);
+                Box::new(move |m, out| {
+                    a(m, out)?;
+                    let mut other = m.take(out.len());
+                    b(m, &mut other)?;
+                    lanewise(out, &other, |a, b| (op.compare(a, b) as i64, false));
+                    m.give(other);
+                    Ok(())
+                })
+            }
+            ExpressionTree::Binding {
+                symbol,
+                value,
+                tail,
+            } => {
+                let value = self.compile(value)?;
+                let slot = self.slots;
+                self.slots += 1;
+                self.scopes.push((symbol.clone(), slot));
+                let tail = self.compile(tail);
+                self.scopes.pop();
+                let tail = tail?;
+                Box::new(move |m, out| {
+                    let mut bound = std::mem::take(&mut m.slots[slot]);
+                    bound.resize(out.len(), 0);
+                    let result = value(m, &mut bound);
+                    m.slots[slot] = bound;
+                    result?;
+                    tail(m, out)
+                })
+            }
+            ExpressionTree::Call { function, args } => match (function, args.as_slice()) {
+                (Function::Tens | Function::Units, [x]) => {
+                    let place = if *function == Function::Tens { 1 } else { 0 };
+                    let x = self.compile(x)?;
+                    Box::new(move |m, out| {
+                        x(m, out)?;
+                        for o in out.iter_mut() {
+                            *o = digit(*o, place);
+                        }
+                        Ok(())
+                    })
+                }
+                (Function::Abs, [x]) => {
+                    let x = self.compile(x)?;
+                    Box::new(move |m, out| {
+                        x(m, out)?;
+                        let mut overflowed = false;
+                        for v in out.iter_mut() {
+                            let (n, o) = v.overflowing_abs();
+                            *v = n;
+                            overflowed |= o;
+                        }
+                        if overflowed { Err(overflow()) } else { Ok(()) }
+                    })
+                }
+                (Function::Save, [damage, saved]) => {
+                    let (damage, saved) = (self.compile(damage)?, self.compile(saved)?);
+                    Box::new(move |m, out| {
+                        damage(m, out)?;
+                        let mut success = m.take(out.len());
+                        saved(m, &mut success)?;
+                        for (o, s) in out.iter_mut().zip(&success) {
+                            if *s != 0 {
+                                *o /= 2;
+                            }
+                        }
+                        m.give(success);
+                        Ok(())
+                    })
+                }
+                (Function::If, [condition, then, otherwise]) => {
+                    let (condition, then, otherwise) = (
+                        self.compile(condition)?,
+                        self.compile(then)?,
+                        self.compile(otherwise)?,
+                    );
+                    Box::new(move |m, out| {
+                        let mut taken = m.take(out.len());
+                        condition(m, &mut taken)?;
+                        then(m, out)?;
+                        let mut other = m.take(out.len());
+                        otherwise(m, &mut other)?;
+                        for ((o, c), e) in out.iter_mut().zip(&taken).zip(&other) {
+                            if *c == 0 {
+                                *o = *e;
+                            }
+                        }
+                        m.give(other);
+                        m.give(taken);
+                        Ok(())
+                    })
+                }
+                (Function::Clamp, [x, lo, hi]) => {
+                    let (x, lo, hi) = (self.compile(x)?, self.compile(lo)?, self.compile(hi)?);
+                    Box::new(move |m, out| {
+                        x(m, out)?;
+                        let mut bound = m.take(out.len());
+                        lo(m, &mut bound)?;
+                        for (o, lo) in out.iter_mut().zip(&bound) {
+                            *o = (*o).max(*lo);
+                        }
+                        hi(m, &mut bound)?;
+                        for (o, hi) in out.iter_mut().zip(&bound) {
+                            *o = (*o).min(*hi);
+                        }
+                        m.give(bound);
+                        Ok(())
+                    })
+                }
+                (Function::Max | Function::Min, [a, b]) => {
+                    let highest = *function == Function::Max;
+                    let (a, b) = (self.compile(a)?, self.compile(b)?);
+                    Box::new(move |m, out| {
+                        a(m, out)?;
+                        let mut other = m.take(out.len());
+                        b(m, &mut other)?;
+                        for (o, b) in out.iter_mut().zip(&other) {
+                            *o = if highest { (*o).max(*b) } else { (*o).min(*b) };
+                        }
+                        m.give(other);
+                        Ok(())
+                    })
+                }
+                (Function::Soak, [damage, armor]) => {
+                    let (damage, armor) = (self.compile(damage)?, self.compile(armor)?);
+                    Box::new(move |m, out| {
+                        damage(m, out)?;
+                        let mut other = m.take(out.len());
+                        armor(m, &mut other)?;
+                        let overflowed = lanewise(out, &other, |d, a| {
+                            if d <= a {
+                                (0, false)
+                            } else {
+                                d.overflowing_sub(a)
+                            }
+                        });
+                        m.give(other);
+                        if overflowed { Err(overflow()) } else { Ok(()) }
+                    })
+                }
+                _ => panic!("wrong number of arguments to {function} in {e}"),
+            },
+            ExpressionTree::Lookup { value, bands } => {
+                let value = self.compile(value)?;
+                let bands = bands.clone();
+                Box::new(move |m, out| {
+                    value(m, out)?;
+                    for o in out.iter_mut() {
+                        *o = bands.apply(*o);
+                    }
+                    Ok(())
+                })
+            }
+            // Modified dice are single dice: roll from their exact distributions.
+            ExpressionTree::Explode { .. }
+            | ExpressionTree::Reroll { .. }
+            | ExpressionTree::Successes { .. } => {
+                let table = Table::new(&self.exact(e)?, self.tilt);
+                Box::new(move |m, out| {
+                    table.roll(m, out);
+                    Ok(())
+                })
+            }
+        })
+    }
+
+    /// Whether this is a plain die, small enough to tilt.
+    fn tiltable(&self, e: &Closed) -> bool {
+        match e.inner() {
+            ExpressionTree::Die(Die(n)) => *n <= TILT_MAX_FACES,
+            ExpressionTree::CustomDie(CustomDie::Range(lo, hi)) => {
+                hi.abs_diff(*lo) < TILT_MAX_FACES as u64
+            }
+            ExpressionTree::CustomDie(CustomDie::Named { faces, .. } | CustomDie::Faces(faces)) => {
+                faces.len() <= TILT_MAX_FACES
+            }
+            ExpressionTree::CustomDie(CustomDie::Weighted(faces)) => faces.len() <= TILT_MAX_FACES,
+            ExpressionTree::CustomDie(CustomDie::Digits(sizes)) => sizes
+                .iter()
+                .try_fold(1usize, |faces, n| faces.checked_mul(*n))
+                .is_some_and(|faces| faces <= TILT_MAX_FACES),
+            ExpressionTree::CustomDie(CustomDie::Fudge) => true,
+            _ => false,
+        }
+    }
+
+    /// The exact distribution of a single (modified) die.
+    fn exact(&self, die: &Closed) -> Result<Distribution, Error> {
+        Evaluator::default()
+            .with_explosion_depth(self.explosion_depth)
+            .eval(die)
+    }
+
+    fn repeat(
+        &mut self,
+        e: &Closed,
+        count: &Closed,
+        value: &Closed,
+        ranker: &Ranker<Box<Closed>>,
+    ) -> Result<Node, Error> {
+        let count = self.compile(count)?;
+        let expression = e.to_string();
+        // Only which rolls are kept: the count kept is rolled for each sample.
+        let kind = ranker.map(|_| 0);
+        // Roll the number of dice for each sample, returning the largest.
+        let count = move |m: &mut Machine, counts: &mut [i64]| {
+            count(m, counts)?;
+            for n in counts.iter() {
+                if *n < 0 {
+                    return Err(Error::NegativeCount(expression.clone()));
+                }
+                if (*n as usize) < kind.min_count() {
+                    return Err(kind.too_few(expression.clone()));
+                }
+            }
+            Ok(counts.iter().copied().max().unwrap_or(0))
+        };
+        let overflow = {
+            let e = e.to_string();
+            move || Error::Overflow(e.clone())
+        };
+
+        if matches!(ranker, Ranker::All) {
+            let value = self.compile(value)?;
+            return Ok(Box::new(move |m, out| {
+                let mut counts = m.take(out.len());
+                let most = count(m, &mut counts)?;
+                out.fill(0);
+                let mut rolls = m.take(out.len());
+                let mut overflowed = false;
+                for i in 0..most {
+                    value(m, &mut rolls)?;
+                    for ((o, r), n) in out.iter_mut().zip(&rolls).zip(&counts) {
+                        let (sum, o2) = o.overflowing_add(if *n > i { *r } else { 0 });
+                        *o = sum;
+                        overflowed |= o2;
+                    }
+                }
+                m.give(rolls);
+                m.give(counts);
+                if overflowed { Err(overflow()) } else { Ok(()) }
+            }));
+        }
+
+        let keep = match ranker.count() {
+            Some(keep) => Some(self.compile(keep)?),
+            None => None,
+        };
+        let expression = e.to_string();
+        // The ranker for each sample, given its count kept (or dropped) and number of rolls.
+        let ranker_for = move |k: i64, n: i64| {
+            if k < 0 {
+                return Err(Error::NegativeCount(expression.clone()));
+            }
+            let ranker = kind.map(|_| k as usize);
+            if (n as usize) < ranker.min_count() {
+                return Err(ranker.too_few(expression.clone()));
+            }
+            Ok(ranker)
+        };
+        let die = self.pool_die(value)?;
+        Ok(Box::new(move |m, out| {
+            let mut counts = m.take(out.len());
+            let most = count(m, &mut counts)?;
+            let mut keeps = m.take(out.len());
+            match &keep {
+                Some(keep) => keep(m, &mut keeps)?,
+                None => keeps.fill(0),
+            }
+            let mut pools = m.free_pools.pop().unwrap_or_default();
+            pools.resize_with(out.len(), Vec::new);
+            for pool in pools.iter_mut() {
+                pool.clear();
+            }
+            for i in 0..most {
+                die(m, &mut pools, &counts, i)?;
+            }
+            let mut overflowed = false;
+            for (((o, pool), n), k) in out
+                .iter_mut()
+                .zip(pools.iter_mut())
+                .zip(&counts)
+                .zip(&keeps)
+            {
+                let ranker = ranker_for(*k, *n)?;
+                if ranker == Ranker::Matching {
+                    *o = largest_set(pool);
+                    continue;
+                }
+                if ranker.keeps_highest() {
+                    pool.sort_unstable_by(|a, b| b.cmp(a));
+                } else {
+                    pool.sort_unstable();
+                }
+                let keep = ranker.keep(*n) as usize;
+                match pool[..keep]
+                    .iter()
+                    .try_fold(0i64, |acc, v| acc.checked_add(*v))
+                {
+                    Some(v) => *o = v,
+                    None => overflowed = true,
+                }
+            }
+            m.free_pools.push(pools);
+            m.give(keeps);
+            m.give(counts);
+            if overflowed { Err(overflow()) } else { Ok(()) }
+        }))
+    }
+
+    /// Compile a die for a pool: each explosion of an exploding die is another die in the pool.
+    fn pool_die(&mut self, value: &Closed) -> Result<PoolDie, Error> {
+        if let ExpressionTree::Explode {
+            value: die,
+            explosion: Explosion::Standard,
+            trigger,
+        } = value.inner()
+        {
+            let table = Table::new(&self.exact(die)?, self.tilt);
+            let max = table.max();
+            let trigger = *trigger;
+            let depth = self.explosion_depth;
+            return Ok(Box::new(move |m, pools, counts, i| {
+                let mut rolls = m.take(pools.len());
+                // Which samples are still exploding.
+                let mut live = m.take(pools.len());
+                table.roll(m, &mut rolls);
+                for (((pool, r), n), l) in pools.iter_mut().zip(&rolls).zip(counts).zip(&mut live) {
+                    if *n > i {
+                        pool.push(*r);
+                        *l = trigger.explodes(max, *r) as i64;
+                    }
+                }
+                for _ in 0..depth {
+                    if !live.contains(&1) {
+                        break;
+                    }
+                    table.roll(m, &mut rolls);
+                    for ((pool, r), l) in pools.iter_mut().zip(&rolls).zip(&mut live) {
+                        if *l == 1 {
+                            pool.push(*r);
+                            *l = trigger.explodes(max, *r) as i64;
+                        }
+                    }
+                }
+                m.give(live);
+                m.give(rolls);
+                Ok(())
+            }));
+        }
+        let value = self.compile(value)?;
+        Ok(Box::new(move |m, pools, counts, i| {
+            let mut rolls = m.take(pools.len());
+            value(m, &mut rolls)?;
+            for ((pool, r), n) in pools.iter_mut().zip(&rolls).zip(counts) {
+                if *n > i {
+                    pool.push(*r);
+                }
+            }
+            m.give(rolls);
+            Ok(())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::SplitMix64;
+
+    const EXPRESSIONS: &[&str] = &[
+        "2d6 + 3",
+        "4d6kh3",
+        "2d20kl - d[-2..2]",
+        "4d6dl",
+        "[ATK: d20] (ATK = 20) * (2d6 + 3) + (ATK < 20) * (ATK + 5 >= 15) * (d6 + 3)",
+        "3d6!kh2",
+        "d6!!",
+        "d20ro1",
+        "2d20ro1kl + 5",
+        "(d4)d6 / 2",
+        "(d3)d6!kl",
+        "(d4 + 1)d6kh(d2)",
+        "(d3 + 1)d4m",
+        "[N: d3] 4d6dl(N - 1)",
+        "soak(3d6, d6)",
+        "4d10s>=6f1",
+        "3d10!s>=8",
+        "3d10!>=9kh2",
+        "d6!p>4",
+        "4dF + 2",
+        "[R: d%] tens(R) = units(R)",
+        "3d{1,1,3,5,8}kh2",
+        "units(3d10) + tens(-d[5..25])",
+        "d66",
+        "d(2,10) - d{1:3,2:1}",
+        "max(d8!, d6!) - min(d4, d[2..3])",
+        "abs(d6 - d6)",
+        "clamp(2d6 - 4, d4, 6)",
+        "if d20 + 5 >= 15 then 2d6 else d4",
+        "save(8d6, d20 + 3 >= 15)",
+        "(3d6 - 10) % d4",
+        "(3d6) -> {3: -3, 4..5: -2, 6..8: -1, 13..15: 1, 16..17: 2, 18: 3} * d4",
+    ];
+
+    /// Check that the samples follow the exact distribution.
+    fn check_frequencies(expression: &str, samples: &[i64]) {
+        let e: Closed = expression.parse().unwrap();
+        let d = e.distribution().unwrap();
+        let mut counts = std::collections::HashMap::<i64, usize>::new();
+        for v in samples {
+            *counts.entry(*v).or_default() += 1;
+        }
+        for v in counts.keys() {
+            assert_ne!(
+                d.probability_f64(*v),
+                0.0,
+                "{expression}: impossible value {v}"
+            );
+        }
+        for (v, _) in d.occurrences() {
+            let p = d.probability_f64(v);
+            if p < 0.01 {
+                continue;
+            }
+            let got = *counts.get(&v).unwrap_or(&0) as f64 / samples.len() as f64;
+            assert!(
+                (got - p).abs() < 0.01,
+                "{expression}: value {v}: {got} vs {p}"
+            );
+        }
+    }
+
+    #[test]
+    fn frequencies() {
+        let mut rng = SplitMix64::new(11);
+        for e in EXPRESSIONS {
+            let mut program = e.parse::<Closed>().unwrap().compile().unwrap();
+            let samples: Vec<i64> = (0..50_000)
+                .map(|_| program.sample(&mut rng).unwrap())
+                .collect();
+            check_frequencies(e, &samples);
+        }
+    }
+
+    #[test]
+    fn batch_frequencies() {
+        let mut rng = SplitMix64::new(12);
+        for e in EXPRESSIONS {
+            let mut program = e.parse::<Closed>().unwrap().compile().unwrap();
+            // Not a multiple of the batch size.
+            let samples = program.sample_n(&mut rng, 100_001).unwrap();
+            assert_eq!(samples.len(), 100_001);
+            check_frequencies(e, &samples);
+        }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let mut rng = SplitMix64::new(3);
+        let mut program = "d6 / (d2 - 1)"
+            .parse::<Closed>()
+            .unwrap()
+            .compile()
+            .unwrap();
+        let errors = (0..100)
+            .filter_map(|_| program.sample(&mut rng).err())
+            .collect::<Vec<_>>();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|e| matches!(e, Error::DivideByZero(_))));
+        assert!(matches!(
+            program.sample_n(&mut rng, 100),
+            Err(Error::DivideByZero(_))
+        ));
+
+        let mut program = "(d2 - 2)d6".parse::<Closed>().unwrap().compile().unwrap();
+        assert!((0..100).any(|_| matches!(program.sample(&mut rng), Err(Error::NegativeCount(_)))));
+
+        let e = "d6rr<7".parse::<Closed>().unwrap();
+        assert!(matches!(e.compile(), Err(Error::RerollsForever(_))));
+    }
+}
@@ -0,0 +1,181 @@
+//! Estimating the probability of rare events.
+//!
+//! Some expressions are too expensive to evaluate exactly, and some events too rare to
+//! observe by plain sampling: a million rolls of `40d10!` will rarely, if ever, total 400.
+//! Instead, we roll dice that are biased towards the event (importance sampling),
+//! and weight each roll by how much more likely the biased dice made it.
+//!
+//! The bias is an exponential tilt: each face `v` of each die is made `e^(θv)` times as likely.
+//! We pick θ so the biased dice total the threshold on average; then about half of the
+//! biased rolls reach the event, and the weights are modest.
+
+use crate::{Closed, Error, Evaluator};
+
+use super::{Estimate, Program, Rng};
+
+/// How many samples to use in each trial when choosing the tilt.
+const PILOT_SAMPLES: usize = 1024;
+
+/// The smallest tilt to try, and the largest: beyond this, the tilted dice
+/// roll their extreme faces almost always.
+const MIN_TILT: f64 = 1e-6;
+const MAX_TILT: f64 = 1e6;
+
+/// How many times to halve the range of tilts, once it brackets the threshold.
+const BISECTIONS: usize = 16;
+
+impl Closed {
+    /// Estimate the probability that the expression is at least `threshold`,
+    /// from `samples` weighted rolls.
+    ///
+    /// Unlike [`Distribution::probability_f64`](crate::Distribution::probability_f64),
+    /// this doesn't compute the whole distribution, so it works on expressions too large
+    /// to evaluate exactly; and unlike counting plain rolls, it stays accurate for events
+    /// far too rare to roll.
+    ///
+    /// Choosing the bias takes some additional rolls, about a thousand for each of a few dozen trials.
+    pub fn estimate_at_least(
+        &self,
+        threshold: i64,
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Estimate, Error> {
+        self.estimate_tail(threshold, 1.0, samples, rng)
+    }
+
+    /// Estimate the probability that the expression is at most `threshold`,
+    /// from `samples` weighted rolls.
+    ///
+    /// See [`Closed::estimate_at_least`].
+    pub fn estimate_at_most(
+        &self,
+        threshold: i64,
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Estimate, Error> {
+        self.estimate_tail(threshold, -1.0, samples, rng)
+    }
+
+    /// Estimate the probability that `direction * self >= direction * threshold`.
+    fn estimate_tail(
+        &self,
+        threshold: i64,
+        direction: f64,
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Estimate, Error> {
+        let tilt = direction * self.choose_tilt(threshold as f64 * direction, direction, rng)?;
+        let mut program = Program::tilted(self, Evaluator::DEFAULT_EXPLOSION_DEPTH, tilt)?;
+        let rolls = program.sample_weighted(rng, samples)?;
+        let hit = |v: i64| {
+            if direction > 0.0 {
+                v >= threshold
+            } else {
+                v <= threshold
+            }
+        };
+        Ok(
+            Estimate::from_terms(rolls.into_iter().map(|(v, log_weight)| {
+                // Only evaluate the weight for hits: misses may have enormous weights.
+                if hit(v) { log_weight.exp() } else { 0.0 }
+            }))
+            .probability(),
+        )
+    }
+
+    /// Find the magnitude of the tilt (in `direction`) that brings the mean of
+    /// `direction * self` to `target`.
+    fn choose_tilt(&self, target: f64, direction: f64, rng: &mut impl Rng) -> Result<f64, Error> {
+        let mut reaches = |tilt: f64| -> Result<bool, Error> {
+            let mut program =
+                Program::tilted(self, Evaluator::DEFAULT_EXPLOSION_DEPTH, direction * tilt)?;
+            let rolls = program.sample_n(rng, PILOT_SAMPLES)?;
+            let mean = rolls.iter().map(|v| *v as f64).sum::<f64>() / rolls.len() as f64;
+            Ok(direction * mean >= target)
+        };
+        if reaches(0.0)? {
+            // Not a rare event: plain sampling works.
+            return Ok(0.0);
+        }
+        let mut low = 0.0;
+        let mut high = MIN_TILT;
+        while !reaches(high)? {
+            if high >= MAX_TILT {
+                // The threshold is at (or beyond) the extreme: tilt as far as we can.
+                return Ok(MAX_TILT);
+            }
+            low = high;
+            high *= 2.0;
+        }
+        for _ in 0..BISECTIONS {
+            let mid = (low + high) / 2.0;
+            if reaches(mid)? {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Closed, sample::SplitMix64};
+
+    /// Check the estimate against the exact probability.
+    fn check(expression: &str, at_least: bool, threshold: i64) {
+        let e: Closed = expression.parse().unwrap();
+        let d = e.distribution().unwrap();
+        let want: f64 = d
+            .occurrences()
+            .filter(|(v, _)| {
+                if at_least {
+                    *v >= threshold
+                } else {
+                    *v <= threshold
+                }
+            })
+            .map(|(v, _)| d.probability_f64(v))
+            .sum();
+        let mut rng = SplitMix64::new(5);
+        let got = if at_least {
+            e.estimate_at_least(threshold, 20_000, &mut rng)
+        } else {
+            e.estimate_at_most(threshold, 20_000, &mut rng)
+        }
+        .unwrap();
+        assert!(
+            (got.value - want).abs() < 4.0 * got.stderr,
+            "{expression} {threshold}: got {got}, want {want}"
+        );
+        assert!(
+            (got.value - want).abs() < 0.1 * want,
+            "{expression} {threshold}: got {got}, want {want}"
+        );
+        assert!(got.ci95.0 <= got.value && got.value <= got.ci95.1);
+    }
+
+    #[test]
+    fn rare_tails() {
+        // About 1 in 10^6; plain sampling would rarely see it at all.
+        check("20d6", true, 105);
+        check("20d6", false, 35);
+        check("4d6kh3 + 10d8 - d4", true, 80);
+        check("6d6! + 3d10", true, 70);
+    }
+
+    #[test]
+    fn common_events() {
+        check("2d6", true, 7);
+        check("d20ro1 + 5", false, 10);
+    }
+
+    #[test]
+    fn unreachable() {
+        let e: Closed = "3d6".parse().unwrap();
+        let mut rng = SplitMix64::new(1);
+        let got = e.estimate_at_least(19, 1000, &mut rng).unwrap();
+        assert_eq!(got.value, 0.0);
+    }
+}
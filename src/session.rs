@@ -0,0 +1,264 @@
+//! An interactive session, as `dicer repl` runs: a collection of macros, and commands to
+//! define, evaluate, save, and load them.
+//!
+//! Each line of input is one of:
+//!
+//! - `name: expression`, which defines (or redefines) a macro;
+//! - a macro's name, which evaluates it;
+//! - `:save` or `:load`, with an optional path, which write the macros to a file or read them
+//!   back, by default [`default_path`];
+//! - `:list`, which lists the macros;
+//! - or any other expression, which is evaluated.
+//!
+//! A macro named in capitals, like `ATTACK`, can be used in other expressions: `ATTACK + 2`
+//! rolls the macro once and adds 2, as if bound with `[ATTACK: ...]`.
+//! Macros are saved in the same format that [`lint`](crate::lint) checks, one per line,
+//! so a saved file can be edited, linted, and watched like any other.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Closed, Error, lint::named, verbal};
+
+/// The file in the home directory that macros are saved to and loaded from by default.
+pub const DEFAULT_FILE: &str = ".dicer_macros";
+
+/// The default file for saving and loading macros, if there's a home directory.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(DEFAULT_FILE))
+}
+
+/// The macros defined in a session, in the order they were first defined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    macros: Vec<(String, String)>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Each macro's name and expression.
+    pub fn macros(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.macros.iter().map(|(n, e)| (n.as_str(), e.as_str()))
+    }
+
+    /// The expression of the named macro, if there is one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.macros()
+            .find(|(n, _)| *n == name)
+            .map(|(_, expression)| expression)
+    }
+
+    /// Define the macro, replacing any macro of the same name.
+    ///
+    /// Returns an error, and leaves the session as it was, if the expression doesn't
+    /// parse with the macros already defined.
+    pub fn define(&mut self, name: &str, expression: &str) -> Result<(), Error> {
+        self.expand(expression)?;
+        self.insert(name, expression);
+        Ok(())
+    }
+
+    fn insert(&mut self, name: &str, expression: &str) {
+        match self.macros.iter_mut().find(|(n, _)| n == name) {
+            Some((_, e)) => *e = expression.to_owned(),
+            None => self.macros.push((name.to_owned(), expression.to_owned())),
+        }
+    }
+
+    /// Parse the expression, binding each macro it uses (and each macro those use) by name.
+    pub fn expand(&self, expression: &str) -> Result<Closed, Error> {
+        let mut expanded = expression.to_owned();
+        // Each round binds at least one more macro, so more rounds than macros is a cycle.
+        for _ in 0..=self.macros.len() {
+            let symbols = match expanded.parse::<Closed>() {
+                Err(Error::UnboundSymbols(symbols)) => symbols,
+                result => return result,
+            };
+            let mut symbols: Vec<_> = symbols.into_iter().map(|s| s.to_string()).collect();
+            symbols.sort();
+            for symbol in symbols {
+                let Some(value) = self.get(&symbol) else {
+                    return expanded.parse();
+                };
+                expanded = format!("[{symbol}: {value}] {expanded}");
+            }
+        }
+        expanded.parse()
+    }
+
+    /// The macros, in the format [`Session::load`] reads.
+    pub fn to_source(&self) -> String {
+        self.macros()
+            .map(|(name, expression)| format!("{name}: {expression}\n"))
+            .collect()
+    }
+
+    /// Write the macros to the file, replacing it.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_source())
+    }
+
+    /// Read the macros in the file, adding them to the session and replacing any of
+    /// the same names. Returns how many there were.
+    ///
+    /// Macros are read as written, without checking them: they may refer to each other in any order.
+    pub fn load(&mut self, path: &Path) -> std::io::Result<usize> {
+        let source = std::fs::read_to_string(path)?;
+        let mut n = 0;
+        for (name, expression) in named(&source) {
+            self.insert(name, expression);
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Handle one line of input, and give the response.
+    pub fn respond(&mut self, line: &str) -> String {
+        let line = line.trim();
+        if let Some(command) = line.strip_prefix(':') {
+            return self.command(command);
+        }
+        if let Some(expression) = self.get(line) {
+            return self.evaluate(Some(line), expression);
+        }
+        if let Some((name, expression)) = named(line).next() {
+            return match self.define(name, expression) {
+                Ok(()) => format!("defined {name}"),
+                Err(e) => format!("error: {e}"),
+            };
+        }
+        self.evaluate(None, line)
+    }
+
+    /// Describe the expression's roll, by name if it has one, or as written if it uses macros.
+    fn evaluate(&self, name: Option<&str>, expression: &str) -> String {
+        match self
+            .expand(expression)
+            .and_then(|e| Ok((e.distribution()?, e)))
+        {
+            Ok((d, e)) => match name {
+                Some(name) => verbal::describe_as(name, &d),
+                None if expression.parse::<Closed>().is_err() => {
+                    verbal::describe_as(expression, &d)
+                }
+                None => verbal::describe(&e, &d),
+            },
+            Err(e) => format!("error: {e}"),
+        }
+    }
+
+    fn command(&mut self, command: &str) -> String {
+        let (command, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(c, a)| (c, a.trim()));
+        let path = || {
+            if argument.is_empty() {
+                default_path().ok_or_else(|| "no home directory; give a path".to_owned())
+            } else {
+                Ok(PathBuf::from(argument))
+            }
+        };
+        let result = match command {
+            "save" => path().and_then(|path| {
+                self.save(&path)
+                    .map(|()| format!("saved {} macros to {}", self.macros.len(), path.display()))
+                    .map_err(|e| format!("failed to save {}: {e}", path.display()))
+            }),
+            "load" => path().and_then(|path| {
+                self.load(&path)
+                    .map(|n| format!("loaded {n} macros from {}", path.display()))
+                    .map_err(|e| format!("failed to load {}: {e}", path.display()))
+            }),
+            "list" => Ok(self.to_source().trim_end().to_owned()),
+            _ => Err(format!(
+                "unknown command :{command}; try :save, :load, or :list"
+            )),
+        };
+        result.unwrap_or_else(|e| format!("error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macros() {
+        let mut session = Session::new();
+        assert_eq!(session.respond("ATTACK: d20 + 5"), "defined ATTACK");
+        assert_eq!(session.respond("hit: ATTACK >= 15"), "defined hit");
+        assert_eq!(
+            session.respond("hit"),
+            "Rolling hit: average 0.55, most likely 1, range 0 to 1"
+        );
+        assert_eq!(
+            session.respond("ATTACK - 5"),
+            "Rolling ATTACK - 5: average 10.5, every result equally likely, range 1 to 20"
+        );
+        // Each use of a macro rolls it once.
+        assert_eq!(
+            session
+                .expand("ATTACK * 2")
+                .unwrap()
+                .distribution()
+                .unwrap(),
+            "2 * (d20 + 5)"
+                .parse::<Closed>()
+                .unwrap()
+                .distribution()
+                .unwrap()
+        );
+        assert!(session.respond("bad: MISSING + 1").starts_with("error: "));
+        assert_eq!(session.get("bad"), None);
+        assert_eq!(session.respond("ATTACK: d20 + 6"), "defined ATTACK");
+        assert_eq!(session.to_source(), "ATTACK: d20 + 6\nhit: ATTACK >= 15\n");
+        assert!(
+            session
+                .respond(":nope")
+                .starts_with("error: unknown command")
+        );
+    }
+
+    #[test]
+    fn nested_and_cyclic() {
+        let mut session = Session::new();
+        session.define("BASE", "d6").unwrap();
+        session.define("MORE", "BASE + BASE").unwrap();
+        assert_eq!(
+            session.expand("MORE + 1").unwrap().distribution().unwrap(),
+            "2 * d6 + 1"
+                .parse::<Closed>()
+                .unwrap()
+                .distribution()
+                .unwrap()
+        );
+        // Loaded macros aren't checked, so they can refer to each other in a cycle.
+        session.insert("A", "B + 1");
+        session.insert("B", "A + 1");
+        assert!(matches!(session.expand("A"), Err(Error::UnboundSymbols(_))));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("dicer-session-{}", std::process::id()));
+        let mut session = Session::new();
+        session.define("STAT", "4d6kh3").unwrap();
+        session.define("two stats", "STAT + STAT").unwrap();
+        let command = format!(":save {}", path.display());
+        assert!(session.respond(&command).starts_with("saved 2 macros"));
+
+        let mut other = Session::new();
+        other.define("STAT", "3d6").unwrap();
+        other.define("other", "d4").unwrap();
+        let command = format!(":load {}", path.display());
+        assert!(other.respond(&command).starts_with("loaded 2 macros"));
+        assert_eq!(
+            other.to_source(),
+            "STAT: 4d6kh3\nother: d4\ntwo stats: STAT + STAT\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(other.respond(&command).starts_with("error: failed to load"));
+    }
+}
@@ -0,0 +1,1054 @@
+//! Instant summaries of expressions: mean, variance, and bounds, without distributions.
+//!
+//! Summaries propagate through the expression using the standard formulas for independent
+//! variables, which are exact for sums, products, and plain repetitions. Where there's no exact
+//! formula, as for keeping the highest rolls or comparing, the mean and variance are
+//! approximations; the bounds are always guaranteed.
+
+use std::collections::HashSet;
+
+use crate::{
+    Closed, Distribution, Error, Evaluator,
+    discrete::digit,
+    symbolic::{
+        Bands, ComparisonOp, Constant, CustomDie, Die, Explosion, ExpressionTree,
+        ExpressionWrapper, Function, Ranker, Symbol,
+    },
+};
+
+/// A binding whose value has at most this many possibilities is summarized case by case,
+/// which is exact if each case is.
+const BINDING_CASES: i64 = 64;
+
+/// The most combinations of values to spend on the exact distribution of a binding's value.
+const BINDING_BUDGET: usize = 10_000;
+
+/// The mean, variance, and bounds of an expression's distribution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// The expected value.
+    pub mean: f64,
+    /// The expected squared distance from the mean.
+    pub variance: f64,
+    /// No value is less than this; though it may be less than the least possible value.
+    pub min: i64,
+    /// No value is greater than this; though it may be greater than the greatest possible value.
+    pub max: i64,
+    /// Whether the mean and variance are exact, rather than approximations.
+    pub exact: bool,
+    /// The sum of the squared widths of independent, bounded terms that add up to the expression:
+    /// the basis of Hoeffding's inequality. A single term is the whole range, `(max - min)²`.
+    pub range_squares: f64,
+}
+
+impl Summary {
+    /// The summary of the distribution, which is exact.
+    pub fn of(d: &Distribution) -> Self {
+        Summary {
+            mean: d.mean(),
+            variance: d.variance(),
+            min: d.min(),
+            max: d.max(),
+            exact: true,
+            range_squares: range_squared(d.min(), d.max()),
+        }
+    }
+
+    /// The standard deviation.
+    pub fn stddev(&self) -> f64 {
+        self.variance.max(0.0).sqrt()
+    }
+
+    fn constant(value: i64) -> Self {
+        Summary {
+            mean: value as f64,
+            variance: 0.0,
+            min: value,
+            max: value,
+            exact: true,
+            range_squares: 0.0,
+        }
+    }
+
+    /// The summary of a die with faces `lo..=hi`.
+    fn uniform(lo: i64, hi: i64) -> Self {
+        let width = (hi as f64) - (lo as f64) + 1.0;
+        Summary {
+            mean: (lo as f64 + hi as f64) / 2.0,
+            variance: (width * width - 1.0) / 12.0,
+            min: lo,
+            max: hi,
+            exact: true,
+            range_squares: range_squared(lo, hi),
+        }
+    }
+
+    /// An approximate summary, kept consistent with its bounds: the mean is within them,
+    /// and the variance no more than they allow (Popoviciu's inequality).
+    fn approximate(mean: f64, variance: f64, min: i64, max: i64) -> Self {
+        let width = max as f64 - min as f64;
+        Summary {
+            mean: mean.clamp(min as f64, max as f64),
+            variance: variance.clamp(0.0, width * width / 4.0),
+            min,
+            max,
+            exact: false,
+            range_squares: range_squared(min, max),
+        }
+    }
+}
+
+/// The square of the width of the range.
+fn range_squared(min: i64, max: i64) -> f64 {
+    (max as f64 - min as f64).powi(2)
+}
+
+impl Closed {
+    /// Summarize the expression's distribution without computing it:
+    /// instant, even for expressions far too large to evaluate.
+    ///
+    /// See [`Summary`] for which parts of the summary are exact.
+    pub fn summarize(&self) -> Result<Summary, Error> {
+        Summarizer {
+            explosion_depth: Evaluator::DEFAULT_EXPLOSION_DEPTH,
+            scopes: Vec::new(),
+        }
+        .summarize(self)
+    }
+}
+
+struct Summarizer {
+    explosion_depth: usize,
+    /// Summaries of the bound symbols, innermost last.
+    scopes: Vec<(Symbol, Summary)>,
+}
+
+impl Summarizer {
+    fn summarize(&mut self, e: &Closed) -> Result<Summary, Error> {
+        let overflow = || Error::Overflow(e.to_string());
+        Ok(match e.inner() {
+            ExpressionTree::Modifier(Constant(c)) => {
+                Summary::constant(i64::try_from(*c).map_err(|_| overflow())?)
+            }
+            ExpressionTree::Die(Die(n)) => {
+                Summary::uniform(1, i64::try_from(*n).map_err(|_| overflow())?)
+            }
+            ExpressionTree::CustomDie(CustomDie::Range(lo, hi)) => Summary::uniform(*lo, *hi),
+            ExpressionTree::CustomDie(CustomDie::Fudge) => Summary::uniform(-1, 1),
+            ExpressionTree::CustomDie(CustomDie::Named { faces, .. } | CustomDie::Faces(faces)) => {
+                Summary::of(
+                    &Distribution::from_faces(faces.iter().copied())
+                        .ok_or_else(|| Error::TooComplex(e.to_string()))?,
+                )
+            }
+            ExpressionTree::CustomDie(CustomDie::Digits(sizes)) => {
+                Summary::of(&Distribution::digits(sizes).ok_or_else(overflow)?)
+            }
+            ExpressionTree::CustomDie(CustomDie::Weighted(faces)) => Summary::of(
+                &Distribution::from_weighted_faces(faces.iter().copied()).ok_or_else(overflow)?,
+            ),
+            // A single modified die has a small distribution: compute it.
+            ExpressionTree::Explode { .. }
+            | ExpressionTree::Reroll { .. }
+            | ExpressionTree::Successes { .. } => Summary::of(
+                &Evaluator::default()
+                    .with_explosion_depth(self.explosion_depth)
+                    .eval(e)?,
+            ),
+            ExpressionTree::Symbol(symbol) => {
+                let (_, summary) = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find(|(s, _)| s == symbol)
+                    .ok_or_else(|| Error::UnboundSymbols(HashSet::from([symbol.clone()])))?;
+                // Other uses of the symbol aren't independent of this one.
+                Summary {
+                    exact: false,
+                    ..*summary
+                }
+            }
+            ExpressionTree::Negated(a) => {
+                let a = self.summarize(a)?;
+                Summary {
+                    mean: -a.mean,
+                    min: a.max.checked_neg().ok_or_else(overflow)?,
+                    max: a.min.checked_neg().ok_or_else(overflow)?,
+                    ..a
+                }
+            }
+            ExpressionTree::Sum(terms) => {
+                let mut sum = Summary::constant(0);
+                for t in terms {
+                    let t = self.summarize(t)?;
+                    sum = Summary {
+                        mean: sum.mean + t.mean,
+                        variance: sum.variance + t.variance,
+                        min: sum.min.checked_add(t.min).ok_or_else(overflow)?,
+                        max: sum.max.checked_add(t.max).ok_or_else(overflow)?,
+                        exact: sum.exact && t.exact,
+                        range_squares: sum.range_squares + t.range_squares,
+                    };
+                }
+                sum
+            }
+            ExpressionTree::Product(a, b) => {
+                let (a, b) = (self.summarize(a)?, self.summarize(b)?);
+                let (min, max) = corners(a, b, i64::checked_mul).ok_or_else(overflow)?;
+                let (sa, sb) = (a.mean * a.mean, b.mean * b.mean);
+                Summary {
+                    mean: a.mean * b.mean,
+                    // Var(AB) = E[A²]E[B²] - E[A]²E[B]².
+                    variance: (a.variance + sa) * (b.variance + sb) - sa * sb,
+                    min,
+                    max,
+                    exact: a.exact && b.exact,
+                    range_squares: range_squared(min, max),
+                }
+            }
+            ExpressionTree::Floor(a, b) => self.floor(e, a, b)?,
+            ExpressionTree::Modulo(a, b) => self.modulo(e, a, b)?,
+            ExpressionTree::Lookup { value, bands } => self.lookup(value, bands)?,
+            ExpressionTree::Comparison { a, b, op } => {
+                let (a, b) = (self.summarize(a)?, self.summarize(b)?);
+                comparison(a, b, *op)
+            }
+            ExpressionTree::Binding {
+                symbol,
+                value,
+                tail,
+            } => self.binding(symbol, value, tail)?,
+            ExpressionTree::Call { function, args } => match (function, args.as_slice()) {
+                (Function::Soak, [damage, armor]) => {
+                    let (damage, armor) = (self.summarize(damage)?, self.summarize(armor)?);
+                    soak(damage, armor).ok_or_else(overflow)?
+                }
+                (Function::Abs, [x]) => abs(self.summarize(x)?).ok_or_else(overflow)?,
+                (Function::Clamp, [x, lo, hi]) => {
+                    let (x, lo, hi) =
+                        (self.summarize(x)?, self.summarize(lo)?, self.summarize(hi)?);
+                    extreme(extreme(x, lo, true), hi, false)
+                }
+                (Function::Save, [damage, saved]) => {
+                    let (damage, saved) = (self.summarize(damage)?, self.summarize(saved)?);
+                    let half = if damage.min == damage.max {
+                        Summary::constant(damage.min / 2)
+                    } else {
+                        Summary::approximate(
+                            damage.mean / 2.0,
+                            damage.variance / 4.0,
+                            damage.min / 2,
+                            damage.max / 2,
+                        )
+                    };
+                    choose(saved, half, damage)
+                }
+                (Function::If, [condition, then, otherwise]) => {
+                    let (condition, then, otherwise) = (
+                        self.summarize(condition)?,
+                        self.summarize(then)?,
+                        self.summarize(otherwise)?,
+                    );
+                    choose(condition, then, otherwise)
+                }
+                (Function::Max | Function::Min, [a, b]) => {
+                    let (a, b) = (self.summarize(a)?, self.summarize(b)?);
+                    extreme(a, b, *function == Function::Max)
+                }
+                (Function::Tens | Function::Units, [x]) => {
+                    let place = if *function == Function::Tens { 1 } else { 0 };
+                    let x = self.summarize(x)?;
+                    if x.min == x.max {
+                        Summary::constant(digit(x.min, place))
+                    } else {
+                        // As if every digit were equally likely.
+                        Summary::approximate(4.5, 8.25, 0, 9)
+                    }
+                }
+                _ => panic!("wrong number of arguments to {function} in {e}"),
+            },
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker,
+            } => self.repeat(e, count, value, ranker)?,
+        })
+    }
+
+    fn floor(&mut self, e: &Closed, a: &Closed, b: &Closed) -> Result<Summary, Error> {
+        let (a, b) = (self.summarize(a)?, self.summarize(b)?);
+        let overflow = || Error::Overflow(e.to_string());
+        if b.min == 0 && b.max == 0 {
+            return Err(Error::DivideByZero(e.to_string()));
+        }
+        if a.min == a.max && b.min == b.max {
+            return Ok(Summary::constant(
+                a.min.checked_div(b.min).ok_or_else(overflow)?,
+            ));
+        }
+        let (min, max) = if b.min > 0 || b.max < 0 {
+            // The quotient is monotonic in each operand.
+            corners(a, b, i64::checked_div).ok_or_else(overflow)?
+        } else {
+            // Dividing by 1 or -1 gives the largest magnitude.
+            let m = a.min.checked_abs().ok_or_else(overflow)?.max(a.max.abs());
+            (-m, m)
+        };
+        // To first order, as if the divisor were always its mean.
+        let mean = a.mean / b.mean;
+        let variance = (a.variance + mean * mean * b.variance) / (b.mean * b.mean);
+        Ok(Summary::approximate(mean, variance, min, max))
+    }
+
+    fn modulo(&mut self, e: &Closed, a: &Closed, b: &Closed) -> Result<Summary, Error> {
+        let (a, b) = (self.summarize(a)?, self.summarize(b)?);
+        let overflow = || Error::Overflow(e.to_string());
+        if b.min == 0 && b.max == 0 {
+            return Err(Error::DivideByZero(e.to_string()));
+        }
+        if a.min == a.max && b.min == b.max {
+            return Ok(Summary::constant(
+                a.min.checked_rem_euclid(b.min).ok_or_else(overflow)?,
+            ));
+        }
+        // The smallest and largest magnitudes of the divisor.
+        let smallest = if b.min > 0 {
+            b.min
+        } else if b.max < 0 {
+            b.max.checked_neg().ok_or_else(overflow)?
+        } else {
+            1
+        };
+        let largest = b.min.checked_abs().ok_or_else(overflow)?.max(b.max.abs());
+        if a.min >= 0 && a.max < smallest {
+            // Never wraps around.
+            return Ok(a);
+        }
+        // As if the remainder were spread evenly over its possible values.
+        let max = if a.min >= 0 {
+            a.max.min(largest - 1)
+        } else {
+            largest - 1
+        };
+        let width = max as f64 + 1.0;
+        Ok(Summary::approximate(
+            max as f64 / 2.0,
+            (width * width - 1.0) / 12.0,
+            0,
+            max,
+        ))
+    }
+
+    fn lookup(&mut self, value: &Closed, bands: &Bands) -> Result<Summary, Error> {
+        let x = self.summarize(value)?;
+        // Without bound symbols, the value can be computed exactly, if it's small enough.
+        if self.scopes.is_empty() {
+            let exact = Evaluator::default()
+                .with_explosion_depth(self.explosion_depth)
+                .with_budget(BINDING_BUDGET)
+                .eval(value);
+            match exact {
+                Ok(d) => return Ok(Summary::of(&d.map_values(|v| bands.apply(v)))),
+                Err(Error::OverBudget(_) | Error::Overflow(_) | Error::TooComplex(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(lookup(x, bands))
+    }
+
+    fn binding(
+        &mut self,
+        symbol: &Symbol,
+        value: &Closed,
+        tail: &Closed,
+    ) -> Result<Summary, Error> {
+        let value_summary = self.summarize(value)?;
+        // Without bound symbols, each value can be substituted exactly, if there are few enough.
+        if self.scopes.is_empty()
+            && value_summary.max.abs_diff(value_summary.min) < BINDING_CASES as u64
+        {
+            let exact = Evaluator::default()
+                .with_explosion_depth(self.explosion_depth)
+                .with_budget(BINDING_BUDGET)
+                .eval(value);
+            match exact {
+                Ok(d) => {
+                    let cases = d
+                        .occurrences()
+                        .map(|(v, _)| {
+                            let t = self.summarize(&tail.substitute(symbol, v))?;
+                            Ok((d.probability_f64(v), t))
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    return Ok(mixture(cases));
+                }
+                Err(Error::OverBudget(_) | Error::Overflow(_) | Error::TooComplex(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        self.scopes.push((symbol.clone(), value_summary));
+        let result = self.summarize(tail);
+        self.scopes.pop();
+        result
+    }
+
+    fn repeat(
+        &mut self,
+        e: &Closed,
+        count: &Closed,
+        value: &Closed,
+        ranker: &Ranker<Box<Closed>>,
+    ) -> Result<Summary, Error> {
+        let Some(keep) = ranker.count() else {
+            return self.ranked(e, count, value, ranker.map(|_| 0));
+        };
+        if let Some(fixed) = ranker.fixed() {
+            return self.ranked(e, count, value, fixed);
+        }
+        let k = self.summarize(keep)?;
+        if k.min < 0 {
+            return Err(Error::NegativeCount(e.to_string()));
+        }
+        // Without bound symbols, mix the summaries for each count kept, if there aren't too many.
+        if self.scopes.is_empty() && k.max.abs_diff(k.min) < BINDING_CASES as u64 {
+            let exact = Evaluator::default()
+                .with_explosion_depth(self.explosion_depth)
+                .with_budget(BINDING_BUDGET)
+                .eval(keep);
+            match exact {
+                Ok(d) => {
+                    let cases = d
+                        .occurrences()
+                        .map(|(v, _)| {
+                            let t = self.ranked(e, count, value, ranker.map(|_| v as usize))?;
+                            Ok((d.probability_f64(v), t))
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    return Ok(mixture(cases));
+                }
+                Err(Error::OverBudget(_) | Error::Overflow(_) | Error::TooComplex(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        // Otherwise, approximate by keeping the expected count,
+        // bounded by keeping the fewest and the most.
+        let at = |n: i64| ranker.map(|_| n as usize);
+        let typical = self.ranked(e, count, value, at(k.mean.round() as i64))?;
+        let fewest = self.ranked(e, count, value, at(k.min))?;
+        let most = self.ranked(e, count, value, at(k.max))?;
+        Ok(Summary::approximate(
+            typical.mean,
+            typical.variance,
+            typical.min.min(fewest.min).min(most.min),
+            typical.max.max(fewest.max).max(most.max),
+        ))
+    }
+
+    /// The summary of the largest set of matching rolls in a pool of `fewest` to `most` rolls,
+    /// `rolls` on average, each of which is `roll`.
+    fn matching(
+        &mut self,
+        e: &Closed,
+        fewest: i64,
+        rolls: f64,
+        most: i64,
+        roll: Summary,
+    ) -> Result<Summary, Error> {
+        if self.scopes.is_empty() {
+            let exact = Evaluator::default()
+                .with_explosion_depth(self.explosion_depth)
+                .with_budget(BINDING_BUDGET)
+                .eval(e);
+            match exact {
+                Ok(d) => return Ok(Summary::of(&d)),
+                Err(Error::OverBudget(_) | Error::Overflow(_) | Error::TooComplex(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        // Some value must repeat once there are more rolls than values.
+        let width = roll.max.abs_diff(roll.min).saturating_add(1);
+        let min = (fewest.max(1) as u64).div_ceil(width) as i64;
+        // Approximate the rolls as spread evenly over the values: the fullest of `width` bins,
+        // from `rolls` balls thrown at random. It varies about as much as any one bin.
+        let width = width as f64;
+        let per_value = rolls / width;
+        let mean = if width > 1.0 {
+            per_value + (2.0 * per_value * width.ln()).sqrt()
+        } else {
+            rolls
+        };
+        Ok(Summary::approximate(
+            mean.clamp(min as f64, most as f64),
+            per_value,
+            min,
+            most,
+        ))
+    }
+
+    /// The summary of `count` rolls of `value`, keeping those chosen by the ranker.
+    fn ranked(
+        &mut self,
+        e: &Closed,
+        count: &Closed,
+        value: &Closed,
+        ranker: Ranker,
+    ) -> Result<Summary, Error> {
+        let overflow = || Error::Overflow(e.to_string());
+        let n = self.summarize(count)?;
+        if n.min < 0 {
+            return Err(Error::NegativeCount(e.to_string()));
+        }
+        if (n.min as usize) < ranker.min_count() {
+            return Err(ranker.too_few(e.to_string()));
+        }
+        let x = self.summarize(value)?;
+        // Each explosion of an exploding die is another die in the pool.
+        let exploding = match value.inner() {
+            ExpressionTree::Explode {
+                value: die,
+                explosion: Explosion::Standard,
+                trigger,
+            } => Some((
+                Evaluator::default()
+                    .with_explosion_depth(self.explosion_depth)
+                    .eval(die)?,
+                *trigger,
+            )),
+            _ => None,
+        };
+        let keeps_all = n.min == n.max
+            && exploding.is_none()
+            && ranker != Ranker::Matching
+            && ranker.keep(n.max) == n.max;
+        if ranker == Ranker::All || keeps_all {
+            let (min, max) = corners(n, x, i64::checked_mul).ok_or_else(overflow)?;
+            // Wald's identities.
+            return Ok(Summary {
+                mean: n.mean * x.mean,
+                variance: n.mean * x.variance + n.variance * x.mean * x.mean,
+                min,
+                max,
+                exact: n.exact && x.exact,
+                // A fixed number of independent rolls.
+                range_squares: if n.min == n.max {
+                    n.mean * x.range_squares
+                } else {
+                    range_squared(min, max)
+                },
+            });
+        }
+
+        // Bound the kept dice by the number kept, and the bounds of each kept roll.
+        let (roll, rolls_per_die, most_rolls) = match &exploding {
+            Some((die, trigger)) => {
+                let explodes = die
+                    .occurrences()
+                    .filter(|(v, _)| trigger.explodes(die.max(), *v))
+                    .map(|(v, _)| die.probability_f64(v))
+                    .sum::<f64>();
+                (
+                    Summary::of(die),
+                    (0..=self.explosion_depth)
+                        .map(|k| explodes.powi(k as i32))
+                        .sum(),
+                    n.max
+                        .checked_mul(self.explosion_depth as i64 + 1)
+                        .ok_or_else(overflow)?,
+                )
+            }
+            None => (x, 1.0, n.max),
+        };
+        if ranker == Ranker::Matching {
+            return self.matching(e, n.min, n.mean * rolls_per_die, most_rolls, roll);
+        }
+        let kept = Summary {
+            min: ranker.keep(n.min),
+            max: ranker.keep(most_rolls),
+            ..n
+        };
+        let (min, max) = corners(kept, roll, i64::checked_mul).ok_or_else(overflow)?;
+
+        // Approximate each roll as normal, and each kept roll by its expected order statistic
+        // (Blom's approximation).
+        let rolls = (n.mean * rolls_per_die).round().max(1.0) as i64;
+        let keep = ranker.keep(rolls);
+        let ranks = if ranker.keeps_highest() {
+            (rolls - keep + 1)..=rolls
+        } else {
+            1..=keep
+        };
+        let mean = ranks
+            .map(|i| {
+                roll.mean
+                    + roll.stddev() * normal_quantile((i as f64 - 0.375) / (rolls as f64 + 0.25))
+            })
+            .sum();
+        // Keeping every roll gives `keep` rolls' variance; keeping one extreme roll of many
+        // gives about half of one roll's. Interpolate between the two.
+        let share = keep as f64 / rolls as f64;
+        let variance = keep as f64 * roll.variance * (0.5 + 0.5 * share);
+        Ok(Summary::approximate(mean, variance, min, max))
+    }
+}
+
+/// The summary of a value drawn from one of several cases, each with its probability:
+/// the law of total expectation, and of total variance.
+fn mixture(cases: Vec<(f64, Summary)>) -> Summary {
+    let (mut mean, mut square) = (0.0, 0.0);
+    let (mut min, mut max) = (i64::MAX, i64::MIN);
+    let mut all_exact = true;
+    for (p, t) in cases {
+        mean += p * t.mean;
+        square += p * (t.variance + t.mean * t.mean);
+        min = min.min(t.min);
+        max = max.max(t.max);
+        all_exact &= t.exact;
+    }
+    Summary {
+        mean,
+        variance: square - mean * mean,
+        min,
+        max,
+        exact: all_exact,
+        range_squares: range_squared(min, max),
+    }
+}
+
+/// The least and greatest results of `f` on the bounds of `a` and `b`,
+/// for an `f` that is monotonic in each argument.
+fn corners(a: Summary, b: Summary, f: impl Fn(i64, i64) -> Option<i64>) -> Option<(i64, i64)> {
+    let values = [
+        f(a.min, b.min)?,
+        f(a.min, b.max)?,
+        f(a.max, b.min)?,
+        f(a.max, b.max)?,
+    ];
+    Some((*values.iter().min()?, *values.iter().max()?))
+}
+
+/// The summary of a comparison, approximating the difference of the two sides as normal.
+fn comparison(a: Summary, b: Summary, op: ComparisonOp) -> Summary {
+    // Decided by the bounds alone?
+    let always = |a: i64, b: i64| op.compare(a, b);
+    let corners = [
+        always(a.min, b.min),
+        always(a.min, b.max),
+        always(a.max, b.min),
+        always(a.max, b.max),
+    ];
+    let monotonic = op != ComparisonOp::Eq || (a.min == a.max && b.min == b.max);
+    if monotonic && corners.iter().all(|c| *c == corners[0]) {
+        return Summary::constant(corners[0] as i64);
+    }
+    if op == ComparisonOp::Eq && (a.max < b.min || b.max < a.min) {
+        return Summary::constant(0);
+    }
+
+    let mean = a.mean - b.mean;
+    let stddev = (a.variance + b.variance).sqrt();
+    // The probability that the difference is at most `d`, with a continuity correction.
+    let at_most = |d: f64| {
+        if stddev == 0.0 {
+            if mean <= d { 1.0 } else { 0.0 }
+        } else {
+            normal_cdf((d + 0.5 - mean) / stddev)
+        }
+    };
+    let p = match op {
+        ComparisonOp::Gt => 1.0 - at_most(0.0),
+        ComparisonOp::Ge => 1.0 - at_most(-1.0),
+        ComparisonOp::Eq => at_most(0.0) - at_most(-1.0),
+        ComparisonOp::Le => at_most(0.0),
+        ComparisonOp::Lt => at_most(-1.0),
+    };
+    Summary::approximate(p, p * (1.0 - p), 0, 1)
+}
+
+/// The summary of `soak(damage, armor)`, approximating the difference as normal.
+fn soak(damage: Summary, armor: Summary) -> Option<Summary> {
+    let min = damage.min.checked_sub(armor.max)?.max(0);
+    let max = damage.max.checked_sub(armor.min)?.max(0);
+    let difference = Summary {
+        mean: damage.mean - armor.mean,
+        variance: damage.variance + armor.variance,
+        min: damage.min.checked_sub(armor.max)?,
+        max: damage.max.checked_sub(armor.min)?,
+        exact: damage.exact && armor.exact,
+        range_squares: damage.range_squares + armor.range_squares,
+    };
+    if difference.min >= 0 {
+        // Never clamped.
+        return Some(difference);
+    }
+    if difference.max <= 0 {
+        return Some(Summary::constant(0));
+    }
+    // The moments of max(0, D), for normal D.
+    let (mu, sigma) = (difference.mean, difference.stddev());
+    let z = mu / sigma;
+    let (cdf, pdf) = (
+        normal_cdf(z),
+        (-z * z / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+    );
+    let mean = mu * cdf + sigma * pdf;
+    let square = (mu * mu + sigma * sigma) * cdf + mu * sigma * pdf;
+    Some(Summary::approximate(mean, square - mean * mean, min, max))
+}
+
+/// The summary of `abs(x)`, approximating x as normal where it may have either sign.
+fn abs(x: Summary) -> Option<Summary> {
+    if x.min >= 0 {
+        return Some(x);
+    }
+    let max = x.min.checked_abs()?.max(x.max.abs());
+    if x.max <= 0 {
+        return Some(Summary {
+            mean: -x.mean,
+            min: -x.max,
+            max,
+            ..x
+        });
+    }
+    // The moments of the folded normal distribution.
+    let (mu, sigma) = (x.mean, x.stddev());
+    let mean = if sigma == 0.0 {
+        mu.abs()
+    } else {
+        let z = mu / sigma;
+        sigma * (2.0 / std::f64::consts::PI).sqrt() * (-z * z / 2.0).exp()
+            + mu * (1.0 - 2.0 * normal_cdf(-z))
+    };
+    // |X|² = X², so the second moment is exact.
+    let square = x.variance + mu * mu;
+    Some(Summary::approximate(mean, square - mean * mean, 0, max))
+}
+
+/// The summary of `if(condition, then, otherwise)`: a mixture of the branches,
+/// weighted by the probability that the condition is nonzero.
+/// That probability is exact for an exact comparison, whose values are 0 and 1;
+/// otherwise it approximates the condition as normal.
+fn choose(condition: Summary, then: Summary, otherwise: Summary) -> Summary {
+    if condition.min > 0 || condition.max < 0 {
+        return then;
+    }
+    if condition.min == 0 && condition.max == 0 {
+        return otherwise;
+    }
+    let boolean = condition.min >= 0 && condition.max <= 1;
+    let p = if boolean {
+        condition.mean
+    } else {
+        let stddev = condition.stddev();
+        // With a continuity correction, as for comparisons.
+        let zero = if stddev == 0.0 {
+            0.0
+        } else {
+            normal_cdf((0.5 - condition.mean) / stddev)
+                - normal_cdf((-0.5 - condition.mean) / stddev)
+        };
+        1.0 - zero
+    };
+    let (then_square, otherwise_square) = (
+        then.variance + then.mean * then.mean,
+        otherwise.variance + otherwise.mean * otherwise.mean,
+    );
+    let mean = p * then.mean + (1.0 - p) * otherwise.mean;
+    let square = p * then_square + (1.0 - p) * otherwise_square;
+    let (min, max) = (then.min.min(otherwise.min), then.max.max(otherwise.max));
+    Summary {
+        exact: boolean && condition.exact && then.exact && otherwise.exact,
+        ..Summary::approximate(mean, square - mean * mean, min, max)
+    }
+}
+
+/// The summary of a lookup table applied to a value, approximating the value as normal.
+fn lookup(x: Summary, bands: &Bands) -> Summary {
+    let (mu, sigma) = (x.mean, x.stddev());
+    // The probability, and the first and second partial moments, of the value in `lo..=hi`:
+    // P(lo <= X <= hi), E[X; lo <= X <= hi], and E[X²; lo <= X <= hi].
+    let moments = |lo: i64, hi: i64| {
+        if sigma == 0.0 {
+            let inside = (lo as f64 - 0.5..=hi as f64 + 0.5).contains(&mu);
+            let p = if inside { 1.0 } else { 0.0 };
+            return (p, p * mu, p * mu * mu);
+        }
+        // Ends, with a continuity correction, standardized; open past the bounds.
+        let end = |v: f64, open: bool, infinity: f64| {
+            if open {
+                (infinity, 0.0)
+            } else {
+                let z = (v - mu) / sigma;
+                (
+                    z,
+                    (-z * z / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+                )
+            }
+        };
+        let (a, b) = (lo as f64 - 0.5, hi as f64 + 0.5);
+        let (alpha, pdf_alpha) = end(a, lo <= x.min, f64::NEG_INFINITY);
+        let (beta, pdf_beta) = end(b, hi >= x.max, f64::INFINITY);
+        let p = normal_cdf(beta) - normal_cdf(alpha);
+        let first = mu * p - sigma * (pdf_beta - pdf_alpha);
+        let tail = |v: f64, pdf: f64| if pdf == 0.0 { 0.0 } else { (mu + v) * pdf };
+        let second =
+            (mu * mu + sigma * sigma) * p - sigma * (tail(b, pdf_beta) - tail(a, pdf_alpha));
+        (p, first, second)
+    };
+    // Start with the value unchanged, then move each band's share to its result.
+    let (mut mean, mut square) = (mu, x.variance + mu * mu);
+    let (mut min, mut max) = (i64::MAX, i64::MIN);
+    // The least and greatest values outside every band pass through.
+    let (mut low, mut high) = (Some(x.min), Some(x.max));
+    for band in bands.bands() {
+        if band.hi < x.min || band.lo > x.max {
+            continue;
+        }
+        min = min.min(band.result);
+        max = max.max(band.result);
+        if low.is_some_and(|v| band.lo <= v) {
+            low = band.hi.checked_add(1).filter(|v| *v <= x.max);
+        }
+        let (p, first, second) = moments(band.lo, band.hi);
+        let result = band.result as f64;
+        mean += result * p - first;
+        square += result * result * p - second;
+    }
+    for band in bands.bands().iter().rev() {
+        if high.is_some_and(|v| band.lo <= v && v <= band.hi) {
+            high = band.lo.checked_sub(1).filter(|v| *v >= x.min);
+        }
+    }
+    if let (Some(low), Some(high)) = (low, high) {
+        min = min.min(low);
+        max = max.max(high);
+    }
+    Summary::approximate(mean, square - mean * mean, min, max)
+}
+
+/// The summary of `max(a, b)` (if `highest`) or `min(a, b)`,
+/// approximating both as normal (Clark's formulas).
+fn extreme(a: Summary, b: Summary, highest: bool) -> Summary {
+    // Decided by the bounds alone?
+    if a.min >= b.max {
+        return if highest { a } else { b };
+    }
+    if b.min >= a.max {
+        return if highest { b } else { a };
+    }
+    let (min, max) = if highest {
+        (a.min.max(b.min), a.max.max(b.max))
+    } else {
+        (a.min.min(b.min), a.max.min(b.max))
+    };
+    let theta = (a.variance + b.variance).sqrt();
+    if theta == 0.0 {
+        let mean = if highest {
+            a.mean.max(b.mean)
+        } else {
+            a.mean.min(b.mean)
+        };
+        return Summary::approximate(mean, 0.0, min, max);
+    }
+    let alpha = (a.mean - b.mean) / theta;
+    let (cdf, pdf) = (
+        normal_cdf(alpha),
+        (-alpha * alpha / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+    );
+    let (a_square, b_square) = (a.variance + a.mean * a.mean, b.variance + b.mean * b.mean);
+    let mean = a.mean * cdf + b.mean * (1.0 - cdf) + theta * pdf;
+    let square = a_square * cdf + b_square * (1.0 - cdf) + (a.mean + b.mean) * theta * pdf;
+    if highest {
+        Summary::approximate(mean, square - mean * mean, min, max)
+    } else {
+        // min(a, b) + max(a, b) = a + b, and likewise for their squares.
+        let mean = a.mean + b.mean - mean;
+        let square = a_square + b_square - square;
+        Summary::approximate(mean, square - mean * mean, min, max)
+    }
+}
+
+/// The standard normal distribution's cumulative distribution function.
+fn normal_cdf(x: f64) -> f64 {
+    // Abramowitz and Stegun 7.1.26, for erf; accurate to about 1e-7.
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        (1.0 + erf) / 2.0
+    } else {
+        (1.0 - erf) / 2.0
+    }
+}
+
+/// The standard normal distribution's quantile function, for `p` in `(0, 1)`.
+fn normal_quantile(p: f64) -> f64 {
+    // Abramowitz and Stegun 26.2.23; accurate to about 5e-4.
+    let tail = |q: f64| {
+        let t = (-2.0 * q.ln()).sqrt();
+        t - (2.515_517 + t * (0.802_853 + t * 0.010_328))
+            / (1.0 + t * (1.432_788 + t * (0.189_269 + t * 0.001_308)))
+    };
+    if p < 0.5 { -tail(p) } else { tail(1.0 - p) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(s: &str) -> Summary {
+        s.parse::<Closed>().unwrap().summarize().unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        for s in [
+            "2d6 + 3",
+            "d20 * d4 - 1",
+            "d6 % 10 + -3 % 5",
+            "(d4)d6",
+            "-d{1,1,3} + 2",
+            "d6!",
+            "d20ro1 + 4dF",
+            "5d10s>=6f1",
+            "[X: d6] X * X",
+            "3d6kl3 + 1",
+            "4d6m",
+            "d10!>=9 + 1",
+            "soak(d6 + 4, d4)",
+            "max(d6 + 6, d4) - min(d4, 5)",
+            "abs(d6 - 10) + abs(d4)",
+            "clamp(d6, 0, 10)",
+            "if d2 - 1 then 2d6 else d4",
+        ] {
+            let got = summary(s);
+            let want = s.parse::<Closed>().unwrap().distribution().unwrap();
+            assert!(got.exact, "{s}: {got:?}");
+            assert!((got.mean - want.mean()).abs() < 1e-9, "{s}: {got:?}");
+            assert!(
+                (got.variance - want.variance()).abs() < 1e-9,
+                "{s}: {got:?}"
+            );
+            assert_eq!((got.min, got.max), (want.min(), want.max()), "{s}");
+        }
+    }
+
+    #[test]
+    fn approximate() {
+        for (s, tolerance) in [
+            ("4d6kh3", 0.5),
+            ("2d20kh", 1.0),
+            ("2d20ro1kh", 1.0),
+            ("4d6dl", 0.5),
+            ("d20 + 5 >= 15", 0.05),
+            ("soak(2d6, d4)", 0.3),
+            ("(2d6) / d3", 1.0),
+            ("3d6 % 4", 0.2),
+            ("3d6!kh2", 1.0),
+            ("3d6!>4kh2", 1.0),
+            ("(d4 + 1)d6kh(d2)", 0.5),
+            ("max(d8!, d6!)", 0.5),
+            ("min(2d6, d12)", 0.5),
+            ("abs(2d6 - 2d6)", 0.5),
+            ("clamp(3d6 - 10, 0, 6)", 0.5),
+            ("if(d6 - 3, 2d6, d4)", 0.5),
+            ("save(8d6, d20 + 3 >= 15)", 1.0),
+        ] {
+            let got = summary(s);
+            let want = s.parse::<Closed>().unwrap().distribution().unwrap();
+            assert!(!got.exact, "{s}: {got:?}");
+            assert!(
+                (got.mean - want.mean()).abs() < tolerance,
+                "{s}: {got:?}, want mean {}",
+                want.mean()
+            );
+            // The bounds are guaranteed, even when the moments aren't exact.
+            assert!(
+                got.min <= want.min() && want.max() <= got.max,
+                "{s}: {got:?}"
+            );
+        }
+
+        // Comparisons decided by the bounds are exact.
+        let got = summary("d6 + 10 > 2d4");
+        assert!(got.exact);
+        assert_eq!(got.mean, 1.0);
+    }
+
+    #[test]
+    fn large() {
+        // Far too large to evaluate, but instant to summarize.
+        let got = summary("[X: 50d20] X * X + 1000d1000");
+        assert!(!got.exact);
+        assert_eq!(got.min, 1000 + 50 * 50);
+        assert!((got.mean - (500_500.0 + 525.0 * 525.0)).abs() < 1.0);
+
+        // Too many to count each way the dice may match.
+        let got = summary("60d6m");
+        assert!(!got.exact);
+        assert_eq!((got.min, got.max), (10, 60));
+        assert!(got.mean > 10.0 && got.mean < 20.0, "{got:?}");
+
+        // Too many counts kept to mix: bounded by keeping the fewest and the most.
+        let got = summary("200d6kh(2d100)");
+        assert!(!got.exact);
+        assert_eq!((got.min, got.max), (2, 1200));
+        assert!(got.mean > 101.0 * 3.5 && got.mean < 101.0 * 6.0, "{got:?}");
+
+        // A small binding whose value refers to a larger one.
+        let got = summary("[A: 100d100] [B: A % 3] B + 1");
+        assert_eq!((got.min, got.max), (1, 3));
+    }
+
+    #[test]
+    fn lookup_tables() {
+        // Small enough to compute exactly.
+        let got =
+            summary("3d6 -> {3: -3, 4..5: -2, 6..8: -1, 9..12: 0, 13..15: 1, 16..17: 2, 18: 3}");
+        assert!(got.exact);
+        assert_eq!((got.min, got.max), (-3, 3));
+        assert!(got.mean.abs() < 1e-12);
+
+        // Too large: approximate, but with exact bounds.
+        let got = summary("100d6 -> {..340: 0, 341..360: 1}");
+        assert!(!got.exact);
+        assert_eq!((got.min, got.max), (0, 600));
+
+        let d = "20d6".parse::<Closed>().unwrap().distribution().unwrap();
+        for bands in ["{..60: 0, 61..80: 1}", "{50..70: 60}", "{20..120: 0}"] {
+            let bands = format!("20d6 -> {bands}");
+            let want = bands.parse::<Closed>().unwrap().distribution().unwrap();
+            let ExpressionTree::Lookup { bands, .. } =
+                bands.parse::<Closed>().unwrap().inner().clone()
+            else {
+                panic!("not a lookup")
+            };
+            let got = lookup(Summary::of(&d), &bands);
+            assert!((got.mean - want.mean()).abs() < 0.1, "{got:?}");
+            assert!(
+                (got.stddev() - want.variance().sqrt()).abs() < 0.2,
+                "{got:?} vs {}",
+                want.variance()
+            );
+            assert_eq!((got.min, got.max), (want.min(), want.max()), "{bands}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for (s, check) in [
+            (
+                "(d4 - 2)d6",
+                (|e| matches!(e, Error::NegativeCount(_))) as fn(&Error) -> bool,
+            ),
+            ("(d2)d6kh2", |e| matches!(e, Error::KeepTooFew(..))),
+            ("d6 / 0", |e| matches!(e, Error::DivideByZero(_))),
+        ] {
+            let e = s.parse::<Closed>().unwrap().summarize().unwrap_err();
+            assert!(check(&e), "{s}: {e}");
+        }
+    }
+}
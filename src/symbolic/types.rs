@@ -15,7 +15,7 @@ use std::str::FromStr;
 /// A constant value.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-pub struct Constant(pub usize);
+pub struct Constant(pub u64);
 
 impl std::fmt::Display for Constant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -34,6 +34,69 @@ impl std::fmt::Display for Die {
     }
 }
 
+/// A die whose faces are something other than `1..=N`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CustomDie {
+    /// A die that is uniform over an inclusive range.
+    Range(i64, i64),
+    /// A die registered with the parser by name, with the given faces.
+    Named { name: String, faces: Vec<i64> },
+    /// A Fudge (Fate) die, `dF`, with faces -1, 0, and +1.
+    Fudge,
+    /// A die with the listed faces, e.g. `d{1,1,3,5,8}`.
+    Faces(Vec<i64>),
+    /// A die with the listed faces and their (positive) weights, e.g. `d{1:3,2:1,3:1}`:
+    /// each face is rolled in proportion to its weight.
+    Weighted(Vec<(i64, usize)>),
+    /// Dice of the given sizes read as digits of one number, rather than summed:
+    /// `d66` rolls 11 through 66, with the first d6 as the tens digit.
+    /// Written `d(3,6)` in general, or `d66` and `d666` for the common cases.
+    Digits(Vec<usize>),
+}
+
+impl std::fmt::Display for CustomDie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CustomDie::Range(lo, hi) => write!(f, "d[{lo}..{hi}]"),
+            CustomDie::Named { name, .. } => write!(f, "d{name}"),
+            CustomDie::Fudge => write!(f, "dF"),
+            CustomDie::Faces(faces) => {
+                write!(f, "d{{")?;
+                for (i, face) in faces.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{face}")?;
+                }
+                write!(f, "}}")
+            }
+            CustomDie::Weighted(faces) => {
+                write!(f, "d{{")?;
+                for (i, (face, weight)) in faces.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{face}:{weight}")?;
+                }
+                write!(f, "}}")
+            }
+            CustomDie::Digits(sizes) if matches!(sizes[..], [6, 6] | [6, 6, 6]) => {
+                write!(f, "d{}", "6".repeat(sizes.len()))
+            }
+            CustomDie::Digits(sizes) => {
+                write!(f, "d(")?;
+                for (i, size) in sizes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{size}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
 /// Representing some portion of the expression.
 /// Restricted to capital letters, A-Z.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -57,50 +120,130 @@ impl FromStr for Symbol {
     }
 }
 
-/// A ranking function: keep highest / keep lowest / keep all.
+/// A ranking function: keep highest / keep lowest / keep all,
+/// or drop highest / drop lowest and keep the rest.
+/// Or, rather than a sum of the values kept, the size of the largest set of matching values.
+///
+/// The count kept or dropped is a number, or (in an expression) an expression.
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-pub enum Ranker {
+pub enum Ranker<N = usize> {
     All,
-    Highest(usize),
-    Lowest(usize),
+    Highest(N),
+    Lowest(N),
+    DropHighest(N),
+    DropLowest(N),
+    Matching,
+}
+
+impl<N> Ranker<N> {
+    /// The count kept or dropped, if any.
+    pub fn count(&self) -> Option<&N> {
+        match self {
+            Ranker::All | Ranker::Matching => None,
+            Ranker::Highest(n)
+            | Ranker::Lowest(n)
+            | Ranker::DropHighest(n)
+            | Ranker::DropLowest(n) => Some(n),
+        }
+    }
+
+    /// The same ranking, with the count converted by `f`.
+    pub fn map<M>(&self, f: impl FnOnce(&N) -> M) -> Ranker<M> {
+        match self {
+            Ranker::All => Ranker::All,
+            Ranker::Matching => Ranker::Matching,
+            Ranker::Highest(n) => Ranker::Highest(f(n)),
+            Ranker::Lowest(n) => Ranker::Lowest(f(n)),
+            Ranker::DropHighest(n) => Ranker::DropHighest(f(n)),
+            Ranker::DropLowest(n) => Ranker::DropLowest(f(n)),
+        }
+    }
+
+    /// The same ranking, with the count converted by `f`, or the first error.
+    pub fn try_map<M, E>(&self, f: impl FnOnce(&N) -> Result<M, E>) -> Result<Ranker<M>, E> {
+        Ok(match self {
+            Ranker::All => Ranker::All,
+            Ranker::Matching => Ranker::Matching,
+            Ranker::Highest(n) => Ranker::Highest(f(n)?),
+            Ranker::Lowest(n) => Ranker::Lowest(f(n)?),
+            Ranker::DropHighest(n) => Ranker::DropHighest(f(n)?),
+            Ranker::DropLowest(n) => Ranker::DropLowest(f(n)?),
+        })
+    }
+
+    /// Whether the values kept are the highest ones (rather than the lowest, or all).
+    pub fn keeps_highest(&self) -> bool {
+        matches!(self, Ranker::Highest(_) | Ranker::DropLowest(_))
+    }
+
+    /// The operator for this ranking, as written after the dice.
+    fn operator(&self) -> &'static str {
+        match self {
+            Ranker::All => "",
+            Ranker::Highest(_) => "kh",
+            Ranker::Lowest(_) => "kl",
+            Ranker::DropHighest(_) => "dh",
+            Ranker::DropLowest(_) => "dl",
+            Ranker::Matching => "m",
+        }
+    }
 }
 
 impl Ranker {
     /// The minimum count of values required by this ranker.
     pub fn min_count(&self) -> usize {
         match self {
-            Ranker::All => 1,
+            Ranker::All | Ranker::Matching => 1,
             Ranker::Highest(n) => *n,
             Ranker::Lowest(n) => *n,
+            Ranker::DropHighest(n) => *n,
+            Ranker::DropLowest(n) => *n,
+        }
+    }
+
+    /// The error for an expression that may not produce [`Ranker::min_count`] values.
+    pub fn too_few(&self, expression: String) -> Error {
+        match self {
+            Ranker::DropHighest(n) | Ranker::DropLowest(n) => Error::DropTooMany(*n, expression),
+            _ => Error::KeepTooFew(self.min_count(), expression),
         }
     }
 
     /// How many to keep, out of n rolls.
-    pub fn keep(&self, n: isize) -> isize {
+    pub fn keep(&self, n: i64) -> i64 {
         match self {
-            Ranker::All => n,
-            Ranker::Highest(m) => std::cmp::min(*m as isize, n),
-            Ranker::Lowest(m) => std::cmp::min(*m as isize, n),
+            Ranker::All | Ranker::Matching => n,
+            Ranker::Highest(m) => std::cmp::min(*m as i64, n),
+            Ranker::Lowest(m) => std::cmp::min(*m as i64, n),
+            Ranker::DropHighest(m) | Ranker::DropLowest(m) => n - *m as i64,
         }
     }
 }
 
 impl std::fmt::Display for Ranker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (s, n) = match self {
-            Ranker::All => return Ok(()),
-            Ranker::Highest(n) => ("kh", *n),
-            Ranker::Lowest(n) => ("kl", *n),
-        };
-        if n == 1 {
-            write!(f, "{s}")
-        } else {
-            write!(f, "{s}{n}")
+        match self.count() {
+            None | Some(1) => write!(f, "{}", self.operator()),
+            Some(n) => write!(f, "{}{n}", self.operator()),
         }
     }
 }
 
+impl<T> Ranker<Box<T>>
+where
+    T: ExpressionWrapper,
+{
+    /// The ranker with a fixed count, if its count is a nonnegative constant.
+    pub fn fixed(&self) -> Option<Ranker> {
+        self.try_map(|n| match n.inner() {
+            ExpressionTree::Modifier(Constant(n)) => usize::try_from(*n).map_err(|_| ()),
+            _ => Err(()),
+        })
+        .ok()
+    }
+}
+
 /// A comparison operation (or the trivial comparison, which is always True)
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
@@ -126,7 +269,7 @@ impl std::fmt::Display for ComparisonOp {
 }
 
 impl ComparisonOp {
-    pub fn compare(&self, a: isize, b: isize) -> bool {
+    pub fn compare(&self, a: i64, b: i64) -> bool {
         match self {
             ComparisonOp::Gt => a > b,
             ComparisonOp::Ge => a >= b,
@@ -137,6 +280,284 @@ impl ComparisonOp {
     }
 }
 
+/// How a die explodes: when it rolls its highest face, it is rolled again.
+///
+/// Explosions are truncated after a limited number of rerolls; see
+/// [`Evaluator::with_explosion_depth`](crate::Evaluator::with_explosion_depth).
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub enum Explosion {
+    /// `d6!`: each roll of the highest face adds another die to the pool.
+    Standard,
+    /// `d6!!`: each roll of the highest face rolls again, adding into the same die's total.
+    ///
+    /// Sums are the same as for standard explosions; the difference is in keeping
+    /// the highest or lowest dice, where a compounding die counts as a single die.
+    Compounding,
+    /// `d6!p`: like a compounding die, but each reroll counts one less (HackMaster's penetration).
+    Penetrating,
+    /// `d6!i`: the mirror of a compounding die: each roll of the lowest face rolls again,
+    /// subtracting from the same die's total.
+    Imploding,
+}
+
+impl std::fmt::Display for Explosion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Explosion::Standard => write!(f, "!"),
+            Explosion::Compounding => write!(f, "!!"),
+            Explosion::Penetrating => write!(f, "!p"),
+            Explosion::Imploding => write!(f, "!i"),
+        }
+    }
+}
+
+/// Which rolls of an exploding die explode: by default its highest face (or lowest, if it
+/// implodes), or those that satisfy a comparison. `d10!>=9` explodes on a 9 or a 10.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub enum Trigger {
+    #[default]
+    Highest,
+    Compare(ComparisonOp, i64),
+}
+
+impl Trigger {
+    /// Whether a roll of this value explodes, on a die whose highest face is `max`;
+    /// for an imploding die, pass its lowest face instead.
+    pub fn explodes(&self, max: i64, value: i64) -> bool {
+        match self {
+            Trigger::Highest => value == max,
+            Trigger::Compare(op, threshold) => op.compare(value, *threshold),
+        }
+    }
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trigger::Highest => Ok(()),
+            Trigger::Compare(op, threshold) => write!(f, "{op}{threshold}"),
+        }
+    }
+}
+
+/// When to reroll a die: `d20ro=1` rerolls a 1 a single time, keeping the new value,
+/// and `d20rr=1` rerolls until the die shows something other than 1.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct Reroll {
+    /// How the die's value is compared against the threshold.
+    pub op: ComparisonOp,
+    /// The value to compare against; the die is rerolled if the comparison holds.
+    pub threshold: i64,
+    /// Whether to keep rerolling while the comparison holds, rather than rerolling once.
+    pub recursive: bool,
+}
+
+impl Reroll {
+    /// Great Weapon Fighting: reroll a 1 or 2 once, and keep the new roll.
+    /// `2d6gwf` is the same as `2d6ro<=2`.
+    pub const GREAT_WEAPON_FIGHTING: Reroll = Reroll {
+        op: ComparisonOp::Le,
+        threshold: 2,
+        recursive: false,
+    };
+
+    /// Whether a die showing this value is rerolled.
+    pub fn applies(&self, value: i64) -> bool {
+        self.op.compare(value, self.threshold)
+    }
+}
+
+impl std::fmt::Display for Reroll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.recursive { "rr" } else { "ro" };
+        write!(f, "{kind}{}{}", self.op, self.threshold)
+    }
+}
+
+/// How to count a die's successes: `d10s>=6` counts 1 for a roll of 6 or more, and 0 otherwise.
+/// `d10s>=6f1` also counts -1, a botch, for a roll of 1.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct Successes {
+    /// How the die's value is compared against the threshold.
+    pub op: ComparisonOp,
+    /// The value to compare against; the die is a success if the comparison holds.
+    pub threshold: i64,
+    /// The comparison and threshold for a botch, which cancels a success.
+    pub botch: Option<(ComparisonOp, i64)>,
+}
+
+impl Successes {
+    /// The number of successes a die showing this value counts for: 1, 0, or -1 for a botch.
+    /// A value that is both a success and a botch counts as a success.
+    pub fn score(&self, value: i64) -> i64 {
+        if self.op.compare(value, self.threshold) {
+            1
+        } else if self
+            .botch
+            .is_some_and(|(op, threshold)| op.compare(value, threshold))
+        {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+impl std::fmt::Display for Successes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "s{}{}", self.op, self.threshold)?;
+        if let Some((op, threshold)) = self.botch {
+            write!(f, "f{op}{threshold}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One band of a lookup table: values from `lo` to `hi`, inclusive, become `result`.
+/// A band from `i64::MIN` (or to `i64::MAX`) is written without that end: `..5`, `16..`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct Band {
+    pub lo: i64,
+    pub hi: i64,
+    pub result: i64,
+}
+
+impl std::fmt::Display for Band {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.lo == self.hi {
+            return write!(f, "{}: {}", self.lo, self.result);
+        }
+        if self.lo != i64::MIN {
+            write!(f, "{}", self.lo)?;
+        }
+        write!(f, "..")?;
+        if self.hi != i64::MAX {
+            write!(f, "{}", self.hi)?;
+        }
+        write!(f, ": {}", self.result)
+    }
+}
+
+/// A lookup table, mapping bands of values to results: `{3..5: -1, 6..8: 0, 9..12: 1}`.
+/// Values outside every band are unchanged.
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub struct Bands(Vec<Band>);
+
+impl Bands {
+    /// A table of the bands, in any order.
+    ///
+    /// Returns None if there are no bands, if any band is empty (`lo > hi`), or if any overlap.
+    pub fn new(bands: impl IntoIterator<Item = Band>) -> Option<Self> {
+        let mut bands: Vec<Band> = bands.into_iter().collect();
+        bands.sort_by_key(|b| b.lo);
+        let valid = !bands.is_empty()
+            && bands.iter().all(|b| b.lo <= b.hi)
+            && bands.windows(2).all(|w| w[0].hi < w[1].lo);
+        valid.then_some(Bands(bands))
+    }
+
+    /// The bands, in ascending order.
+    pub fn bands(&self) -> &[Band] {
+        &self.0
+    }
+
+    /// The result for the value: that of its band, or the value itself if it's in no band.
+    pub fn apply(&self, value: i64) -> i64 {
+        let i = self.0.partition_point(|b| b.hi < value);
+        match self.0.get(i) {
+            Some(b) if b.lo <= value => b.result,
+            _ => value,
+        }
+    }
+}
+
+impl std::fmt::Display for Bands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        for (i, band) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{band}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A function that can be called in an expression, e.g. `soak(2d6, d4)`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub enum Function {
+    /// `soak(damage, armor)`: damage reduced by armor, but never below zero.
+    Soak,
+    /// `tens(x)`: the tens digit of x, e.g. 7 for 73 (or -73).
+    Tens,
+    /// `units(x)`: the units digit of x, e.g. 3 for 73 (or -73).
+    Units,
+    /// `max(a, b)`: the higher of two independent rolls.
+    Max,
+    /// `min(a, b)`: the lower of two independent rolls.
+    Min,
+    /// `abs(x)`: the absolute value of x, e.g. 3 for -3.
+    Abs,
+    /// `clamp(x, lo, hi)`: x, raised to lo if it's below, or lowered to hi if it's above.
+    Clamp,
+    /// `if(condition, a, b)`, also written `if condition then a else b`:
+    /// a where the condition is nonzero (true), or b where it is zero (false).
+    If,
+    /// `save(damage, saved)`: damage, halved (truncated toward zero) where the save succeeds (is nonzero).
+    Save,
+}
+
+impl Function {
+    /// All functions, for lookup by name.
+    pub const ALL: &[Function] = &[
+        Function::Soak,
+        Function::Tens,
+        Function::Units,
+        Function::Max,
+        Function::Min,
+        Function::Abs,
+        Function::Clamp,
+        Function::If,
+        Function::Save,
+    ];
+
+    /// The name of the function, as written in an expression.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Soak => "soak",
+            Function::Tens => "tens",
+            Function::Units => "units",
+            Function::Max => "max",
+            Function::Min => "min",
+            Function::Abs => "abs",
+            Function::Clamp => "clamp",
+            Function::If => "if",
+            Function::Save => "save",
+        }
+    }
+
+    /// The number of arguments the function takes.
+    pub fn arity(&self) -> usize {
+        match self {
+            Function::Soak | Function::Max | Function::Min | Function::Save => 2,
+            Function::Tens | Function::Units | Function::Abs => 1,
+            Function::Clamp | Function::If => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 /// The ExpressionTree construct wraps all expressions in a tag type implementing this trait.
 pub trait ExpressionWrapper: Sized {
     fn inner(&self) -> &ExpressionTree<Self>;
@@ -147,17 +568,19 @@ pub trait ExpressionWrapper: Sized {
 pub enum ExpressionTree<T> {
     Modifier(Constant),
     Die(Die),
+    CustomDie(CustomDie),
     Symbol(Symbol),
 
     Negated(Box<T>),
     Repeated {
         count: Box<T>,
         value: Box<T>,
-        ranker: Ranker,
+        ranker: Ranker<Box<T>>,
     },
     Product(Box<T>, Box<T>),
     Sum(Vec<T>),
     Floor(Box<T>, Box<T>),
+    Modulo(Box<T>, Box<T>),
     Comparison {
         a: Box<T>,
         b: Box<T>,
@@ -168,6 +591,27 @@ pub enum ExpressionTree<T> {
         value: Box<T>,
         tail: Box<T>,
     },
+    Call {
+        function: Function,
+        args: Vec<T>,
+    },
+    Explode {
+        value: Box<T>,
+        explosion: Explosion,
+        trigger: Trigger,
+    },
+    Reroll {
+        value: Box<T>,
+        reroll: Reroll,
+    },
+    Successes {
+        value: Box<T>,
+        successes: Successes,
+    },
+    Lookup {
+        value: Box<T>,
+        bands: Bands,
+    },
 }
 
 impl<T> From<Die> for ExpressionTree<T> {
@@ -176,6 +620,12 @@ impl<T> From<Die> for ExpressionTree<T> {
     }
 }
 
+impl<T> From<CustomDie> for ExpressionTree<T> {
+    fn from(value: CustomDie) -> Self {
+        ExpressionTree::CustomDie(value)
+    }
+}
+
 impl<T> From<Constant> for ExpressionTree<T> {
     fn from(value: Constant) -> Self {
         ExpressionTree::Modifier(value)
@@ -188,6 +638,90 @@ impl<T> From<Symbol> for ExpressionTree<T> {
     }
 }
 
+impl<T> ExpressionTree<T> {
+    /// The same node, with each child converted by `f`, in the order they appear.
+    pub fn map_children<U>(&self, mut f: impl FnMut(&T) -> U) -> ExpressionTree<U> {
+        match self {
+            ExpressionTree::Modifier(c) => ExpressionTree::Modifier(*c),
+            ExpressionTree::Die(d) => ExpressionTree::Die(*d),
+            ExpressionTree::CustomDie(d) => ExpressionTree::CustomDie(d.clone()),
+            ExpressionTree::Symbol(s) => ExpressionTree::Symbol(s.clone()),
+            ExpressionTree::Negated(e) => ExpressionTree::Negated(Box::new(f(e))),
+            ExpressionTree::Repeated {
+                count,
+                value,
+                ranker,
+            } => {
+                let count = Box::new(f(count));
+                let value = Box::new(f(value));
+                ExpressionTree::Repeated {
+                    count,
+                    value,
+                    ranker: ranker.map(|n| Box::new(f(n))),
+                }
+            }
+            ExpressionTree::Product(a, b) => {
+                let a = f(a);
+                ExpressionTree::Product(Box::new(a), Box::new(f(b)))
+            }
+            ExpressionTree::Sum(es) => ExpressionTree::Sum(es.iter().map(f).collect()),
+            ExpressionTree::Floor(a, b) => {
+                let a = f(a);
+                ExpressionTree::Floor(Box::new(a), Box::new(f(b)))
+            }
+            ExpressionTree::Modulo(a, b) => {
+                let a = f(a);
+                ExpressionTree::Modulo(Box::new(a), Box::new(f(b)))
+            }
+            ExpressionTree::Comparison { a, b, op } => {
+                let a = f(a);
+                ExpressionTree::Comparison {
+                    a: Box::new(a),
+                    b: Box::new(f(b)),
+                    op: *op,
+                }
+            }
+            ExpressionTree::Binding {
+                symbol,
+                value,
+                tail,
+            } => {
+                let value = f(value);
+                ExpressionTree::Binding {
+                    symbol: symbol.clone(),
+                    value: Box::new(value),
+                    tail: Box::new(f(tail)),
+                }
+            }
+            ExpressionTree::Call { function, args } => ExpressionTree::Call {
+                function: *function,
+                args: args.iter().map(f).collect(),
+            },
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => ExpressionTree::Explode {
+                value: Box::new(f(value)),
+                explosion: *explosion,
+                trigger: *trigger,
+            },
+            ExpressionTree::Reroll { value, reroll } => ExpressionTree::Reroll {
+                value: Box::new(f(value)),
+                reroll: *reroll,
+            },
+            ExpressionTree::Successes { value, successes } => ExpressionTree::Successes {
+                value: Box::new(f(value)),
+                successes: *successes,
+            },
+            ExpressionTree::Lookup { value, bands } => ExpressionTree::Lookup {
+                value: Box::new(f(value)),
+                bands: bands.clone(),
+            },
+        }
+    }
+}
+
 impl<T> ExpressionTree<T>
 where
     T: ExpressionWrapper,
@@ -206,7 +740,10 @@ where
         // to switch up spaces, multiplication signs, etc.
 
         match self {
+            // d66 and d666 read as digits, so a die with that many faces is written as a range.
+            ExpressionTree::Die(Die(n @ (66 | 666))) => write!(f, "d[1..{n}]"),
             ExpressionTree::Die(Die(n)) => write!(f, "d{n}"),
+            ExpressionTree::CustomDie(d) => write!(f, "{d}"),
             ExpressionTree::Modifier(Constant(n)) => write!(f, "{n}"),
             ExpressionTree::Symbol(n) => write!(f, "{n}"),
             ExpressionTree::Repeated {
@@ -221,18 +758,38 @@ where
                 } else {
                     count.with_paren(f)?
                 };
-                if matches!(value, ExpressionTree::Die(_)) {
+                if matches!(
+                    value,
+                    ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
+                ) {
                     value.fmt(f)?
                 } else {
                     value.with_paren(f)?
                 };
-                write!(f, "{ranker}")
+                match (ranker.fixed(), ranker.count()) {
+                    (Some(fixed), _) => write!(f, "{fixed}"),
+                    (None, Some(n)) => {
+                        write!(f, "{}", ranker.operator())?;
+                        n.inner().with_paren(f)
+                    }
+                    (None, None) => Ok(()),
+                }
             }
             ExpressionTree::Negated(expression) => {
                 let n = expression.inner();
                 if matches!(
                     n,
                     ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
@@ -249,6 +806,12 @@ where
                 if matches!(
                     a,
                     ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
@@ -264,25 +827,43 @@ where
                 if matches!(
                     b,
                     ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
                         | ExpressionTree::Negated(_)
                         | ExpressionTree::Product(_, _)
                         | ExpressionTree::Floor(_, _)
+                        | ExpressionTree::Modulo(_, _)
                 ) {
                     b.fmt(f)
                 } else {
                     b.with_paren(f)
                 }
             }
-            ExpressionTree::Floor(a, b) => {
+            ExpressionTree::Floor(a, b) | ExpressionTree::Modulo(a, b) => {
+                let operator = if matches!(self, ExpressionTree::Floor(..)) {
+                    "/"
+                } else {
+                    "%"
+                };
                 let a = a.inner();
                 let b = b.inner();
 
                 if matches!(
                     a,
                     ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
@@ -293,17 +874,24 @@ where
                     a.with_paren(f)?
                 };
 
-                write!(f, " / ")?;
+                write!(f, " {operator} ")?;
 
                 if matches!(
                     b,
                     ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
                         | ExpressionTree::Negated(_)
                         | ExpressionTree::Product(_, _)
                         | ExpressionTree::Floor(_, _)
+                        | ExpressionTree::Modulo(_, _)
                 ) {
                     b.fmt(f)
                 } else {
@@ -327,10 +915,17 @@ where
 
                     match e {
                         ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Lookup { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
                         | ExpressionTree::Modifier(_)
                         | ExpressionTree::Symbol(_)
                         | ExpressionTree::Repeated { .. }
                         | ExpressionTree::Floor(_, _)
+                        | ExpressionTree::Modulo(_, _)
                         | ExpressionTree::Product(_, _) => e.fmt(f),
                         _ => e.with_paren(f),
                     }?;
@@ -365,6 +960,78 @@ where
                 let tail = tail.inner();
                 write!(f, "[{symbol}: {value}] {tail}")
             }
+            ExpressionTree::Call { function, args } => {
+                write!(f, "{function}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    arg.inner().fmt(f)?;
+                }
+                write!(f, ")")
+            }
+            ExpressionTree::Explode {
+                value,
+                explosion,
+                trigger,
+            } => {
+                let value = value.inner();
+                if matches!(
+                    value,
+                    ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Reroll { .. }
+                ) {
+                    value.fmt(f)?
+                } else {
+                    value.with_paren(f)?
+                };
+                write!(f, "{explosion}{trigger}")
+            }
+            ExpressionTree::Reroll { value, reroll } => {
+                let value = value.inner();
+                if matches!(value, ExpressionTree::Die(_) | ExpressionTree::CustomDie(_)) {
+                    value.fmt(f)?
+                } else {
+                    value.with_paren(f)?
+                };
+                write!(f, "{reroll}")
+            }
+            ExpressionTree::Successes { value, successes } => {
+                let value = value.inner();
+                if matches!(
+                    value,
+                    ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                ) {
+                    value.fmt(f)?
+                } else {
+                    value.with_paren(f)?
+                };
+                write!(f, "{successes}")
+            }
+            ExpressionTree::Lookup { value, bands } => {
+                let value = value.inner();
+                if matches!(
+                    value,
+                    ExpressionTree::Die(_)
+                        | ExpressionTree::CustomDie(_)
+                        | ExpressionTree::Call { .. }
+                        | ExpressionTree::Explode { .. }
+                        | ExpressionTree::Reroll { .. }
+                        | ExpressionTree::Successes { .. }
+                        | ExpressionTree::Modifier(_)
+                        | ExpressionTree::Symbol(_)
+                        | ExpressionTree::Repeated { .. }
+                ) {
+                    value.fmt(f)?
+                } else {
+                    value.with_paren(f)?
+                };
+                write!(f, " -> {bands}")
+            }
         }
     }
 }
@@ -0,0 +1,118 @@
+//! Assertions for testing dice mechanics, in this crate and in crates built on it.
+//!
+//! [`assert_distribution_eq!`](crate::assert_distribution_eq) checks a [`Distribution`]
+//! against a table of the exact probability of each value, and on failure lists every value
+//! whose probability differs, rather than just the first.
+
+use std::collections::BTreeMap;
+
+pub use num::rational::Ratio;
+
+use crate::Distribution;
+
+/// The differences between the distribution and the expected probability of each value,
+/// one per line, or None if there are none.
+///
+/// Values missing from the table are expected never to occur.
+/// A value listed more than once is a difference too, whatever its probabilities.
+pub fn distribution_diff(
+    actual: &Distribution,
+    expected: impl IntoIterator<Item = (i64, Ratio<usize>)>,
+) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut table = BTreeMap::new();
+    for (value, p) in expected {
+        if table.insert(value, p).is_some() {
+            lines.push(format!("value {value}: listed more than once"));
+        }
+    }
+    let values: BTreeMap<i64, ()> = table
+        .keys()
+        .copied()
+        .chain(actual.occurrences().map(|(v, _)| v))
+        .map(|v| (v, ()))
+        .collect();
+    for value in values.into_keys() {
+        let want = table.get(&value).copied().unwrap_or_default();
+        let got = actual.probability(value);
+        if want != got {
+            lines.push(format!("value {value}: expected {want}, got {got}"));
+        }
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Assert that a [`Distribution`] gives each value exactly the expected probability,
+/// listing every value that differs if it doesn't.
+///
+/// The expected probabilities may be written as a table of fractions,
+/// `assert_distribution_eq!(d, {2: 1/4, 3: 1/2, 4: 1/4})`, or given as any iterator
+/// of `(i64, Ratio<usize>)` pairs. Values missing from the table are expected never to occur.
+#[macro_export]
+macro_rules! assert_distribution_eq {
+    ($actual:expr, { $($value:literal : $numer:literal / $denom:literal),* $(,)? } $(,)?) => {
+        $crate::assert_distribution_eq!(
+            $actual,
+            [$(($value, $crate::testing::Ratio::new($numer, $denom))),*]
+        )
+    };
+    ($actual:expr, $expected:expr $(,)?) => {
+        if let Some(diff) = $crate::testing::distribution_diff(&$actual, $expected) {
+            panic!("distributions differ:\n{diff}");
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Closed;
+
+    fn distribution(s: &str) -> Distribution {
+        s.parse::<Closed>().unwrap().distribution().unwrap()
+    }
+
+    #[test]
+    fn matching() {
+        crate::assert_distribution_eq!(distribution("d4 - 2"), {-1: 1/4, 0: 1/4, 1: 2/8, 2: 1/4});
+        let d = distribution("3d6");
+        let table: Vec<_> = d
+            .occurrences()
+            .map(|(v, o)| (v, Ratio::new(o, 216)))
+            .collect();
+        crate::assert_distribution_eq!(d, table);
+    }
+
+    #[test]
+    fn diff() {
+        let d = distribution("2d2");
+        let got = distribution_diff(
+            &d,
+            [
+                (2, Ratio::new(1, 4)),
+                (3, Ratio::new(1, 4)),
+                (4, Ratio::new(1, 4)),
+                (5, Ratio::new(1, 4)),
+                (2, Ratio::new(1, 4)),
+            ],
+        );
+        assert_eq!(
+            got.unwrap(),
+            "value 2: listed more than once\n\
+             value 3: expected 1/4, got 1/2\n\
+             value 5: expected 1/4, got 0"
+        );
+        assert_eq!(
+            distribution_diff(&d, [(3, Ratio::new(1, 1))]).unwrap(),
+            "value 2: expected 0, got 1/4\n\
+             value 3: expected 1, got 1/2\n\
+             value 4: expected 0, got 1/4"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "distributions differ:\nvalue 1: expected 1/3, got 1/2")]
+    fn mismatch_panics() {
+        crate::assert_distribution_eq!(distribution("d2"), {1: 1/3, 2: 1/2});
+    }
+}
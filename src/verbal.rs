@@ -0,0 +1,208 @@
+//! Plain-sentence descriptions of rolls, for screen readers and voice assistants.
+//!
+//! Dice notation reads poorly aloud: a screen reader says "4d6kh3" letter by letter.
+//! [`describe`] renders a roll as a sentence instead, with the operators spelled out:
+//! "Rolling 4d6 keep highest 3: average 12.24, most likely 13, range 3 to 18".
+
+use crate::{
+    Closed, Distribution,
+    symbolic::{ComparisonOp, ExpressionTree, ExpressionWrapper, Ranker},
+};
+
+/// A sentence describing the roll of the expression, whose distribution is `d`:
+/// what's rolled, its average, its most likely results, and its range.
+pub fn describe(e: &Closed, d: &Distribution) -> String {
+    describe_as(&spoken(e), d)
+}
+
+/// A sentence describing the roll of `d`, called `expression`: [`describe`], for a roll
+/// with a name of its own, like a macro.
+pub fn describe_as(expression: &str, d: &Distribution) -> String {
+    if d.min() == d.max() {
+        return format!("Rolling {expression}: always {}", d.min());
+    }
+    let most = d.occurrences().map(|(_, o)| o).max().unwrap_or_default();
+    let modes: Vec<String> = d
+        .occurrences()
+        .filter(|(_, o)| *o == most)
+        .map(|(v, _)| v.to_string())
+        .collect();
+    let likely = if modes.len() == d.occurrences().count() {
+        "every result equally likely".to_owned()
+    } else {
+        format!("most likely {}", list(&modes))
+    };
+    format!(
+        "Rolling {expression}: average {}, {likely}, range {} to {}",
+        decimal(d.mean()),
+        d.min(),
+        d.max()
+    )
+}
+
+/// The expression as it would be read aloud: operators as words, rather than symbols.
+///
+/// Dice themselves, like `4d6`, read well enough as written; so do the less common
+/// operators, which are left in notation.
+pub fn spoken(e: &Closed) -> String {
+    match e.inner() {
+        ExpressionTree::Repeated {
+            count,
+            value,
+            ranker,
+        } => {
+            let rolls = ExpressionTree::Repeated {
+                count: count.clone(),
+                value: value.clone(),
+                ranker: Ranker::All,
+            };
+            let count = |n: &Closed| match n.inner() {
+                ExpressionTree::Modifier(_) => n.to_string(),
+                _ => format!("({})", spoken(n)),
+            };
+            let ranked = |verb: &str, n: &Closed| match n.inner() {
+                ExpressionTree::Modifier(c) if c.0 == 1 => format!("{rolls} {verb}"),
+                _ => format!("{rolls} {verb} {}", count(n)),
+            };
+            match ranker {
+                Ranker::All => rolls.to_string(),
+                Ranker::Highest(n) => ranked("keep highest", n),
+                Ranker::Lowest(n) => ranked("keep lowest", n),
+                Ranker::DropHighest(n) => ranked("drop highest", n),
+                Ranker::DropLowest(n) => ranked("drop lowest", n),
+                Ranker::Matching => format!("{rolls}, counting the most matching"),
+            }
+        }
+        ExpressionTree::Negated(n) => format!("minus {}", operand(n)),
+        ExpressionTree::Sum(terms) => {
+            let mut s = String::new();
+            for (i, term) in terms.iter().enumerate() {
+                match (i, term.inner()) {
+                    (0, _) => s.push_str(&operand(term)),
+                    (_, ExpressionTree::Negated(n)) => {
+                        s.push_str(" minus ");
+                        s.push_str(&operand(n));
+                    }
+                    _ => {
+                        s.push_str(" plus ");
+                        s.push_str(&operand(term));
+                    }
+                }
+            }
+            s
+        }
+        ExpressionTree::Product(a, b) => format!("{} times {}", operand(a), operand(b)),
+        ExpressionTree::Floor(a, b) => {
+            format!(
+                "{} divided by {}, truncated toward zero",
+                operand(a),
+                operand(b)
+            )
+        }
+        ExpressionTree::Modulo(a, b) => format!("{} modulo {}", operand(a), operand(b)),
+        ExpressionTree::Comparison { a, b, op } => {
+            let op = match op {
+                ComparisonOp::Gt => "is more than",
+                ComparisonOp::Ge => "is at least",
+                ComparisonOp::Eq => "equals",
+                ComparisonOp::Le => "is at most",
+                ComparisonOp::Lt => "is less than",
+            };
+            format!("{} {op} {}", operand(a), operand(b))
+        }
+        _ => e.to_string(),
+    }
+}
+
+/// The expression as spoken, in parentheses if it has operators of its own.
+fn operand(e: &Closed) -> String {
+    match e.inner() {
+        ExpressionTree::Sum(_)
+        | ExpressionTree::Product(..)
+        | ExpressionTree::Floor(..)
+        | ExpressionTree::Modulo(..)
+        | ExpressionTree::Comparison { .. }
+        | ExpressionTree::Binding { .. } => format!("({})", spoken(e)),
+        _ => spoken(e),
+    }
+}
+
+/// The number to two decimal places, without trailing zeros.
+fn decimal(x: f64) -> String {
+    let s = format!("{x:.2}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// The items as a list in prose: "a", "a or b", "a, b, or c".
+fn list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [a] => a.clone(),
+        [a, b] => format!("{a} or {b}"),
+        [rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe_str(s: &str) -> String {
+        let e = s.parse::<Closed>().unwrap();
+        describe(&e, &e.distribution().unwrap())
+    }
+
+    #[test]
+    fn sentences() {
+        assert_eq!(
+            describe_str("4d6kh3"),
+            "Rolling 4d6 keep highest 3: average 12.24, most likely 13, range 3 to 18"
+        );
+        assert_eq!(
+            describe_str("2d6 + 1"),
+            "Rolling 2d6 plus 1: average 8, most likely 8, range 3 to 13"
+        );
+        assert_eq!(
+            describe_str("d20"),
+            "Rolling d20: average 10.5, every result equally likely, range 1 to 20"
+        );
+        assert_eq!(describe_str("3 * 2"), "Rolling 3 times 2: always 6");
+        assert_eq!(
+            describe_str("d4 + d2"),
+            "Rolling d4 plus d2: average 4, most likely 3, 4, or 5, range 2 to 6"
+        );
+    }
+
+    #[test]
+    fn spoken_operators() {
+        for (notation, want) in [
+            ("2d20kh", "2d20 keep highest"),
+            ("2d20kl + 5", "2d20 keep lowest plus 5"),
+            ("4d6dl", "4d6 drop lowest"),
+            ("5d10dh2", "5d10 drop highest 2"),
+            ("d6 - d4 - 1", "d6 minus d4 minus 1"),
+            ("-d6", "minus d6"),
+            ("(d6 + 1) * 2", "(d6 plus 1) times 2"),
+            ("d20 / 2", "d20 divided by 2, truncated toward zero"),
+            ("d20 + 5 >= 15", "(d20 plus 5) is at least 15"),
+            ("d100 % 10", "d100 modulo 10"),
+            ("d6!", "d6!"),
+        ] {
+            let e = notation.parse::<Closed>().unwrap();
+            assert_eq!(spoken(&e), want, "{notation}");
+        }
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(decimal(3.5), "3.5");
+        assert_eq!(decimal(12.0), "12");
+        assert_eq!(decimal(-0.001), "0");
+        assert_eq!(decimal(-1.256), "-1.26");
+    }
+}